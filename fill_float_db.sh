@@ -1,7 +1,7 @@
 #!/bin/bash
 
 # Command to run Cargo with your specified options
-CARGO_CMD="/home/leo/.cargo/bin/cargo run --color=always --package rql --bin rql --profile dev"
+CARGO_CMD="/home/leo/.cargo/bin/cargo run --color=always --package rql --bin rql --profile dev -- --quiet"
 
 # Start a subshell to collect all SQL commands
 (
@@ -0,0 +1,179 @@
+use crate::database::database::Database;
+use crate::io::copy::{export, import};
+use crate::io::reader::{read_table_definition, table_exists};
+use crate::io::sqlite::import_sqlite;
+use crate::query::reference::check_foreign_keys;
+use crate::io::writer::write_table_definition;
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{AlterDefinition, AttachDefinition, CopyDefinition, CopyDirection, CreateIndexDefinition, DeleteDefinition, DetachDefinition, DropDefinition, InsertDefinition, ResultSet, SelectDefinition, SqliteImportDefinition, StatementResult, TableDefinition, Timings, UpdateDefinition};
+use std::time::{Duration, Instant};
+
+/// A session on a database. Each connection has its own transaction state, so
+/// changes staged after `BEGIN` stay invisible to other connections until
+/// `COMMIT`. Files the transaction has not changed are read as currently
+/// committed, and `COMMIT` fails if another connection changed a file the
+/// transaction used since it first did.
+#[derive(Debug)]
+pub struct Connection {
+    database: Database,
+    timings: Timings,
+}
+
+impl Database {
+    pub fn connect(&self) -> Connection {
+        Connection::new(self.clone())
+    }
+}
+
+impl Connection {
+    pub fn new(mut database: Database) -> Connection {
+        if database.in_transaction() {
+            let _ = database.rollback();
+        }
+        Connection {
+            database,
+            timings: Timings::default(),
+        }
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.database.in_transaction()
+    }
+
+    /// How long each stage of the last statement run on this connection took.
+    pub fn last_timings(&self) -> Timings {
+        self.timings
+    }
+
+    /// Runs a statement and returns the rows it produced, if any.
+    pub fn run(&mut self, sql: &str) -> Result<StatementResult> {
+        let start = Instant::now();
+        let mut timings = Timings::default();
+        let result = run_statement(&mut self.database, sql, &mut timings);
+        timings.total = start.elapsed();
+        self.timings = timings;
+        result
+    }
+
+    /// Runs a statement and returns the number of rows it inserted, updated or deleted.
+    pub fn execute(&mut self, sql: &str) -> Result<usize> {
+        match self.run(sql)? {
+            StatementResult::Rows(rows) => Ok(rows.len()),
+            StatementResult::Done { rows_affected, .. } => Ok(rows_affected),
+        }
+    }
+
+    /// Runs a statement that produces rows, such as `SELECT`.
+    pub fn query(&mut self, sql: &str) -> Result<ResultSet> {
+        match self.run(sql)? {
+            StatementResult::Rows(rows) => Ok(rows),
+            StatementResult::Done { .. } => Err(RqlError::parse("Statement does not return rows")),
+        }
+    }
+}
+
+fn done<S: Into<String>>(rows_affected: usize, message: S) -> StatementResult {
+    StatementResult::Done {
+        rows_affected,
+        message: message.into(),
+    }
+}
+
+fn run_statement(db: &mut Database, sql: &str, timings: &mut Timings) -> Result<StatementResult> {
+    let command = sql.trim();
+    let keyword = command.trim_end_matches(';').trim().to_uppercase();
+    if keyword == "BEGIN" || keyword == "BEGIN TRANSACTION" {
+        db.begin()?;
+        Ok(done(0, "Transaction started."))
+    } else if keyword == "COMMIT" {
+        db.commit()?;
+        Ok(done(0, "Transaction committed."))
+    } else if keyword == "ROLLBACK" {
+        db.rollback()?;
+        Ok(done(0, "Transaction rolled back."))
+    } else if command.starts_with("ATTACH") {
+        let attach_def = timed(&mut timings.parse, || AttachDefinition::from_sql(command))?;
+        db.attach(&attach_def.path, &attach_def.name)?;
+        Ok(done(0, format!("Database '{}' attached as '{}'.", attach_def.path, attach_def.name)))
+    } else if command.starts_with("DETACH") {
+        let detach_def = timed(&mut timings.parse, || DetachDefinition::from_sql(command))?;
+        db.detach(&detach_def.name)?;
+        Ok(done(0, format!("Database '{}' detached.", detach_def.name)))
+    } else if command.starts_with("CREATE TABLE") {
+        let table_def = timed(&mut timings.parse, || TableDefinition::from_sql(command))?;
+        if table_exists(db, &table_def.name)? {
+            return Err(RqlError::Schema(format!("Table '{}' already exists", table_def.name)));
+        }
+        check_foreign_keys(db, &table_def, &table_def.foreign_keys)?;
+        write_table_definition(db, &table_def)?;
+        Ok(done(0, format!("Table '{}' created successfully.", table_def.name)))
+    } else if command.starts_with("CREATE INDEX") || command.starts_with("CREATE UNIQUE INDEX") {
+        let index_def = timed(&mut timings.parse, || CreateIndexDefinition::from_sql(command))?;
+        index_def.execute(db)?;
+        Ok(done(0, format!("Index '{}' created on table '{}'.", index_def.index.name, index_def.table_name)))
+    } else if command.starts_with("DROP TABLE") {
+        let drop_def = timed(&mut timings.parse, || DropDefinition::from_sql(command))?;
+        drop_def.execute(db)?;
+        Ok(done(0, format!("Table '{}' dropped.", drop_def.table_name)))
+    } else if command.starts_with("ALTER TABLE") {
+        let alter_def = timed(&mut timings.parse, || AlterDefinition::from_sql(command))?;
+        alter_def.execute(db)?;
+        Ok(done(0, format!("Column '{}' added to table '{}'.", alter_def.column.name, alter_def.table_name)))
+    } else if command.starts_with("INSERT INTO") {
+        let mut insert_def = timed(&mut timings.parse, || InsertDefinition::from_sql(command))?;
+        match insert_def.validate_and_insert(db)? {
+            1 => Ok(done(1, format!("Row inserted successfully into table '{}'.", insert_def.name))),
+            rows => Ok(done(rows, format!("{} rows inserted successfully into table '{}'.", rows, insert_def.name))),
+        }
+    } else if command.starts_with("DELETE") {
+        let delete_def = timed(&mut timings.parse, || DeleteDefinition::from_sql(command))?;
+        match delete_def.execute(db)? {
+            1 => Ok(done(1, format!("Row deleted from table '{}'.", delete_def.table_name))),
+            rows => Ok(done(rows, format!("{} rows deleted from table '{}'.", rows, delete_def.table_name))),
+        }
+    } else if command.starts_with("SELECT") {
+        let select_def = timed(&mut timings.parse, || SelectDefinition::from_sql(command))?;
+        Ok(StatementResult::Rows(select_def.execute(db, timings)?))
+    } else if command.starts_with("UPDATE") {
+        let update_def = timed(&mut timings.parse, || UpdateDefinition::from_sql(command))?;
+        let table_def = read_table_definition(db, &update_def.table_name)?;
+        let rows = update_def.load_data(db, timings)?;
+        Ok(StatementResult::Rows(ResultSet::from_rows(&table_def, rows)))
+    } else if command.starts_with("COPY") {
+        let copy_def = timed(&mut timings.parse, || CopyDefinition::from_sql(command))?;
+        match copy_def.direction {
+            CopyDirection::From => {
+                let rows = import(db, &copy_def)?;
+                Ok(done(rows, format!("{} rows copied from '{}' into '{}'.", rows, copy_def.path, copy_def.table_name)))
+            }
+            CopyDirection::To => {
+                let rows = export(db, &copy_def)?;
+                match copy_def.query {
+                    Some(_) => Ok(done(rows, format!("{} rows copied to '{}'.", rows, copy_def.path))),
+                    None => Ok(done(rows, format!("{} rows copied from '{}' to '{}'.", rows, copy_def.table_name, copy_def.path))),
+                }
+            }
+        }
+    } else if command.starts_with("IMPORT") {
+        let import_def = timed(&mut timings.parse, || SqliteImportDefinition::from_sql(command))?;
+        let rows = import_sqlite(db, &import_def)?;
+        Ok(done(rows, format!(
+            "{} rows imported from '{}' in '{}' into '{}'.",
+            rows, import_def.source_table, import_def.path, import_def.table_name
+        )))
+    } else {
+        let keyword = command.split_whitespace().next().unwrap_or("");
+        Err(RqlError::parse_at(sql, command, format!("Unsupported statement '{}'", keyword)))
+    }
+}
+
+fn timed<T>(elapsed: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    *elapsed += start.elapsed();
+    result
+}
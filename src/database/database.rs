@@ -0,0 +1,231 @@
+use crate::io::storage::{FileStorage, MemoryStorage, Storage};
+use crate::io::transaction::TransactionStorage;
+use crate::types::error::{Result, RqlError};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A database: the storage holding its `schema/` and `data/` files, plus any
+/// databases attached to it under an alias. Clones share the same storage.
+#[derive(Debug, Clone)]
+pub struct Database {
+    storage: Arc<dyn Storage>,
+    transaction: Option<Arc<TransactionStorage>>,
+    attached: BTreeMap<String, Database>,
+    recovered_statements: usize,
+}
+
+impl Database {
+    /// Opens the database in the directory at `path`, creating it if needed and
+    /// replaying statements left in its write-ahead log.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Database> {
+        let (storage, recovered_statements) = FileStorage::open(path)?;
+        Ok(Database::with_storage(Arc::new(storage), recovered_statements))
+    }
+
+    /// Opens an empty database that lives only as long as this handle and its clones.
+    pub fn open_in_memory() -> Database {
+        Database::with_storage(Arc::new(MemoryStorage::default()), 0)
+    }
+
+    fn with_storage(storage: Arc<dyn Storage>, recovered_statements: usize) -> Database {
+        Database {
+            storage,
+            transaction: None,
+            attached: BTreeMap::new(),
+            recovered_statements,
+        }
+    }
+
+    /// Number of interrupted statements replayed from the write-ahead log on open.
+    pub fn recovered_statements(&self) -> usize {
+        self.recovered_statements
+    }
+
+    pub fn attach<P: AsRef<Path>>(&mut self, path: P, name: &str) -> Result<()> {
+        let name = name.to_lowercase();
+        if name == "main" || self.attached.contains_key(&name) {
+            return Err(RqlError::Schema(format!("Database '{}' is already in use", name)));
+        }
+        if self.in_transaction() {
+            return Err(RqlError::Transaction("Cannot attach a database inside a transaction".to_string()));
+        }
+
+        let attached = if path.as_ref() == Path::new(":memory:") {
+            Database::open_in_memory()
+        } else {
+            Database::open(path)?
+        };
+        self.attached.insert(name, attached);
+        Ok(())
+    }
+
+    pub fn detach(&mut self, name: &str) -> Result<()> {
+        if self.in_transaction() {
+            return Err(RqlError::Transaction("Cannot detach a database inside a transaction".to_string()));
+        }
+
+        self.attached
+            .remove(&name.to_lowercase())
+            .map(|_| ())
+            .ok_or_else(|| unknown_database(name))
+    }
+
+    pub fn attached(&self) -> impl Iterator<Item = (&String, &Database)> {
+        self.attached.iter()
+    }
+
+    /// Names of the tables in this database, followed by those of attached
+    /// databases qualified as `alias.table`.
+    pub fn tables(&self) -> Result<Vec<String>> {
+        let mut tables: Vec<String> = self
+            .storage()
+            .list("schema")?
+            .iter()
+            .filter_map(|path| path.strip_prefix("schema/")?.strip_suffix("_def.bin"))
+            .map(str::to_string)
+            .collect();
+
+        for (alias, attached) in &self.attached {
+            tables.extend(attached.tables()?.into_iter().map(|table| format!("{}.{}", alias, table)));
+        }
+        Ok(tables)
+    }
+
+    /// The storage statements read from and write to: the transaction's shadow
+    /// copies while one is open, the database's own storage otherwise.
+    pub(crate) fn storage(&self) -> &dyn Storage {
+        match &self.transaction {
+            Some(transaction) => transaction.as_ref(),
+            None => self.storage.as_ref(),
+        }
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Starts staging changes to this database and every attached one.
+    pub fn begin(&mut self) -> Result<()> {
+        if self.in_transaction() {
+            return Err(RqlError::Transaction("A transaction is already in progress".to_string()));
+        }
+
+        self.transaction = Some(Arc::new(TransactionStorage::new(self.storage.clone())));
+        for attached in self.attached.values_mut() {
+            if let Err(e) = attached.begin() {
+                self.discard();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Publishes the staged changes, to this database first and then to the
+    /// attached ones in name order. Each database commits them as one write-ahead
+    /// log entry. When one fails, for instance because another connection changed
+    /// a file the transaction used, the changes to it and to the databases after
+    /// it are rolled back, and the error names the databases already published.
+    pub fn commit(&mut self) -> Result<()> {
+        if !self.in_transaction() {
+            return Err(no_transaction());
+        }
+
+        let mut published = Vec::new();
+        let result = self.publish("main", &mut published);
+        self.discard();
+        match result {
+            Err(e) if !published.is_empty() => Err(RqlError::Transaction(format!(
+                "The transaction was only committed to {}: {}",
+                published.join(", "),
+                e
+            ))),
+            result => result,
+        }
+    }
+
+    /// Publishes the transactions of this database and the attached ones, adding
+    /// the names of those that had changes to `published`.
+    fn publish(&self, name: &str, published: &mut Vec<String>) -> Result<()> {
+        if let Some(transaction) = &self.transaction {
+            if transaction.publish()? {
+                published.push(name.to_string());
+            }
+        }
+        for (alias, attached) in &self.attached {
+            attached.publish(alias, published)?;
+        }
+        Ok(())
+    }
+
+    pub fn rollback(&mut self) -> Result<()> {
+        if !self.in_transaction() {
+            return Err(no_transaction());
+        }
+        self.discard();
+        Ok(())
+    }
+
+    /// Drops the staged changes of this database and every attached one.
+    fn discard(&mut self) {
+        self.transaction = None;
+        for attached in self.attached.values_mut() {
+            attached.discard();
+        }
+    }
+
+    /// Resolves a table name, optionally qualified as `alias.table`, to the
+    /// database that owns it and the path of its schema file.
+    pub(crate) fn schema_location(&self, table_name: &str) -> Result<(&Database, String)> {
+        let (db, table) = self.resolve(table_name)?;
+        Ok((db, format!("schema/{}_def.bin", table)))
+    }
+
+    pub(crate) fn data_location(&self, table_name: &str) -> Result<(&Database, String)> {
+        let (db, table) = self.resolve(table_name)?;
+        Ok((db, format!("data/{}_data.bin", table)))
+    }
+
+    /// The database that owns a table and the path of one of its index files.
+    pub(crate) fn index_location(&self, table_name: &str, index_name: &str) -> Result<(&Database, String)> {
+        let (db, table) = self.resolve(table_name)?;
+        Ok((db, format!("index/{}.{}.bin", table, checked_name(index_name)?)))
+    }
+
+    fn resolve(&self, table_name: &str) -> Result<(&Database, String)> {
+        let (db, table) = match table_name.split_once('.') {
+            Some((alias, table)) if alias.eq_ignore_ascii_case("main") => (self, table),
+            Some((alias, table)) => (
+                self.attached
+                    .get(&checked_name(alias)?)
+                    .ok_or_else(|| unknown_database(alias))?,
+                table,
+            ),
+            None => (self, table_name),
+        };
+
+        Ok((db, checked_name(table)?))
+    }
+}
+
+/// A lowercased database alias, or table or index name as it appears in file
+/// paths. Only letters, digits and underscores are allowed, so a name cannot
+/// reach outside the database directory.
+fn checked_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(RqlError::parse(format!(
+            "'{}' is not a valid name; names may only contain letters, digits and underscores",
+            name
+        )));
+    }
+    Ok(name.to_lowercase())
+}
+
+fn unknown_database(name: &str) -> RqlError {
+    RqlError::Schema(format!("No database attached as '{}'", name))
+}
+
+fn no_transaction() -> RqlError {
+    RqlError::Transaction("No transaction is in progress".to_string())
+}
@@ -0,0 +1,341 @@
+use crate::io::storage::Storage;
+use crate::types::types::WalOp;
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+
+pub const PAGE_SIZE: usize = 4096;
+/// The longest key a page takes, so that splitting a full page leaves room on both sides.
+pub const MAX_KEY: usize = 1024;
+
+const MAGIC: &[u8; 8] = b"RQLBTREE";
+/// Kind, key count and link of a node page.
+const NODE_HEADER: usize = 7;
+const LEAF: u8 = 0;
+const INTERNAL: u8 = 1;
+
+/// A page of the tree. A leaf links to the next leaf, 0 ending the chain. An
+/// internal node has one more child than keys: `children[i + 1]` holds the keys
+/// from `keys[i]` up to the next key.
+#[derive(Debug, Clone, Default)]
+struct Node {
+    keys: Vec<Vec<u8>>,
+    children: Vec<u32>,
+    next: u32,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn entry_size(&self, key: &[u8]) -> usize {
+        2 + key.len() + if self.is_leaf() { 0 } else { 4 }
+    }
+
+    fn size(&self) -> usize {
+        NODE_HEADER + self.keys.iter().map(|key| self.entry_size(key)).sum::<usize>()
+    }
+
+    /// `[kind u8][count u16][link u32]`, then `[length u16][key]` per key, each
+    /// followed by its child in internal nodes. The link is the next leaf, or the
+    /// first child.
+    fn encode(&self) -> Vec<u8> {
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        page.push(if self.is_leaf() { LEAF } else { INTERNAL });
+        page.extend((self.keys.len() as u16).to_le_bytes());
+        page.extend(self.children.first().unwrap_or(&self.next).to_le_bytes());
+        for (index, key) in self.keys.iter().enumerate() {
+            page.extend((key.len() as u16).to_le_bytes());
+            page.extend_from_slice(key);
+            if let Some(child) = self.children.get(index + 1) {
+                page.extend(child.to_le_bytes());
+            }
+        }
+        page.resize(PAGE_SIZE, 0);
+        page
+    }
+
+    fn decode(page: &[u8]) -> io::Result<Node> {
+        let corrupt = || io::Error::new(io::ErrorKind::InvalidData, "Corrupt index page");
+        let mut cursor = page;
+        let mut take = |len: usize| -> io::Result<&[u8]> {
+            if cursor.len() < len {
+                return Err(corrupt());
+            }
+            let (taken, rest) = cursor.split_at(len);
+            cursor = rest;
+            Ok(taken)
+        };
+
+        let kind = take(1)?[0];
+        let count = u16::from_le_bytes(take(2)?.try_into().unwrap());
+        let link = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        let mut node = match kind {
+            LEAF => Node { next: link, ..Node::default() },
+            INTERNAL => Node { children: vec![link], ..Node::default() },
+            _ => return Err(corrupt()),
+        };
+        for _ in 0..count {
+            let len = u16::from_le_bytes(take(2)?.try_into().unwrap());
+            node.keys.push(take(len as usize)?.to_vec());
+            if kind == INTERNAL {
+                node.children.push(u32::from_le_bytes(take(4)?.try_into().unwrap()));
+            }
+        }
+        Ok(node)
+    }
+
+    /// Where to split an overflowing node so both halves hold about as many bytes.
+    fn split_point(&self) -> usize {
+        let total: usize = self.keys.iter().map(|key| self.entry_size(key)).sum();
+        let mut size = 0;
+        let mut point = self.keys.len();
+        for (index, key) in self.keys.iter().enumerate() {
+            size += self.entry_size(key);
+            if size * 2 >= total {
+                point = index + 1;
+                break;
+            }
+        }
+        // An internal node gives its middle key to its parent, so both halves
+        // must keep at least one key besides it.
+        let last = if self.is_leaf() { self.keys.len() - 1 } else { self.keys.len() - 2 };
+        point.clamp(1, last)
+    }
+}
+
+/// A B+ tree of byte keys in fixed-size pages of an index file. Page 0 is a header
+/// with the root and the number of pages. Pages are read as they are visited, and
+/// the changed ones are written back through `ops`, as part of the statement's
+/// write-ahead log entry. Keys are unique; entries are never removed, as changing
+/// a table's rows rebuilds its indexes.
+pub struct BTree<'a> {
+    storage: &'a dyn Storage,
+    path: String,
+    root: u32,
+    page_count: u32,
+    pages: HashMap<u32, Node>,
+    dirty: BTreeSet<u32>,
+    /// Built from scratch, so the file is replaced rather than updated.
+    rebuilt: bool,
+}
+
+impl<'a> BTree<'a> {
+    /// Opens the tree in the file at `path`, or an empty one if there is none.
+    pub fn open(storage: &'a dyn Storage, path: &str) -> io::Result<BTree<'a>> {
+        if storage.file_len(path)? == 0 {
+            return Ok(BTree::build(storage, path, Vec::new()));
+        }
+
+        let header = storage.read_range(path, 0, 16)?;
+        if header.len() < 16 || &header[..8] != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not an index file", path)));
+        }
+        Ok(BTree {
+            storage,
+            path: path.to_string(),
+            root: u32::from_le_bytes(header[8..12].try_into().unwrap()),
+            page_count: u32::from_le_bytes(header[12..16].try_into().unwrap()),
+            pages: HashMap::new(),
+            dirty: BTreeSet::new(),
+            rebuilt: false,
+        })
+    }
+
+    /// Builds a tree from keys sorted in ascending order, filling every page.
+    pub fn build(storage: &'a dyn Storage, path: &str, keys: Vec<Vec<u8>>) -> BTree<'a> {
+        let mut tree = BTree {
+            storage,
+            path: path.to_string(),
+            root: 0,
+            page_count: 1,
+            pages: HashMap::new(),
+            dirty: BTreeSet::new(),
+            rebuilt: true,
+        };
+
+        // The first key under each node of the level being built, with its page.
+        let mut level: Vec<(Vec<u8>, u32)> = Vec::new();
+        let mut leaf = Node::default();
+        for key in keys {
+            if leaf.size() + leaf.entry_size(&key) > PAGE_SIZE {
+                let first = leaf.keys[0].clone();
+                level.push((first, tree.allocate(std::mem::take(&mut leaf))));
+            }
+            leaf.keys.push(key);
+        }
+        let first = leaf.keys.first().cloned().unwrap_or_default();
+        level.push((first, tree.allocate(leaf)));
+        // Leaves were allocated in order, each linking to the page after it.
+        for (_, page) in &level[..level.len() - 1] {
+            tree.pages.get_mut(page).unwrap().next = page + 1;
+        }
+
+        while level.len() > 1 {
+            let mut parents = Vec::new();
+            let mut node = Node::default();
+            let mut first = Vec::new();
+            for (key, page) in level {
+                if node.children.is_empty() {
+                    first = key;
+                    node.children.push(page);
+                } else if node.size() + node.entry_size(&key) > PAGE_SIZE {
+                    parents.push((std::mem::replace(&mut first, key), tree.allocate(std::mem::take(&mut node))));
+                    node.children.push(page);
+                } else {
+                    node.keys.push(key);
+                    node.children.push(page);
+                }
+            }
+            parents.push((first, tree.allocate(node)));
+            level = parents;
+        }
+        tree.root = level[0].1;
+        tree
+    }
+
+    fn allocate(&mut self, node: Node) -> u32 {
+        let page = self.page_count;
+        self.page_count += 1;
+        self.pages.insert(page, node);
+        self.dirty.insert(page);
+        page
+    }
+
+    fn node(&mut self, page: u32) -> io::Result<&mut Node> {
+        if !self.pages.contains_key(&page) {
+            if page == 0 || page >= self.page_count {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} links to a missing page", self.path)));
+            }
+            let bytes = self.storage.read_range(&self.path, page as u64 * PAGE_SIZE as u64, PAGE_SIZE)?;
+            self.pages.insert(page, Node::decode(&bytes)?);
+        }
+        Ok(self.pages.get_mut(&page).unwrap())
+    }
+
+    /// Adds a key, splitting the pages it no longer fits in from the leaf up.
+    pub fn insert(&mut self, key: Vec<u8>) -> io::Result<()> {
+        let mut path = Vec::new();
+        let mut page = self.root;
+        loop {
+            let node = self.node(page)?;
+            if node.is_leaf() {
+                break;
+            }
+            let index = node.keys.partition_point(|k| k.as_slice() <= key.as_slice());
+            path.push((page, index));
+            page = node.children[index];
+        }
+
+        let node = self.node(page)?;
+        let position = node.keys.partition_point(|k| k.as_slice() < key.as_slice());
+        node.keys.insert(position, key);
+        self.dirty.insert(page);
+
+        while self.node(page)?.size() > PAGE_SIZE {
+            let (separator, right) = self.split(page)?;
+            match path.pop() {
+                Some((parent, index)) => {
+                    let node = self.node(parent)?;
+                    node.keys.insert(index, separator);
+                    node.children.insert(index + 1, right);
+                    self.dirty.insert(parent);
+                    page = parent;
+                }
+                None => {
+                    self.root = self.allocate(Node { keys: vec![separator], children: vec![page, right], next: 0 });
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the upper half of a node to a new page, returning the key that
+    /// separates the halves in their parent and the new page.
+    fn split(&mut self, page: u32) -> io::Result<(Vec<u8>, u32)> {
+        let new_page = self.page_count;
+        let node = self.node(page)?;
+        let point = node.split_point();
+        let mut keys = node.keys.split_off(point);
+        let (separator, right) = if node.is_leaf() {
+            let right = Node { keys, children: Vec::new(), next: node.next };
+            node.next = new_page;
+            (right.keys[0].clone(), right)
+        } else {
+            let separator = keys.remove(0);
+            (separator, Node { keys, children: node.children.split_off(point + 1), next: 0 })
+        };
+        self.dirty.insert(page);
+        Ok((separator, self.allocate(right)))
+    }
+
+    /// Calls `visit` with the keys from `low` up to, but not including, `high`, in
+    /// order, until it returns false.
+    pub fn scan(&mut self, low: &[u8], high: Option<&[u8]>, mut visit: impl FnMut(&[u8]) -> bool) -> io::Result<()> {
+        let mut page = self.root;
+        loop {
+            let node = self.node(page)?;
+            if node.is_leaf() {
+                break;
+            }
+            page = node.children[node.keys.partition_point(|k| k.as_slice() <= low)];
+        }
+
+        let mut start = self.node(page)?.keys.partition_point(|k| k.as_slice() < low);
+        loop {
+            let node = self.node(page)?;
+            for key in &node.keys[start..] {
+                if high.is_some_and(|high| key.as_slice() >= high) || !visit(key) {
+                    return Ok(());
+                }
+            }
+            if node.next == 0 {
+                return Ok(());
+            }
+            page = node.next;
+            start = 0;
+        }
+    }
+
+    /// The writes that save the header and the changed pages.
+    pub fn ops(self) -> Vec<WalOp> {
+        let mut ops = Vec::new();
+        if self.rebuilt {
+            ops.push(WalOp::Truncate { path: self.path.clone(), len: 0 });
+        }
+        if self.dirty.is_empty() {
+            return ops;
+        }
+
+        let mut header = MAGIC.to_vec();
+        header.extend(self.root.to_le_bytes());
+        header.extend(self.page_count.to_le_bytes());
+        header.resize(PAGE_SIZE, 0);
+        let pages = std::iter::once((0, header)).chain(self.dirty.iter().map(|page| (*page, self.pages[page].encode())));
+        ops.extend(page_writes(&self.path, pages));
+        ops
+    }
+}
+
+/// Writes pages given in ascending order, runs of consecutive pages together.
+pub(crate) fn page_writes(path: &str, pages: impl IntoIterator<Item = (u32, Vec<u8>)>) -> Vec<WalOp> {
+    let mut ops = Vec::new();
+    let mut run: Option<(u32, Vec<u8>)> = None;
+    for (page, bytes) in pages {
+        match &mut run {
+            Some((start, run_bytes)) if *start as usize + run_bytes.len() / PAGE_SIZE == page as usize => {
+                run_bytes.extend(bytes);
+            }
+            _ => {
+                if let Some((start, bytes)) = run.replace((page, bytes)) {
+                    ops.push(WalOp::Write { path: path.to_string(), offset: start as u64 * PAGE_SIZE as u64, bytes });
+                }
+            }
+        }
+    }
+    if let Some((start, bytes)) = run {
+        ops.push(WalOp::Write { path: path.to_string(), offset: start as u64 * PAGE_SIZE as u64, bytes });
+    }
+    ops
+}
@@ -0,0 +1,114 @@
+use crate::io::reader::{decode_row, read_frames};
+use crate::io::writer::frame_row;
+use crate::types::types::{ChunkHeader, ColumnStats, TableDefinition};
+use std::cmp::Ordering;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+pub const CHUNK_MARKER: u8 = 0xC5;
+pub const CHUNK_ROWS: usize = 4096;
+
+/// Encodes rows into LZ4 framed chunks of at most `CHUNK_ROWS` rows each.
+/// Every chunk is laid out as `[0xC5][header size u64][header][lz4 frame]`,
+/// where the frame decompresses to the same row framing as uncompressed tables.
+pub fn encode_chunks(rows: &[Vec<u8>], table_definition: &TableDefinition) -> io::Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+
+    for chunk_rows in rows.chunks(CHUNK_ROWS) {
+        let mut encoder = lz4::EncoderBuilder::new().build(Vec::new())?;
+        for row in chunk_rows {
+            encoder.write_all(&frame_row(row))?;
+        }
+        let (compressed, result) = encoder.finish();
+        result?;
+
+        let header = ChunkHeader {
+            row_count: chunk_rows.len() as u64,
+            compressed_size: compressed.len() as u64,
+            stats: compute_stats(chunk_rows, table_definition)?,
+        };
+        let header_bytes = bincode::serialize(&header).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Serialization error: {}", e))
+        })?;
+
+        encoded.push(CHUNK_MARKER);
+        encoded.extend((header_bytes.len() as u64).to_le_bytes());
+        encoded.extend(header_bytes);
+        encoded.extend(compressed);
+    }
+
+    Ok(encoded)
+}
+
+/// Reads the next chunk header, leaving the reader positioned at the start of
+/// the compressed payload. Returns `None` at end of file.
+pub fn next_chunk_header<R: Read>(reader: &mut R) -> io::Result<Option<ChunkHeader>> {
+    let mut marker = [0u8; 1];
+    if reader.read_exact(&mut marker).is_err() {
+        return Ok(None);
+    }
+
+    if marker[0] != CHUNK_MARKER {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid chunk marker",
+        ));
+    }
+
+    let mut size_buf = [0u8; 8];
+    reader.read_exact(&mut size_buf)?;
+    let mut header_bytes = vec![0u8; u64::from_le_bytes(size_buf) as usize];
+    reader.read_exact(&mut header_bytes)?;
+
+    let header = bincode::deserialize(&header_bytes).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("Deserialization error: {}", e))
+    })?;
+    Ok(Some(header))
+}
+
+pub fn read_chunk_rows<R: Read>(reader: &mut R, header: &ChunkHeader) -> io::Result<Vec<Vec<u8>>> {
+    let mut compressed = vec![0u8; header.compressed_size as usize];
+    reader.read_exact(&mut compressed)?;
+
+    let mut decoder = lz4::Decoder::new(&compressed[..])?;
+    let rows = read_frames(&mut decoder)?;
+    if rows.len() as u64 != header.row_count {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Chunk row count does not match its header",
+        ));
+    }
+
+    Ok(rows)
+}
+
+pub fn skip_chunk_rows<R: Seek>(reader: &mut R, header: &ChunkHeader) -> io::Result<()> {
+    reader.seek(SeekFrom::Current(header.compressed_size as i64))?;
+    Ok(())
+}
+
+fn compute_stats(rows: &[Vec<u8>], table_definition: &TableDefinition) -> io::Result<Vec<ColumnStats>> {
+    let mut stats = vec![ColumnStats::default(); table_definition.columns.len()];
+
+    for row in rows {
+        let decoded = decode_row(row, table_definition)?;
+        for (column, column_stats) in table_definition.columns.iter().zip(stats.iter_mut()) {
+            let Some(value) = decoded.get(&column.name) else {
+                continue;
+            };
+            // NaN has no place in an ordering, so it never narrows the range.
+            if value.partial_cmp(value).is_none() {
+                continue;
+            }
+
+            if column_stats.min.as_ref().is_none_or(|min| value.partial_cmp(min) == Some(Ordering::Less)) {
+                column_stats.min = Some(value.clone());
+            }
+            if column_stats.max.as_ref().is_none_or(|max| value.partial_cmp(max) == Some(Ordering::Greater)) {
+                column_stats.max = Some(value.clone());
+            }
+        }
+    }
+
+    Ok(stats)
+}
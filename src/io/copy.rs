@@ -0,0 +1,513 @@
+use crate::database::database::Database;
+use crate::io::format::format_value;
+use crate::io::interchange::{arrow_columns, read_columnar, write_columnar};
+use crate::io::reader::{read_batch, read_table_definition, table_exists};
+use crate::io::storage::Storage;
+use crate::io::writer::{append_offset, remove_table, serialize_value, table_definition_ops, write_table_definition};
+use crate::query::query::{append_values, next_id};
+use crate::query::temporal::now;
+use crate::types::error::{Position, Result, RqlError};
+use crate::types::types::{
+    ColumnBatch, ColumnDefinition, CopyDefinition, CopyFormat, DataType, ResultSet, SelectDefinition, TableDefinition,
+    Timings, Value, WalOp,
+};
+use csv::StringRecord;
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+/// Rows checked and appended at a time during an import.
+pub const IMPORT_BATCH_ROWS: usize = 65_536;
+
+/// Loads a file into a table in the format of the `COPY`. An import that fails
+/// partway is undone, leaving the table, or the table it would have created,
+/// untouched. Returns the number of rows imported.
+pub fn import(db: &Database, copy: &CopyDefinition) -> Result<usize> {
+    undo_on_failure(db, &copy.table_name, |db| match copy.format {
+        CopyFormat::Csv => import_csv(db, copy),
+        CopyFormat::Arrow | CopyFormat::Parquet => import_columnar(db, copy),
+    })
+}
+
+/// Runs a bulk import into a table, which appends its batches as statements of
+/// their own, and undoes them if it fails: the data file is cut back to where
+/// the import started, the table's definition restored and its index files
+/// removed, to be rebuilt when next used. A table the import created is dropped.
+pub(crate) fn undo_on_failure<F>(db: &Database, table_name: &str, import: F) -> Result<usize>
+where
+    F: FnOnce(&Database) -> Result<usize>,
+{
+    let start = ImportStart::record(db, table_name)?;
+    import(db).or_else(|e| {
+        start.undo(db, table_name)?;
+        Err(e)
+    })
+}
+
+/// A table as a bulk import found it.
+struct ImportStart {
+    /// The table's definition, or `None` if the import creates the table.
+    table_definition: Option<TableDefinition>,
+    /// Where the import's appends start in the data file.
+    offset: u64,
+    /// The bytes of the data file from `offset` to its end, which appends rewrite:
+    /// nothing, or the last LZ4 chunk while it has room for more rows.
+    tail: Vec<u8>,
+}
+
+impl ImportStart {
+    fn record(db: &Database, table_name: &str) -> Result<ImportStart> {
+        if !table_exists(db, table_name)? {
+            return Ok(ImportStart { table_definition: None, offset: 0, tail: Vec::new() });
+        }
+
+        let table_definition = read_table_definition(db, table_name)?;
+        let (owner, data_path) = db.data_location(table_name)?;
+        let storage = owner.storage();
+        let offset = append_offset(storage, &data_path, table_definition.compression)?;
+        let tail = read_tail(storage, &data_path, offset)?;
+        Ok(ImportStart { table_definition: Some(table_definition), offset, tail })
+    }
+
+    fn undo(self, db: &Database, table_name: &str) -> Result<()> {
+        let Some(table_definition) = self.table_definition else {
+            if table_exists(db, table_name)? {
+                remove_table(db, table_name, &read_table_definition(db, table_name)?)?;
+            }
+            return Ok(());
+        };
+
+        let (owner, data_path) = db.data_location(table_name)?;
+        if read_tail(owner.storage(), &data_path, self.offset)? == self.tail {
+            return Ok(());
+        }
+
+        let mut ops = table_definition_ops(db, table_name, &table_definition)?;
+        ops.push(WalOp::Truncate { path: data_path.clone(), len: self.offset });
+        if !self.tail.is_empty() {
+            ops.push(WalOp::Write { path: data_path, offset: self.offset, bytes: self.tail });
+        }
+        for index in &table_definition.indexes {
+            ops.push(WalOp::Remove { path: db.index_location(table_name, &index.name)?.1 });
+        }
+        owner.storage().commit(&ops)?;
+        Ok(())
+    }
+}
+
+/// The bytes of a data file from `offset` to its end.
+fn read_tail(storage: &dyn Storage, data_path: &str, offset: u64) -> Result<Vec<u8>> {
+    let len = storage.file_len(data_path)?.saturating_sub(offset);
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+    Ok(storage.read_range(data_path, offset, len as usize)?)
+}
+
+/// Writes a table, or the result of the `COPY`'s query, to a file in the format of
+/// the `COPY`. Returns the number of rows exported.
+pub fn export(db: &Database, copy: &CopyDefinition) -> Result<usize> {
+    match copy.format {
+        CopyFormat::Csv => export_csv(db, copy),
+        format => {
+            let batch = match copy.query {
+                Some(_) => source_rows(db, copy)?.to_batch()?,
+                None => read_batch(db, &copy.table_name)?,
+            };
+            write_columnar(&copy.path, format, &batch)?;
+            Ok(batch.len())
+        }
+    }
+}
+
+/// Loads a CSV file into a table, creating the table with an inferred schema if
+/// it does not exist yet. An empty field is NULL and `""` an empty string, as
+/// `export_csv` writes them. The file is checked in full before any row is
+/// written, so a malformed file leaves the table untouched. Returns the number of
+/// rows imported.
+pub fn import_csv(db: &Database, copy: &CopyDefinition) -> Result<usize> {
+    let table_definition = if table_exists(db, &copy.table_name)? {
+        let table_definition = read_table_definition(db, &copy.table_name)?;
+        validate(copy, &table_definition)?;
+        table_definition
+    } else {
+        let table_definition = infer_table_definition(copy)?;
+        write_table_definition(db, &table_definition)?;
+        table_definition
+    };
+
+    let mut quoted_fields = QuotedFields::open(copy)?;
+    let rows = open_reader(copy)?.into_records().map(|record| {
+        let record = record.map_err(csv_error)?;
+        let quoted = quoted_fields.of(&record)?;
+        record
+            .iter()
+            .zip(quoted)
+            .zip(&table_definition.columns)
+            .map(|((field, quoted), column)| field_value(field, quoted, column))
+            .collect::<Result<Vec<Value>>>()
+    });
+    append_rows(db, &copy.table_name, rows)
+}
+
+/// Appends rows in schema order to a table, `IMPORT_BATCH_ROWS` per write. As with
+/// `INSERT`, NULL in the `AUTOINCREMENT` column takes the next id, explicit ids
+/// move the counter past them and each batch is checked against the table's CHECK
+/// constraints and foreign keys before it is written. Returns the number of rows written.
+pub fn append_rows<I>(db: &Database, table_name: &str, rows: I) -> Result<usize>
+where
+    I: IntoIterator<Item = Result<Vec<Value>>>,
+{
+    let mut table_definition = read_table_definition(db, table_name)?;
+    let auto_increment = table_definition.columns.iter().position(|column| column.auto_increment);
+    let now = now();
+    let mut imported = 0;
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_ROWS);
+    let mut rows = rows.into_iter().peekable();
+    while let Some(row) = rows.next() {
+        batch.push(row?);
+        if batch.len() == IMPORT_BATCH_ROWS || rows.peek().is_none() {
+            let mut last_id = table_definition.last_id;
+            if let Some(index) = auto_increment {
+                let column = &table_definition.columns[index];
+                for row in &mut batch {
+                    row[index] = next_id(column, std::mem::replace(&mut row[index], Value::Null), &mut last_id)?;
+                }
+            }
+            imported += batch.len();
+            append_values(db, table_name, &mut table_definition, &batch, last_id, now)?;
+            batch.clear();
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Writes the rows of a table or query to a CSV file in schema order. NULL is
+/// written as an empty field and an empty string as `""`. Returns the number of
+/// rows exported.
+pub fn export_csv(db: &Database, copy: &CopyDefinition) -> Result<usize> {
+    let rows = source_rows(db, copy)?;
+
+    let file = File::create(&copy.path).map_err(|e| file_error(copy, e))?;
+    // Fields are quoted here, since the writer would leave empty strings unquoted.
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(copy.delimiter)
+        .quote_style(csv::QuoteStyle::Never)
+        .from_writer(file);
+    if copy.header {
+        writer
+            .write_record(rows.columns().iter().map(|c| quote_field(c.name(), copy.delimiter)))
+            .map_err(csv_error)?;
+    }
+    for row in &rows {
+        let fields = row.values().iter().map(|value| match value {
+            Value::Null => String::new(),
+            value => quote_field(&format_value(value, None), copy.delimiter),
+        });
+        writer.write_record(fields).map_err(csv_error)?;
+    }
+    writer.flush()?;
+
+    Ok(rows.len())
+}
+
+/// Loads an Arrow IPC or Parquet file into a table, matching columns by name. If
+/// the table does not exist it is created from the file's schema. Every record
+/// batch is converted before anything is written, so a file that does not fit the
+/// table leaves it untouched.
+pub fn import_columnar(db: &Database, copy: &CopyDefinition) -> Result<usize> {
+    let (schema, record_batches) = read_columnar(&copy.path, copy.format)?;
+
+    let (table_definition, batches) = if table_exists(db, &copy.table_name)? {
+        let table_definition = read_table_definition(db, &copy.table_name)?;
+        let columns = ColumnBatch::for_table(&table_definition).columns;
+        let batches = record_batches
+            .iter()
+            .map(|record_batch| ColumnBatch::from_record_batch_as(record_batch, columns.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        (None, batches)
+    } else {
+        let mut batches = record_batches
+            .iter()
+            .map(ColumnBatch::from_record_batch)
+            .collect::<Result<Vec<_>>>()?;
+        let table_definition = TableDefinition {
+            name: copy.table_name.clone(),
+            columns: arrow_columns(&schema)?
+                .iter()
+                .enumerate()
+                .map(|(index, column)| ColumnDefinition {
+                    name: column_name(index, column.name()),
+                    data_type: column.data_type(),
+                    nullable: column.is_nullable(),
+                    unique: false,
+                    primary_key: false,
+                    auto_increment: false,
+                    default: None,
+                })
+                .collect(),
+            ..TableDefinition::default()
+        };
+        let columns = ColumnBatch::for_table(&table_definition).columns;
+        batches.iter_mut().for_each(|batch| batch.columns = columns.clone());
+        (Some(table_definition), batches)
+    };
+
+    if let Some(table_definition) = table_definition {
+        write_table_definition(db, &table_definition)?;
+    }
+
+    let rows = batches.iter().flat_map(|batch| (0..batch.len()).map(|index| Ok(batch.row(index))));
+    append_rows(db, &copy.table_name, rows)
+}
+
+/// The rows a `COPY ... TO` exports: the result of its query, or the whole table.
+fn source_rows(db: &Database, copy: &CopyDefinition) -> Result<ResultSet> {
+    let select = match &copy.query {
+        Some(query) => SelectDefinition::from_sql(query)?,
+        None => SelectDefinition::table(&copy.table_name),
+    };
+    select.execute(db, &mut Timings::default())
+}
+
+fn open_reader(copy: &CopyDefinition) -> Result<csv::Reader<File>> {
+    let file = File::open(&copy.path).map_err(|e| file_error(copy, e))?;
+    Ok(csv::ReaderBuilder::new()
+        .has_headers(copy.header)
+        .delimiter(copy.delimiter)
+        .from_reader(file))
+}
+
+/// Quotes a CSV field if it is empty, so it is not read back as NULL, or if it
+/// holds the delimiter, a quote or a line break.
+fn quote_field(field: &str, delimiter: u8) -> String {
+    if field.is_empty() || field.bytes().any(|b| matches!(b, b'"' | b'\n' | b'\r') || b == delimiter) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Tells which fields of a record were quoted. The csv reader unquotes fields,
+/// so an empty string, written `""`, would read the same as an empty field,
+/// which is NULL. Records with an empty field are scanned again in the file.
+struct QuotedFields {
+    file: BufReader<File>,
+    position: u64,
+    delimiter: u8,
+}
+
+impl QuotedFields {
+    fn open(copy: &CopyDefinition) -> Result<QuotedFields> {
+        let file = File::open(&copy.path).map_err(|e| file_error(copy, e))?;
+        Ok(QuotedFields { file: BufReader::new(file), position: 0, delimiter: copy.delimiter })
+    }
+
+    /// Whether each field of `record` starts with a quote.
+    fn of(&mut self, record: &StringRecord) -> Result<Vec<bool>> {
+        let mut quoted = vec![false; record.len()];
+        let Some(start) = record.position().map(|p| p.byte()).filter(|_| record.iter().any(str::is_empty)) else {
+            return Ok(quoted);
+        };
+        // Records are read in order, so this usually skips ahead within the buffer.
+        self.file.seek_relative(start as i64 - self.position as i64)?;
+        self.position = start;
+
+        let mut fields = quoted.iter_mut();
+        let mut field = fields.next();
+        let mut field_start = true;
+        let mut in_quote = false;
+        for byte in (&mut self.file).bytes() {
+            let byte = byte?;
+            self.position += 1;
+            if std::mem::take(&mut field_start) {
+                if let Some(quoted) = &mut field {
+                    **quoted = byte == b'"';
+                }
+            }
+            match byte {
+                b'"' => in_quote = !in_quote,
+                b'\n' | b'\r' if !in_quote => break,
+                byte if byte == self.delimiter && !in_quote => {
+                    field = fields.next();
+                    field_start = true;
+                    if field.is_none() {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(quoted)
+    }
+}
+
+/// Checks every record against the table before anything is written.
+fn validate(copy: &CopyDefinition, table_definition: &TableDefinition) -> Result<()> {
+    let mut quoted_fields = QuotedFields::open(copy)?;
+    for record in open_reader(copy)?.records() {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map_or(0, |p| p.line());
+        if record.len() != table_definition.columns.len() {
+            return Err(RqlError::Schema(format!(
+                "{} line {}: expected {} fields for table '{}', found {}",
+                copy.path,
+                line,
+                table_definition.columns.len(),
+                table_definition.name,
+                record.len()
+            )));
+        }
+        for ((field, quoted), column) in record.iter().zip(quoted_fields.of(&record)?).zip(&table_definition.columns) {
+            field_value(field, quoted, column).map_err(|e| match e {
+                RqlError::Constraint(_) => RqlError::Constraint(format!(
+                    "{} line {}: column '{}' cannot be NULL",
+                    copy.path, line, column.name
+                )),
+                _ => RqlError::Type(format!(
+                    "{} line {}: '{}' is not a valid {} value for column '{}'",
+                    copy.path,
+                    line,
+                    field,
+                    column.data_type.to_sql_type(),
+                    column.name
+                )),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Picks the narrowest type every value of a column parses as, trying INTEGER,
+/// FLOAT and BOOLEAN before falling back to TEXT. Column names come from the
+/// header, or are `col1`, `col2`, ... without one.
+fn infer_table_definition(copy: &CopyDefinition) -> Result<TableDefinition> {
+    let mut reader = open_reader(copy)?;
+    let names: Vec<String> = if copy.header {
+        reader
+            .headers()
+            .map_err(csv_error)?
+            .iter()
+            .enumerate()
+            .map(|(index, header)| column_name(index, header))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut quoted_fields = QuotedFields::open(copy)?;
+    let mut types: Vec<Option<DataType>> = vec![None; names.len()];
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        if types.is_empty() {
+            types = vec![None; record.len()];
+        }
+        if record.len() != types.len() {
+            let line = record.position().map_or(0, |p| p.line());
+            return Err(RqlError::Schema(format!(
+                "{} line {}: expected {} fields, found {}",
+                copy.path,
+                line,
+                types.len(),
+                record.len()
+            )));
+        }
+        for ((field, quoted), data_type) in record.iter().zip(quoted_fields.of(&record)?).zip(types.iter_mut()) {
+            if !field.is_empty() || quoted {
+                *data_type = Some(widen(*data_type, field));
+            }
+        }
+    }
+
+    if types.is_empty() {
+        return Err(RqlError::Schema(format!(
+            "Cannot infer a schema for '{}' from an empty file",
+            copy.table_name
+        )));
+    }
+
+    let columns = types
+        .into_iter()
+        .enumerate()
+        .map(|(index, data_type)| ColumnDefinition {
+            name: names.get(index).cloned().unwrap_or_else(|| format!("col{}", index + 1)),
+            data_type: data_type.unwrap_or(DataType::Text),
+            nullable: true,
+            unique: false,
+            primary_key: false,
+            auto_increment: false,
+            default: None,
+        })
+        .collect();
+
+    Ok(TableDefinition {
+        name: copy.table_name.clone(),
+        columns,
+        ..TableDefinition::default()
+    })
+}
+
+/// An empty field is NULL in every column, while a quoted empty field is an
+/// empty string. In the `AUTOINCREMENT` column NULL then takes the next id.
+fn field_value(field: &str, quoted: bool, column: &ColumnDefinition) -> Result<Value> {
+    if field.is_empty() && !quoted {
+        if !column.nullable && !column.auto_increment {
+            return Err(RqlError::Constraint(format!("Column '{}' cannot be NULL", column.name)));
+        }
+        return Ok(Value::Null);
+    }
+    Value::parse(field, &column.data_type).ok_or_else(|| {
+        RqlError::Type(format!("'{}' is not a valid {} value", field, column.data_type.to_sql_type()))
+    })
+}
+
+fn widen(current: Option<DataType>, field: &str) -> DataType {
+    let fits = |data_type: DataType| serialize_value(field, &data_type).is_ok();
+    // Numbers beyond the range of f32 would parse as infinity.
+    let fits_float = field.parse::<f32>().is_ok_and(|v| v.is_finite() || field.parse::<f64>().is_ok_and(|v| !v.is_finite()));
+    match current {
+        None | Some(DataType::Integer) if fits(DataType::Integer) => DataType::Integer,
+        None | Some(DataType::Integer) | Some(DataType::BigInt) if fits(DataType::BigInt) => DataType::BigInt,
+        None | Some(DataType::Integer) | Some(DataType::Float) if fits_float => DataType::Float,
+        None | Some(DataType::Integer) | Some(DataType::BigInt) | Some(DataType::Float) | Some(DataType::Double)
+            if fits(DataType::Double) =>
+        {
+            DataType::Double
+        }
+        None | Some(DataType::Boolean) if fits(DataType::Boolean) => DataType::Boolean,
+        None | Some(DataType::Date) if fits(DataType::Date) => DataType::Date,
+        None | Some(DataType::Date) | Some(DataType::Timestamp) if fits(DataType::Timestamp) => DataType::Timestamp,
+        None | Some(DataType::Time) if fits(DataType::Time) => DataType::Time,
+        _ => DataType::Text,
+    }
+}
+
+/// Turns a header field into a column name the SQL parser accepts.
+pub(crate) fn column_name(index: usize, header: &str) -> String {
+    let name: String = header
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        format!("col{}", index + 1)
+    } else {
+        name
+    }
+}
+
+fn file_error(copy: &CopyDefinition, e: std::io::Error) -> RqlError {
+    RqlError::Io(std::io::Error::new(e.kind(), format!("{}: {}", copy.path, e)))
+}
+
+fn csv_error(e: csv::Error) -> RqlError {
+    let message = format!("Malformed CSV: {}", e);
+    let line = e.position().map(|p| p.line() as usize);
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => RqlError::Io(e),
+        _ => RqlError::Parse {
+            message,
+            position: line.map(|line| Position { line, column: 1 }),
+        },
+    }
+}
@@ -0,0 +1,161 @@
+use crate::types::types::{BlobFormat, OutputFormat, ResultSet, Value};
+use prettytable::{format, Cell, Row, Table};
+use std::fmt::Write;
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name.to_lowercase().as_str() {
+            "table" => Some(OutputFormat::Table),
+            "csv" => Some(OutputFormat::Csv),
+            "json" => Some(OutputFormat::Json),
+            "ndjson" => Some(OutputFormat::Ndjson),
+            "markdown" | "md" => Some(OutputFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Markdown => "markdown",
+        }
+    }
+}
+
+/// Renders a result in `output_format`, with columns in schema order. Floats are
+/// printed with `precision` decimal places, or in their shortest exact form if `None`,
+/// and blobs in hex or base64 as `blobs` says.
+pub fn format_result(rows: &ResultSet, output_format: OutputFormat, precision: Option<usize>, blobs: BlobFormat) -> String {
+    let names: Vec<&str> = rows.columns().iter().map(|column| column.name()).collect();
+    let text = |value: &Value| match value {
+        Value::Blob(bytes) => blobs.format(bytes),
+        value => format_value(value, precision),
+    };
+
+    let mut out = String::new();
+    match output_format {
+        OutputFormat::Table => {
+            let mut table = Table::new();
+            table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            if !rows.is_empty() {
+                table.set_titles(Row::new(names.iter().map(|name| Cell::new(name)).collect()));
+            }
+            for row in rows {
+                table.add_row(Row::new(row.values().iter().map(|value| Cell::new(&text(value))).collect()));
+            }
+            out = table.to_string();
+        }
+        OutputFormat::Csv => {
+            let header: Vec<String> = names.iter().map(|name| csv_field(name)).collect();
+            let _ = writeln!(out, "{}", header.join(","));
+            for row in rows {
+                let fields: Vec<String> = row
+                    .values()
+                    .iter()
+                    .map(|value| match value {
+                        Value::Null => String::new(),
+                        value => csv_field(&text(value)),
+                    })
+                    .collect();
+                let _ = writeln!(out, "{}", fields.join(","));
+            }
+        }
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            let objects: Vec<String> = rows
+                .iter()
+                .map(|row| {
+                    let members: Vec<String> = names
+                        .iter()
+                        .zip(row.values())
+                        .map(|(name, value)| format!("{}:{}", json_string(name), json_value(value, precision, blobs)))
+                        .collect();
+                    format!("{{{}}}", members.join(","))
+                })
+                .collect();
+            if output_format == OutputFormat::Json {
+                let _ = writeln!(out, "[{}]", objects.join(",\n "));
+            } else {
+                for object in objects {
+                    let _ = writeln!(out, "{}", object);
+                }
+            }
+        }
+        OutputFormat::Markdown => {
+            let header: Vec<String> = names.iter().map(|name| markdown_cell(name)).collect();
+            let _ = writeln!(out, "| {} |", header.join(" | "));
+            let _ = writeln!(out, "|{}", "---|".repeat(names.len()));
+            for row in rows {
+                let cells: Vec<String> = row.values().iter().map(|value| markdown_cell(&text(value))).collect();
+                let _ = writeln!(out, "| {} |", cells.join(" | "));
+            }
+        }
+    }
+    out
+}
+
+pub fn format_value(value: &Value, precision: Option<usize>) -> String {
+    match (value, precision) {
+        (Value::Float(v), Some(precision)) => format!("{:.*}", precision, v),
+        (Value::Double(v), Some(precision)) => format!("{:.*}", precision, v),
+        (value, Some(_)) if value.components().is_some() => {
+            let components: Vec<String> =
+                value.components().unwrap_or_default().iter().map(|c| format_value(&Value::Float(*c), precision)).collect();
+            format!("[{}]", components.join(", "))
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break (RFC 4180).
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('\\', "\\\\").replace('|', "\\|").replace('\n', "<br>")
+}
+
+fn json_value(value: &Value, precision: Option<usize>, blobs: BlobFormat) -> String {
+    match value {
+        // JSON has no NaN or infinity.
+        Value::Float(v) if !v.is_finite() => "null".to_string(),
+        Value::Double(v) if !v.is_finite() => "null".to_string(),
+        Value::Null => "null".to_string(),
+        Value::Text(v) => json_string(v),
+        Value::Blob(v) => json_string(&blobs.format(v)),
+        Value::Date(_) | Value::Time(_) | Value::Timestamp(_) => json_string(&value.to_string()),
+        Value::Vec2(_) | Value::Vec3(_) | Value::Vec4(_) | Value::Mat4(_) | Value::Vector(_) => {
+            let components: Vec<String> =
+                value.components().unwrap_or_default().iter().map(|c| json_value(&Value::Float(*c), precision, blobs)).collect();
+            format!("[{}]", components.join(","))
+        }
+        _ => format_value(value, precision),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
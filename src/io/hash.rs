@@ -0,0 +1,399 @@
+use crate::io::btree::{page_writes, PAGE_SIZE};
+use crate::io::storage::Storage;
+use crate::types::types::{RowLocation, WalOp};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+
+const MAGIC: &[u8; 8] = b"RQLHASH1";
+/// Checksum, entry count and overflow link of a bucket page.
+const BUCKET_HEADER: usize = 10;
+/// Hash, key length, offset and row of an entry, besides the key itself.
+const ENTRY_OVERHEAD: usize = 18;
+/// Bucket pages listed by a directory page, after its checksum.
+const DIRECTORY_ENTRIES: usize = (PAGE_SIZE - 4) / 4;
+/// Directory pages the header has room for, which caps the number of buckets.
+const MAX_DIRECTORY_PAGES: usize = (PAGE_SIZE - 44) / 4;
+/// The average number of entries per bucket past which a bucket is split.
+const SPLIT_LOAD: u64 = 32;
+
+/// FNV-1a, which does not change between runs or builds like the standard hasher may.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let hash = fnv1a(bytes);
+    (hash ^ hash >> 32) as u32
+}
+
+fn corrupt(path: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Corrupt hash index {}", path))
+}
+
+/// Starts a page with the checksum of the rest of it.
+fn seal(mut page: Vec<u8>) -> Vec<u8> {
+    page.resize(PAGE_SIZE, 0);
+    let sum = checksum(&page[4..]);
+    page[..4].copy_from_slice(&sum.to_le_bytes());
+    page
+}
+
+/// The rest of a page whose checksum matches, or `None`.
+fn unseal(page: &[u8]) -> Option<&[u8]> {
+    if page.len() != PAGE_SIZE || page[..4] != checksum(&page[4..]).to_le_bytes() {
+        return None;
+    }
+    Some(&page[4..])
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    hash: u32,
+    key: Vec<u8>,
+    location: RowLocation,
+}
+
+/// A page of a bucket's chain: its entries and the next page of the chain, 0
+/// ending it. Pages on the free list are empty and link to the next free page.
+#[derive(Debug, Clone, Default)]
+struct Bucket {
+    entries: Vec<Entry>,
+    next: u32,
+}
+
+impl Bucket {
+    fn size(&self) -> usize {
+        BUCKET_HEADER + self.entries.iter().map(|entry| ENTRY_OVERHEAD + entry.key.len()).sum::<usize>()
+    }
+
+    fn fits(&self, entry: &Entry) -> bool {
+        self.size() + ENTRY_OVERHEAD + entry.key.len() <= PAGE_SIZE
+    }
+
+    /// `[checksum u32][count u16][next u32]`, then `[hash u32][length u16][key][offset u64][row u32]`
+    /// per entry.
+    fn encode(&self) -> Vec<u8> {
+        let mut page = vec![0; 4];
+        page.extend((self.entries.len() as u16).to_le_bytes());
+        page.extend(self.next.to_le_bytes());
+        for entry in &self.entries {
+            page.extend(entry.hash.to_le_bytes());
+            page.extend((entry.key.len() as u16).to_le_bytes());
+            page.extend_from_slice(&entry.key);
+            page.extend(entry.location.offset.to_le_bytes());
+            page.extend(entry.location.row.to_le_bytes());
+        }
+        seal(page)
+    }
+
+    fn decode(page: &[u8]) -> Option<Bucket> {
+        let mut cursor = unseal(page)?;
+        let mut take = |len: usize| -> Option<&[u8]> {
+            let (taken, rest) = cursor.split_at_checked(len)?;
+            cursor = rest;
+            Some(taken)
+        };
+
+        let count = u16::from_le_bytes(take(2)?.try_into().ok()?);
+        let next = u32::from_le_bytes(take(4)?.try_into().ok()?);
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let hash = u32::from_le_bytes(take(4)?.try_into().ok()?);
+            let len = u16::from_le_bytes(take(2)?.try_into().ok()?);
+            let key = take(len as usize)?.to_vec();
+            let offset = u64::from_le_bytes(take(8)?.try_into().ok()?);
+            let row = u32::from_le_bytes(take(4)?.try_into().ok()?);
+            entries.push(Entry { hash, key, location: RowLocation { offset, row } });
+        }
+        Some(Bucket { entries, next })
+    }
+}
+
+/// A linear hash index: keys map to buckets of pages in an index file, and the
+/// table of buckets grows one bucket at a time, by splitting the bucket at the
+/// split pointer whenever buckets hold too many entries on average. Page 0 is a
+/// header with the hashing state and the directory pages, which list the first
+/// page of every bucket. Every page carries a checksum, so a damaged file is
+/// reported as invalid data rather than read.
+pub struct HashIndex<'a> {
+    storage: &'a dyn Storage,
+    path: String,
+    /// Buckets are addressed with `level` bits of the hash, or one more below `split`.
+    level: u32,
+    split: u32,
+    page_count: u32,
+    entry_count: u64,
+    /// The first page of the free list, 0 if it is empty.
+    free: u32,
+    buckets: Vec<u32>,
+    directory_pages: Vec<u32>,
+    pages: HashMap<u32, Bucket>,
+    dirty: BTreeSet<u32>,
+    /// Built from scratch, so the file is replaced rather than updated.
+    rebuilt: bool,
+}
+
+impl<'a> HashIndex<'a> {
+    /// Opens the index in the file at `path`, or an empty one if there is none.
+    pub fn open(storage: &'a dyn Storage, path: &str) -> io::Result<HashIndex<'a>> {
+        if storage.file_len(path)? == 0 {
+            return Ok(HashIndex::build(storage, path, Vec::new()));
+        }
+
+        let header = storage.read_range(path, 0, PAGE_SIZE)?;
+        let fields = unseal(&header).filter(|fields| fields[..8] == *MAGIC).ok_or_else(|| corrupt(path))?;
+        let field = |index: usize| u32::from_le_bytes(fields[8 + index * 4..12 + index * 4].try_into().unwrap());
+        let (level, split, bucket_count, page_count, free, directory_count) =
+            (field(0), field(1), field(2), field(3), field(6), field(7) as usize);
+        let entry_count = u64::from_le_bytes(fields[24..32].try_into().unwrap());
+        let consistent = bucket_count >= 1
+            && directory_count == (bucket_count as usize).div_ceil(DIRECTORY_ENTRIES)
+            && directory_count <= MAX_DIRECTORY_PAGES
+            && split < 1u32.checked_shl(level).unwrap_or(0)
+            && (1u64 << level) + split as u64 == bucket_count as u64;
+        if !consistent {
+            return Err(corrupt(path));
+        }
+
+        let mut index = HashIndex {
+            storage,
+            path: path.to_string(),
+            level,
+            split,
+            page_count,
+            entry_count,
+            free,
+            buckets: Vec::with_capacity(bucket_count as usize),
+            directory_pages: (0..directory_count).map(|i| field(8 + i)).collect(),
+            pages: HashMap::new(),
+            dirty: BTreeSet::new(),
+            rebuilt: false,
+        };
+        for page in index.directory_pages.clone() {
+            let bytes = index.read_page(page)?;
+            let pages = unseal(&bytes).ok_or_else(|| corrupt(path))?;
+            let wanted = (bucket_count as usize - index.buckets.len()).min(DIRECTORY_ENTRIES);
+            for bucket in pages.chunks_exact(4).take(wanted) {
+                index.buckets.push(u32::from_le_bytes(bucket.try_into().unwrap()));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Builds an index of `entries`, keys with the locations of their rows, with
+    /// enough buckets that none has to be split for a while.
+    pub fn build(storage: &'a dyn Storage, path: &str, entries: Vec<(Vec<u8>, RowLocation)>) -> HashIndex<'a> {
+        let bucket_count = (entries.len() as u64 / (SPLIT_LOAD / 2))
+            .clamp(1, (MAX_DIRECTORY_PAGES * DIRECTORY_ENTRIES) as u64) as u32;
+        let level = bucket_count.ilog2();
+        let mut index = HashIndex {
+            storage,
+            path: path.to_string(),
+            level,
+            split: bucket_count - (1 << level),
+            page_count: 1,
+            entry_count: 0,
+            free: 0,
+            buckets: Vec::new(),
+            directory_pages: Vec::new(),
+            pages: HashMap::new(),
+            dirty: BTreeSet::new(),
+            rebuilt: true,
+        };
+        for _ in 0..(bucket_count as usize).div_ceil(DIRECTORY_ENTRIES) {
+            index.directory_pages.push(index.page_count);
+            index.page_count += 1;
+        }
+        for _ in 0..bucket_count {
+            let page = index.allocate().expect("a new index has no free pages to read");
+            index.buckets.push(page);
+        }
+        for (key, location) in entries {
+            let entry = Entry { hash: fnv1a(&key) as u32, key, location };
+            index.add(entry).expect("a new index has all of its pages in memory");
+        }
+        index
+    }
+
+    fn read_page(&self, page: u32) -> io::Result<Vec<u8>> {
+        if page == 0 || page >= self.page_count {
+            return Err(corrupt(&self.path));
+        }
+        self.storage.read_range(&self.path, page as u64 * PAGE_SIZE as u64, PAGE_SIZE)
+    }
+
+    fn page(&mut self, page: u32) -> io::Result<&mut Bucket> {
+        if !self.pages.contains_key(&page) {
+            let bucket = Bucket::decode(&self.read_page(page)?).ok_or_else(|| corrupt(&self.path))?;
+            self.pages.insert(page, bucket);
+        }
+        Ok(self.pages.get_mut(&page).unwrap())
+    }
+
+    /// A new empty page, taken from the free list if it has one.
+    fn allocate(&mut self) -> io::Result<u32> {
+        let page = match self.free {
+            0 => {
+                self.page_count += 1;
+                self.page_count - 1
+            }
+            free => {
+                self.free = self.page(free)?.next;
+                free
+            }
+        };
+        self.pages.insert(page, Bucket::default());
+        self.dirty.insert(page);
+        Ok(page)
+    }
+
+    fn bucket_of(&self, hash: u32) -> usize {
+        let bucket = hash & ((1u64 << self.level) - 1) as u32;
+        if bucket < self.split {
+            (hash & ((1u64 << (self.level + 1)) - 1) as u32) as usize
+        } else {
+            bucket as usize
+        }
+    }
+
+    /// The pages of a bucket's chain, in order.
+    fn chain(&mut self, bucket: usize) -> io::Result<Vec<u32>> {
+        let mut pages = vec![self.buckets[bucket]];
+        loop {
+            let next = self.page(*pages.last().unwrap())?.next;
+            if next == 0 {
+                return Ok(pages);
+            }
+            if pages.len() as u32 >= self.page_count {
+                return Err(corrupt(&self.path));
+            }
+            pages.push(next);
+        }
+    }
+
+    /// The locations of the rows whose key is `key`.
+    pub fn get(&mut self, key: &[u8]) -> io::Result<Vec<RowLocation>> {
+        let hash = fnv1a(key) as u32;
+        let mut locations = Vec::new();
+        for page in self.chain(self.bucket_of(hash))? {
+            for entry in &self.page(page)?.entries {
+                if entry.hash == hash && entry.key == key {
+                    locations.push(entry.location);
+                }
+            }
+        }
+        Ok(locations)
+    }
+
+    /// Adds the key of a row, then splits the next bucket if buckets are too full.
+    pub fn insert(&mut self, key: Vec<u8>, location: RowLocation) -> io::Result<()> {
+        self.add(Entry { hash: fnv1a(&key) as u32, key, location })?;
+        let at_limit = self.buckets.len() >= MAX_DIRECTORY_PAGES * DIRECTORY_ENTRIES;
+        if self.entry_count > self.buckets.len() as u64 * SPLIT_LOAD && !at_limit {
+            self.split_bucket()?;
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, entry: Entry) -> io::Result<()> {
+        let chain = self.chain(self.bucket_of(entry.hash))?;
+        self.entry_count += 1;
+        self.place(&chain, vec![entry])
+    }
+
+    /// Puts entries in the first pages of `chain` with room for them, extending
+    /// the chain with new pages when it is full.
+    fn place(&mut self, chain: &[u32], entries: Vec<Entry>) -> io::Result<()> {
+        let mut pages = chain.to_vec();
+        for entry in entries {
+            let mut placed = false;
+            for page in &pages {
+                let bucket = self.page(*page)?;
+                if bucket.fits(&entry) {
+                    bucket.entries.push(entry.clone());
+                    self.dirty.insert(*page);
+                    placed = true;
+                    break;
+                }
+            }
+            if !placed {
+                let page = self.allocate()?;
+                let last = *pages.last().unwrap();
+                self.page(last)?.next = page;
+                self.dirty.insert(last);
+                self.page(page)?.entries.push(entry);
+                pages.push(page);
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits the bucket at the split pointer: the entries whose hash has the next
+    /// bit set move to a new bucket, and emptied overflow pages go to the free list.
+    fn split_bucket(&mut self) -> io::Result<()> {
+        let bucket = self.split as usize;
+        let chain = self.chain(bucket)?;
+        let mut entries = Vec::new();
+        for page in &chain {
+            let page = self.page(*page)?;
+            entries.append(&mut page.entries);
+            page.next = 0;
+        }
+        self.dirty.insert(chain[0]);
+        for page in chain[1..].iter().rev() {
+            let free = self.free;
+            self.page(*page)?.next = free;
+            self.dirty.insert(*page);
+            self.free = *page;
+        }
+
+        let new_page = self.allocate()?;
+        if self.buckets.len().is_multiple_of(DIRECTORY_ENTRIES) {
+            let directory_page = self.allocate()?;
+            self.pages.remove(&directory_page);
+            self.dirty.remove(&directory_page);
+            self.directory_pages.push(directory_page);
+        }
+        self.buckets.push(new_page);
+        self.split += 1;
+        if self.split == 1 << self.level {
+            self.level += 1;
+            self.split = 0;
+        }
+
+        let (kept, moved) = entries.into_iter().partition(|entry| self.bucket_of(entry.hash) == bucket);
+        self.place(&[chain[0]], kept)?;
+        self.place(&[new_page], moved)
+    }
+
+    /// The writes that save the header, the directory and the changed pages.
+    pub fn ops(self) -> Vec<WalOp> {
+        let mut ops = Vec::new();
+        if self.rebuilt {
+            ops.push(WalOp::Truncate { path: self.path.clone(), len: 0 });
+        }
+
+        let mut header = vec![0; 4];
+        header.extend(MAGIC);
+        for field in [self.level, self.split, self.buckets.len() as u32, self.page_count] {
+            header.extend(field.to_le_bytes());
+        }
+        header.extend(self.entry_count.to_le_bytes());
+        header.extend(self.free.to_le_bytes());
+        header.extend((self.directory_pages.len() as u32).to_le_bytes());
+        for page in &self.directory_pages {
+            header.extend(page.to_le_bytes());
+        }
+
+        let mut pages = vec![(0, seal(header))];
+        for (page, buckets) in self.directory_pages.iter().zip(self.buckets.chunks(DIRECTORY_ENTRIES)) {
+            let mut directory = vec![0; 4];
+            buckets.iter().for_each(|bucket| directory.extend(bucket.to_le_bytes()));
+            pages.push((*page, seal(directory)));
+        }
+        pages.extend(self.dirty.iter().map(|page| (*page, self.pages[page].encode())));
+        pages.sort_unstable_by_key(|(page, _)| *page);
+        ops.extend(page_writes(&self.path, pages));
+        ops
+    }
+}
@@ -0,0 +1,317 @@
+use crate::database::database::Database;
+use crate::io::btree::{BTree, MAX_KEY};
+use crate::io::hash::HashIndex;
+use crate::io::reader::read_located_rows;
+use crate::io::storage::Storage;
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{IndexDefinition, IndexKind, RowLocation, TableDefinition, Value, WalOp};
+use std::io;
+
+/// Appends a value to an index key so that keys compare byte by byte in the order
+/// of their values, NULL first. Each value starts with a tag byte: 0 for NULL and
+/// 1 otherwise. Numbers are stored big-endian with their sign bit flipped, and
+/// text and blobs end with `00 00`, a zero byte in them being escaped as `00 FF`.
+pub(crate) fn encode_value(value: &Value, key: &mut Vec<u8>) -> Result<()> {
+    let integer = |key: &mut Vec<u8>, v: i64| key.extend((v as u64 ^ 1 << 63).to_be_bytes());
+    let float = |key: &mut Vec<u8>, v: f64| {
+        // -0.0 equals 0.0, so both are stored as 0.0.
+        let bits = if v == 0.0 { 0 } else { v.to_bits() };
+        let bits = if bits >> 63 == 1 { !bits } else { bits | 1 << 63 };
+        key.extend(bits.to_be_bytes());
+    };
+    let bytes = |key: &mut Vec<u8>, v: &[u8]| {
+        for byte in v {
+            key.push(*byte);
+            if *byte == 0 {
+                key.push(0xFF);
+            }
+        }
+        key.extend([0, 0]);
+    };
+
+    if *value == Value::Null {
+        key.push(0);
+        return Ok(());
+    }
+    key.push(1);
+    match value {
+        Value::Integer(v) | Value::Date(v) => integer(key, *v as i64),
+        Value::BigInt(v) | Value::Time(v) | Value::Timestamp(v) => integer(key, *v),
+        Value::Float(v) => float(key, *v as f64),
+        Value::Double(v) => float(key, *v),
+        Value::Boolean(v) => key.push(*v as u8),
+        Value::Text(v) => bytes(key, v.as_bytes()),
+        Value::Blob(v) => bytes(key, v),
+        _ => return Err(RqlError::Type(format!("{} cannot be part of an index key", value))),
+    }
+    Ok(())
+}
+
+/// The smallest key greater than every key that starts with `prefix`, or `None`
+/// if there is no such key.
+pub(crate) fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|byte| *byte != 0xFF)?;
+    let mut end = prefix[..=last].to_vec();
+    end[last] += 1;
+    Some(end)
+}
+
+/// The location a key of an index ends with.
+fn key_location(key: &[u8]) -> RowLocation {
+    let suffix = &key[key.len() - 12..];
+    RowLocation {
+        offset: u64::from_be_bytes(suffix[..8].try_into().unwrap()),
+        row: u32::from_be_bytes(suffix[8..].try_into().unwrap()),
+    }
+}
+
+/// The file of an index, whichever its kind.
+enum IndexFile<'a> {
+    BTree(BTree<'a>),
+    Hash(HashIndex<'a>),
+}
+
+impl<'a> IndexFile<'a> {
+    fn open(kind: IndexKind, storage: &'a dyn Storage, path: &str) -> io::Result<IndexFile<'a>> {
+        Ok(match kind {
+            IndexKind::BTree => IndexFile::BTree(BTree::open(storage, path)?),
+            IndexKind::Hash => IndexFile::Hash(HashIndex::open(storage, path)?),
+        })
+    }
+
+    /// Whether a row has the key, given without its location.
+    fn contains(&mut self, key: &[u8]) -> io::Result<bool> {
+        match self {
+            IndexFile::BTree(tree) => {
+                let mut found = false;
+                tree.scan(key, prefix_end(key).as_deref(), |_| {
+                    found = true;
+                    false
+                })?;
+                Ok(found)
+            }
+            IndexFile::Hash(index) => Ok(!index.get(key)?.is_empty()),
+        }
+    }
+
+    fn insert(&mut self, key: Vec<u8>, location: RowLocation) -> io::Result<()> {
+        match self {
+            IndexFile::BTree(tree) => tree.insert(with_location(key, location)),
+            IndexFile::Hash(index) => index.insert(key, location),
+        }
+    }
+
+    fn ops(self) -> Vec<WalOp> {
+        match self {
+            IndexFile::BTree(tree) => tree.ops(),
+            IndexFile::Hash(index) => index.ops(),
+        }
+    }
+}
+
+/// Whether an error comes from a damaged index file, which can be rebuilt.
+fn is_corrupt(err: &RqlError) -> bool {
+    matches!(err, RqlError::Io(e) if e.kind() == io::ErrorKind::InvalidData)
+}
+
+impl IndexDefinition {
+    /// The positions of the index's columns in the table.
+    pub(crate) fn positions(&self, table_definition: &TableDefinition) -> Result<Vec<usize>> {
+        self.columns
+            .iter()
+            .map(|name| {
+                table_definition.columns.iter().position(|column| column.name == *name).ok_or_else(|| {
+                    RqlError::Schema(format!("Index '{}' names the unknown column '{}'", self.name, name))
+                })
+            })
+            .collect()
+    }
+
+    /// The key a row has in the index, without its location, and whether one of
+    /// its columns is NULL, in which case it never conflicts with another key.
+    fn key(&self, positions: &[usize], row: &[Value]) -> Result<(Vec<u8>, bool)> {
+        let mut key = Vec::new();
+        for position in positions {
+            encode_value(&row[*position], &mut key)?;
+        }
+        if key.len() + 12 > MAX_KEY {
+            return Err(RqlError::Constraint(format!(
+                "A key of index '{}' is longer than its limit of {} bytes",
+                self.name,
+                MAX_KEY - 12
+            )));
+        }
+        let has_null = positions.iter().any(|position| row[*position] == Value::Null);
+        Ok((key, has_null))
+    }
+
+    fn duplicate(&self, table_name: &str, table_definition: &TableDefinition, positions: &[usize], row: &[Value]) -> RqlError {
+        let values: Vec<String> = positions.iter().map(|position| row[*position].to_string()).collect();
+        let key = format!("({}) = ({})", self.columns.join(", "), values.join(", "));
+        let primary_key: Vec<&str> =
+            table_definition.primary_key().iter().map(|index| table_definition.columns[*index].name.as_str()).collect();
+        if self.implicit && self.columns == primary_key {
+            RqlError::Constraint(format!("Duplicate primary key {} in table '{}'", key, table_name))
+        } else {
+            RqlError::Constraint(format!("Duplicate key {} in UNIQUE index '{}' of table '{}'", key, self.name, table_name))
+        }
+    }
+
+    /// Writes the index for `rows`, the whole table, replacing its file. Fails if
+    /// the index is `UNIQUE` and two rows share a key without NULLs.
+    pub(crate) fn build_ops(
+        &self,
+        db: &Database,
+        table_name: &str,
+        table_definition: &TableDefinition,
+        rows: &[(RowLocation, Vec<Value>)],
+    ) -> Result<Vec<WalOp>> {
+        let positions = self.positions(table_definition)?;
+        let mut keys = Vec::with_capacity(rows.len());
+        for (index, (location, row)) in rows.iter().enumerate() {
+            let (key, has_null) = self.key(&positions, row)?;
+            keys.push((key, *location, has_null, index));
+        }
+        keys.sort_unstable_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+        if self.unique {
+            if let Some(pair) = keys.windows(2).find(|pair| pair[0].0 == pair[1].0 && !pair[0].2) {
+                return Err(self.duplicate(table_name, table_definition, &positions, &rows[pair[1].3].1));
+            }
+        }
+
+        let (owner, path) = db.index_location(table_name, &self.name)?;
+        let keys = keys.into_iter().map(|(key, location, _, _)| (key, location));
+        Ok(match self.kind {
+            IndexKind::BTree => {
+                let keys = keys.map(|(key, location)| with_location(key, location)).collect();
+                BTree::build(owner.storage(), &path, keys).ops()
+            }
+            IndexKind::Hash => HashIndex::build(owner.storage(), &path, keys.collect()).ops(),
+        })
+    }
+
+    /// Opens the index file. A missing file counts as damaged when the table has
+    /// rows, as the index is then out of date.
+    fn open<'a>(&self, db: &'a Database, table_name: &str) -> Result<IndexFile<'a>> {
+        let (owner, path) = db.index_location(table_name, &self.name)?;
+        let (data_owner, data_path) = db.data_location(table_name)?;
+        if owner.storage().file_len(&path)? == 0 && data_owner.storage().file_len(&data_path)? > 0 {
+            let message = format!("Index file {} is missing", path);
+            return Err(RqlError::Io(io::Error::new(io::ErrorKind::InvalidData, message)));
+        }
+        Ok(IndexFile::open(self.kind, owner.storage(), &path)?)
+    }
+
+    /// Rebuilds a damaged index from the rows in the table and `appended`, the
+    /// rows about to be added to it.
+    fn rebuild_ops(
+        &self,
+        db: &Database,
+        table_name: &str,
+        table_definition: &TableDefinition,
+        appended: &[(RowLocation, Vec<Value>)],
+    ) -> Result<Vec<WalOp>> {
+        let mut rows = read_located_rows(db, table_name, table_definition)?;
+        rows.extend_from_slice(appended);
+        self.build_ops(db, table_name, table_definition, &rows)
+    }
+
+    /// Adds the keys of appended rows to the index file, or rebuilds it if it is
+    /// damaged. Fails if the index is `UNIQUE` and a new key without NULLs is
+    /// already in it.
+    pub(crate) fn insert_ops(
+        &self,
+        db: &Database,
+        table_name: &str,
+        table_definition: &TableDefinition,
+        rows: &[(RowLocation, Vec<Value>)],
+    ) -> Result<Vec<WalOp>> {
+        let positions = self.positions(table_definition)?;
+        let insert = || -> Result<Vec<WalOp>> {
+            let mut file = self.open(db, table_name)?;
+            for (location, row) in rows {
+                let (key, has_null) = self.key(&positions, row)?;
+                if self.unique && !has_null && file.contains(&key)? {
+                    return Err(self.duplicate(table_name, table_definition, &positions, row));
+                }
+                file.insert(key, *location)?;
+            }
+            Ok(file.ops())
+        };
+        match insert() {
+            Err(e) if is_corrupt(&e) => self.rebuild_ops(db, table_name, table_definition, rows),
+            result => result,
+        }
+    }
+
+    /// The locations of the rows whose keys lie from `low` up to, but not including,
+    /// `high`, in storage order. A hash index only finds the key `low`, which must
+    /// hold all of its columns. A damaged index file is rebuilt first.
+    pub(crate) fn lookup(
+        &self,
+        db: &Database,
+        table_name: &str,
+        table_definition: &TableDefinition,
+        low: &[u8],
+        high: Option<&[u8]>,
+    ) -> Result<Vec<RowLocation>> {
+        let find = || -> Result<Vec<RowLocation>> {
+            let mut locations = Vec::new();
+            match self.open(db, table_name)? {
+                IndexFile::BTree(mut tree) => tree.scan(low, high, |key| {
+                    locations.push(key_location(key));
+                    true
+                })?,
+                IndexFile::Hash(mut index) => locations = index.get(low)?,
+            }
+            locations.sort_unstable();
+            Ok(locations)
+        };
+        match find() {
+            Err(e) if is_corrupt(&e) => {
+                let (owner, _) = db.index_location(table_name, &self.name)?;
+                owner.storage().commit(&self.rebuild_ops(db, table_name, table_definition, &[])?)?;
+                find()
+            }
+            result => result,
+        }
+    }
+
+    pub fn to_sql(&self, table_name: &str) -> String {
+        format!(
+            "CREATE {}INDEX {} ON {} {}({});",
+            if self.unique { "UNIQUE " } else { "" },
+            self.name,
+            table_name,
+            if self.kind == IndexKind::Hash { "USING HASH " } else { "" },
+            self.columns.join(", ")
+        )
+    }
+}
+
+/// Ends a key with the row's location, which makes every key of an index unique.
+fn with_location(mut key: Vec<u8>, location: RowLocation) -> Vec<u8> {
+    key.extend(location.offset.to_be_bytes());
+    key.extend(location.row.to_be_bytes());
+    key
+}
+
+/// The index file changes for rows written to a table: its appended rows, or all
+/// of its rows when the data file is replaced.
+pub(crate) fn index_ops(
+    db: &Database,
+    table_name: &str,
+    table_definition: &TableDefinition,
+    rows: &[(RowLocation, Vec<Value>)],
+    append: bool,
+) -> Result<Vec<WalOp>> {
+    let mut ops = Vec::new();
+    for index in &table_definition.indexes {
+        if append {
+            ops.extend(index.insert_ops(db, table_name, table_definition, rows)?);
+        } else {
+            ops.extend(index.build_ops(db, table_name, table_definition, rows)?);
+        }
+    }
+    Ok(ops)
+}
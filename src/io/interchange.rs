@@ -0,0 +1,341 @@
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{Column, ColumnBatch, ColumnData, CopyFormat, DataType};
+use arrow::array::{
+    Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, FixedSizeListArray, Float32Array, Float64Array, Int32Array, Int64Array,
+    StringArray, Time64MicrosecondArray, TimestampMicrosecondArray,
+};
+use arrow::compute::{cast_with_options, CastOptions};
+use arrow::datatypes::{DataType as ArrowType, Field, Float32Type, Schema, SchemaRef, TimeUnit};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::sync::Arc;
+
+impl DataType {
+    pub fn to_arrow(self) -> ArrowType {
+        match self {
+            DataType::Integer => ArrowType::Int32,
+            DataType::Float => ArrowType::Float32,
+            DataType::Text => ArrowType::Utf8,
+            DataType::Boolean => ArrowType::Boolean,
+            DataType::BigInt => ArrowType::Int64,
+            DataType::Double => ArrowType::Float64,
+            DataType::Date => ArrowType::Date32,
+            DataType::Time => ArrowType::Time64(TimeUnit::Microsecond),
+            DataType::Timestamp => ArrowType::Timestamp(TimeUnit::Microsecond, None),
+            DataType::Vec2 | DataType::Vec3 | DataType::Vec4 | DataType::Mat4 | DataType::Vector(_) => ArrowType::FixedSizeList(
+                Arc::new(Field::new("item", ArrowType::Float32, true)),
+                self.components() as i32,
+            ),
+            DataType::Blob => ArrowType::Binary,
+        }
+    }
+
+    /// The column type an Arrow type is read into. Narrower integers and floats
+    /// are converted; unsigned integers only if every value fits. Times and
+    /// timestamps of any unit are read as microseconds. Fixed size lists of 2, 3,
+    /// 4 or 16 floats are read as vectors and matrices, other sizes as `VECTOR(n)`.
+    /// Binary data of any offset size, or of a fixed size, is read as `BLOB`.
+    pub fn from_arrow(arrow_type: &ArrowType) -> Option<DataType> {
+        match arrow_type {
+            ArrowType::Int8 | ArrowType::Int16 | ArrowType::Int32 | ArrowType::UInt8 | ArrowType::UInt16 => {
+                Some(DataType::Integer)
+            }
+            ArrowType::Int64 | ArrowType::UInt32 | ArrowType::UInt64 => Some(DataType::BigInt),
+            ArrowType::Float16 | ArrowType::Float32 => Some(DataType::Float),
+            ArrowType::Float64 => Some(DataType::Double),
+            ArrowType::Utf8 | ArrowType::LargeUtf8 | ArrowType::Utf8View => Some(DataType::Text),
+            ArrowType::Boolean => Some(DataType::Boolean),
+            ArrowType::Binary | ArrowType::LargeBinary | ArrowType::BinaryView | ArrowType::FixedSizeBinary(_) => {
+                Some(DataType::Blob)
+            }
+            ArrowType::Date32 | ArrowType::Date64 => Some(DataType::Date),
+            ArrowType::Time32(_) | ArrowType::Time64(_) => Some(DataType::Time),
+            ArrowType::Timestamp(_, _) => Some(DataType::Timestamp),
+            ArrowType::FixedSizeList(field, size)
+                if matches!(field.data_type(), ArrowType::Float16 | ArrowType::Float32 | ArrowType::Float64) =>
+            {
+                let fixed = [DataType::Vec2, DataType::Vec3, DataType::Vec4, DataType::Mat4]
+                    .into_iter()
+                    .find(|data_type| data_type.components() == *size as usize);
+                fixed.or_else(|| u32::try_from(*size).ok().filter(|size| *size > 0).map(DataType::Vector))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ColumnBatch {
+    pub fn arrow_schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(
+            self.columns
+                .iter()
+                .map(|c| Field::new(&c.name, c.data_type.to_arrow(), c.nullable))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    pub fn to_record_batch(&self) -> Result<RecordBatch> {
+        let arrays = self
+            .data
+            .iter()
+            .map(|data| -> ArrayRef {
+                match data {
+                    ColumnData::Integer(values) => Arc::new(Int32Array::from(values.clone())),
+                    ColumnData::Float(values) => Arc::new(Float32Array::from(values.clone())),
+                    ColumnData::Text(values) => Arc::new(StringArray::from(values.clone())),
+                    ColumnData::Boolean(values) => Arc::new(BooleanArray::from(values.clone())),
+                    ColumnData::BigInt(values) => Arc::new(Int64Array::from(values.clone())),
+                    ColumnData::Double(values) => Arc::new(Float64Array::from(values.clone())),
+                    ColumnData::Date(values) => Arc::new(Date32Array::from(values.clone())),
+                    ColumnData::Time(values) => Arc::new(Time64MicrosecondArray::from(values.clone())),
+                    ColumnData::Timestamp(values) => Arc::new(TimestampMicrosecondArray::from(values.clone())),
+                    ColumnData::Vec2(values) => vector_array(values),
+                    ColumnData::Vec3(values) => vector_array(values),
+                    ColumnData::Vec4(values) => vector_array(values),
+                    ColumnData::Mat4(values) => vector_array(values),
+                    ColumnData::Vector(dimensions, values) => Arc::new(FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                        values.iter().map(|value| value.as_ref().map(|components| components.iter().copied().map(Some))),
+                        *dimensions as i32,
+                    )),
+                    ColumnData::Blob(values) => Arc::new(values.iter().map(Option::as_deref).collect::<BinaryArray>()),
+                }
+            })
+            .collect();
+        RecordBatch::try_new(self.arrow_schema(), arrays).map_err(arrow_error)
+    }
+
+    /// Reads a record batch into columns typed after its Arrow schema.
+    pub fn from_record_batch(record_batch: &RecordBatch) -> Result<ColumnBatch> {
+        let columns = arrow_columns(&record_batch.schema())?;
+        ColumnBatch::from_record_batch_as(record_batch, columns)
+    }
+
+    /// Reads a record batch into the given columns, matching them to its fields by
+    /// name. Columns missing from the batch are NULL.
+    pub fn from_record_batch_as(record_batch: &RecordBatch, columns: Arc<[Column]>) -> Result<ColumnBatch> {
+        let schema = record_batch.schema();
+        if let Some(field) = schema.fields().iter().find(|f| !columns.iter().any(|c| c.name == *f.name())) {
+            return Err(RqlError::Schema(format!("There is no column '{}' to copy into", field.name())));
+        }
+
+        let rows = record_batch.num_rows();
+        let data = columns
+            .iter()
+            .map(|column| {
+                let data = match schema.index_of(&column.name) {
+                    Ok(index) => column_data(record_batch.column(index), column)?,
+                    Err(_) => nulls(column.data_type, rows),
+                };
+                if !column.nullable && data.has_nulls() {
+                    return Err(RqlError::Constraint(format!("Column '{}' cannot be NULL", column.name)));
+                }
+                Ok(data)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(ColumnBatch { columns, data })
+    }
+}
+
+impl ColumnData {
+    fn has_nulls(&self) -> bool {
+        match self {
+            ColumnData::Integer(values) => values.iter().any(Option::is_none),
+            ColumnData::Float(values) => values.iter().any(Option::is_none),
+            ColumnData::Text(values) => values.iter().any(Option::is_none),
+            ColumnData::Boolean(values) => values.iter().any(Option::is_none),
+            ColumnData::BigInt(values) => values.iter().any(Option::is_none),
+            ColumnData::Double(values) => values.iter().any(Option::is_none),
+            ColumnData::Date(values) => values.iter().any(Option::is_none),
+            ColumnData::Time(values) => values.iter().any(Option::is_none),
+            ColumnData::Timestamp(values) => values.iter().any(Option::is_none),
+            ColumnData::Vec2(values) => values.iter().any(Option::is_none),
+            ColumnData::Vec3(values) => values.iter().any(Option::is_none),
+            ColumnData::Vec4(values) => values.iter().any(Option::is_none),
+            ColumnData::Mat4(values) => values.iter().any(Option::is_none),
+            ColumnData::Vector(_, values) => values.iter().any(Option::is_none),
+            ColumnData::Blob(values) => values.iter().any(Option::is_none),
+        }
+    }
+}
+
+/// The columns a table created from an Arrow schema gets.
+pub fn arrow_columns(schema: &Schema) -> Result<Arc<[Column]>> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let data_type = DataType::from_arrow(field.data_type()).ok_or_else(|| {
+                RqlError::Type(format!("Field '{}' has the unsupported type {}", field.name(), field.data_type()))
+            })?;
+            Ok(Column {
+                name: field.name().clone(),
+                data_type,
+                nullable: field.is_nullable(),
+            })
+        })
+        .collect()
+}
+
+/// Writes a batch to an Arrow IPC or Parquet file.
+pub fn write_columnar(path: &str, format: CopyFormat, batch: &ColumnBatch) -> Result<()> {
+    let record_batch = batch.to_record_batch()?;
+    let file = File::create(path).map_err(|e| file_error(path, e))?;
+    match format {
+        CopyFormat::Arrow => {
+            let mut writer = FileWriter::try_new(file, &record_batch.schema()).map_err(arrow_error)?;
+            writer.write(&record_batch).map_err(arrow_error)?;
+            writer.finish().map_err(arrow_error)?;
+        }
+        CopyFormat::Parquet => {
+            let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+            let mut writer =
+                ArrowWriter::try_new(file, record_batch.schema(), Some(properties)).map_err(parquet_error)?;
+            writer.write(&record_batch).map_err(parquet_error)?;
+            writer.close().map_err(parquet_error)?;
+        }
+        CopyFormat::Csv => return Err(RqlError::Type("CSV is not a columnar format".to_string())),
+    }
+    Ok(())
+}
+
+/// Reads every record batch of an Arrow IPC or Parquet file, together with its schema.
+pub fn read_columnar(path: &str, format: CopyFormat) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+    let file = File::open(path).map_err(|e| file_error(path, e))?;
+    match format {
+        CopyFormat::Arrow => {
+            let reader = FileReader::try_new(file, None).map_err(arrow_error)?;
+            let schema = reader.schema();
+            let batches = reader.collect::<std::result::Result<_, _>>().map_err(arrow_error)?;
+            Ok((schema, batches))
+        }
+        CopyFormat::Parquet => {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(file).map_err(parquet_error)?;
+            let schema = builder.schema().clone();
+            let reader = builder.build().map_err(parquet_error)?;
+            let batches = reader.collect::<std::result::Result<_, _>>().map_err(arrow_error)?;
+            Ok((schema, batches))
+        }
+        CopyFormat::Csv => Err(RqlError::Type("CSV is not a columnar format".to_string())),
+    }
+}
+
+fn column_data(array: &ArrayRef, column: &Column) -> Result<ColumnData> {
+    // Besides widening, 64-bit values may be read into their 32-bit counterparts:
+    // integers fail the cast below if they do not fit, doubles are rounded.
+    let narrows = |from, to| matches!((from, to), (DataType::BigInt, DataType::Integer) | (DataType::Double, DataType::Float));
+    let fits = DataType::from_arrow(array.data_type())
+        .is_some_and(|data_type| data_type.widens_to(column.data_type) || narrows(data_type, column.data_type));
+    if !fits {
+        return Err(RqlError::Type(format!(
+            "Cannot copy {} values into {} column '{}'",
+            array.data_type(),
+            column.data_type.to_sql_type(),
+            column.name
+        )));
+    }
+
+    let options = CastOptions { safe: false, ..CastOptions::default() };
+    let array = cast_with_options(array, &column.data_type.to_arrow(), &options)
+        .map_err(|e| RqlError::Type(format!("Column '{}': {}", column.name, e)))?;
+    let array = array.as_ref();
+    let data = match column.data_type {
+        DataType::Integer => ColumnData::Integer(downcast::<Int32Array>(array).iter().collect()),
+        DataType::Float => ColumnData::Float(downcast::<Float32Array>(array).iter().collect()),
+        DataType::Text => {
+            ColumnData::Text(downcast::<StringArray>(array).iter().map(|v| v.map(str::to_string)).collect())
+        }
+        DataType::Boolean => ColumnData::Boolean(downcast::<BooleanArray>(array).iter().collect()),
+        DataType::BigInt => ColumnData::BigInt(downcast::<Int64Array>(array).iter().collect()),
+        DataType::Double => ColumnData::Double(downcast::<Float64Array>(array).iter().collect()),
+        DataType::Date => ColumnData::Date(downcast::<Date32Array>(array).iter().collect()),
+        DataType::Time => ColumnData::Time(downcast::<Time64MicrosecondArray>(array).iter().collect()),
+        DataType::Timestamp => ColumnData::Timestamp(downcast::<TimestampMicrosecondArray>(array).iter().collect()),
+        DataType::Vec2 => ColumnData::Vec2(vector_values(array, column)?),
+        DataType::Vec3 => ColumnData::Vec3(vector_values(array, column)?),
+        DataType::Vec4 => ColumnData::Vec4(vector_values(array, column)?),
+        DataType::Mat4 => ColumnData::Mat4(vector_values(array, column)?),
+        DataType::Vector(dimensions) => ColumnData::Vector(dimensions, list_values(array, column, dimensions as usize)?),
+        DataType::Blob => ColumnData::Blob(downcast::<BinaryArray>(array).iter().map(|v| v.map(<[u8]>::to_vec)).collect()),
+    };
+    Ok(data)
+}
+
+fn vector_array<const N: usize>(values: &[Option<[f32; N]>]) -> ArrayRef {
+    let lists = values.iter().map(|value| value.map(|components| components.map(Some)));
+    Arc::new(FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(lists, N as i32))
+}
+
+fn vector_values<const N: usize>(array: &dyn Array, column: &Column) -> Result<Vec<Option<[f32; N]>>> {
+    let lists = list_values(array, column, N)?;
+    Ok(lists.into_iter().map(|list| list.and_then(|components| components.try_into().ok())).collect())
+}
+
+/// Reads a fixed size list array of `size` floats. A vector may be NULL as a
+/// whole but not in one of its components.
+fn list_values(array: &dyn Array, column: &Column, size: usize) -> Result<Vec<Option<Vec<f32>>>> {
+    let lists = downcast::<FixedSizeListArray>(array);
+    let components = downcast::<Float32Array>(lists.values().as_ref());
+    (0..lists.len())
+        .map(|index| {
+            if lists.is_null(index) {
+                return Ok(None);
+            }
+            let start = lists.value_offset(index) as usize;
+            if (start..start + size).any(|component| components.is_null(component)) {
+                return Err(RqlError::Constraint(format!("Column '{}' has a vector with a NULL component", column.name)));
+            }
+            Ok(Some((start..start + size).map(|component| components.value(component)).collect()))
+        })
+        .collect()
+}
+
+fn downcast<T: 'static>(array: &dyn Array) -> &T {
+    array.as_any().downcast_ref::<T>().expect("array was cast to the column type")
+}
+
+fn nulls(data_type: DataType, rows: usize) -> ColumnData {
+    match data_type {
+        DataType::Integer => ColumnData::Integer(vec![None; rows]),
+        DataType::Float => ColumnData::Float(vec![None; rows]),
+        DataType::Text => ColumnData::Text(vec![None; rows]),
+        DataType::Boolean => ColumnData::Boolean(vec![None; rows]),
+        DataType::BigInt => ColumnData::BigInt(vec![None; rows]),
+        DataType::Double => ColumnData::Double(vec![None; rows]),
+        DataType::Date => ColumnData::Date(vec![None; rows]),
+        DataType::Time => ColumnData::Time(vec![None; rows]),
+        DataType::Timestamp => ColumnData::Timestamp(vec![None; rows]),
+        DataType::Vec2 => ColumnData::Vec2(vec![None; rows]),
+        DataType::Vec3 => ColumnData::Vec3(vec![None; rows]),
+        DataType::Vec4 => ColumnData::Vec4(vec![None; rows]),
+        DataType::Mat4 => ColumnData::Mat4(vec![None; rows]),
+        DataType::Vector(dimensions) => ColumnData::Vector(dimensions, vec![None; rows]),
+        DataType::Blob => ColumnData::Blob(vec![None; rows]),
+    }
+}
+
+fn file_error(path: &str, e: std::io::Error) -> RqlError {
+    RqlError::Io(std::io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+fn arrow_error(e: ArrowError) -> RqlError {
+    match e {
+        ArrowError::IoError(_, e) => RqlError::Io(e),
+        e => RqlError::Parse { message: format!("Malformed Arrow data: {}", e), position: None },
+    }
+}
+
+fn parquet_error(e: ParquetError) -> RqlError {
+    match e {
+        ParquetError::External(e) => RqlError::Io(std::io::Error::other(e)),
+        e => RqlError::Parse { message: format!("Malformed Parquet file: {}", e), position: None },
+    }
+}
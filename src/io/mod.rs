@@ -1,3 +1,14 @@
 pub mod writer;
 pub mod reader;
-pub mod util;
\ No newline at end of file
+pub mod util;
+pub mod format;
+pub mod copy;
+pub mod interchange;
+pub mod sqlite;
+pub mod chunk;
+pub mod wal;
+pub mod storage;
+pub mod transaction;
+pub mod btree;
+pub mod hash;
+pub mod index;
\ No newline at end of file
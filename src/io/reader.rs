@@ -1,94 +1,346 @@
-use crate::types::types::{DataType, TableDefinition, Value};
+use crate::database::database::Database;
+use crate::io::chunk::{next_chunk_header, read_chunk_rows, skip_chunk_rows};
+use crate::io::storage::{RangeReader, Storage, HEADER_BUFFER, STREAM_BUFFER};
+use crate::io::writer::{NULLABLE_ROW_MARKER, ROW_MARKER, SCHEMA_MAGIC, SCHEMA_VERSION};
+use crate::query::temporal::{format_date, format_time, format_timestamp};
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{BlobFormat, ChunkHeader, ColumnBatch, ColumnDefinition, Compression, DataType, RowLocation, TableDefinition, Value};
+use serde::Deserialize;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::{fmt, io};
 
-pub fn read_table_definition(table_name: &str) -> io::Result<TableDefinition> {
-    let table_name_lowercase = table_name.to_lowercase() + "_def.bin";
-    let file_path = format!("schema/{}", table_name_lowercase);
+/// Reads the definition of a table. A schema file from before the format was
+/// versioned is migrated, and is saved in the current format by the next
+/// statement that changes the table.
+pub fn read_table_definition(db: &Database, table_name: &str) -> Result<TableDefinition> {
+    let (owner, file_path) = db.schema_location(table_name)?;
+    let storage = owner.storage();
+    if storage.file_len(&file_path)? == 0 {
+        return Err(RqlError::Schema(format!("Table '{}' does not exist", table_name)));
+    }
 
-    let mut file = File::open(file_path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+    let mut file = RangeReader::new(storage, &file_path, HEADER_BUFFER)?;
+    let mut prefix = [0u8; 8];
+    if file.read_exact(&mut prefix).is_err() || prefix[..4] != SCHEMA_MAGIC {
+        file.seek(SeekFrom::Start(0))?;
+        return read_baseline_definition(file, table_name);
+    }
 
-    let table_definition: TableDefinition = bincode::deserialize(&buffer).unwrap();
+    let version = u32::from_le_bytes(prefix[4..].try_into().unwrap());
+    if version != SCHEMA_VERSION {
+        return Err(RqlError::Schema(format!(
+            "Table '{}' is saved in schema format {}, but this version of RQL reads format {}",
+            table_name, version, SCHEMA_VERSION
+        )));
+    }
+    let table_definition: TableDefinition = bincode::deserialize_from(file)?;
     Ok(table_definition)
 }
 
-pub fn read_vec_of_bytes_from_file(table_name: &str) -> io::Result<Vec<HashMap<String, Value>>> {
-    let mut results = Vec::new();
-    let table_name_lowercase = table_name.to_lowercase() + "_data.bin";
-    let table_definition = read_table_definition(table_name)?;
-    let file_path = format!("data/{}", table_name_lowercase);
-    let mut file = File::open(file_path)?;
+/// The schema file layout before `SCHEMA_MAGIC`: a table name and its columns.
+#[derive(Deserialize)]
+struct BaselineTableDefinition {
+    name: String,
+    columns: Vec<BaselineColumnDefinition>,
+}
 
-    loop {
-        let mut start_marker = [0u8; 1];
-        if file.read_exact(&mut start_marker).is_err() {
-            break;
-        }
+#[derive(Deserialize)]
+struct BaselineColumnDefinition {
+    name: String,
+    data_type: DataType,
+    nullable: bool,
+    unique: bool,
+}
 
-        if start_marker[0] != 0xAB {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid start marker",
-            ));
-        }
+/// Reads a schema file in the layout from before the format was versioned, with
+/// the settings added since at their defaults. `UNIQUE` columns get the index
+/// that now enforces them, whose file is built when it is first used. Bytes left
+/// over mean the file is in neither layout.
+fn read_baseline_definition<R: Read>(mut file: R, table_name: &str) -> Result<TableDefinition> {
+    let unknown = || RqlError::Schema(format!("The schema file of table '{}' is in an unknown format", table_name));
+    let baseline: BaselineTableDefinition = bincode::deserialize_from(&mut file).map_err(|_| unknown())?;
+    if file.read(&mut [0u8; 1])? != 0 {
+        return Err(unknown());
+    }
 
-        let mut size_buf = [0u8; 8];
-        file.read_exact(&mut size_buf)?;
-        let row_size = u64::from_le_bytes(size_buf);
-
-        let mut end_marker = [0u8; 1];
-        file.read_exact(&mut end_marker)?;
-        if end_marker[0] != 0xCD {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid end marker",
-            ));
-        }
+    let mut table_definition = TableDefinition {
+        name: baseline.name,
+        columns: baseline
+            .columns
+            .into_iter()
+            .map(|column| ColumnDefinition {
+                name: column.name,
+                data_type: column.data_type,
+                nullable: column.nullable,
+                unique: column.unique,
+                primary_key: false,
+                auto_increment: false,
+                default: None,
+            })
+            .collect(),
+        ..TableDefinition::default()
+    };
+    table_definition.indexes = table_definition
+        .columns
+        .iter()
+        .filter(|column| column.unique)
+        .map(|column| table_definition.implicit_index(&format!("{}_key", column.name), vec![column.name.clone()]))
+        .collect();
+    Ok(table_definition)
+}
 
-        let mut row_data = vec![0u8; row_size as usize];
-        file.read_exact(&mut row_data)?;
-
-        let mut cursor = &row_data[..];
-        let mut inner_results_map = HashMap::new();
-        for column in &table_definition.columns {
-            let value = match column.data_type {
-                DataType::Integer => {
-                    let value: i32 = bincode::deserialize_from(&mut cursor).map_err(|e| {
-                        io::Error::new(io::ErrorKind::InvalidData, format!("Deserialization error: {}", e))
-                    })?;
-                    Value::Integer(value)
-                }
-                DataType::Float => {
-                    let value: f32 = bincode::deserialize_from(&mut cursor).map_err(|e| {
-                        io::Error::new(io::ErrorKind::InvalidData, format!("Deserialization error: {}", e))
-                    })?;
-                    Value::Float(value)
-                }
-                DataType::Text => {
-                    let value: String = bincode::deserialize_from(&mut cursor).map_err(|e| {
-                        io::Error::new(io::ErrorKind::InvalidData, format!("Deserialization error: {}", e))
-                    })?;
-                    Value::Text(value)
-                }
-                DataType::Boolean => {
-                    let value: bool = bincode::deserialize_from(&mut cursor).map_err(|e| {
-                        io::Error::new(io::ErrorKind::InvalidData, format!("Deserialization error: {}", e))
-                    })?;
-                    Value::Boolean(value)
+pub fn table_exists(db: &Database, table_name: &str) -> Result<bool> {
+    let (owner, file_path) = db.schema_location(table_name)?;
+    Ok(owner.storage().list("schema")?.contains(&file_path))
+}
+
+pub fn read_vec_of_bytes_from_file(db: &Database, table_name: &str) -> Result<Vec<HashMap<String, Value>>> {
+    read_vec_of_bytes_from_file_filtered(db, table_name, |_| true)
+}
+
+/// Reads all rows of a table, skipping compressed chunks for which `chunk_filter`
+/// returns false. Uncompressed tables have no chunks and are always read in full.
+pub fn read_vec_of_bytes_from_file_filtered<F>(db: &Database, table_name: &str, chunk_filter: F) -> Result<Vec<HashMap<String, Value>>>
+where
+    F: Fn(&ChunkHeader) -> bool,
+{
+    let table_definition = read_table_definition(db, table_name)?;
+    let rows = read_encoded_rows(db, table_name, &table_definition, chunk_filter)?
+        .iter()
+        .map(|row_data| decode_row(row_data, &table_definition))
+        .collect::<io::Result<_>>()?;
+    Ok(rows)
+}
+
+/// Reads the rows of a table as values in schema order, skipping compressed chunks
+/// for which `chunk_filter` returns false.
+pub fn read_rows_filtered<F>(db: &Database, table_name: &str, table_definition: &TableDefinition, chunk_filter: F) -> Result<Vec<Vec<Value>>>
+where
+    F: Fn(&ChunkHeader) -> bool,
+{
+    let rows = read_encoded_rows(db, table_name, table_definition, chunk_filter)?
+        .iter()
+        .map(|row_data| decode_values(row_data, table_definition))
+        .collect::<io::Result<_>>()?;
+    Ok(rows)
+}
+
+/// Reads all rows of a table column by column, without building a map per row.
+pub fn read_batch(db: &Database, table_name: &str) -> Result<ColumnBatch> {
+    let table_definition = read_table_definition(db, table_name)?;
+    let mut batch = ColumnBatch::for_table(&table_definition);
+    for row_data in read_encoded_rows(db, table_name, &table_definition, |_| true)? {
+        batch.push_row(decode_values(&row_data, &table_definition)?)?;
+    }
+    Ok(batch)
+}
+
+fn read_encoded_rows<F>(db: &Database, table_name: &str, table_definition: &TableDefinition, chunk_filter: F) -> Result<Vec<Vec<u8>>>
+where
+    F: Fn(&ChunkHeader) -> bool,
+{
+    let (owner, file_path) = db.data_location(table_name)?;
+    let mut file = data_reader(owner.storage(), &file_path, table_definition)?;
+
+    let rows = match table_definition.compression {
+        Compression::None => read_frames(&mut file)?,
+        Compression::Lz4 => {
+            let mut rows = Vec::new();
+            while let Some(header) = next_chunk_header(&mut file)? {
+                if chunk_filter(&header) {
+                    rows.extend(read_chunk_rows(&mut file, &header)?);
+                } else {
+                    skip_chunk_rows(&mut file, &header)?;
                 }
+            }
+            rows
+        }
+    };
+    Ok(rows)
+}
+
+/// Streams a data file: uncompressed rows through a large buffer, and chunks by
+/// their headers, so the payloads of skipped chunks are never read.
+fn data_reader<'a>(storage: &'a dyn Storage, file_path: &'a str, table_definition: &TableDefinition) -> io::Result<RangeReader<'a>> {
+    let capacity = match table_definition.compression {
+        Compression::None => STREAM_BUFFER,
+        Compression::Lz4 => HEADER_BUFFER,
+    };
+    RangeReader::new(storage, file_path, capacity)
+}
+
+/// Reads `[0xAB|0xAC][size u64][0xCD][row data]` frames until end of input. Each
+/// row is returned with its start marker in front, as `decode_row` expects it.
+pub fn read_frames<R: Read>(reader: &mut R) -> io::Result<Vec<Vec<u8>>> {
+    let mut rows = Vec::new();
+    while let Some(row_data) = read_frame(reader)? {
+        rows.push(row_data);
+    }
+    Ok(rows)
+}
+
+/// Reads the next frame, or returns `None` at end of input.
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut start_marker = [0u8; 1];
+    if reader.read_exact(&mut start_marker).is_err() {
+        return Ok(None);
+    }
+
+    if start_marker[0] != ROW_MARKER && start_marker[0] != NULLABLE_ROW_MARKER {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid start marker",
+        ));
+    }
+
+    let mut size_buf = [0u8; 8];
+    reader.read_exact(&mut size_buf)?;
+    let row_size = u64::from_le_bytes(size_buf);
+
+    let mut end_marker = [0u8; 1];
+    reader.read_exact(&mut end_marker)?;
+    if end_marker[0] != 0xCD {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid end marker",
+        ));
+    }
+
+    let mut row_data = vec![0u8; row_size as usize + 1];
+    row_data[0] = start_marker[0];
+    reader.read_exact(&mut row_data[1..])?;
+    Ok(Some(row_data))
+}
+
+/// Reads every row of a table in schema order together with its location, as
+/// indexes refer to it.
+pub fn read_located_rows(db: &Database, table_name: &str, table_definition: &TableDefinition) -> Result<Vec<(RowLocation, Vec<Value>)>> {
+    let (owner, file_path) = db.data_location(table_name)?;
+    let mut file = data_reader(owner.storage(), &file_path, table_definition)?;
+
+    let mut rows = Vec::new();
+    match table_definition.compression {
+        Compression::None => loop {
+            let offset = file.stream_position()?;
+            let Some(row_data) = read_frame(&mut file)? else {
+                break;
             };
+            rows.push((RowLocation { offset, row: 0 }, decode_values(&row_data, table_definition)?));
+        },
+        Compression::Lz4 => loop {
+            let offset = file.stream_position()?;
+            let Some(header) = next_chunk_header(&mut file)? else {
+                break;
+            };
+            for (row, row_data) in read_chunk_rows(&mut file, &header)?.iter().enumerate() {
+                let location = RowLocation { offset, row: row as u32 };
+                rows.push((location, decode_values(row_data, table_definition)?));
+            }
+        },
+    }
+    Ok(rows)
+}
+
+/// Reads the rows at `locations`, which must be in ascending order, reading only
+/// their frames, or only the chunks they are in.
+pub fn read_rows_at(db: &Database, table_name: &str, table_definition: &TableDefinition, locations: &[RowLocation]) -> Result<Vec<Vec<Value>>> {
+    let (owner, file_path) = db.data_location(table_name)?;
+    let storage = owner.storage();
+    let truncated = || io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} ends inside a row", file_path));
 
-            inner_results_map.insert(column.name.clone(), value);
+    let mut rows = Vec::with_capacity(locations.len());
+    match table_definition.compression {
+        Compression::None => {
+            for location in locations {
+                let frame = storage.read_range(&file_path, location.offset, 10)?;
+                let size = frame.get(1..9).ok_or_else(truncated)?;
+                let size = u64::from_le_bytes(size.try_into().unwrap()) as usize;
+                let mut frame = Cursor::new(storage.read_range(&file_path, location.offset, 10 + size)?);
+                let row_data = read_frame(&mut frame)?.ok_or_else(truncated)?;
+                rows.push(decode_values(&row_data, table_definition)?);
+            }
         }
+        Compression::Lz4 => {
+            for chunk in locations.chunk_by(|a, b| a.offset == b.offset) {
+                let offset = chunk[0].offset;
+                let start = storage.read_range(&file_path, offset, 9)?;
+                let header_size = start.get(1..9).ok_or_else(truncated)?;
+                let header_size = u64::from_le_bytes(header_size.try_into().unwrap());
+                let mut file = Cursor::new(storage.read_range(&file_path, offset, 9 + header_size as usize)?);
+                let header = next_chunk_header(&mut file)?.ok_or_else(truncated)?;
+                let payload = storage.read_range(&file_path, offset + 9 + header_size, header.compressed_size as usize)?;
+                let chunk_rows = read_chunk_rows(&mut Cursor::new(payload), &header)?;
+                for location in chunk {
+                    let row_data = chunk_rows.get(location.row as usize).ok_or_else(truncated)?;
+                    rows.push(decode_values(row_data, table_definition)?);
+                }
+            }
+        }
+    }
+    Ok(rows)
+}
+
+pub fn decode_row(row_data: &[u8], table_definition: &TableDefinition) -> io::Result<HashMap<String, Value>> {
+    let values = decode_values(row_data, table_definition)?;
+    Ok(table_definition
+        .columns
+        .iter()
+        .map(|column| column.name.clone())
+        .zip(values)
+        .collect())
+}
+
+/// Decodes a row into its values in schema order. Rows starting with
+/// `NULLABLE_ROW_MARKER` carry a bitmap of their NULL columns, which have no bytes
+/// of their own.
+pub fn decode_values(row_data: &[u8], table_definition: &TableDefinition) -> io::Result<Vec<Value>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let column_count = table_definition.columns.len();
+    let (nulls, mut cursor) = match row_data.split_first() {
+        Some((&ROW_MARKER, rest)) => (&[][..], rest),
+        Some((&NULLABLE_ROW_MARKER, rest)) if rest.len() >= column_count.div_ceil(8) => {
+            rest.split_at(column_count.div_ceil(8))
+        }
+        _ => return Err(invalid("Invalid row data".to_string())),
+    };
 
-        results.push(inner_results_map);
+    let mut values = Vec::with_capacity(column_count);
+    for (index, column) in table_definition.columns.iter().enumerate() {
+        if nulls.get(index / 8).is_some_and(|byte| byte & (1 << (index % 8)) != 0) {
+            values.push(Value::Null);
+            continue;
+        }
+
+        let deserialize_error = |e: bincode::Error| invalid(format!("Deserialization error: {}", e));
+        let value = match column.data_type {
+            DataType::Integer => Value::Integer(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Float => Value::Float(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Text => Value::Text(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Boolean => Value::Boolean(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::BigInt => Value::BigInt(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Double => Value::Double(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Date => Value::Date(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Time => Value::Time(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Timestamp => Value::Timestamp(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Vec2 => Value::Vec2(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Vec3 => Value::Vec3(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Vec4 => Value::Vec4(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Mat4 => Value::Mat4(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+            DataType::Vector(dimensions) => {
+                let components: Vec<f32> = bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?;
+                if components.len() != dimensions as usize {
+                    return Err(invalid(format!("Expected {} components in column '{}', found {}", dimensions, column.name, components.len())));
+                }
+                Value::Vector(components)
+            }
+            DataType::Blob => Value::Blob(bincode::deserialize_from(&mut cursor).map_err(deserialize_error)?),
+        };
+        values.push(value);
     }
 
-    Ok(results)
+    Ok(values)
 }
 
 impl fmt::Display for Value {
@@ -98,6 +350,40 @@ impl fmt::Display for Value {
             Value::Float(v) => write!(f, "{}", v),
             Value::Text(v) => write!(f, "{}", v),
             Value::Boolean(v) => write!(f, "{}", v),
+            Value::Null => write!(f, "NULL"),
+            Value::BigInt(v) => write!(f, "{}", v),
+            Value::Double(v) => write!(f, "{}", v),
+            Value::Date(v) => write!(f, "{}", format_date(*v)),
+            Value::Time(v) => write!(f, "{}", format_time(*v)),
+            Value::Timestamp(v) => write!(f, "{}", format_timestamp(*v)),
+            Value::Vec2(_) | Value::Vec3(_) | Value::Vec4(_) | Value::Mat4(_) | Value::Vector(_) => {
+                let components: Vec<String> = self.components().unwrap_or_default().iter().map(f32::to_string).collect();
+                write!(f, "[{}]", components.join(", "))
+            }
+            Value::Blob(v) => write!(f, "{}", BlobFormat::Hex.format(v)),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.partial_cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+            (Value::BigInt(a), Value::BigInt(b)) => a.partial_cmp(b),
+            (Value::Double(a), Value::Double(b)) => a.partial_cmp(b),
+            (Value::Date(a), Value::Date(b)) => a.partial_cmp(b),
+            (Value::Time(a), Value::Time(b)) => a.partial_cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.partial_cmp(b),
+            (Value::Vec2(a), Value::Vec2(b)) => a.partial_cmp(b),
+            (Value::Vec3(a), Value::Vec3(b)) => a.partial_cmp(b),
+            (Value::Vec4(a), Value::Vec4(b)) => a.partial_cmp(b),
+            (Value::Mat4(a), Value::Mat4(b)) => a.partial_cmp(b),
+            (Value::Vector(a), Value::Vector(b)) => a.partial_cmp(b),
+            (Value::Blob(a), Value::Blob(b)) => a.partial_cmp(b),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,230 @@
+use crate::database::database::Database;
+use crate::io::copy::{append_rows, column_name, undo_on_failure};
+use crate::io::reader::{read_table_definition, table_exists};
+use crate::io::writer::write_table_definition;
+use crate::query::temporal::MICROS_PER_SECOND;
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{ColumnDefinition, DataType, SqliteImportDefinition, TableDefinition, Value};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags, Row, Statement};
+
+impl DataType {
+    /// The column type a declared SQLite type is read into, following SQLite's
+    /// type affinity rules. SQLite integers are 64-bit, so INTEGER affinity
+    /// columns become BIGINT, and NUMERIC affinity columns become DOUBLE. DATE,
+    /// TIME and DATETIME/TIMESTAMP columns keep their meaning. Columns declared
+    /// BLOB are read as blobs, while columns without a declared type, which may
+    /// hold anything, are read as text.
+    pub fn from_sqlite(declared_type: &str) -> DataType {
+        let declared_type = declared_type.to_uppercase();
+        if declared_type.contains("DATETIME") || declared_type.contains("TIMESTAMP") {
+            DataType::Timestamp
+        } else if declared_type.contains("DATE") {
+            DataType::Date
+        } else if declared_type.contains("TIME") {
+            DataType::Time
+        } else if declared_type.contains("BOOL") {
+            DataType::Boolean
+        } else if declared_type.contains("INT") {
+            DataType::BigInt
+        } else if ["CHAR", "CLOB", "TEXT"].iter().any(|t| declared_type.contains(t)) || declared_type.is_empty() {
+            DataType::Text
+        } else if declared_type.contains("BLOB") {
+            DataType::Blob
+        } else if declared_type.contains("REAL") || declared_type.contains("FLOA") {
+            DataType::Float
+        } else {
+            DataType::Double
+        }
+    }
+}
+
+/// Copies a table out of a SQLite database file, creating the RQL table from the
+/// SQLite schema if it does not exist yet. Columns are matched by name. Every row
+/// is converted before anything is written, and an import that fails partway is
+/// undone like a `COPY`, so it leaves the table untouched. Returns the number of
+/// rows imported.
+pub fn import_sqlite(db: &Database, import: &SqliteImportDefinition) -> Result<usize> {
+    undo_on_failure(db, &import.table_name, |db| import_table(db, import))
+}
+
+fn import_table(db: &Database, import: &SqliteImportDefinition) -> Result<usize> {
+    let sqlite = Connection::open_with_flags(&import.path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| sqlite_error(import, e))?;
+    let source_columns = source_columns(&sqlite, import)?;
+
+    let exists = table_exists(db, &import.table_name)?;
+    let table_definition = if exists {
+        let table_definition = read_table_definition(db, &import.table_name)?;
+        if let Some((_, column)) = source_columns
+            .iter()
+            .find(|(_, c)| !table_definition.columns.iter().any(|t| t.name == c.name))
+        {
+            return Err(RqlError::Schema(format!(
+                "Table '{}' has no column '{}' to import into",
+                import.table_name, column.name
+            )));
+        }
+        table_definition
+    } else {
+        TableDefinition {
+            name: import.table_name.clone(),
+            columns: source_columns.iter().map(|(_, column)| column.clone()).collect(),
+            ..TableDefinition::default()
+        }
+    };
+
+    // Columns the SQLite table lacks are selected as NULL.
+    let selected: Vec<String> = table_definition
+        .columns
+        .iter()
+        .map(|column| match source_columns.iter().find(|(_, c)| c.name == column.name) {
+            Some((source_name, _)) => quote_identifier(source_name),
+            None => "NULL".to_string(),
+        })
+        .collect();
+    let query = format!("SELECT {} FROM {}", selected.join(", "), quote_identifier(&import.source_table));
+    let mut statement = sqlite.prepare(&query).map_err(|e| sqlite_error(import, e))?;
+
+    check_rows(&mut statement, import, &table_definition)?;
+    if !exists {
+        write_table_definition(db, &table_definition)?;
+    }
+
+    let mut sqlite_rows = statement.query([]).map_err(|e| sqlite_error(import, e))?;
+    let mut row_number = 0;
+    let rows = std::iter::from_fn(|| {
+        row_number += 1;
+        match sqlite_rows.next() {
+            Ok(Some(sqlite_row)) => Some(row_values(sqlite_row, row_number, import, &table_definition)),
+            Ok(None) => None,
+            Err(e) => Some(Err(sqlite_error(import, e))),
+        }
+    });
+    append_rows(db, &import.table_name, rows)
+}
+
+/// Reads the columns of the source table as `(SQLite name, RQL column)` pairs.
+fn source_columns(sqlite: &Connection, import: &SqliteImportDefinition) -> Result<Vec<(String, ColumnDefinition)>> {
+    let mut statement = sqlite
+        .prepare("SELECT name, type, \"notnull\" FROM pragma_table_info(?1)")
+        .map_err(|e| sqlite_error(import, e))?;
+    let columns = statement
+        .query_map([&import.source_table], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, bool>(2)?))
+        })
+        .map_err(|e| sqlite_error(import, e))?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| sqlite_error(import, e))?;
+
+    if columns.is_empty() {
+        return Err(RqlError::Schema(format!(
+            "Table '{}' does not exist in '{}'",
+            import.source_table, import.path
+        )));
+    }
+
+    Ok(columns
+        .into_iter()
+        .enumerate()
+        .map(|(index, (name, declared_type, not_null))| {
+            let column = ColumnDefinition {
+                name: column_name(index, &name),
+                data_type: DataType::from_sqlite(&declared_type),
+                nullable: !not_null,
+                unique: false,
+                primary_key: false,
+                auto_increment: false,
+                default: None,
+            };
+            (name, column)
+        })
+        .collect())
+}
+
+fn check_rows(statement: &mut Statement, import: &SqliteImportDefinition, table_definition: &TableDefinition) -> Result<()> {
+    let mut sqlite_rows = statement.query([]).map_err(|e| sqlite_error(import, e))?;
+    let mut row_number = 0;
+    while let Some(sqlite_row) = sqlite_rows.next().map_err(|e| sqlite_error(import, e))? {
+        row_number += 1;
+        row_values(sqlite_row, row_number, import, table_definition)?;
+    }
+    Ok(())
+}
+
+fn row_values(
+    sqlite_row: &Row,
+    row_number: usize,
+    import: &SqliteImportDefinition,
+    table_definition: &TableDefinition,
+) -> Result<Vec<Value>> {
+    table_definition
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            let raw = sqlite_row.get_ref(index).map_err(|e| sqlite_error(import, e))?;
+            let value = convert(raw, column.data_type).ok_or_else(|| {
+                RqlError::Type(format!(
+                    "{} row {}: {} is not a valid {} value for column '{}'",
+                    import.source_table,
+                    row_number,
+                    describe(raw),
+                    column.data_type.to_sql_type(),
+                    column.name
+                ))
+            })?;
+            if value == Value::Null && !column.nullable && !column.auto_increment {
+                return Err(RqlError::Constraint(format!(
+                    "{} row {}: column '{}' cannot be NULL",
+                    import.source_table, row_number, column.name
+                )));
+            }
+            Ok(value)
+        })
+        .collect()
+}
+
+/// Converts a stored SQLite value to a column type. SQLite lets any column hold
+/// any value, so text is parsed and integers widened where that loses nothing.
+/// Integers in timestamp columns are read as Unix time in seconds.
+fn convert(value: ValueRef, data_type: DataType) -> Option<Value> {
+    let value = match (value, data_type) {
+        (ValueRef::Null, _) => Value::Null,
+        (ValueRef::Integer(v), DataType::Integer) => Value::Integer(i32::try_from(v).ok()?),
+        (ValueRef::Integer(v), DataType::BigInt) => Value::BigInt(v),
+        (ValueRef::Integer(v), DataType::Float) => Value::Float(v as f32),
+        (ValueRef::Integer(v), DataType::Double) => Value::Double(v as f64),
+        (ValueRef::Integer(v @ (0 | 1)), DataType::Boolean) => Value::Boolean(v == 1),
+        (ValueRef::Integer(v), DataType::Text) => Value::Text(v.to_string()),
+        (ValueRef::Integer(v), DataType::Timestamp) => Value::Timestamp(v.checked_mul(MICROS_PER_SECOND)?),
+        (ValueRef::Real(v), DataType::Float) => Value::Float(v as f32),
+        (ValueRef::Real(v), DataType::Double) => Value::Double(v),
+        (ValueRef::Real(v), DataType::Integer) if v.fract() == 0.0 => Value::Integer(i32::try_from(v as i64).ok()?),
+        (ValueRef::Real(v), DataType::BigInt) if v.fract() == 0.0 && v.abs() < 9.2e18 => Value::BigInt(v as i64),
+        (ValueRef::Real(v), DataType::Text) => Value::Text(v.to_string()),
+        (ValueRef::Blob(v) | ValueRef::Text(v), DataType::Blob) => Value::Blob(v.to_vec()),
+        (ValueRef::Text(v), data_type) => Value::parse(std::str::from_utf8(v).ok()?, &data_type)?,
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// A stored SQLite value as it is shown in error messages.
+fn describe(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(v) => v.to_string(),
+        ValueRef::Real(v) => v.to_string(),
+        ValueRef::Text(v) => format!("'{}'", String::from_utf8_lossy(v)),
+        ValueRef::Blob(v) => Value::Blob(v.to_vec()).to_string(),
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sqlite_error(import: &SqliteImportDefinition, e: rusqlite::Error) -> RqlError {
+    RqlError::Io(std::io::Error::other(format!("{}: {}", import.path, e)))
+}
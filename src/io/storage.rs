@@ -0,0 +1,340 @@
+use crate::io::wal::Wal;
+use crate::types::types::WalOp;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::io::{Read, Seek, SeekFrom};
+use std::{fmt, fs, io};
+
+/// Where a database keeps its schema and data files. Paths are relative to the
+/// database root, e.g. `schema/floats_def.bin`.
+pub trait Storage: fmt::Debug + Send + Sync {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    /// Up to `len` bytes of the file at `path` starting at `offset`, fewer at its end.
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        Ok(slice(&self.read(path)?, offset, len).to_vec())
+    }
+
+    /// Length of the file at `path`, or 0 if it does not exist.
+    fn file_len(&self, path: &str) -> io::Result<u64>;
+
+    /// Applies all operations of one statement atomically.
+    fn commit(&self, ops: &[WalOp]) -> io::Result<()>;
+
+    /// How many committed statements have changed the file at `path` since the
+    /// storage was opened.
+    fn version(&self, path: &str) -> u64;
+
+    /// Commits `ops` like `commit` if every file in `expected` is still at its
+    /// version there. Otherwise writes nothing and returns the first changed path.
+    fn commit_unchanged(&self, ops: &[WalOp], expected: &BTreeMap<String, u64>) -> io::Result<Option<String>>;
+
+    /// Paths of the files directly inside `dir`.
+    fn list(&self, dir: &str) -> io::Result<Vec<String>>;
+}
+
+/// Files under a root directory, with changes made durable through the WAL.
+#[derive(Debug)]
+pub struct FileStorage {
+    root: PathBuf,
+    wal: Wal,
+    versions: Versions,
+}
+
+impl FileStorage {
+    /// Opens the directory and returns the storage together with the number of
+    /// statements replayed from its write-ahead log.
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<(FileStorage, usize)> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+
+        let wal = Wal::open(&root);
+        let recovered = wal.recover()?;
+        Ok((
+            FileStorage {
+                root,
+                wal,
+                versions: Versions::default(),
+            },
+            recovered,
+        ))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Storage for FileStorage {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.root.join(path))
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = fs::File::open(self.root.join(path))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = Vec::with_capacity(len);
+        file.take(len as u64).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn file_len(&self, path: &str) -> io::Result<u64> {
+        match fs::metadata(self.root.join(path)) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn commit(&self, ops: &[WalOp]) -> io::Result<()> {
+        self.commit_unchanged(ops, &BTreeMap::new()).map(|_| ())
+    }
+
+    fn version(&self, path: &str) -> u64 {
+        self.versions.get(path)
+    }
+
+    fn commit_unchanged(&self, ops: &[WalOp], expected: &BTreeMap<String, u64>) -> io::Result<Option<String>> {
+        self.versions.commit(ops, expected, || self.wal.commit(ops))
+    }
+
+    fn list(&self, dir: &str) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(self.root.join(dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(format!("{}/{}", dir, entry.file_name().to_string_lossy()));
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// Files kept in memory only, for tests and ephemeral databases.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    files: Mutex<BTreeMap<String, Vec<u8>>>,
+    versions: Versions,
+}
+
+impl MemoryStorage {
+    fn apply(&self, ops: &[WalOp]) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        let mut changed: BTreeMap<String, Option<Vec<u8>>> = BTreeMap::new();
+        for op in ops {
+            let path = op_path(op);
+            let file = changed
+                .entry(path.to_string())
+                .or_insert_with(|| files.get(path).cloned());
+            apply_op(file, op);
+        }
+
+        for (path, contents) in changed {
+            match contents {
+                Some(contents) => files.insert(path, contents),
+                None => files.remove(&path),
+            };
+        }
+        Ok(())
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        let file = files.get(path).ok_or_else(|| not_found(path))?;
+        Ok(slice(file, offset, len).to_vec())
+    }
+
+    fn file_len(&self, path: &str) -> io::Result<u64> {
+        Ok(self.files.lock().unwrap().get(path).map_or(0, |f| f.len() as u64))
+    }
+
+    fn commit(&self, ops: &[WalOp]) -> io::Result<()> {
+        self.commit_unchanged(ops, &BTreeMap::new()).map(|_| ())
+    }
+
+    fn version(&self, path: &str) -> u64 {
+        self.versions.get(path)
+    }
+
+    fn commit_unchanged(&self, ops: &[WalOp], expected: &BTreeMap<String, u64>) -> io::Result<Option<String>> {
+        self.versions.commit(ops, expected, || self.apply(ops))
+    }
+
+    fn list(&self, dir: &str) -> io::Result<Vec<String>> {
+        Ok(self
+            .files
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| is_in_dir(path, dir))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Bytes fetched at a time when streaming the rows of a file.
+pub(crate) const STREAM_BUFFER: usize = 1 << 20;
+
+/// Bytes fetched at a time when walking chunk headers, skipping their payloads.
+pub(crate) const HEADER_BUFFER: usize = 4096;
+
+/// Reads a file of a storage through `read_range`, `capacity` bytes at a time,
+/// so only the parts that are read, and not those seeked over, are loaded. A
+/// read of at least `capacity` bytes goes to the storage directly. A missing
+/// file reads as empty.
+pub(crate) struct RangeReader<'a> {
+    storage: &'a dyn Storage,
+    path: &'a str,
+    len: u64,
+    position: u64,
+    buffer: Vec<u8>,
+    buffer_start: u64,
+    capacity: usize,
+}
+
+impl<'a> RangeReader<'a> {
+    pub(crate) fn new(storage: &'a dyn Storage, path: &'a str, capacity: usize) -> io::Result<RangeReader<'a>> {
+        Ok(RangeReader {
+            storage,
+            path,
+            len: storage.file_len(path)?,
+            position: 0,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            capacity,
+        })
+    }
+}
+
+impl Read for RangeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+
+        let buffer_end = self.buffer_start + self.buffer.len() as u64;
+        if self.position < self.buffer_start || self.position >= buffer_end {
+            if buf.len() >= self.capacity {
+                let bytes = self.storage.read_range(self.path, self.position, buf.len())?;
+                buf[..bytes.len()].copy_from_slice(&bytes);
+                self.position += bytes.len() as u64;
+                return Ok(bytes.len());
+            }
+            self.buffer = self.storage.read_range(self.path, self.position, self.capacity)?;
+            self.buffer_start = self.position;
+        }
+
+        let start = (self.position - self.buffer_start) as usize;
+        let count = buf.len().min(self.buffer.len() - start);
+        buf[..count].copy_from_slice(&self.buffer[start..start + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for RangeReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = position
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start of the file"))?;
+        Ok(self.position)
+    }
+}
+
+/// Per-file versions of a storage, bumped by every statement it commits.
+/// Transactions compare them on `COMMIT` to detect files changed meanwhile.
+#[derive(Debug, Default)]
+pub(crate) struct Versions {
+    versions: Mutex<HashMap<String, u64>>,
+}
+
+impl Versions {
+    fn get(&self, path: &str) -> u64 {
+        self.versions.lock().unwrap().get(path).copied().unwrap_or(0)
+    }
+
+    /// Runs `commit` unless a file in `expected` changed, holding the versions
+    /// locked so no other statement can commit in between.
+    fn commit<F>(&self, ops: &[WalOp], expected: &BTreeMap<String, u64>, commit: F) -> io::Result<Option<String>>
+    where
+        F: FnOnce() -> io::Result<()>,
+    {
+        let mut versions = self.versions.lock().unwrap();
+        for (path, version) in expected {
+            if versions.get(path).copied().unwrap_or(0) != *version {
+                return Ok(Some(path.clone()));
+            }
+        }
+
+        // A failed commit may have changed some files before it stopped.
+        let result = commit();
+        for op in ops {
+            *versions.entry(op_path(op).to_string()).or_insert(0) += 1;
+        }
+        result.map(|_| None)
+    }
+}
+
+/// Whether `path` names a file directly inside `dir`.
+pub(crate) fn is_in_dir(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .is_some_and(|name| !name.is_empty() && !name.contains('/'))
+}
+
+pub(crate) fn op_path(op: &WalOp) -> &str {
+    match op {
+        WalOp::Truncate { path, .. } | WalOp::Write { path, .. } | WalOp::Remove { path } => path,
+    }
+}
+
+/// Applies an operation to an in-memory copy of a file; `None` is a missing file.
+pub(crate) fn apply_op(file: &mut Option<Vec<u8>>, op: &WalOp) {
+    match op {
+        WalOp::Truncate { len, .. } => {
+            file.get_or_insert_with(Vec::new).resize(*len as usize, 0);
+        }
+        WalOp::Write { offset, bytes, .. } => {
+            let file = file.get_or_insert_with(Vec::new);
+            let offset = *offset as usize;
+            if file.len() < offset + bytes.len() {
+                file.resize(offset + bytes.len(), 0);
+            }
+            file[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        WalOp::Remove { .. } => *file = None,
+    }
+}
+
+/// The bytes of `file` a range read returns.
+pub(crate) fn slice(file: &[u8], offset: u64, len: usize) -> &[u8] {
+    let start = usize::try_from(offset).unwrap_or(usize::MAX).min(file.len());
+    &file[start..start + len.min(file.len() - start)]
+}
+
+pub(crate) fn not_found(path: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path))
+}
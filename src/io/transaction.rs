@@ -0,0 +1,139 @@
+use crate::io::storage::{apply_op, is_in_dir, not_found, op_path, slice, Storage};
+use crate::types::error::{Result, RqlError};
+use crate::types::types::WalOp;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// Storage seen from inside a transaction: shadow copies of every file touched
+/// since `BEGIN` over the untouched files of the base storage. `None` marks a
+/// removed file. Nothing reaches the base storage until `publish`, which fails
+/// if another connection changed a file since the transaction first used it.
+#[derive(Debug)]
+pub struct TransactionStorage {
+    base: Arc<dyn Storage>,
+    files: Mutex<BTreeMap<String, Option<Vec<u8>>>>,
+    versions: Mutex<BTreeMap<String, u64>>,
+}
+
+impl TransactionStorage {
+    pub fn new(base: Arc<dyn Storage>) -> TransactionStorage {
+        TransactionStorage {
+            base,
+            files: Mutex::new(BTreeMap::new()),
+            versions: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Remembers the base version of a file the first time the transaction uses
+    /// it. Recorded before the file is read, so a change in between is caught too.
+    fn track(&self, path: &str) {
+        if let Entry::Vacant(entry) = self.versions.lock().unwrap().entry(path.to_string()) {
+            entry.insert(self.base.version(path));
+        }
+    }
+
+    /// Commits every staged file to the base storage as a single statement.
+    /// Returns whether there was anything to commit.
+    pub fn publish(&self) -> Result<bool> {
+        let files = std::mem::take(&mut *self.files.lock().unwrap());
+        let versions = std::mem::take(&mut *self.versions.lock().unwrap());
+        let ops: Vec<WalOp> = files
+            .into_iter()
+            .flat_map(|(path, contents)| match contents {
+                Some(bytes) => vec![
+                    WalOp::Truncate { path: path.clone(), len: 0 },
+                    WalOp::Write { path, offset: 0, bytes },
+                ],
+                None => vec![WalOp::Remove { path }],
+            })
+            .collect();
+        if ops.is_empty() {
+            return Ok(false);
+        }
+
+        match self.base.commit_unchanged(&ops, &versions)? {
+            None => Ok(true),
+            Some(path) => Err(RqlError::Transaction(format!(
+                "Cannot commit: {} was changed by another connection during the transaction, which was rolled back",
+                path
+            ))),
+        }
+    }
+}
+
+impl Storage for TransactionStorage {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.track(path);
+        match self.files.lock().unwrap().get(path) {
+            Some(Some(contents)) => Ok(contents.clone()),
+            Some(None) => Err(not_found(path)),
+            None => self.base.read(path),
+        }
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.track(path);
+        match self.files.lock().unwrap().get(path) {
+            Some(Some(contents)) => Ok(slice(contents, offset, len).to_vec()),
+            Some(None) => Err(not_found(path)),
+            None => self.base.read_range(path, offset, len),
+        }
+    }
+
+    fn file_len(&self, path: &str) -> io::Result<u64> {
+        self.track(path);
+        match self.files.lock().unwrap().get(path) {
+            Some(contents) => Ok(contents.as_ref().map_or(0, |c| c.len() as u64)),
+            None => self.base.file_len(path),
+        }
+    }
+
+    fn commit(&self, ops: &[WalOp]) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        for op in ops {
+            let path = op_path(op);
+            if !files.contains_key(path) {
+                self.track(path);
+                let contents = match self.base.read(path) {
+                    Ok(contents) => Some(contents),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                    Err(e) => return Err(e),
+                };
+                files.insert(path.to_string(), contents);
+            }
+            apply_op(files.get_mut(path).unwrap(), op);
+        }
+
+        Ok(())
+    }
+
+    /// Transactions do not nest, so the base storage keeps the versions.
+    fn version(&self, path: &str) -> u64 {
+        self.base.version(path)
+    }
+
+    fn commit_unchanged(&self, ops: &[WalOp], expected: &BTreeMap<String, u64>) -> io::Result<Option<String>> {
+        match expected.iter().find(|(path, version)| self.base.version(path) != **version) {
+            Some((path, _)) => Ok(Some(path.clone())),
+            None => self.commit(ops).map(|_| None),
+        }
+    }
+
+    fn list(&self, dir: &str) -> io::Result<Vec<String>> {
+        let mut paths = self.base.list(dir)?;
+        for (path, contents) in self.files.lock().unwrap().iter() {
+            if !is_in_dir(path, dir) {
+                continue;
+            }
+            match contents {
+                Some(_) if !paths.contains(path) => paths.push(path.clone()),
+                None => paths.retain(|p| p != path),
+                _ => {}
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
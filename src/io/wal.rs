@@ -0,0 +1,195 @@
+use crate::types::types::WalOp;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::{fs, io};
+
+const ENTRY_MARKER: u8 = 0xEF;
+
+/// Write-ahead log for data and schema files. Every statement is logged as one
+/// entry of `[0xEF][size u64][checksum u64][ops]`, synced, applied and then
+/// cleared. An entry that is torn or fails its checksum was never committed, and
+/// one that failed to apply is replayed before the next statement is logged.
+/// Operation paths are relative to the database root. Statements committed by
+/// several connections at once go through the log one at a time, so none can
+/// clear an entry that another has not applied yet.
+#[derive(Debug)]
+pub struct Wal {
+    root: PathBuf,
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl Wal {
+    pub fn open<P: AsRef<Path>>(root: P) -> Wal {
+        Wal {
+            root: root.as_ref().to_path_buf(),
+            path: root.as_ref().join("data").join("wal.log"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn commit(&self, ops: &[WalOp]) -> io::Result<()> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+
+        // A commit that panicked left the log in a state that recovery replays.
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        // A commit that failed to apply its entry left it in the log, and the files
+        // half written. It is replayed first, as clearing the log would lose it.
+        self.replay()?;
+        self.append_entry(ops)?;
+        self.apply(ops)?;
+        self.clear()
+    }
+
+    /// Replays every complete entry left behind by an interrupted commit and
+    /// discards a trailing incomplete one. Returns the number of replayed entries.
+    pub fn recover(&self) -> io::Result<usize> {
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.replay()
+    }
+
+    /// `recover`, for a caller that holds the lock.
+    fn replay(&self) -> io::Result<usize> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        while let Some(ops) = read_entry(&mut file)? {
+            entries.push(ops);
+        }
+
+        for ops in &entries {
+            self.apply(ops)?;
+        }
+
+        self.clear()?;
+        Ok(entries.len())
+    }
+
+    pub(crate) fn append_entry(&self, ops: &[WalOp]) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let payload = bincode::serialize(ops).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Serialization error: {}", e))
+        })?;
+
+        let mut entry = Vec::with_capacity(payload.len() + 17);
+        entry.push(ENTRY_MARKER);
+        entry.extend((payload.len() as u64).to_le_bytes());
+        entry.extend(checksum(&payload).to_le_bytes());
+        entry.extend(payload);
+
+        let created = !self.path.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(&entry)?;
+        file.sync_all()?;
+        if created {
+            sync_parent_dir(&self.path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies operations directly to their files, syncing each file it touches.
+    pub fn apply(&self, ops: &[WalOp]) -> io::Result<()> {
+        for op in ops {
+            match op {
+                WalOp::Truncate { path, len } => {
+                    let file = open_for_write(&self.root.join(path))?;
+                    file.set_len(*len)?;
+                    file.sync_all()?;
+                }
+                WalOp::Write { path, offset, bytes } => {
+                    let mut file = open_for_write(&self.root.join(path))?;
+                    file.seek(SeekFrom::Start(*offset))?;
+                    file.write_all(bytes)?;
+                    file.sync_all()?;
+                }
+                WalOp::Remove { path } => {
+                    let path = self.root.join(path);
+                    match fs::remove_file(&path) {
+                        Ok(()) => sync_parent_dir(&path)?,
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+
+        let file = OpenOptions::new().write(true).open(&self.path)?;
+        file.set_len(0)?;
+        file.sync_all()
+    }
+}
+
+fn open_for_write(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let created = !path.exists();
+    let file = OpenOptions::new().write(true).create(true).truncate(false).open(path)?;
+    if created {
+        sync_parent_dir(path)?;
+    }
+    Ok(file)
+}
+
+fn read_entry<R: Read>(reader: &mut R) -> io::Result<Option<Vec<WalOp>>> {
+    let mut marker = [0u8; 1];
+    if reader.read_exact(&mut marker).is_err() || marker[0] != ENTRY_MARKER {
+        return Ok(None);
+    }
+
+    let mut size_buf = [0u8; 8];
+    let mut checksum_buf = [0u8; 8];
+    if reader.read_exact(&mut size_buf).is_err() || reader.read_exact(&mut checksum_buf).is_err() {
+        return Ok(None);
+    }
+
+    let mut payload = Vec::new();
+    let size = u64::from_le_bytes(size_buf);
+    reader.take(size).read_to_end(&mut payload)?;
+    if payload.len() as u64 != size || checksum(&payload) != u64::from_le_bytes(checksum_buf) {
+        return Ok(None);
+    }
+
+    Ok(bincode::deserialize(&payload).ok())
+}
+
+/// FNV-1a, enough to tell a torn entry from a complete one.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
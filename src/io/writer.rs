@@ -1,22 +1,45 @@
-use crate::types::types::{DataType, TableDefinition, Value};
-use std::fs::{File, OpenOptions};
-use std::io::Write;
-use std::{fs, io};
+use crate::database::database::Database;
+use crate::io::chunk::{encode_chunks, next_chunk_header, read_chunk_rows, skip_chunk_rows, CHUNK_ROWS};
+use crate::io::index::index_ops;
+use crate::io::reader::{decode_values, read_located_rows, read_table_definition};
+use crate::io::storage::{RangeReader, Storage, HEADER_BUFFER};
+use crate::query::temporal::{parse_date, parse_time, parse_timestamp};
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{ChunkHeader, Compression, DataType, IndexDefinition, RowLocation, TableDefinition, Value, WalOp};
+use std::io::{Cursor, Seek, SeekFrom};
+use std::io;
 use std::collections::HashMap;
 
-pub fn write_table_definition(table_definition: &TableDefinition) -> io::Result<()> {
-    fs::create_dir_all("schema")?;
+/// Starts every schema file, followed by `SCHEMA_VERSION` as a little-endian u32.
+/// Files without it are in the layout from before the format was versioned.
+pub const SCHEMA_MAGIC: [u8; 4] = *b"RQLS";
+/// Bumped whenever the serialized `TableDefinition` changes.
+pub const SCHEMA_VERSION: u32 = 2;
 
-    let table_name_lowercase = table_definition.name.clone().to_lowercase() + "_def.bin";
-    let file_path = format!("schema/{}", table_name_lowercase);
-
-    let mut file = File::create(file_path)?;
-    let encoded: Vec<u8> = bincode::serialize(&table_definition).unwrap();
-    file.write_all(&encoded)?;
+/// Starts the frame of a row without NULL columns.
+pub const ROW_MARKER: u8 = 0xAB;
+/// Starts the frame of a row whose data begins with a bitmap of its NULL columns.
+pub const NULLABLE_ROW_MARKER: u8 = 0xAC;
 
+pub fn write_table_definition(db: &Database, table_definition: &TableDefinition) -> Result<()> {
+    let (owner, _) = db.schema_location(&table_definition.name)?;
+    owner.storage().commit(&table_definition_ops(db, &table_definition.name, table_definition)?)?;
     Ok(())
 }
 
+pub(crate) fn table_definition_ops(db: &Database, table_name: &str, table_definition: &TableDefinition) -> Result<Vec<WalOp>> {
+    let (_, file_path) = db.schema_location(table_name)?;
+
+    let mut encoded = SCHEMA_MAGIC.to_vec();
+    encoded.extend(SCHEMA_VERSION.to_le_bytes());
+    encoded.extend(bincode::serialize(&table_definition)?);
+
+    Ok(vec![
+        WalOp::Truncate { path: file_path.clone(), len: 0 },
+        WalOp::Write { path: file_path, offset: 0, bytes: encoded },
+    ])
+}
+
 pub fn update_rows(
     existing_rows: Vec<HashMap<String, Value>>,
     updates: Vec<HashMap<String, Value>>,
@@ -35,69 +58,271 @@ pub fn update_rows(
         .collect()
 }
 
-pub fn serialize_from_value(value: &Value) -> io::Result<Vec<u8>> {
-    match value {
-        Value::Integer(parsed) => Ok(bincode::serialize(parsed).unwrap()),
-        Value::Float(parsed) => Ok(bincode::serialize(parsed).unwrap()),
-        Value::Text(parsed) => Ok(bincode::serialize(parsed).unwrap()),
-        Value::Boolean(parsed) => Ok(bincode::serialize(parsed).unwrap()),
-    }
+pub fn serialize_from_value(value: &Value) -> Result<Vec<u8>> {
+    let bytes = match value {
+        Value::Integer(parsed) => bincode::serialize(parsed)?,
+        Value::Float(parsed) => bincode::serialize(parsed)?,
+        Value::Text(parsed) => bincode::serialize(parsed)?,
+        Value::Boolean(parsed) => bincode::serialize(parsed)?,
+        Value::Null => Vec::new(),
+        Value::BigInt(parsed) => bincode::serialize(parsed)?,
+        Value::Double(parsed) => bincode::serialize(parsed)?,
+        Value::Date(parsed) => bincode::serialize(parsed)?,
+        Value::Time(parsed) => bincode::serialize(parsed)?,
+        Value::Timestamp(parsed) => bincode::serialize(parsed)?,
+        Value::Vec2(parsed) => bincode::serialize(parsed)?,
+        Value::Vec3(parsed) => bincode::serialize(parsed)?,
+        Value::Vec4(parsed) => bincode::serialize(parsed)?,
+        Value::Mat4(parsed) => bincode::serialize(parsed)?,
+        Value::Vector(parsed) => bincode::serialize(parsed)?,
+        // Prefixed with its length in bytes, like TEXT.
+        Value::Blob(parsed) => bincode::serialize(parsed)?,
+    };
+    Ok(bytes)
 }
 
-pub fn serialize_value(value: &str, data_type: &DataType) -> io::Result<Vec<u8>> {
-    match data_type {
-        DataType::Integer => {
-            let parsed: i32 = value.parse().map_err(|_| {
-                io::Error::new(io::ErrorKind::InvalidInput, "Invalid integer value")
-            })?;
-            Ok(bincode::serialize(&parsed).unwrap())
+pub fn serialize_value(value: &str, data_type: &DataType) -> Result<Vec<u8>> {
+    let invalid = || RqlError::Type(format!("'{}' is not a valid {} value", value, data_type.to_sql_type()));
+    let bytes = match data_type {
+        DataType::Integer => bincode::serialize(&value.parse::<i32>().map_err(|_| invalid())?)?,
+        DataType::Float => bincode::serialize(&value.parse::<f32>().map_err(|_| invalid())?)?,
+        DataType::Text => bincode::serialize(&value)?,
+        DataType::Boolean => bincode::serialize(&value.parse::<bool>().map_err(|_| invalid())?)?,
+        DataType::BigInt => bincode::serialize(&value.parse::<i64>().map_err(|_| invalid())?)?,
+        DataType::Double => bincode::serialize(&value.parse::<f64>().map_err(|_| invalid())?)?,
+        DataType::Date => bincode::serialize(&parse_date(value).ok_or_else(invalid)?)?,
+        DataType::Time => bincode::serialize(&parse_time(value).ok_or_else(invalid)?)?,
+        DataType::Timestamp => bincode::serialize(&parse_timestamp(value).ok_or_else(invalid)?)?,
+        DataType::Vec2 | DataType::Vec3 | DataType::Vec4 | DataType::Mat4 | DataType::Vector(_) | DataType::Blob => {
+            serialize_from_value(&Value::parse(value, data_type).ok_or_else(invalid)?)?
         }
-        DataType::Float => {
-            let parsed: f32 = value.parse::<f32>().map_err(|_| {
-                io::Error::new(io::ErrorKind::InvalidInput, "Invalid float value")
-            })?;
-            Ok(bincode::serialize(&parsed).unwrap())
+    };
+    Ok(bytes)
+}
+
+/// Joins serialized column values into a row, led by its frame marker. An empty
+/// value stands for NULL: such rows get a bitmap of their NULL columns, so rows
+/// without any keep the plain layout.
+pub fn encode_row(values: &[Vec<u8>]) -> Vec<u8> {
+    let size = values.iter().map(Vec::len).sum::<usize>() + 1;
+    if values.iter().all(|value| !value.is_empty()) {
+        let mut row = Vec::with_capacity(size);
+        row.push(ROW_MARKER);
+        values.iter().for_each(|value| row.extend_from_slice(value));
+        return row;
+    }
+
+    let mut nulls = vec![0u8; values.len().div_ceil(8)];
+    for (index, value) in values.iter().enumerate() {
+        if value.is_empty() {
+            nulls[index / 8] |= 1 << (index % 8);
         }
-        DataType::Text => Ok(bincode::serialize(&value).unwrap()),
-        DataType::Boolean => {
-            let parsed: bool = value.parse().map_err(|_| {
-                io::Error::new(io::ErrorKind::InvalidInput, "Invalid boolean value")
-            })?;
-            Ok(bincode::serialize(&parsed).unwrap())
+    }
+    let mut row = Vec::with_capacity(size + nulls.len());
+    row.push(NULLABLE_ROW_MARKER);
+    row.extend(nulls);
+    values.iter().for_each(|value| row.extend_from_slice(value));
+    row
+}
+
+/// Appends rows to, or replaces the contents of, a table's data file. The change
+/// is logged to the write-ahead log before the data file is touched.
+pub fn write_vec_of_bytes_to_file(db: &Database, data: Vec<Vec<Vec<u8>>>, table_name: &str, append: bool) -> Result<()> {
+    let table_definition = read_table_definition(db, table_name)?;
+    let (owner, _) = db.data_location(table_name)?;
+    owner.storage().commit(&data_ops(db, data, table_name, &table_definition, append)?)?;
+    Ok(())
+}
+
+/// Like `write_vec_of_bytes_to_file`, but also replaces the table's definition, in
+/// the same write-ahead log entry so the rows and the schema change together.
+pub fn write_rows_and_table_definition(
+    db: &Database,
+    table_name: &str,
+    data: Vec<Vec<Vec<u8>>>,
+    table_definition: &TableDefinition,
+    append: bool,
+) -> Result<()> {
+    let (owner, _) = db.data_location(table_name)?;
+    let mut ops = table_definition_ops(db, table_name, table_definition)?;
+    ops.extend(data_ops(db, data, table_name, table_definition, append)?);
+    owner.storage().commit(&ops)?;
+    Ok(())
+}
+
+/// Builds a new index over the rows of a table and saves it together with the
+/// table's definition, which lists it, in one write-ahead log entry.
+pub fn write_index(db: &Database, table_name: &str, table_definition: &TableDefinition, index: &IndexDefinition) -> Result<()> {
+    let (owner, _) = db.data_location(table_name)?;
+    let rows = read_located_rows(db, table_name, table_definition)?;
+    let mut ops = table_definition_ops(db, table_name, table_definition)?;
+    ops.extend(index.build_ops(db, table_name, table_definition, &rows)?);
+    owner.storage().commit(&ops)?;
+    Ok(())
+}
+
+/// Replaces the rows of several tables, such as those a cascading `DELETE` changes.
+/// The changes to each database are committed in one write-ahead log entry.
+pub fn write_tables(db: &Database, tables: Vec<(&str, Vec<Vec<Vec<u8>>>)>) -> Result<()> {
+    let mut commits: Vec<(&Database, Vec<WalOp>)> = Vec::new();
+    for (table_name, data) in tables {
+        let table_definition = read_table_definition(db, table_name)?;
+        let (owner, _) = db.data_location(table_name)?;
+        let ops = data_ops(db, data, table_name, &table_definition, false)?;
+        match commits.iter_mut().find(|(database, _)| std::ptr::eq(*database, owner)) {
+            Some((_, pending)) => pending.extend(ops),
+            None => commits.push((owner, ops)),
         }
     }
+    for (owner, ops) in commits {
+        owner.storage().commit(&ops)?;
+    }
+    Ok(())
 }
 
+/// Removes the schema, data and index files of a table in one write-ahead log entry.
+pub fn remove_table(db: &Database, table_name: &str, table_definition: &TableDefinition) -> Result<()> {
+    let (owner, schema_path) = db.schema_location(table_name)?;
+    let (_, data_path) = db.data_location(table_name)?;
+    let mut ops = vec![WalOp::Remove { path: schema_path }, WalOp::Remove { path: data_path }];
+    for index in &table_definition.indexes {
+        ops.push(WalOp::Remove { path: db.index_location(table_name, &index.name)?.1 });
+    }
+    owner.storage().commit(&ops)?;
+    Ok(())
+}
 
+fn data_ops(
+    db: &Database,
+    data: Vec<Vec<Vec<u8>>>,
+    table_name: &str,
+    table_definition: &TableDefinition,
+    append: bool,
+) -> Result<Vec<WalOp>> {
+    let (owner, file_path) = db.data_location(table_name)?;
+    let storage = owner.storage();
 
-pub fn write_vec_of_bytes_to_file(data: Vec<Vec<Vec<u8>>>, table_name: &str, append: bool) -> io::Result<()> {
-    fs::create_dir_all("data")?;
+    let rows: Vec<Vec<u8>> = data.iter().map(|row| encode_row(row)).collect();
+    let new_rows = rows.len();
 
-    let table_name_lowercase = table_name.to_lowercase() + "_data.bin";
-    let file_path = format!("data/{}", table_name_lowercase);
+    let (offset, bytes, rows) = match table_definition.compression {
+        Compression::None => {
+            let offset = if append { storage.file_len(&file_path)? } else { 0 };
+            (offset, rows.iter().flat_map(|row| frame_row(row)).collect(), rows)
+        }
+        Compression::Lz4 => {
+            let (offset, rows) = if append {
+                merge_with_last_chunk(storage, &file_path, rows)?
+            } else {
+                (0, rows)
+            };
+            (offset, encode_chunks(&rows, table_definition)?, rows)
+        }
+    };
 
-    let mut file = OpenOptions::new()
-        .append(append)
-        .write(!append)
-        .create(true)
-        .truncate(!append)
-        .open(file_path)?;
+    // Rows merged into the last chunk keep their locations, so only the new ones
+    // are added to the indexes.
+    let mut located = Vec::new();
+    if !table_definition.indexes.is_empty() {
+        let locations = row_locations(table_definition.compression, offset, &bytes, &rows)?;
+        for (location, row) in locations.into_iter().zip(&rows).skip(rows.len() - new_rows) {
+            located.push((location, decode_values(row, table_definition)?));
+        }
+    }
 
-    for row in data {
-        let mut row_data = Vec::new();
+    let mut ops = vec![
+        WalOp::Truncate { path: file_path.clone(), len: offset },
+        WalOp::Write { path: file_path, offset, bytes },
+    ];
+    ops.extend(index_ops(db, table_name, table_definition, &located, append)?);
+    Ok(ops)
+}
 
-        for column in row {
-            row_data.extend(column);
+/// Where each of `rows` ends up once `bytes`, their encoding, is written at `offset`.
+fn row_locations(compression: Compression, offset: u64, bytes: &[u8], rows: &[Vec<u8>]) -> io::Result<Vec<RowLocation>> {
+    let mut locations = Vec::with_capacity(rows.len());
+    match compression {
+        Compression::None => {
+            let mut offset = offset;
+            for row in rows {
+                locations.push(RowLocation { offset, row: 0 });
+                // The marker, size and end marker around the row data.
+                offset += row.len() as u64 + 9;
+            }
         }
+        Compression::Lz4 => {
+            let mut file = Cursor::new(bytes);
+            loop {
+                let start = offset + file.position();
+                let Some(header) = next_chunk_header(&mut file)? else {
+                    break;
+                };
+                skip_chunk_rows(&mut file, &header)?;
+                locations.extend((0..header.row_count as u32).map(|row| RowLocation { offset: start, row }));
+            }
+        }
+    }
+    Ok(locations)
+}
+
+/// Frames a row from `encode_row` as `[marker][size u64][0xCD][row data]`.
+pub fn frame_row(row: &[u8]) -> Vec<u8> {
+    let (marker, row_data) = row.split_first().expect("encoded rows start with their marker");
+    let mut framed = Vec::with_capacity(row_data.len() + 10);
+    framed.push(*marker);
+    framed.extend((row_data.len() as u64).to_le_bytes());
+    framed.push(0xCD);
+    framed.extend_from_slice(row_data);
+    framed
+}
+
+/// The offset rows appended to a table's data file are written at: the end of
+/// the file, or the start of its last LZ4 chunk while that still has room, as
+/// the new rows are merged into it. The bytes from there on are all an append
+/// rewrites.
+pub(crate) fn append_offset(storage: &dyn Storage, file_path: &str, compression: Compression) -> io::Result<u64> {
+    match compression {
+        Compression::None => storage.file_len(file_path),
+        Compression::Lz4 => match last_chunk(storage, file_path)? {
+            (Some((offset, header)), _) if (header.row_count as usize) < CHUNK_ROWS => Ok(offset),
+            (_, end) => Ok(end),
+        },
+    }
+}
 
-        let row_size = row_data.len() as u64;
+/// Returns the offset the new chunks should be written at, together with the rows
+/// to encode there: the rows of the last chunk if it still has room, followed by `rows`.
+/// Only the chunk headers and the last chunk are read.
+fn merge_with_last_chunk(storage: &dyn Storage, file_path: &str, rows: Vec<Vec<u8>>) -> io::Result<(u64, Vec<Vec<u8>>)> {
+    match last_chunk(storage, file_path)? {
+        (Some((offset, header)), _) if (header.row_count as usize) < CHUNK_ROWS => {
+            let mut file = RangeReader::new(storage, file_path, HEADER_BUFFER)?;
+            file.seek(SeekFrom::Start(offset))?;
+            next_chunk_header(&mut file)?;
+            let mut merged = read_chunk_rows(&mut file, &header)?;
+            merged.extend(rows);
+            Ok((offset, merged))
+        }
+        (_, end) => Ok((end, rows)),
+    }
+}
 
-        file.write_all(&[0xAB])?;
-        file.write_all(&row_size.to_le_bytes())?;
-        file.write_all(&[0xCD])?;
+/// The offset and header of the last chunk of an LZ4 data file, if it has one,
+/// and the offset its chunks end at. Only the chunk headers are read.
+fn last_chunk(storage: &dyn Storage, file_path: &str) -> io::Result<(Option<(u64, ChunkHeader)>, u64)> {
+    let mut file = RangeReader::new(storage, file_path, HEADER_BUFFER)?;
 
-        file.write_all(&row_data)?;
+    let mut last_chunk = None;
+    loop {
+        let offset = file.stream_position()?;
+        match next_chunk_header(&mut file)? {
+            Some(header) => {
+                skip_chunk_rows(&mut file, &header)?;
+                last_chunk = Some((offset, header));
+            }
+            None => break,
+        }
     }
 
-    Ok(())
+    Ok((last_chunk, file.stream_position()?))
 }
@@ -0,0 +1,32 @@
+//! RQL, a structured database engine that runs its mathematical statements as
+//! compute shaders through wgpu.
+//!
+//! ```no_run
+//! use rql::Database;
+//!
+//! let db = Database::open("floats_db")?;
+//! let mut conn = db.connect();
+//! conn.execute("CREATE TABLE floats (col1 FLOAT, col2 FLOAT);")?;
+//! conn.execute("INSERT INTO floats VALUES (0.25, 0.5)")?;
+//! conn.execute("UPDATE floats SET col1 = col2 * sin(col1);")?;
+//!
+//! for row in &conn.query("SELECT * FROM floats;")? {
+//!     let col1: f32 = row.get("col1")?;
+//!     println!("{}", col1);
+//! }
+//! # Ok::<(), rql::RqlError>(())
+//! ```
+#![allow(clippy::module_inception)]
+
+pub mod types;
+pub mod query;
+pub mod io;
+pub mod rqle;
+pub mod database;
+mod tests;
+
+pub use database::connection::Connection;
+pub use database::database::Database;
+pub use query::result::{FromValue, RowIndex};
+pub use types::error::{Position, RqlError};
+pub use types::types::{BlobFormat, Column, ColumnBatch, ColumnData, DataType, OutputFormat, ResultSet, Row, StatementResult, Value};
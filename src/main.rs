@@ -1,12 +1,133 @@
-use crate::query::runner::QueryRunner;
+use rql::query::runner::QueryRunner;
+use rql::{BlobFormat, Database, OutputFormat};
+use std::fs::File;
+use std::io::{BufReader, IsTerminal};
+use std::process::ExitCode;
 
-mod types;
-mod query;
-mod io;
-mod tests;
-mod rqle;
+const USAGE: &str = "Usage: rql [--db <path> | --memory] [-f <file>]... [-c <sql>]... [--format <format>] [--precision <digits>] [--blobs <format>] [--quiet]
 
-fn main() -> Result<(), ()> {
-    QueryRunner::repl().expect("TODO: panic message");
-    Ok(())
+  --db <path>          Use the database in <path> (default: the current directory)
+  --memory             Use a database that is never written to disk
+  -f, --file <file>    Run the statements in <file>
+  -c, --command <sql>  Run the statements in <sql>
+  --format <format>    Print results as table (default), csv, json, ndjson or markdown
+  --precision <digits> Print floats with <digits> decimal places
+  --blobs <format>     Print BLOB values as hex (default) or base64
+  -q, --quiet          Only print query results and errors
+
+Without -f or -c, statements piped into stdin are run, or a REPL is started
+when stdin is a terminal. The exit code is 1 if any statement failed.";
+
+enum Source {
+    File(String),
+    Command(String),
+}
+
+fn main() -> ExitCode {
+    let mut db_path = String::from(".");
+    let mut in_memory = false;
+    let mut quiet = false;
+    let mut format = OutputFormat::Table;
+    let mut precision = None;
+    let mut blobs = BlobFormat::Hex;
+    let mut sources = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" | "-f" | "--file" | "-c" | "--command" | "--format" | "--precision" | "--blobs" => {
+                let Some(value) = args.next() else {
+                    eprintln!("Error: {} requires a value", arg);
+                    return ExitCode::FAILURE;
+                };
+                match arg.as_str() {
+                    "--db" => db_path = value,
+                    "-f" | "--file" => sources.push(Source::File(value)),
+                    "-c" | "--command" => sources.push(Source::Command(value)),
+                    "--format" => match OutputFormat::from_name(&value) {
+                        Some(value) => format = value,
+                        None => {
+                            eprintln!("Error: Unknown format '{}'", value);
+                            return ExitCode::FAILURE;
+                        }
+                    },
+                    "--blobs" => match BlobFormat::from_name(&value) {
+                        Some(value) => blobs = value,
+                        None => {
+                            eprintln!("Error: Unknown blob format '{}'", value);
+                            return ExitCode::FAILURE;
+                        }
+                    },
+                    _ => match value.parse() {
+                        Ok(value) => precision = Some(value),
+                        Err(_) => {
+                            eprintln!("Error: --precision expects a number of digits, got '{}'", value);
+                            return ExitCode::FAILURE;
+                        }
+                    },
+                }
+            }
+            "--memory" => in_memory = true,
+            "-q" | "--quiet" => quiet = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                eprintln!("Error: Unknown argument '{}'\n\n{}", arg, USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let database = if in_memory {
+        Database::open_in_memory()
+    } else {
+        match Database::open(&db_path) {
+            Ok(database) => database,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+    let mut runner = QueryRunner::new(database.connect());
+    runner.set_quiet(quiet);
+    runner.set_format(format);
+    runner.set_precision(precision);
+    runner.set_blob_format(blobs);
+
+    if sources.is_empty() && std::io::stdin().is_terminal() {
+        return match runner.repl() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let result = if sources.is_empty() {
+        runner.run_script(std::io::stdin().lock())
+    } else {
+        sources.iter().try_fold(0, |failures, source| {
+            let failed = match source {
+                Source::File(path) => {
+                    let file = File::open(path).map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+                    runner.run_script(BufReader::new(file))
+                }
+                Source::Command(sql) => runner.run_script(sql.as_bytes()),
+            }?;
+            Ok(failures + failed)
+        })
+    };
+
+    let result = result.and_then(|failures| runner.rollback_open_transaction().map(|_| failures));
+    match result {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
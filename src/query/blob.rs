@@ -0,0 +1,118 @@
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{BlobFormat, Value};
+use std::fmt::Write;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Decodes hexadecimal digits, two per byte, as in the `0a1b` of `X'0a1b'`.
+pub(crate) fn parse_hex(digits: &str) -> Option<Vec<u8>> {
+    if !digits.len().is_multiple_of(2) || !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&digits[index..index + 2], 16).ok())
+        .collect()
+}
+
+/// Reads a blob written as `\x` and hexadecimal digits, the way blobs are printed.
+pub(crate) fn parse_blob(text: &str) -> Option<Vec<u8>> {
+    parse_hex(text.trim().strip_prefix("\\x")?)
+}
+
+pub(crate) fn format_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{:02x}", byte);
+    }
+    out
+}
+
+/// Standard base64 with `=` padding (RFC 4648).
+pub(crate) fn format_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (index, byte)| bits | (*byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= group.len() {
+                out.push(BASE64_ALPHABET[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+impl BlobFormat {
+    pub fn from_name(name: &str) -> Option<BlobFormat> {
+        match name.to_lowercase().as_str() {
+            "hex" => Some(BlobFormat::Hex),
+            "base64" => Some(BlobFormat::Base64),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BlobFormat::Hex => "hex",
+            BlobFormat::Base64 => "base64",
+        }
+    }
+
+    pub fn format(self, bytes: &[u8]) -> String {
+        match self {
+            BlobFormat::Hex => format!("\\x{}", format_hex(bytes)),
+            BlobFormat::Base64 => format_base64(bytes),
+        }
+    }
+}
+
+/// `length(value)`: the number of characters of a text, or of bytes of a blob.
+pub(crate) fn length(value: &Value) -> Result<Value> {
+    let length = match value {
+        Value::Null => return Ok(Value::Null),
+        Value::Text(text) => text.chars().count(),
+        Value::Blob(bytes) => bytes.len(),
+        value => return Err(RqlError::Type(format!("length() needs TEXT or BLOB, not {}", value))),
+    };
+    i32::try_from(length)
+        .map(Value::Integer)
+        .map_err(|_| RqlError::Type(format!("length() of {} does not fit an INTEGER", length)))
+}
+
+/// `substring(value, start[, count])`: `count` characters of a text, or bytes of a
+/// blob, from the 1-based position `start`. As in PostgreSQL, a start before the
+/// first position shortens the result instead of moving it.
+pub(crate) fn substring(value: &Value, start: &Value, count: Option<&Value>) -> Result<Value> {
+    let integer = |value: &Value| match value {
+        Value::Integer(v) => Some(*v as i64),
+        Value::BigInt(v) => Some(*v),
+        _ => None,
+    };
+    if *value == Value::Null || *start == Value::Null || count == Some(&Value::Null) {
+        return Ok(Value::Null);
+    }
+    let first = integer(start).ok_or_else(|| RqlError::Type(format!("substring() needs an integer start, not {}", start)))?;
+    let end = match count {
+        None => i64::MAX,
+        Some(count) => match integer(count) {
+            Some(count) if count >= 0 => first.saturating_add(count),
+            _ => return Err(RqlError::Type(format!("substring() needs a non-negative count, not {}", count))),
+        },
+    };
+    // 0-based positions, clamped to the value.
+    let range = |len: usize| {
+        let clamp = |position: i64| (position.max(1) - 1).min(len as i64) as usize;
+        clamp(first)..clamp(end).max(clamp(first))
+    };
+
+    match value {
+        Value::Text(text) => {
+            let chars: Vec<char> = text.chars().collect();
+            Ok(Value::Text(chars[range(chars.len())].iter().collect()))
+        }
+        Value::Blob(bytes) => Ok(Value::Blob(bytes[range(bytes.len())].to_vec())),
+        value => Err(RqlError::Type(format!("substring() needs TEXT or BLOB, not {}", value))),
+    }
+}
@@ -0,0 +1,1282 @@
+use crate::query::blob::{length, substring};
+use crate::query::temporal::{date_trunc, extract, extract_type, MICROS_PER_DAY};
+use crate::rqle::similarity::Metric;
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{BinaryOperator, DataType, Expression, Interval, Literal, TableDefinition, UnaryOperator, Value};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Words that start a clause or belong to an operator, and so cannot name a column.
+const RESERVED: [&str; 19] = [
+    "SELECT", "FROM", "WHERE", "GROUP", "BY", "ORDER", "ASC", "DESC", "LIMIT", "OFFSET", "AND", "OR", "NOT", "IS",
+    "NULL", "TRUE", "FALSE", "AS", "BETWEEN",
+];
+
+/// Operators and punctuation, longest first.
+const SYMBOLS: [&str; 15] = ["<=", ">=", "<>", "!=", "(", ")", ",", "*", "+", "-", "/", "%", "=", "<", ">"];
+
+/// The functions expressions may call, with the least and most arguments they take.
+const FUNCTIONS: [(&str, usize, usize); 12] = [
+    ("now", 0, 0),
+    ("length", 1, 1),
+    ("substring", 2, 3),
+    ("l2_distance", 2, 2),
+    ("cosine_distance", 2, 2),
+    ("date_trunc", 2, 2),
+    ("extract", 2, 2),
+    ("count", 1, 1),
+    ("sum", 1, 1),
+    ("avg", 1, 1),
+    ("min", 1, 1),
+    ("max", 1, 1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Word,
+    Number,
+    /// A string or typed literal, such as `'text'`, `E'\n'`, `X'0a1b'` or `DATE '2024-01-31'`.
+    Literal,
+    Symbol,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind,
+    /// The text of the token, a subslice of the statement so errors can point at it.
+    text: &'a str,
+}
+
+/// Splits `sql`, a subslice of `source`, into tokens.
+fn tokenize<'a>(source: &str, sql: &'a str) -> Result<Vec<Token<'a>>> {
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while let Some(c) = sql[index..].chars().next() {
+        let rest = &sql[index..];
+        if c.is_whitespace() {
+            index += c.len_utf8();
+            continue;
+        }
+
+        let (kind, len) = if c == '\'' || (matches!(c, 'E' | 'e' | 'X' | 'x') && rest[1..].starts_with('\'')) {
+            (TokenKind::Literal, string_length(source, rest)?)
+        } else if c.is_alphabetic() || c == '_' {
+            let word = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')).unwrap_or(rest.len());
+            let after = &rest[word..];
+            let quoted = after.trim_start();
+            let typed = ["DATE", "TIME", "TIMESTAMP", "INTERVAL"].iter().any(|k| k.eq_ignore_ascii_case(&rest[..word]));
+            if typed && quoted.len() < after.len() && quoted.starts_with('\'') {
+                (TokenKind::Literal, rest.len() - quoted.len() + string_length(source, quoted)?)
+            } else {
+                (TokenKind::Word, word)
+            }
+        } else if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) {
+            (TokenKind::Number, number_length(rest))
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            (TokenKind::Symbol, symbol.len())
+        } else {
+            return Err(RqlError::parse_at(source, rest, format!("Unexpected character '{}'", c)));
+        };
+        tokens.push(Token { kind, text: &rest[..len] });
+        index += len;
+    }
+    Ok(tokens)
+}
+
+/// The length of the string literal `quoted` starts with, quotes included.
+fn string_length(source: &str, quoted: &str) -> Result<usize> {
+    let escapes = quoted.starts_with(['E', 'e']);
+    let start = if quoted.starts_with('\'') { 1 } else { 2 };
+    let mut chars = quoted.char_indices().skip(start).peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' if escapes => {
+                chars.next();
+            }
+            '\'' if chars.peek().is_some_and(|(_, next)| *next == '\'') => {
+                chars.next();
+            }
+            '\'' => return Ok(index + 1),
+            _ => {}
+        }
+    }
+    Err(RqlError::parse_at(source, quoted, "Unterminated string literal"))
+}
+
+/// The length of the number `rest` starts with: digits, a fraction and an exponent.
+fn number_length(rest: &str) -> usize {
+    let digits = |from: usize| from + rest[from..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - from);
+    let mut end = digits(0);
+    if rest[end..].starts_with('.') {
+        end = digits(end + 1);
+    }
+    if rest[end..].starts_with(['e', 'E']) {
+        let exponent = rest[end + 1..].strip_prefix(['+', '-']).map_or(end + 1, |_| end + 2);
+        if rest[exponent..].starts_with(|c: char| c.is_ascii_digit()) {
+            end = digits(exponent);
+        }
+    }
+    end
+}
+
+/// A recursive descent parser over the tokens of a statement, used for the
+/// clauses of `SELECT` and the expressions within them.
+pub(crate) struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    end: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// Tokenizes `sql`, which must be a subslice of `source`.
+    pub(crate) fn new(source: &'a str, sql: &'a str) -> Result<Parser<'a>> {
+        Ok(Parser {
+            source,
+            tokens: tokenize(source, sql)?,
+            position: 0,
+            end: &sql[sql.len()..],
+        })
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn peek_symbol(&self, offset: usize, symbol: &str) -> bool {
+        self.tokens
+            .get(self.position + offset)
+            .is_some_and(|token| token.kind == TokenKind::Symbol && token.text == symbol)
+    }
+
+    /// A parse error located at the next token.
+    pub(crate) fn error<S: Into<String>>(&self, message: S) -> RqlError {
+        let at = self.peek().map_or(self.end, |token| token.text);
+        RqlError::parse_at(self.source, at, message)
+    }
+
+    fn found(&self) -> String {
+        self.peek().map_or("the end of the statement".to_string(), |token| format!("'{}'", token.text))
+    }
+
+    pub(crate) fn expect_end(&self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(self.error(format!("Unexpected '{}'", token.text))),
+        }
+    }
+
+    pub(crate) fn is_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|token| token.kind == TokenKind::Word && token.text.eq_ignore_ascii_case(keyword))
+    }
+
+    pub(crate) fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+        Err(self.error(format!("Expected {}, found {}", keyword, self.found())))
+    }
+
+    pub(crate) fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.peek_symbol(0, symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        if self.eat_symbol(symbol) {
+            return Ok(());
+        }
+        Err(self.error(format!("Expected '{}', found {}", symbol, self.found())))
+    }
+
+    /// Whether the next token is a word that can name a table, column or alias.
+    pub(crate) fn is_identifier(&self) -> bool {
+        self.peek().is_some_and(|token| {
+            token.kind == TokenKind::Word && !RESERVED.iter().any(|word| word.eq_ignore_ascii_case(token.text))
+        })
+    }
+
+    pub(crate) fn identifier(&mut self) -> Result<String> {
+        if !self.is_identifier() {
+            return Err(self.error(format!("Expected a name, found {}", self.found())));
+        }
+        self.position += 1;
+        Ok(self.tokens[self.position - 1].text.to_string())
+    }
+
+    pub(crate) fn unsigned_integer(&mut self) -> Result<usize> {
+        match self.peek() {
+            Some(token) if token.kind == TokenKind::Number => {
+                let value = token.text.parse().map_err(|_| self.error("Expected a non-negative integer"))?;
+                self.position += 1;
+                Ok(value)
+            }
+            _ => Err(self.error(format!("Expected a non-negative integer, found {}", self.found()))),
+        }
+    }
+
+    pub(crate) fn expression(&mut self) -> Result<Expression> {
+        let mut left = self.and()?;
+        while self.eat_keyword("OR") {
+            left = Expression::Binary(Box::new(left), BinaryOperator::Or, Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expression> {
+        let mut left = self.not()?;
+        while self.eat_keyword("AND") {
+            left = Expression::Binary(Box::new(left), BinaryOperator::And, Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expression> {
+        if self.eat_keyword("NOT") {
+            return Ok(Expression::Unary(UnaryOperator::Not, Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expression> {
+        let left = self.additive()?;
+        let operators = [
+            ("=", BinaryOperator::Equal),
+            ("<>", BinaryOperator::NotEqual),
+            ("!=", BinaryOperator::NotEqual),
+            ("<=", BinaryOperator::LessOrEqual),
+            (">=", BinaryOperator::GreaterOrEqual),
+            ("<", BinaryOperator::Less),
+            (">", BinaryOperator::Greater),
+        ];
+        if let Some((_, operator)) = operators.iter().find(|(symbol, _)| self.peek_symbol(0, symbol)) {
+            self.position += 1;
+            return Ok(Expression::Binary(Box::new(left), *operator, Box::new(self.additive()?)));
+        }
+
+        if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Expression::IsNull(Box::new(left), negated));
+        }
+
+        let negated = self.is_keyword("NOT")
+            && self.tokens.get(self.position + 1).is_some_and(|token| token.text.eq_ignore_ascii_case("BETWEEN"));
+        if negated {
+            self.position += 1;
+        }
+        if self.eat_keyword("BETWEEN") {
+            let low = self.additive()?;
+            self.expect_keyword("AND")?;
+            let high = self.additive()?;
+            let between = Expression::Binary(
+                Box::new(Expression::Binary(Box::new(left.clone()), BinaryOperator::GreaterOrEqual, Box::new(low))),
+                BinaryOperator::And,
+                Box::new(Expression::Binary(Box::new(left), BinaryOperator::LessOrEqual, Box::new(high))),
+            );
+            return Ok(if negated { Expression::Unary(UnaryOperator::Not, Box::new(between)) } else { between });
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expression> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let operator = if self.eat_symbol("+") {
+                BinaryOperator::Add
+            } else if self.eat_symbol("-") {
+                BinaryOperator::Subtract
+            } else {
+                return Ok(left);
+            };
+            left = Expression::Binary(Box::new(left), operator, Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expression> {
+        let mut left = self.unary()?;
+        loop {
+            let operator = if self.eat_symbol("*") {
+                BinaryOperator::Multiply
+            } else if self.eat_symbol("/") {
+                BinaryOperator::Divide
+            } else if self.eat_symbol("%") {
+                BinaryOperator::Modulo
+            } else {
+                return Ok(left);
+            };
+            left = Expression::Binary(Box::new(left), operator, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression> {
+        if self.eat_symbol("+") {
+            return self.unary();
+        }
+        if !self.eat_symbol("-") {
+            return self.primary();
+        }
+        // Negative numbers are literals, so `-2147483648` fits an INTEGER.
+        match self.peek() {
+            Some(token) if token.kind == TokenKind::Number => {
+                self.position += 1;
+                match Literal::from_sql(self.source, token.text)? {
+                    Literal::Integer(v) => Ok(Expression::Literal(Literal::Integer(-v))),
+                    Literal::Float(v) => Ok(Expression::Literal(Literal::Float(-v))),
+                    literal => Ok(Expression::Unary(UnaryOperator::Negate, Box::new(Expression::Literal(literal)))),
+                }
+            }
+            _ => Ok(Expression::Unary(UnaryOperator::Negate, Box::new(self.unary()?))),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression> {
+        let Some(token) = self.peek() else {
+            return Err(self.error("Expected an expression, found the end of the statement"));
+        };
+        match token.kind {
+            TokenKind::Number | TokenKind::Literal => {
+                self.position += 1;
+                Ok(Expression::Literal(Literal::from_sql(self.source, token.text)?))
+            }
+            TokenKind::Symbol if token.text == "(" => {
+                self.position += 1;
+                let expression = self.expression()?;
+                self.expect_symbol(")")?;
+                Ok(expression)
+            }
+            TokenKind::Word if ["NULL", "TRUE", "FALSE"].iter().any(|k| k.eq_ignore_ascii_case(token.text)) => {
+                self.position += 1;
+                Ok(Expression::Literal(Literal::from_sql(self.source, token.text)?))
+            }
+            TokenKind::Word if self.peek_symbol(1, "(") && DataType::from_sql_type(token.text).is_some_and(DataType::is_vector) => {
+                self.vector()
+            }
+            TokenKind::Word if self.peek_symbol(1, "(") => self.function(),
+            _ if self.is_identifier() => Ok(Expression::Column(self.identifier()?)),
+            _ => Err(self.error(format!("Expected an expression, found {}", self.found()))),
+        }
+    }
+
+    /// A vector literal such as `VEC3(1, 0, 0)`, read from the text it spans.
+    fn vector(&mut self) -> Result<Expression> {
+        let start = self.tokens[self.position].text;
+        let Some(close) = self.tokens[self.position..].iter().position(|token| token.text == ")") else {
+            self.position = self.tokens.len();
+            return Err(self.error(format!("Expected ')' to close {}(", start)));
+        };
+        let end = self.tokens[self.position + close].text;
+        self.position += close + 1;
+
+        let offset = |text: &str| text.as_ptr() as usize - self.source.as_ptr() as usize;
+        let literal = &self.source[offset(start)..offset(end) + end.len()];
+        Ok(Expression::Literal(Literal::from_sql(self.source, literal)?))
+    }
+
+    fn function(&mut self) -> Result<Expression> {
+        let name_token = self.peek().map_or(self.end, |token| token.text);
+        let name = name_token.to_lowercase();
+        let Some((_, least, most)) = FUNCTIONS.iter().find(|(function, ..)| *function == name) else {
+            return Err(self.error(format!("Unknown function '{}'", name_token)));
+        };
+        self.position += 2;
+
+        let mut args = Vec::new();
+        let star = name == "count" && self.eat_symbol("*");
+        if name == "extract" {
+            let field = self.identifier()?;
+            self.expect_keyword("FROM")?;
+            args.push(Expression::Literal(Literal::Text(field.to_lowercase())));
+            args.push(self.expression()?);
+        } else if !star && !self.peek_symbol(0, ")") {
+            loop {
+                args.push(self.expression()?);
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        self.expect_symbol(")")?;
+
+        let count = args.len() + star as usize;
+        if count < *least || count > *most {
+            let arity = match (least, most) {
+                (1, 1) => "1 argument".to_string(),
+                (least, most) if least == most => format!("{} arguments", least),
+                (least, most) => format!("{} or {} arguments", least, most),
+            };
+            return Err(RqlError::parse_at(self.source, name_token, format!("{}() takes {}", name, arity)));
+        }
+        Ok(Expression::Function(name, args))
+    }
+}
+
+impl Expression {
+    /// Whether the expression calls an aggregate function such as `count` or `sum`.
+    pub(crate) fn contains_aggregate(&self) -> bool {
+        match self {
+            Expression::Literal(_) | Expression::Column(_) => false,
+            Expression::Unary(_, operand) | Expression::IsNull(operand, _) => operand.contains_aggregate(),
+            Expression::Binary(left, _, right) => left.contains_aggregate() || right.contains_aggregate(),
+            Expression::Function(name, args) => {
+                AggregateFunction::from_name(name).is_some() || args.iter().any(Expression::contains_aggregate)
+            }
+        }
+    }
+
+    /// Whether the expression can be evaluated without a row, as a `DEFAULT` must be.
+    pub(crate) fn is_constant(&self) -> bool {
+        match self {
+            Expression::Literal(_) => true,
+            Expression::Column(_) => false,
+            Expression::Unary(_, operand) | Expression::IsNull(operand, _) => operand.is_constant(),
+            Expression::Binary(left, _, right) => left.is_constant() && right.is_constant(),
+            Expression::Function(name, args) => {
+                AggregateFunction::from_name(name).is_none() && args.iter().all(Expression::is_constant)
+            }
+        }
+    }
+
+    /// The names of the columns the expression reads, in order of appearance.
+    pub(crate) fn columns(&self) -> Vec<&str> {
+        match self {
+            Expression::Literal(_) => Vec::new(),
+            Expression::Column(name) => vec![name.as_str()],
+            Expression::Unary(_, operand) | Expression::IsNull(operand, _) => operand.columns(),
+            Expression::Binary(left, _, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Expression::Function(_, args) => args.iter().flat_map(Expression::columns).collect(),
+        }
+    }
+
+    /// The operands of the top-level `AND`s of a condition.
+    pub(crate) fn conjuncts(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Binary(left, BinaryOperator::And, right) => {
+                let mut conjuncts = left.conjuncts();
+                conjuncts.extend(right.conjuncts());
+                conjuncts
+            }
+            expression => vec![expression],
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Binary(_, operator, _) => operator.precedence(),
+            Expression::Unary(UnaryOperator::Not, _) => 3,
+            Expression::IsNull(..) => 4,
+            Expression::Unary(UnaryOperator::Negate, _) => 7,
+            _ => 8,
+        }
+    }
+
+    /// Writes the expression, in parentheses if it binds less tightly than `parent`.
+    fn write(&self, f: &mut fmt::Formatter<'_>, parent: u8) -> fmt::Result {
+        let precedence = self.precedence();
+        if precedence < parent {
+            write!(f, "(")?;
+        }
+        match self {
+            Expression::Literal(literal) => write!(f, "{}", literal)?,
+            Expression::Column(name) => write!(f, "{}", name)?,
+            Expression::Unary(UnaryOperator::Negate, operand) => {
+                write!(f, "-")?;
+                operand.write(f, precedence)?;
+            }
+            Expression::Unary(UnaryOperator::Not, operand) => {
+                write!(f, "NOT ")?;
+                operand.write(f, precedence)?;
+            }
+            Expression::Binary(left, operator, right) => {
+                let left_precedence = if operator.is_comparison() { precedence + 1 } else { precedence };
+                left.write(f, left_precedence)?;
+                write!(f, " {} ", operator)?;
+                right.write(f, precedence + 1)?;
+            }
+            Expression::IsNull(operand, negated) => {
+                operand.write(f, precedence + 1)?;
+                write!(f, " IS {}NULL", if *negated { "NOT " } else { "" })?;
+            }
+            Expression::Function(name, args) => match (name.as_str(), args.as_slice()) {
+                ("extract", [Expression::Literal(Literal::Text(field)), operand]) => {
+                    write!(f, "extract({} FROM {})", field, operand)?
+                }
+                ("count", []) => write!(f, "count(*)")?,
+                _ => {
+                    let args: Vec<String> = args.iter().map(Expression::to_string).collect();
+                    write!(f, "{}({})", name, args.join(", "))?
+                }
+            },
+        }
+        if precedence < parent {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+impl BinaryOperator {
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::Add | BinaryOperator::Subtract => 5,
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => 6,
+            _ => 4,
+        }
+    }
+
+    pub(crate) fn is_comparison(self) -> bool {
+        self.precedence() == 4
+    }
+
+    /// The comparison that holds with the operands swapped, as `<` for `>`.
+    pub(crate) fn flip(self) -> BinaryOperator {
+        match self {
+            BinaryOperator::Less => BinaryOperator::Greater,
+            BinaryOperator::LessOrEqual => BinaryOperator::GreaterOrEqual,
+            BinaryOperator::Greater => BinaryOperator::Less,
+            BinaryOperator::GreaterOrEqual => BinaryOperator::LessOrEqual,
+            operator => operator,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Equal => "=",
+            BinaryOperator::NotEqual => "<>",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterOrEqual => ">=",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Or => "OR",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// An expression checked against the columns of a table, with its literals
+/// converted to the types of the values they meet.
+#[derive(Debug, Clone)]
+pub(crate) enum Bound {
+    Constant(Value),
+    /// A column of the row, by position.
+    Column(usize),
+    /// The value of the `GROUP BY` expression at this position, in grouped queries.
+    Group(usize),
+    /// The result of the aggregate at this position, in grouped queries.
+    Aggregate(usize),
+    Unary(UnaryOperator, Box<Bound>),
+    Binary(Box<Bound>, BinaryOperator, Box<Bound>),
+    /// A date or time moved by an interval, forwards for a sign of 1, backwards for -1.
+    Shift(Box<Bound>, Interval, i32),
+    IsNull(Box<Bound>, bool),
+    DateTrunc(String, Box<Bound>),
+    Extract(String, Box<Bound>),
+    /// The distance between two vectors, NULL where there is none.
+    Distance(Metric, Box<Bound>, Box<Bound>),
+    /// The number of characters of a text, or of bytes of a blob.
+    Length(Box<Bound>),
+    /// Part of a text or blob, from a 1-based start, up to an optional count.
+    Substring(Box<Bound>, Box<Bound>, Option<Box<Bound>>),
+}
+
+/// A bound expression and its type, `None` for an untyped NULL.
+pub(crate) type Typed = (Bound, Option<DataType>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    fn from_name(name: &str) -> Option<AggregateFunction> {
+        match name {
+            "count" => Some(AggregateFunction::Count),
+            "sum" => Some(AggregateFunction::Sum),
+            "avg" => Some(AggregateFunction::Avg),
+            "min" => Some(AggregateFunction::Min),
+            "max" => Some(AggregateFunction::Max),
+            _ => None,
+        }
+    }
+}
+
+/// An aggregate function call of a grouped query. `count(*)` has no argument.
+#[derive(Debug, Clone)]
+pub(crate) struct Aggregate {
+    pub(crate) function: AggregateFunction,
+    pub(crate) argument: Option<Bound>,
+}
+
+/// The running state of an aggregate over the rows of one group.
+#[derive(Debug, Clone)]
+pub(crate) struct Accumulator {
+    value: Value,
+    count: i64,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Accumulator { value: Value::Null, count: 0 }
+    }
+}
+
+impl Accumulator {
+    /// Adds the row `values` to the aggregate. NULL arguments are skipped.
+    pub(crate) fn update(&mut self, aggregate: &Aggregate, values: &[Value]) -> Result<()> {
+        let Some(argument) = &aggregate.argument else {
+            self.count += 1;
+            return Ok(());
+        };
+        let value = argument.evaluate(&Scope::row(values))?;
+        if value == Value::Null {
+            return Ok(());
+        }
+        self.count += 1;
+        self.value = match (aggregate.function, &self.value) {
+            (AggregateFunction::Count, _) => Value::Null,
+            (AggregateFunction::Sum | AggregateFunction::Avg, sum) => {
+                match (sum, as_i64(&value)) {
+                    (Value::Null, Some(v)) if aggregate.function == AggregateFunction::Sum => Value::BigInt(v),
+                    (Value::BigInt(sum), Some(v)) => {
+                        Value::BigInt(sum.checked_add(v).ok_or_else(|| RqlError::Type("sum() overflowed BIGINT".to_string()))?)
+                    }
+                    (sum, _) => Value::Double(as_f64(sum).unwrap_or(0.0) + as_f64(&value).unwrap_or(0.0)),
+                }
+            }
+            (AggregateFunction::Min, current) if *current != Value::Null && compare(current, &value) != Some(Ordering::Greater) => {
+                current.clone()
+            }
+            (AggregateFunction::Max, current) if *current != Value::Null && compare(current, &value) != Some(Ordering::Less) => {
+                current.clone()
+            }
+            _ => value,
+        };
+        Ok(())
+    }
+
+    pub(crate) fn finish(&self, aggregate: &Aggregate) -> Value {
+        match aggregate.function {
+            AggregateFunction::Count => Value::BigInt(self.count),
+            AggregateFunction::Avg if self.count > 0 => {
+                Value::Double(as_f64(&self.value).unwrap_or(0.0) / self.count as f64)
+            }
+            AggregateFunction::Avg => Value::Null,
+            _ => self.value.clone(),
+        }
+    }
+}
+
+/// Checks expressions against the columns of a table and binds them for evaluation.
+/// In a grouped query, expressions must be made of the `GROUP BY` expressions and
+/// of aggregates, which the binder collects.
+pub(crate) struct Binder<'a> {
+    table: &'a TableDefinition,
+    groups: Vec<(Expression, Option<DataType>)>,
+    grouped: bool,
+    inside_aggregate: bool,
+    pub(crate) aggregates: Vec<Aggregate>,
+    now: i64,
+}
+
+impl<'a> Binder<'a> {
+    /// `now` is the value of `now()`, the same throughout a statement.
+    pub(crate) fn new(table: &'a TableDefinition, now: i64) -> Binder<'a> {
+        Binder {
+            table,
+            groups: Vec::new(),
+            grouped: false,
+            inside_aggregate: false,
+            aggregates: Vec::new(),
+            now,
+        }
+    }
+
+    /// Binds a `WHERE` condition, which must be BOOLEAN.
+    pub(crate) fn condition(&mut self, expression: &Expression) -> Result<Bound> {
+        let (bound, data_type) = self.bind(expression)?;
+        if data_type.is_some_and(|data_type| data_type != DataType::Boolean) {
+            return Err(RqlError::Type(format!(
+                "The condition {} is {}, not BOOLEAN",
+                expression,
+                type_name(data_type)
+            )));
+        }
+        Ok(bound)
+    }
+
+    /// Binds the `GROUP BY` expressions and makes the following expressions grouped.
+    pub(crate) fn group_by(&mut self, expressions: &[Expression]) -> Result<Vec<Bound>> {
+        let mut bound = Vec::with_capacity(expressions.len());
+        for expression in expressions {
+            let (group, data_type) = self.bind(expression)?;
+            self.groups.push((expression.clone(), data_type));
+            bound.push(group);
+        }
+        self.grouped = true;
+        Ok(bound)
+    }
+
+    pub(crate) fn bind(&mut self, expression: &Expression) -> Result<Typed> {
+        if self.grouped && !self.inside_aggregate {
+            if let Some(index) = self.groups.iter().position(|(group, _)| group == expression) {
+                return Ok((Bound::Group(index), self.groups[index].1));
+            }
+        }
+
+        match expression {
+            Expression::Literal(Literal::Interval(interval)) => Err(RqlError::Type(format!(
+                "INTERVAL '{}' can only be added to or subtracted from a DATE, TIME or TIMESTAMP",
+                interval
+            ))),
+            Expression::Literal(literal) => {
+                let value = literal.value().unwrap_or(Value::Null);
+                let data_type = value.data_type();
+                Ok((Bound::Constant(value), data_type))
+            }
+            Expression::Column(name) => {
+                let index = self.table.columns.iter().position(|c| c.name == *name).ok_or_else(|| {
+                    RqlError::Schema(format!("Table '{}' has no column '{}'", self.table.name, name))
+                })?;
+                if self.grouped && !self.inside_aggregate {
+                    return Err(RqlError::Schema(format!(
+                        "Column '{}' must appear in GROUP BY or be used in an aggregate function",
+                        name
+                    )));
+                }
+                Ok((Bound::Column(index), Some(self.table.columns[index].data_type)))
+            }
+            Expression::Unary(operator, operand) => {
+                let (bound, data_type) = self.bind(operand)?;
+                let valid = match operator {
+                    UnaryOperator::Negate => data_type.is_none_or(DataType::is_numeric),
+                    UnaryOperator::Not => data_type.is_none_or(|data_type| data_type == DataType::Boolean),
+                };
+                if !valid {
+                    return Err(RqlError::Type(format!("Cannot apply {} to {}", expression, type_name(data_type))));
+                }
+                let data_type = if *operator == UnaryOperator::Not { Some(DataType::Boolean) } else { data_type };
+                Ok((Bound::Unary(*operator, Box::new(bound)), data_type))
+            }
+            Expression::IsNull(operand, negated) => {
+                let (bound, _) = self.bind(operand)?;
+                Ok((Bound::IsNull(Box::new(bound), *negated), Some(DataType::Boolean)))
+            }
+            Expression::Binary(left, operator, right) => self.binary(left, *operator, right),
+            Expression::Function(name, args) => self.function(name, args),
+        }
+    }
+
+    fn binary(&mut self, left: &Expression, operator: BinaryOperator, right: &Expression) -> Result<Typed> {
+        if matches!(operator, BinaryOperator::Add | BinaryOperator::Subtract) {
+            if let Expression::Literal(Literal::Interval(interval)) = right {
+                let sign = if operator == BinaryOperator::Add { 1 } else { -1 };
+                return self.shift(left, *interval, sign);
+            }
+            if let (Expression::Literal(Literal::Interval(interval)), BinaryOperator::Add) = (left, operator) {
+                return self.shift(right, *interval, 1);
+            }
+        }
+
+        let ((left_bound, left_type), (right_bound, right_type)) = self.operands(left, right)?;
+        let mismatch = || {
+            RqlError::Type(format!(
+                "Cannot apply '{}' to {} and {} in {} {} {}",
+                operator,
+                type_name(left_type),
+                type_name(right_type),
+                left,
+                operator,
+                right
+            ))
+        };
+        let data_type = match operator {
+            BinaryOperator::And | BinaryOperator::Or => {
+                let boolean = |data_type: Option<DataType>| data_type.is_none_or(|t| t == DataType::Boolean);
+                if !boolean(left_type) || !boolean(right_type) {
+                    return Err(mismatch());
+                }
+                Some(DataType::Boolean)
+            }
+            operator if operator.is_comparison() => {
+                let ordered = !matches!(operator, BinaryOperator::Equal | BinaryOperator::NotEqual);
+                let vector = left_type.is_some_and(DataType::is_vector) || right_type.is_some_and(DataType::is_vector);
+                if !comparable(left_type, right_type) || (ordered && vector) {
+                    return Err(mismatch());
+                }
+                Some(DataType::Boolean)
+            }
+            operator => arithmetic_type(operator, left_type, right_type).ok_or_else(mismatch)?,
+        };
+        Ok((Bound::Binary(Box::new(left_bound), operator, Box::new(right_bound)), data_type))
+    }
+
+    /// Binds both operands of an operator. A literal takes the type of the other
+    /// operand when it stands for a value of that type, so `reading < 0.5`
+    /// compares FLOAT values and `day = '2024-01-31'` compares dates.
+    fn operands(&mut self, left: &Expression, right: &Expression) -> Result<(Typed, Typed)> {
+        match (adaptable(left), adaptable(right)) {
+            (Some(literal), None) => {
+                let right = self.bind(right)?;
+                Ok((self.adapt(left, literal, right.1)?, right))
+            }
+            (None, Some(literal)) => {
+                let left = self.bind(left)?;
+                let right = self.adapt(right, literal, left.1)?;
+                Ok((left, right))
+            }
+            _ => Ok((self.bind(left)?, self.bind(right)?)),
+        }
+    }
+
+    fn adapt(&mut self, expression: &Expression, literal: &Literal, data_type: Option<DataType>) -> Result<Typed> {
+        match data_type.and_then(|data_type| literal.cast(data_type)) {
+            Some(value) => Ok((Bound::Constant(value), data_type)),
+            None => self.bind(expression),
+        }
+    }
+
+    fn shift(&mut self, operand: &Expression, interval: Interval, sign: i32) -> Result<Typed> {
+        let (bound, data_type) = self.temporal_operand(operand)?;
+        let data_type = match data_type {
+            Some(DataType::Date) if interval.micros == 0 => Some(DataType::Date),
+            Some(DataType::Date | DataType::Timestamp) => Some(DataType::Timestamp),
+            Some(DataType::Time) if interval.months == 0 && interval.days == 0 => Some(DataType::Time),
+            None => None,
+            data_type => {
+                return Err(RqlError::Type(format!(
+                    "Cannot add INTERVAL '{}' to {} {}",
+                    interval,
+                    type_name(data_type),
+                    operand
+                )))
+            }
+        };
+        Ok((Bound::Shift(Box::new(bound), interval, sign), data_type))
+    }
+
+    /// Binds the operand of a date and time function, reading quoted text as a TIMESTAMP.
+    fn temporal_operand(&mut self, operand: &Expression) -> Result<Typed> {
+        match adaptable(operand) {
+            Some(literal @ Literal::Text(_)) => self.adapt(operand, literal, Some(DataType::Timestamp)),
+            _ => self.bind(operand),
+        }
+    }
+
+    fn function(&mut self, name: &str, args: &[Expression]) -> Result<Typed> {
+        if let Some(function) = AggregateFunction::from_name(name) {
+            return self.aggregate(function, name, args);
+        }
+        if let (Some(metric), [left, right]) = (Metric::from_name(name), args) {
+            return self.distance(metric, left, right);
+        }
+        match (name, args) {
+            ("now", []) => Ok((Bound::Constant(Value::Timestamp(self.now)), Some(DataType::Timestamp))),
+            ("date_trunc", [unit, operand]) => {
+                let Expression::Literal(Literal::Text(unit)) = unit else {
+                    return Err(RqlError::Type("date_trunc takes its unit as a quoted string, as in date_trunc('day', ts)".to_string()));
+                };
+                date_trunc(unit, &Value::Null)?;
+                let (bound, data_type) = self.temporal_operand(operand)?;
+                if !data_type.is_none_or(DataType::is_temporal) {
+                    return Err(RqlError::Type(format!("date_trunc cannot round {} {}", type_name(data_type), operand)));
+                }
+                Ok((Bound::DateTrunc(unit.clone(), Box::new(bound)), data_type))
+            }
+            ("extract", [Expression::Literal(Literal::Text(field)), operand]) => {
+                let (bound, data_type) = self.temporal_operand(operand)?;
+                let data_type = extract_type(field, data_type.unwrap_or(DataType::Timestamp))?;
+                Ok((Bound::Extract(field.clone(), Box::new(bound)), Some(data_type)))
+            }
+            ("length", [operand]) => {
+                let (bound, _) = self.string_operand(name, operand)?;
+                Ok((Bound::Length(Box::new(bound)), Some(DataType::Integer)))
+            }
+            ("substring", [operand, start, count @ ..]) => {
+                let (bound, data_type) = self.string_operand(name, operand)?;
+                let start = self.integer_operand(name, start)?;
+                let count = count.first().map(|count| self.integer_operand(name, count)).transpose()?;
+                Ok((Bound::Substring(Box::new(bound), Box::new(start), count.map(Box::new)), data_type))
+            }
+            _ => Err(RqlError::parse(format!("Invalid call to {}()", name))),
+        }
+    }
+
+    /// Binds the TEXT or BLOB argument of `length` or `substring`.
+    fn string_operand(&mut self, function: &str, operand: &Expression) -> Result<Typed> {
+        let (bound, data_type) = self.bind(operand)?;
+        if !data_type.is_none_or(|data_type| matches!(data_type, DataType::Text | DataType::Blob)) {
+            return Err(RqlError::Type(format!("{}() needs TEXT or BLOB, not {} {}", function, type_name(data_type), operand)));
+        }
+        Ok((bound, data_type))
+    }
+
+    fn integer_operand(&mut self, function: &str, operand: &Expression) -> Result<Bound> {
+        let (bound, data_type) = self.bind(operand)?;
+        if !data_type.is_none_or(|data_type| matches!(data_type, DataType::Integer | DataType::BigInt)) {
+            return Err(RqlError::Type(format!("{}() needs an integer position, not {} {}", function, type_name(data_type), operand)));
+        }
+        Ok(bound)
+    }
+
+    /// Binds `l2_distance(a, b)` or `cosine_distance(a, b)`. A literal takes the
+    /// vector type of the other argument, so `'[0.1, 0.2]'` can stand for a query vector.
+    fn distance(&mut self, metric: Metric, left: &Expression, right: &Expression) -> Result<Typed> {
+        let ((left_bound, left_type), (right_bound, right_type)) = self.operands(left, right)?;
+        let size = |data_type: Option<DataType>| data_type.map(|t| t.is_vector().then(|| t.components()));
+        match (size(left_type), size(right_type)) {
+            (Some(Some(left)), Some(Some(right))) if left == right => {}
+            (None, Some(Some(_)) | None) | (Some(Some(_)), None) => {}
+            _ => {
+                return Err(RqlError::Type(format!(
+                    "{}() needs two vectors of the same size, not {} and {}",
+                    metric.name(),
+                    type_name(left_type),
+                    type_name(right_type)
+                )))
+            }
+        }
+        Ok((Bound::Distance(metric, Box::new(left_bound), Box::new(right_bound)), Some(DataType::Float)))
+    }
+
+    fn aggregate(&mut self, function: AggregateFunction, name: &str, args: &[Expression]) -> Result<Typed> {
+        if self.inside_aggregate {
+            return Err(RqlError::Type(format!("Aggregate function {}() cannot be nested", name)));
+        }
+        if !self.grouped {
+            return Err(RqlError::Type(format!("Aggregate function {}() is not allowed in WHERE or GROUP BY", name)));
+        }
+
+        self.inside_aggregate = true;
+        let argument = args.first().map(|argument| self.bind(argument)).transpose();
+        self.inside_aggregate = false;
+        let argument = argument?;
+
+        let argument_type = argument.as_ref().and_then(|(_, data_type)| *data_type);
+        let numeric = argument_type.is_none_or(DataType::is_numeric);
+        let data_type = match function {
+            AggregateFunction::Count => Some(DataType::BigInt),
+            AggregateFunction::Sum if numeric => match argument_type {
+                Some(DataType::Float | DataType::Double) => Some(DataType::Double),
+                _ => Some(DataType::BigInt),
+            },
+            AggregateFunction::Avg if numeric => Some(DataType::Double),
+            AggregateFunction::Min | AggregateFunction::Max => argument_type,
+            _ => {
+                return Err(RqlError::Type(format!("{}() needs a numeric argument, not {}", name, type_name(argument_type))))
+            }
+        };
+
+        self.aggregates.push(Aggregate { function, argument: argument.map(|(bound, _)| bound) });
+        Ok((Bound::Aggregate(self.aggregates.len() - 1), data_type))
+    }
+}
+
+/// The literal an operand is, if it may take the type of the other operand.
+fn adaptable(expression: &Expression) -> Option<&Literal> {
+    match expression {
+        Expression::Literal(Literal::Interval(_)) => None,
+        Expression::Literal(literal) => Some(literal),
+        _ => None,
+    }
+}
+
+fn type_name(data_type: Option<DataType>) -> String {
+    data_type.map_or("NULL".to_string(), DataType::to_sql_type)
+}
+
+fn comparable(left: Option<DataType>, right: Option<DataType>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => {
+            left == right
+                || (left.is_numeric() && right.is_numeric())
+                || matches!((left, right), (DataType::Date, DataType::Timestamp) | (DataType::Timestamp, DataType::Date))
+        }
+        _ => true,
+    }
+}
+
+/// The type two numbers are combined in: the wider of the two, where BIGINT and
+/// FLOAT together need a DOUBLE.
+fn numeric_type(left: DataType, right: DataType) -> DataType {
+    use DataType::*;
+    match (left, right) {
+        (Double, _) | (_, Double) | (Float, BigInt) | (BigInt, Float) => Double,
+        (Float, _) | (_, Float) => Float,
+        (BigInt, _) | (_, BigInt) => BigInt,
+        _ => Integer,
+    }
+}
+
+/// The type of an arithmetic operation, or `None` if the operands cannot be
+/// combined. Dates move by whole days; subtracting dates gives days, and
+/// subtracting times or timestamps gives microseconds.
+fn arithmetic_type(operator: BinaryOperator, left: Option<DataType>, right: Option<DataType>) -> Option<Option<DataType>> {
+    use DataType::*;
+    let (left, right) = match (left, right) {
+        (None, None) => return Some(None),
+        (Some(data_type), None) | (None, Some(data_type)) => {
+            return (data_type.is_numeric() || data_type.is_temporal()).then_some(Some(data_type))
+        }
+        (Some(left), Some(right)) => (left, right),
+    };
+    let additive = matches!(operator, BinaryOperator::Add | BinaryOperator::Subtract);
+    let subtract = operator == BinaryOperator::Subtract;
+    let data_type = match (left, right) {
+        _ if left.is_numeric() && right.is_numeric() => numeric_type(left, right),
+        (Date, Integer | BigInt) if additive => Date,
+        (Integer | BigInt, Date) if operator == BinaryOperator::Add => Date,
+        (Date, Date) if subtract => Integer,
+        (Date | Timestamp, Date | Timestamp) | (Time, Time) if subtract => BigInt,
+        _ => return None,
+    };
+    Some(Some(data_type))
+}
+
+/// The values an expression is evaluated against: the row of the table, or the
+/// `GROUP BY` values and aggregate results of a group.
+pub(crate) struct Scope<'a> {
+    pub(crate) row: &'a [Value],
+    pub(crate) groups: &'a [Value],
+    pub(crate) aggregates: &'a [Value],
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn row(row: &'a [Value]) -> Scope<'a> {
+        Scope { row, groups: &[], aggregates: &[] }
+    }
+}
+
+impl Bound {
+    pub(crate) fn evaluate(&self, scope: &Scope) -> Result<Value> {
+        match self {
+            Bound::Constant(value) => Ok(value.clone()),
+            Bound::Column(index) => Ok(scope.row[*index].clone()),
+            Bound::Group(index) => Ok(scope.groups[*index].clone()),
+            Bound::Aggregate(index) => Ok(scope.aggregates[*index].clone()),
+            Bound::Unary(operator, operand) => match (operator, operand.evaluate(scope)?) {
+                (_, Value::Null) => Ok(Value::Null),
+                (UnaryOperator::Not, Value::Boolean(v)) => Ok(Value::Boolean(!v)),
+                (UnaryOperator::Negate, value) => arithmetic(&Value::Integer(0), BinaryOperator::Subtract, &value),
+                (_, value) => Err(RqlError::Type(format!("Cannot apply NOT to {}", value))),
+            },
+            Bound::Binary(left, operator, right) => {
+                let left = left.evaluate(scope)?;
+                // AND and OR follow three-valued logic and skip the right side when they can.
+                match (operator, &left) {
+                    (BinaryOperator::And, Value::Boolean(false)) | (BinaryOperator::Or, Value::Boolean(true)) => {
+                        return Ok(left)
+                    }
+                    _ => {}
+                }
+                let right = right.evaluate(scope)?;
+                binary(&left, *operator, &right)
+            }
+            Bound::Shift(operand, interval, sign) => match operand.evaluate(scope)? {
+                Value::Null => Ok(Value::Null),
+                Value::Date(days) => interval.add_to_date(days, *sign),
+                Value::Timestamp(micros) => Ok(Value::Timestamp(interval.add_to_timestamp(micros, *sign)?)),
+                Value::Time(micros) => Ok(Value::Time(interval.add_to_time(micros, *sign))),
+                value => Err(RqlError::Type(format!("Cannot add INTERVAL '{}' to {}", interval, value))),
+            },
+            Bound::IsNull(operand, negated) => Ok(Value::Boolean((operand.evaluate(scope)? == Value::Null) != *negated)),
+            Bound::DateTrunc(unit, operand) => date_trunc(unit, &operand.evaluate(scope)?),
+            Bound::Extract(field, operand) => extract(field, &operand.evaluate(scope)?),
+            Bound::Distance(metric, left, right) => match (left.evaluate(scope)?, right.evaluate(scope)?) {
+                (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+                (left, right) => match (left.components(), right.components()) {
+                    (Some(a), Some(b)) if a.len() == b.len() => Ok(metric.distance(a, b).map_or(Value::Null, Value::Float)),
+                    _ => Err(RqlError::Type(format!(
+                        "{}() needs two vectors of the same size, not {} and {}",
+                        metric.name(),
+                        left,
+                        right
+                    ))),
+                },
+            },
+            Bound::Length(operand) => length(&operand.evaluate(scope)?),
+            Bound::Substring(operand, start, count) => {
+                let count = count.as_ref().map(|count| count.evaluate(scope)).transpose()?;
+                substring(&operand.evaluate(scope)?, &start.evaluate(scope)?, count.as_ref())
+            }
+        }
+    }
+
+    /// Evaluates a condition: only TRUE matches, FALSE and NULL do not.
+    pub(crate) fn matches(&self, scope: &Scope) -> Result<bool> {
+        Ok(self.evaluate(scope)? == Value::Boolean(true))
+    }
+}
+
+fn binary(left: &Value, operator: BinaryOperator, right: &Value) -> Result<Value> {
+    let result = match (operator, left, right) {
+        (BinaryOperator::And, Value::Boolean(false), _) | (BinaryOperator::And, _, Value::Boolean(false)) => {
+            Value::Boolean(false)
+        }
+        (BinaryOperator::Or, Value::Boolean(true), _) | (BinaryOperator::Or, _, Value::Boolean(true)) => {
+            Value::Boolean(true)
+        }
+        (_, Value::Null, _) | (_, _, Value::Null) => Value::Null,
+        (BinaryOperator::And | BinaryOperator::Or, _, _) => Value::Boolean(*left == Value::Boolean(true)),
+        (operator, _, _) if operator.is_comparison() => {
+            let ordering = compare(left, right)
+                .ok_or_else(|| RqlError::Type(format!("Cannot compare {} with {}", left, right)))?;
+            Value::Boolean(match operator {
+                BinaryOperator::Equal => ordering == Ordering::Equal,
+                BinaryOperator::NotEqual => ordering != Ordering::Equal,
+                BinaryOperator::Less => ordering == Ordering::Less,
+                BinaryOperator::LessOrEqual => ordering != Ordering::Greater,
+                BinaryOperator::Greater => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            })
+        }
+        (operator, _, _) => arithmetic(left, operator, right)?,
+    };
+    Ok(result)
+}
+
+/// Orders two values of comparable types. Numbers of different types compare by
+/// value, and a date compares as midnight with a timestamp.
+pub(crate) fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Date(days), Value::Timestamp(micros)) => (*days as i64 * MICROS_PER_DAY).partial_cmp(micros),
+        (Value::Timestamp(micros), Value::Date(days)) => micros.partial_cmp(&(*days as i64 * MICROS_PER_DAY)),
+        _ => match (as_i64(left), as_i64(right)) {
+            (Some(left), Some(right)) => left.partial_cmp(&right),
+            _ => match (as_f64(left), as_f64(right)) {
+                (Some(left), Some(right)) => left.partial_cmp(&right),
+                _ => left.partial_cmp(right),
+            },
+        },
+    }
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(v) => Some(*v as i64),
+        Value::BigInt(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(v) => Some(*v as f64),
+        Value::Double(v) => Some(*v),
+        value => as_i64(value).map(|v| v as f64),
+    }
+}
+
+fn arithmetic(left: &Value, operator: BinaryOperator, right: &Value) -> Result<Value> {
+    let overflow = || RqlError::Type(format!("{} {} {} is out of range", left, operator, right));
+    let by_zero = matches!(operator, BinaryOperator::Divide | BinaryOperator::Modulo)
+        && as_f64(right).is_some_and(|divisor| divisor == 0.0);
+    if by_zero && left.data_type().is_some_and(DataType::is_numeric) {
+        return Err(RqlError::Type(format!("Division by zero in {} {} {}", left, operator, right)));
+    }
+
+    let (Some(left_type), Some(right_type)) = (left.data_type(), right.data_type()) else {
+        return Ok(Value::Null);
+    };
+    let integers = |a: i64, b: i64| match operator {
+        BinaryOperator::Add => a.checked_add(b),
+        BinaryOperator::Subtract => a.checked_sub(b),
+        BinaryOperator::Multiply => a.checked_mul(b),
+        BinaryOperator::Divide => a.checked_div(b),
+        _ => a.checked_rem(b),
+    };
+    let days = |days: i32, delta: Option<i64>| -> Result<Value> {
+        let delta = delta.ok_or_else(overflow)?;
+        let days = (days as i64).checked_add(delta).and_then(|days| i32::try_from(days).ok());
+        Ok(Value::Date(days.ok_or_else(overflow)?))
+    };
+    let micros = |value: &Value| match value {
+        Value::Date(days) => *days as i64 * MICROS_PER_DAY,
+        Value::Time(micros) | Value::Timestamp(micros) => *micros,
+        _ => 0,
+    };
+
+    let result = match (left, right) {
+        _ if left_type.is_numeric() && right_type.is_numeric() => match numeric_type(left_type, right_type) {
+            DataType::Integer | DataType::BigInt => {
+                let result = integers(as_i64(left).unwrap_or(0), as_i64(right).unwrap_or(0)).ok_or_else(overflow)?;
+                match numeric_type(left_type, right_type) {
+                    DataType::Integer => Value::Integer(i32::try_from(result).map_err(|_| overflow())?),
+                    _ => Value::BigInt(result),
+                }
+            }
+            data_type => {
+                let (a, b) = (as_f64(left).unwrap_or(0.0), as_f64(right).unwrap_or(0.0));
+                let result = match operator {
+                    BinaryOperator::Add => a + b,
+                    BinaryOperator::Subtract => a - b,
+                    BinaryOperator::Multiply => a * b,
+                    BinaryOperator::Divide => a / b,
+                    _ => a % b,
+                };
+                if data_type == DataType::Float {
+                    Value::Float(result as f32)
+                } else {
+                    Value::Double(result)
+                }
+            }
+        },
+        (Value::Date(d), delta) if operator == BinaryOperator::Add && as_i64(delta).is_some() => days(*d, as_i64(delta))?,
+        (Value::Date(d), delta) if operator == BinaryOperator::Subtract && as_i64(delta).is_some() => {
+            days(*d, as_i64(delta).and_then(i64::checked_neg))?
+        }
+        (delta, Value::Date(d)) if operator == BinaryOperator::Add && as_i64(delta).is_some() => days(*d, as_i64(delta))?,
+        (Value::Date(a), Value::Date(b)) if operator == BinaryOperator::Subtract => {
+            Value::Integer(a.checked_sub(*b).ok_or_else(overflow)?)
+        }
+        (Value::Date(_) | Value::Timestamp(_), Value::Date(_) | Value::Timestamp(_))
+        | (Value::Time(_), Value::Time(_))
+            if operator == BinaryOperator::Subtract =>
+        {
+            Value::BigInt(micros(left).checked_sub(micros(right)).ok_or_else(overflow)?)
+        }
+        _ => {
+            return Err(RqlError::Type(format!(
+                "Cannot apply '{}' to {} and {}",
+                operator,
+                left_type.to_sql_type(),
+                right_type.to_sql_type()
+            )))
+        }
+    };
+    Ok(result)
+}
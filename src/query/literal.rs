@@ -0,0 +1,315 @@
+use crate::query::blob::{format_hex, parse_blob, parse_hex};
+use crate::query::temporal::{MICROS_PER_DAY, format_date, format_time, format_timestamp, parse_date, parse_time, parse_timestamp};
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{ColumnDefinition, DataType, Interval, Literal, Value};
+use std::fmt;
+
+impl Literal {
+    /// Parses a single literal. `raw` must be a slice of `source`, which is used to
+    /// report the position of errors.
+    pub fn from_sql(source: &str, raw: &str) -> Result<Literal> {
+        let literal = raw.trim();
+        let keyword = literal.to_uppercase();
+        if keyword == "NULL" {
+            return Ok(Literal::Null);
+        }
+        if keyword == "TRUE" || keyword == "FALSE" {
+            return Ok(Literal::Boolean(keyword == "TRUE"));
+        }
+
+        if let Some(quoted) = literal.strip_prefix(['E', 'e']).filter(|q| q.starts_with('\'')) {
+            return parse_string(source, quoted, true).map(Literal::Text);
+        }
+        if literal.starts_with('\'') {
+            return parse_string(source, literal, false).map(Literal::Text);
+        }
+        if let Some(quoted) = literal.strip_prefix(['X', 'x']).filter(|q| q.starts_with('\'')) {
+            let digits = parse_string(source, quoted, false)?;
+            return parse_hex(&digits).map(Literal::Blob).ok_or_else(|| {
+                RqlError::parse_at(source, literal, format!("X'{}' is not a valid BLOB, which needs two hexadecimal digits per byte", digits))
+            });
+        }
+        if let Some((keyword, quoted)) = literal.split_once(char::is_whitespace) {
+            let quoted = quoted.trim_start();
+            if quoted.starts_with('\'') {
+                let text = parse_string(source, quoted, false)?;
+                let parsed = match keyword.to_uppercase().as_str() {
+                    "DATE" => Some(parse_date(&text).map(Literal::Date)),
+                    "TIME" => Some(parse_time(&text).map(Literal::Time)),
+                    "TIMESTAMP" => Some(parse_timestamp(&text).map(Literal::Timestamp)),
+                    "INTERVAL" => Some(Interval::parse(&text).map(Literal::Interval)),
+                    _ => None,
+                };
+                if let Some(parsed) = parsed {
+                    return parsed.ok_or_else(|| {
+                        RqlError::parse_at(source, quoted, format!("'{}' is not a valid {}", text, keyword.to_uppercase()))
+                    });
+                }
+            }
+        }
+
+        if let Some(vector) = parse_vector(source, literal)? {
+            return Ok(vector);
+        }
+
+        if is_number(literal) {
+            if !literal.contains(['.', 'e', 'E']) {
+                if let Ok(value) = literal.parse() {
+                    return Ok(Literal::Integer(value));
+                }
+            }
+            if let Ok(value) = literal.parse::<f64>() {
+                if !value.is_finite() {
+                    return Err(RqlError::Type(format!("{} is out of range for DOUBLE", literal)));
+                }
+                return Ok(Literal::Float(value));
+            }
+        }
+
+        let message = if literal.is_empty() {
+            "Expected a value".to_string()
+        } else {
+            format!("Expected a value, found {}; text must be quoted, as in '{}'", literal, literal.replace('\'', "''"))
+        };
+        Err(RqlError::parse_at(source, literal, message))
+    }
+
+    /// The value this literal stands for in a column. Integers widen to BIGINT and
+    /// the floating point types, and quoted text is parsed for date and time
+    /// columns; any other mismatch between the literal and the column type is an error.
+    pub fn to_value(&self, column: &ColumnDefinition) -> Result<Value> {
+        let out_of_range = match (self, column.data_type) {
+            (Literal::Integer(v), DataType::Integer) => i32::try_from(*v).is_err(),
+            (Literal::Float(v), DataType::Float) => !(*v as f32).is_finite(),
+            _ => false,
+        };
+        if out_of_range {
+            return Err(RqlError::Type(format!(
+                "{} is out of range for {} column '{}'",
+                self,
+                column.data_type.to_sql_type(),
+                column.name
+            )));
+        }
+        self.cast(column.data_type).ok_or_else(|| {
+            RqlError::Type(format!(
+                "{} is not a valid {} value for column '{}'",
+                self,
+                column.data_type.to_sql_type(),
+                column.name
+            ))
+        })
+    }
+
+    /// The literal as a value of `data_type`, if it stands for one. NULL is a
+    /// value of every type.
+    pub(crate) fn cast(&self, data_type: DataType) -> Option<Value> {
+        let value = match (self, data_type) {
+            (Literal::Null, _) => Value::Null,
+            (Literal::Integer(v), DataType::Integer) => Value::Integer(i32::try_from(*v).ok()?),
+            (Literal::Integer(v), DataType::BigInt) => Value::BigInt(*v),
+            (Literal::Integer(v), DataType::Float) => Value::Float(*v as f32),
+            (Literal::Integer(v), DataType::Double) => Value::Double(*v as f64),
+            (Literal::Float(v), DataType::Float) => Value::Float(*v as f32),
+            (Literal::Float(v), DataType::Double) => Value::Double(*v),
+            (Literal::Text(v), DataType::Text) => Value::Text(v.clone()),
+            (Literal::Text(v), DataType::Date) => Value::Date(parse_date(v)?),
+            (Literal::Text(v), DataType::Time) => Value::Time(parse_time(v)?),
+            (Literal::Text(v), DataType::Timestamp) => Value::Timestamp(parse_timestamp(v)?),
+            (Literal::Boolean(v), DataType::Boolean) => Value::Boolean(*v),
+            (Literal::Date(v), DataType::Date) => Value::Date(*v),
+            (Literal::Date(v), DataType::Timestamp) => Value::Timestamp(*v as i64 * MICROS_PER_DAY),
+            (Literal::Time(v), DataType::Time) => Value::Time(*v),
+            (Literal::Timestamp(v), DataType::Timestamp) => Value::Timestamp(*v),
+            (Literal::Vector(v), data_type) => Value::vector(data_type, v)?,
+            (Literal::Text(v), data_type) if data_type.is_vector() => Value::parse(v, &data_type)?,
+            (Literal::Blob(v), DataType::Blob) => Value::Blob(v.clone()),
+            (Literal::Text(v), DataType::Blob) => Value::Blob(parse_blob(v)?),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// The value the literal stands for on its own: integers are INTEGER when they
+    /// fit and BIGINT otherwise, and numbers with a fraction are DOUBLE. Intervals
+    /// are not values.
+    pub(crate) fn value(&self) -> Option<Value> {
+        match self {
+            Literal::Integer(v) => Some(i32::try_from(*v).map_or(Value::BigInt(*v), Value::Integer)),
+            Literal::Float(v) => Some(Value::Double(*v)),
+            Literal::Text(_) => self.cast(DataType::Text),
+            Literal::Boolean(_) => self.cast(DataType::Boolean),
+            Literal::Date(_) => self.cast(DataType::Date),
+            Literal::Time(_) => self.cast(DataType::Time),
+            Literal::Timestamp(_) => self.cast(DataType::Timestamp),
+            Literal::Vector(v) => self.cast(vector_type(v.len())?),
+            Literal::Blob(_) => self.cast(DataType::Blob),
+            Literal::Null => Some(Value::Null),
+            Literal::Interval(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Null => write!(f, "NULL"),
+            Literal::Boolean(v) => write!(f, "{}", if *v { "TRUE" } else { "FALSE" }),
+            Literal::Integer(v) => write!(f, "{}", v),
+            Literal::Float(v) => write!(f, "{:?}", v),
+            Literal::Text(v) => write!(f, "'{}'", v.replace('\'', "''")),
+            Literal::Date(v) => write!(f, "DATE '{}'", format_date(*v)),
+            Literal::Time(v) => write!(f, "TIME '{}'", format_time(*v)),
+            Literal::Timestamp(v) => write!(f, "TIMESTAMP '{}'", format_timestamp(*v)),
+            Literal::Interval(v) => write!(f, "INTERVAL '{}'", v),
+            Literal::Vector(v) => {
+                let name = vector_type(v.len()).map_or("VECTOR".to_string(), DataType::to_sql_type);
+                let components: Vec<String> = v.iter().map(f32::to_string).collect();
+                write!(f, "{}({})", name, components.join(", "))
+            }
+            Literal::Blob(v) => write!(f, "X'{}'", format_hex(v)),
+        }
+    }
+}
+
+/// The vector or matrix type with `len` components.
+fn vector_type(len: usize) -> Option<DataType> {
+    [DataType::Vec2, DataType::Vec3, DataType::Vec4, DataType::Mat4]
+        .into_iter()
+        .find(|data_type| data_type.components() == len)
+}
+
+/// Reads `VEC2(x, y)`, `VEC3(..)`, `VEC4(..)` or `MAT4(..)`, whose components must
+/// be numbers. Returns `None` if `literal` is not a vector constructor.
+fn parse_vector(source: &str, literal: &str) -> Result<Option<Literal>> {
+    let Some((name, rest)) = literal.split_once('(') else {
+        return Ok(None);
+    };
+    let Some(data_type) = DataType::from_sql_type(name.trim()).filter(|data_type| data_type.is_vector()) else {
+        return Ok(None);
+    };
+    let list = rest
+        .strip_suffix(')')
+        .ok_or_else(|| RqlError::parse_at(source, literal, format!("Expected ')' to close {}(", name.trim())))?;
+
+    let components = list
+        .split(',')
+        .map(|component| {
+            let component = component.trim();
+            match component.parse::<f32>() {
+                Ok(value) if is_number(component) => Ok(value),
+                _ => Err(RqlError::parse_at(source, component, format!("Expected a number, found '{}'", component))),
+            }
+        })
+        .collect::<Result<Vec<f32>>>()?;
+    if components.len() != data_type.components() {
+        return Err(RqlError::parse_at(
+            source,
+            literal,
+            format!("{} takes {} components, found {}", data_type.to_sql_type(), data_type.components(), components.len()),
+        ));
+    }
+    Ok(Some(Literal::Vector(components)))
+}
+
+/// `[+-]digits[.digits][e[+-]digits]`, where either side of the point may be empty.
+fn is_number(s: &str) -> bool {
+    let s = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(index) => (&s[..index], Some(&s[index + 1..])),
+        None => (s, None),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    let exponent_ok = exponent.is_none_or(|e| {
+        let e = e.strip_prefix(['+', '-']).unwrap_or(e);
+        !e.is_empty() && digits(e)
+    });
+    !(whole.is_empty() && fraction.is_empty()) && digits(whole) && digits(fraction) && exponent_ok
+}
+
+/// Reads a quoted string that must make up all of `quoted`. A doubled quote stands
+/// for one quote; in E-strings a backslash also starts an escape sequence.
+fn parse_string(source: &str, quoted: &str, escapes: bool) -> Result<String> {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices().skip(1).peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\'' if chars.peek().is_some_and(|(_, next)| *next == '\'') => {
+                chars.next();
+                value.push('\'');
+            }
+            '\'' => {
+                let rest = &quoted[index + 1..];
+                if !rest.trim().is_empty() {
+                    return Err(RqlError::parse_at(source, rest.trim_start(), "Unexpected text after string literal"));
+                }
+                return Ok(value);
+            }
+            '\\' if escapes => {
+                let Some((escape_index, escape)) = chars.next() else {
+                    break;
+                };
+                let invalid = || RqlError::parse_at(source, &quoted[index..], format!("Invalid escape sequence '\\{}'", escape));
+                match escape {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    'b' => value.push('\u{8}'),
+                    'f' => value.push('\u{c}'),
+                    '0' => value.push('\0'),
+                    'x' | 'u' => {
+                        let len = if escape == 'x' { 2 } else { 4 };
+                        let start = escape_index + 1;
+                        let hex = quoted.get(start..start + len).ok_or_else(invalid)?;
+                        let code = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+                        value.push(char::from_u32(code).ok_or_else(invalid)?);
+                        for _ in 0..len {
+                            chars.next();
+                        }
+                    }
+                    other => value.push(other),
+                }
+            }
+            c => value.push(c),
+        }
+    }
+    Err(RqlError::parse_at(source, quoted, "Unterminated string literal"))
+}
+
+/// Copies `sql` with the contents of every string literal replaced by `_`, so
+/// keywords and separators can be searched for by byte offset without matching
+/// inside strings. The quotes themselves are kept.
+pub(crate) fn mask_literals(sql: &str) -> String {
+    let mut masked = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut previous = ' ';
+    while let Some(c) = chars.next() {
+        masked.push(c);
+        if c != '\'' {
+            previous = c;
+            continue;
+        }
+
+        let escapes = matches!(previous, 'E' | 'e');
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                    masked.push_str("__");
+                    continue;
+                }
+                masked.push('\'');
+                break;
+            }
+            masked.push_str(&"_".repeat(c.len_utf8()));
+            if c == '\\' && escapes {
+                if let Some(escaped) = chars.next() {
+                    masked.push_str(&"_".repeat(escaped.len_utf8()));
+                }
+            }
+        }
+        previous = '\'';
+    }
+    masked
+}
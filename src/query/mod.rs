@@ -1,2 +1,9 @@
 pub mod query;
-pub mod runner;
\ No newline at end of file
+pub mod literal;
+pub mod runner;
+pub mod result;
+pub mod script;pub mod temporal;
+pub mod expression;
+pub mod select;
+pub mod blob;
+pub mod reference;
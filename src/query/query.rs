@@ -1,280 +1,1407 @@
-use crate::io::reader::{read_table_definition, read_vec_of_bytes_from_file};
-use crate::io::util::{print_table, reconstruct_rows};
-use crate::io::writer::{serialize_from_value, serialize_value, write_vec_of_bytes_to_file};
-use crate::rqle::rqle_parser::ExpressionParser;
+use crate::database::database::Database;
+use crate::io::reader::{read_rows_filtered, read_table_definition, read_vec_of_bytes_from_file};
+use crate::io::util::reconstruct_rows;
+use crate::io::writer::{remove_table, serialize_from_value, update_rows, write_index, write_rows_and_table_definition, write_tables, write_vec_of_bytes_to_file};
+use crate::query::blob::parse_blob;
+use crate::query::expression::{Binder, Parser, Scope};
+use crate::query::literal::mask_literals;
+use crate::query::reference::{check_children, check_foreign_keys, check_parents, column_list, delete_cascade, positions, referencing_tables, Changes};
+use crate::query::temporal::{now, parse_date, parse_time, parse_timestamp};
+use crate::rqle::check::{check_shader, CheckPass};
+use crate::rqle::cpu_executor::CpuExecutor;
+use crate::rqle::rqle_parser::{Assignment, ExpressionParser};
 use crate::rqle::shader_executor::ShaderExecutor;
-use crate::types::types::{ColumnDefinition, DataType, InsertDefinition, SelectDefinition, TableDefinition, UpdateDefinition, Value};
+use crate::types::types::{AlterDefinition, AttachDefinition, CopyDefinition, CreateIndexDefinition, CopyDirection, CopyFormat, ColumnDefinition, Compression, ComputeDevice, DataType, DeleteDefinition, DetachDefinition, DropDefinition, Expression, ForeignKey, IndexDefinition, IndexKind, InsertDefinition, InsertSource, Literal, SelectDefinition, SqliteImportDefinition, TableDefinition, Timings, UpdateDefinition, Value};
+use crate::types::error::{Result, RqlError};
 use regex::Regex;
-use std::collections::HashMap;
-use std::fmt::Debug;
-use std::io;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
 impl DataType {
     pub(crate) fn from_sql_type(sql_type: &str) -> Option<DataType> {
-        match sql_type.to_uppercase().as_str() {
+        let upper = sql_type.to_uppercase();
+        if let Some(dimensions) = upper.strip_prefix("VECTOR(").and_then(|rest| rest.strip_suffix(')')) {
+            return dimensions.trim().parse().ok().filter(|dimensions| *dimensions > 0).map(DataType::Vector);
+        }
+        match upper.as_str() {
             "INTEGER" => Some(DataType::Integer),
             "FLOAT" => Some(DataType::Float),
             "TEXT" => Some(DataType::Text),
             "BOOLEAN" => Some(DataType::Boolean),
+            "BIGINT" => Some(DataType::BigInt),
+            "DOUBLE" => Some(DataType::Double),
+            "DATE" => Some(DataType::Date),
+            "TIME" => Some(DataType::Time),
+            "TIMESTAMP" => Some(DataType::Timestamp),
+            "VEC2" => Some(DataType::Vec2),
+            "VEC3" => Some(DataType::Vec3),
+            "VEC4" => Some(DataType::Vec4),
+            "MAT4" => Some(DataType::Mat4),
+            "BLOB" | "BYTEA" => Some(DataType::Blob),
             _ => None,
         }
     }
-    pub(crate) fn to_sql_type(&self) -> &str {
-        match self {
+    pub fn to_sql_type(self) -> String {
+        let name = match self {
             DataType::Integer => "INTEGER",
             DataType::Float => "FLOAT",
             DataType::Text => "TEXT",
             DataType::Boolean => "BOOLEAN",
+            DataType::BigInt => "BIGINT",
+            DataType::Double => "DOUBLE",
+            DataType::Date => "DATE",
+            DataType::Time => "TIME",
+            DataType::Timestamp => "TIMESTAMP",
+            DataType::Vec2 => "VEC2",
+            DataType::Vec3 => "VEC3",
+            DataType::Vec4 => "VEC4",
+            DataType::Mat4 => "MAT4",
+            DataType::Vector(dimensions) => return format!("VECTOR({})", dimensions),
+            DataType::Blob => "BLOB",
+        };
+        name.to_string()
+    }
+
+    pub(crate) fn is_numeric(self) -> bool {
+        matches!(self, DataType::Integer | DataType::Float | DataType::BigInt | DataType::Double)
+    }
+
+    pub(crate) fn is_temporal(self) -> bool {
+        matches!(self, DataType::Date | DataType::Time | DataType::Timestamp)
+    }
+
+    pub(crate) fn is_vector(self) -> bool {
+        matches!(self, DataType::Vec2 | DataType::Vec3 | DataType::Vec4 | DataType::Mat4 | DataType::Vector(_))
+    }
+
+    /// Whether WGSL has a type for the values, so an `UPDATE` can compute them.
+    /// `VECTOR(n)` columns are only read by the nearest neighbour search.
+    pub(crate) fn is_computable(self) -> bool {
+        self.is_numeric() || self.is_temporal() || (self.is_vector() && !matches!(self, DataType::Vector(_)))
+    }
+
+    /// The number of floats in a value of a vector or matrix type, 1 for every other type.
+    pub(crate) fn components(self) -> usize {
+        match self {
+            DataType::Vec2 => 2,
+            DataType::Vec3 => 3,
+            DataType::Vec4 => 4,
+            DataType::Mat4 => 16,
+            DataType::Vector(dimensions) => dimensions as usize,
+            _ => 1,
+        }
+    }
+
+    /// Whether values of this type can be stored in a `target` column: integers
+    /// widen to BIGINT and to the floating point types, FLOAT widens to DOUBLE.
+    pub(crate) fn widens_to(self, target: DataType) -> bool {
+        self == target
+            || matches!(
+                (self, target),
+                (DataType::Integer, DataType::BigInt | DataType::Float | DataType::Double)
+                    | (DataType::BigInt | DataType::Float, DataType::Double)
+            )
+    }
+}
+
+impl Compression {
+    pub(crate) fn from_sql(name: &str) -> Option<Compression> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(Compression::None),
+            "lz4" => Some(Compression::Lz4),
+            _ => None,
         }
     }
 
-    fn rust_type(&self) -> &str {
+    pub fn to_sql(self) -> &'static str {
         match self {
-            DataType::Integer => "i32",
-            DataType::Float => "f32",
-            DataType::Text => "String",
-            DataType::Boolean => "bool",
+            Compression::None => "none",
+            Compression::Lz4 => "lz4",
         }
     }
 }
 
 impl TableDefinition {
-    pub(crate) fn from_sql(sql: &str) -> Option<TableDefinition> {
-        let sql = sql.trim();
-        if !sql.starts_with("CREATE TABLE") || !sql.ends_with(");") {
-            return None;
+    pub fn from_sql(source: &str) -> Result<TableDefinition> {
+        let sql = source.trim();
+        if !sql.starts_with("CREATE TABLE") {
+            return Err(RqlError::parse_at(source, sql, "Expected CREATE TABLE"));
+        }
+        if !sql.ends_with(';') {
+            return Err(RqlError::parse_at(source, &sql[sql.len()..], "Expected ';' at the end of CREATE TABLE"));
         }
 
         let name_start = "CREATE TABLE ".len();
-        let name_end = sql.find('(')?;
+        let name_end = sql
+            .find('(')
+            .ok_or_else(|| RqlError::parse_at(source, &sql[name_start.min(sql.len())..], "Expected '(' after the table name"))?;
         let name = sql[name_start..name_end].trim().to_string();
+        if name.is_empty() {
+            return Err(RqlError::parse_at(source, &sql[name_end..], "Expected a table name"));
+        }
 
         let columns_start = name_end + 1;
-        let columns_end = sql.len() - 2;
-        let columns_str = sql[columns_start..columns_end].trim();
+        let columns_end = find_closing_paren(sql, name_end)
+            .ok_or_else(|| RqlError::parse_at(source, &sql[name_end..], "Unclosed '(' in column list"))?;
+
+        let mut columns = Vec::new();
+        let mut checks = Vec::new();
+        let mut foreign_keys = Vec::new();
+        let mut key_constraint = None;
+        for element in split_outside_quotes(&sql[columns_start..columns_end], ',') {
+            let element = element.trim();
+            let mut parser = Parser::new(source, element)?;
+            // `CHECK (..)`, `FOREIGN KEY (..)` and `PRIMARY KEY (..)` start table
+            // constraints, while `check INTEGER` and `primary TEXT` are columns.
+            if parser.eat_keyword("CHECK") && parser.eat_symbol("(") {
+                checks.push(parser.expression()?);
+                parser.expect_symbol(")")?;
+                parser.expect_end()?;
+                continue;
+            }
+            let mut parser = Parser::new(source, element)?;
+            if parser.eat_keyword("FOREIGN") && parser.eat_keyword("KEY") {
+                let columns = column_list(&mut parser)?;
+                parser.expect_keyword("REFERENCES")?;
+                foreign_keys.push(ForeignKey::parse(&mut parser, columns)?);
+                parser.expect_end()?;
+                continue;
+            }
+            let mut parser = Parser::new(source, element)?;
+            if parser.eat_keyword("PRIMARY") && parser.eat_keyword("KEY") {
+                if key_constraint.is_some() {
+                    return Err(RqlError::parse_at(source, element, "A table can only have one PRIMARY KEY constraint"));
+                }
+                parser.expect_symbol("(")?;
+                let mut key = vec![parser.identifier()?];
+                while parser.eat_symbol(",") {
+                    key.push(parser.identifier()?);
+                }
+                parser.expect_symbol(")")?;
+                parser.expect_end()?;
+                key_constraint = Some((element, key));
+            } else {
+                let (column, column_checks, foreign_key) = ColumnDefinition::from_sql_in(source, element)?;
+                if columns.iter().any(|c: &ColumnDefinition| c.name.eq_ignore_ascii_case(&column.name)) {
+                    return Err(RqlError::parse_at(
+                        source,
+                        element,
+                        format!("Column '{}' is declared more than once", column.name),
+                    ));
+                }
+                columns.push(column);
+                checks.extend(column_checks);
+                foreign_keys.extend(foreign_key);
+            }
+        }
 
-        let columns: Vec<ColumnDefinition> = columns_str
-            .split(',')
-            .filter_map(|col| ColumnDefinition::from_sql(col.trim()))
-            .collect();
+        let mut table = TableDefinition {
+            name,
+            columns,
+            compression: TableDefinition::parse_options(source, sql[columns_end + 1..sql.len() - 1].trim())?,
+            last_id: 0,
+            checks,
+            foreign_keys,
+            indexes: Vec::new(),
+        };
+        if table.primary_key().len() > 1 {
+            return Err(RqlError::Schema(format!(
+                "Table '{}' has more than one PRIMARY KEY column; declare a composite key as PRIMARY KEY (a, b)",
+                table.name
+            )));
+        }
+        if let Some((element, key)) = key_constraint {
+            table.set_primary_key(source, element, &key)?;
+        }
+        let key: Vec<String> = table.primary_key().iter().map(|index| table.columns[*index].name.clone()).collect();
+        let mut indexes = Vec::new();
+        if !key.is_empty() {
+            indexes.push(table.implicit_index("pkey", key.clone()));
+        }
+        for column in table.columns.iter().filter(|column| column.unique && key != [column.name.clone()]) {
+            indexes.push(table.implicit_index(&format!("{}_key", column.name), vec![column.name.clone()]));
+        }
+        table.indexes = indexes;
+        table.check_constraints()?;
+        Ok(table)
+    }
 
-        if columns.is_empty() {
-            return None;
+    /// The unique index backing the primary key or a `UNIQUE` column, named after
+    /// the table like `readings_pkey` or `readings_serial_key`.
+    pub(crate) fn implicit_index(&self, suffix: &str, columns: Vec<String>) -> IndexDefinition {
+        let table = self.name.rsplit('.').next().unwrap_or(&self.name);
+        IndexDefinition {
+            name: format!("{}_{}", table, suffix),
+            columns,
+            unique: true,
+            implicit: true,
+            kind: IndexKind::BTree,
         }
+    }
 
-        Some(TableDefinition { name, columns })
+    /// Applies a table-level `PRIMARY KEY (a, b)`, which makes its columns NOT NULL.
+    fn set_primary_key(&mut self, source: &str, element: &str, key: &[String]) -> Result<()> {
+        if self.columns.iter().any(|column| column.primary_key) {
+            return Err(RqlError::parse_at(source, element, "The table already has a column declared PRIMARY KEY"));
+        }
+        for (index, name) in key.iter().enumerate() {
+            if key[..index].contains(name) {
+                return Err(RqlError::parse_at(source, element, format!("Column '{}' is listed more than once", name)));
+            }
+            let column = self.columns.iter_mut().find(|column| column.name == *name).ok_or_else(|| {
+                RqlError::parse_at(source, element, format!("PRIMARY KEY names the unknown column '{}'", name))
+            })?;
+            column.primary_key = true;
+            column.nullable = false;
+        }
+        Ok(())
+    }
+
+    /// Checks the constraints that involve more than one column, that every
+    /// `CHECK` is a BOOLEAN condition on the columns of a single row, and that
+    /// foreign keys and indexes name columns of the table. Parents are checked by
+    /// `check_foreign_keys` once the database is at hand.
+    fn check_constraints(&self) -> Result<()> {
+        if self.columns.iter().filter(|column| column.auto_increment).count() > 1 {
+            return Err(RqlError::Schema(format!("Table '{}' can only have one AUTOINCREMENT column", self.name)));
+        }
+        for index in &self.indexes {
+            for position in index.positions(self)? {
+                let column = &self.columns[position];
+                if column.data_type.is_vector() {
+                    return Err(RqlError::Schema(format!(
+                        "Column '{}' of type {} cannot be indexed",
+                        column.name,
+                        column.data_type.to_sql_type()
+                    )));
+                }
+            }
+        }
+        for foreign_key in &self.foreign_keys {
+            let mut columns = positions(self, &foreign_key.columns)?;
+            columns.sort_unstable();
+            columns.dedup();
+            if columns.len() < foreign_key.columns.len() {
+                return Err(RqlError::Schema(format!("{} lists a column more than once", foreign_key.to_sql())));
+            }
+        }
+        let mut binder = Binder::new(self, now());
+        for check in &self.checks {
+            if check.contains_aggregate() {
+                return Err(RqlError::Schema(format!("CHECK ({}) cannot use aggregate functions", check)));
+            }
+            binder.condition(check)?;
+        }
+        Ok(())
+    }
+
+    /// Fails if one of `checks` is FALSE for one of `rows`, given in schema order.
+    /// A check that is NULL passes, as in SQL.
+    pub(crate) fn check_rows(&self, checks: &[&Expression], rows: &[Vec<Value>], now: i64) -> Result<()> {
+        let mut binder = Binder::new(self, now);
+        for check in checks {
+            let bound = binder.condition(check)?;
+            for row in rows {
+                if bound.evaluate(&Scope::row(row))? == Value::Boolean(false) {
+                    let values: Vec<String> = row.iter().map(Value::to_string).collect();
+                    return Err(RqlError::Constraint(format!(
+                        "Row ({}) violates CHECK ({}) of table '{}'",
+                        values.join(", "),
+                        check,
+                        self.name
+                    )));
+                }
+            }
+        }
+        Ok(())
     }
-    pub(crate) fn to_sql(&self) -> String {
-        let column_definitions: Vec<String> = self
+
+    /// The columns of the primary key, in schema order.
+    pub(crate) fn primary_key(&self) -> Vec<usize> {
+        (0..self.columns.len()).filter(|index| self.columns[*index].primary_key).collect()
+    }
+
+    /// Parses the optional `WITH (key = 'value', ...)` clause after the column list.
+    fn parse_options(source: &str, options: &str) -> Result<Compression> {
+        let mut compression = Compression::None;
+        if options.is_empty() {
+            return Ok(compression);
+        }
+
+        let expected = || RqlError::parse_at(source, options, "Expected WITH (option = 'value', ...)");
+        let list = options.strip_prefix("WITH").ok_or_else(expected)?.trim();
+        let list = list.strip_prefix('(').and_then(|l| l.strip_suffix(')')).ok_or_else(expected)?;
+        for option in list.split(',') {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| RqlError::parse_at(source, option.trim_start(), "Expected option = 'value'"))?;
+            let value = value.trim().trim_matches('\'');
+            match key.trim().to_lowercase().as_str() {
+                "compression" => {
+                    compression = Compression::from_sql(value).ok_or_else(|| {
+                        RqlError::parse_at(source, option.trim_start(), format!("Unknown compression '{}'", value))
+                    })?
+                }
+                other => {
+                    return Err(RqlError::parse_at(source, option.trim_start(), format!("Unknown table option '{}'", other)))
+                }
+            }
+        }
+
+        Ok(compression)
+    }
+
+    pub fn to_sql(&self) -> String {
+        // A composite key is written as a table constraint after the columns.
+        let key = self.primary_key();
+        let composite = key.len() > 1;
+        let mut column_definitions: Vec<String> = self
             .columns
             .iter()
-            .map(|col| col.to_sql())
+            .map(|col| if composite { ColumnDefinition { primary_key: false, ..col.clone() }.to_sql() } else { col.to_sql() })
             .collect();
+        if composite {
+            let names: Vec<&str> = key.iter().map(|index| self.columns[*index].name.as_str()).collect();
+            column_definitions.push(format!("PRIMARY KEY ({})", names.join(", ")));
+        }
+        column_definitions.extend(self.checks.iter().map(|check| format!("CHECK ({})", check)));
+        column_definitions.extend(self.foreign_keys.iter().map(ForeignKey::to_sql));
+
+        let options = match self.compression {
+            Compression::None => String::new(),
+            compression => format!(" WITH (compression = '{}')", compression.to_sql()),
+        };
 
         format!(
-            "CREATE TABLE {} (\n  {}\n);",
+            "CREATE TABLE {} (\n  {}\n){};",
             self.name,
-            column_definitions.join(",\n  ")
+            column_definitions.join(",\n  "),
+            options
         )
     }
 }
 
+/// The byte offset of the `)` closing the first `(` at or after `open_index`.
+/// Parentheses inside string literals are skipped.
+fn find_closing_paren(sql: &str, open_index: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in mask_literals(sql).char_indices().skip_while(|(i, _)| *i < open_index) {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Value {
+    pub(crate) fn parse(raw: &str, data_type: &DataType) -> Option<Value> {
+        match data_type {
+            DataType::Integer => raw.parse().ok().map(Value::Integer),
+            DataType::Float => raw.parse().ok().map(Value::Float),
+            DataType::Text => Some(Value::Text(raw.to_string())),
+            DataType::Boolean => raw.parse().ok().map(Value::Boolean),
+            DataType::BigInt => raw.parse().ok().map(Value::BigInt),
+            DataType::Double => raw.parse().ok().map(Value::Double),
+            DataType::Date => parse_date(raw).map(Value::Date),
+            DataType::Time => parse_time(raw).map(Value::Time),
+            DataType::Timestamp => parse_timestamp(raw).map(Value::Timestamp),
+            DataType::Vec2 | DataType::Vec3 | DataType::Vec4 | DataType::Mat4 | DataType::Vector(_) => {
+                let list = raw.trim().strip_prefix('[')?.strip_suffix(']')?;
+                let components = list.split(',').map(|c| c.trim().parse().ok()).collect::<Option<Vec<f32>>>()?;
+                Value::vector(*data_type, &components)
+            }
+            DataType::Blob => parse_blob(raw).map(Value::Blob),
+        }
+    }
+
+    /// A value of the vector or matrix type `data_type`, if `components` has its size.
+    pub(crate) fn vector(data_type: DataType, components: &[f32]) -> Option<Value> {
+        let value = match data_type {
+            DataType::Vec2 => Value::Vec2(components.try_into().ok()?),
+            DataType::Vec3 => Value::Vec3(components.try_into().ok()?),
+            DataType::Vec4 => Value::Vec4(components.try_into().ok()?),
+            DataType::Mat4 => Value::Mat4(components.try_into().ok()?),
+            DataType::Vector(dimensions) if components.len() == dimensions as usize => Value::Vector(components.to_vec()),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// The floats of a vector or matrix value.
+    pub(crate) fn components(&self) -> Option<&[f32]> {
+        match self {
+            Value::Vec2(v) => Some(v),
+            Value::Vec3(v) => Some(v),
+            Value::Vec4(v) => Some(v),
+            Value::Mat4(v) => Some(v),
+            Value::Vector(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Integer(_) => Some(DataType::Integer),
+            Value::Float(_) => Some(DataType::Float),
+            Value::Text(_) => Some(DataType::Text),
+            Value::Boolean(_) => Some(DataType::Boolean),
+            Value::BigInt(_) => Some(DataType::BigInt),
+            Value::Double(_) => Some(DataType::Double),
+            Value::Date(_) => Some(DataType::Date),
+            Value::Time(_) => Some(DataType::Time),
+            Value::Timestamp(_) => Some(DataType::Timestamp),
+            Value::Vec2(_) => Some(DataType::Vec2),
+            Value::Vec3(_) => Some(DataType::Vec3),
+            Value::Vec4(_) => Some(DataType::Vec4),
+            Value::Mat4(_) => Some(DataType::Mat4),
+            Value::Vector(v) => Some(DataType::Vector(v.len() as u32)),
+            Value::Blob(_) => Some(DataType::Blob),
+            Value::Null => None,
+        }
+    }
+
+    /// Converts the value to a column of type `target`, following `DataType::widens_to`.
+    /// NULL converts to any type.
+    pub(crate) fn widen_to(&self, target: DataType) -> Option<Value> {
+        let value = match (self, target) {
+            (Value::Integer(v), DataType::BigInt) => Value::BigInt(*v as i64),
+            (Value::Integer(v), DataType::Float) => Value::Float(*v as f32),
+            (Value::Integer(v), DataType::Double) => Value::Double(*v as f64),
+            (Value::BigInt(v), DataType::Double) => Value::Double(*v as f64),
+            (Value::Float(v), DataType::Double) => Value::Double(*v as f64),
+            (value, target) if value.data_type().is_none_or(|data_type| data_type == target) => value.clone(),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
 impl ColumnDefinition {
-    pub(crate) fn from_sql(sql: &str) -> Option<ColumnDefinition> {
+    /// Parses a column definition. `CHECK` and `REFERENCES` constraints belong to the
+    /// table, so a column with one is parsed as part of `CREATE TABLE` or `ALTER TABLE`
+    /// instead.
+    pub fn from_sql(sql: &str) -> Result<ColumnDefinition> {
+        match ColumnDefinition::from_sql_in(sql, sql)? {
+            (column, checks, None) if checks.is_empty() => Ok(column),
+            _ => Err(RqlError::parse_at(sql, sql, "A CHECK or REFERENCES constraint needs the table of its column")),
+        }
+    }
+
+    /// Parses a column definition that is a subslice of the statement `source`,
+    /// so errors point into the full statement. Returns the column together with
+    /// its `CHECK` constraints and the parent it `REFERENCES`.
+    fn from_sql_in(source: &str, sql: &str) -> Result<(ColumnDefinition, Vec<Expression>, Option<ForeignKey>)> {
         let parts: Vec<&str> = sql.split_whitespace().collect();
 
         if parts.len() < 2 {
-            return None;
+            return Err(RqlError::parse_at(source, sql, "Expected a column name followed by its type"));
         }
 
         let name = parts[0].to_string();
-        let data_type = DataType::from_sql_type(parts[1])?;
+        let type_start = &sql[parts[1].as_ptr() as usize - sql.as_ptr() as usize..];
+        let data_type = DataType::from_sql_type(parts[1])
+            .ok_or_else(|| RqlError::parse_at(source, type_start, format!("Unknown column type '{}'", parts[1])))?;
 
-        let nullable = !sql.contains("NOT NULL");
-        let unique = sql.contains("UNIQUE");
-
-        Some(ColumnDefinition {
+        let mut column = ColumnDefinition {
             name,
             data_type,
-            nullable,
-            unique,
+            nullable: true,
+            unique: false,
+            primary_key: false,
+            auto_increment: false,
+            default: None,
+        };
+        let constraints = &type_start[parts[1].len()..];
+        let mut parser = Parser::new(source, constraints)?;
+        let mut checks = Vec::new();
+        let mut foreign_key = None;
+        loop {
+            if parser.eat_keyword("NOT") {
+                parser.expect_keyword("NULL")?;
+                column.nullable = false;
+            } else if parser.eat_keyword("NULL") {
+                // Columns are nullable unless declared otherwise.
+            } else if parser.eat_keyword("UNIQUE") {
+                column.unique = true;
+            } else if parser.eat_keyword("PRIMARY") {
+                parser.expect_keyword("KEY")?;
+                column.primary_key = true;
+                column.nullable = false;
+            } else if parser.eat_keyword("AUTOINCREMENT") || parser.eat_keyword("AUTO_INCREMENT") {
+                column.auto_increment = true;
+            } else if parser.eat_keyword("GENERATED") {
+                for keyword in ["BY", "DEFAULT", "AS", "IDENTITY"] {
+                    parser.expect_keyword(keyword)?;
+                }
+                column.auto_increment = true;
+            } else if parser.eat_keyword("DEFAULT") {
+                column.default = Some(parser.expression()?);
+            } else if parser.eat_keyword("CHECK") {
+                parser.expect_symbol("(")?;
+                checks.push(parser.expression()?);
+                parser.expect_symbol(")")?;
+            } else if foreign_key.is_none() && parser.eat_keyword("REFERENCES") {
+                foreign_key = Some(ForeignKey::parse(&mut parser, vec![column.name.clone()])?);
+            } else {
+                parser.expect_end()?;
+                break;
+            }
+        }
+
+        if column.auto_increment && !matches!(column.data_type, DataType::Integer | DataType::BigInt) {
+            return Err(RqlError::Schema(format!(
+                "AUTOINCREMENT column '{}' must be INTEGER or BIGINT, not {}",
+                column.name,
+                column.data_type.to_sql_type()
+            )));
+        }
+        if column.auto_increment && column.default.is_some() {
+            return Err(RqlError::Schema(format!("AUTOINCREMENT column '{}' cannot also have a DEFAULT", column.name)));
+        }
+        // Evaluated once here so a default of the wrong type fails the statement that declares it.
+        column.default_value(now())?;
+
+        Ok((column, checks, foreign_key))
+    }
+
+    pub fn to_sql(&self) -> String {
+        let mut sql = format!("{} {}", self.name, self.data_type.to_sql_type());
+        if !self.nullable && !self.primary_key {
+            sql.push_str(" NOT NULL");
+        }
+        if self.unique {
+            sql.push_str(" UNIQUE");
+        }
+        if self.primary_key {
+            sql.push_str(" PRIMARY KEY");
+        }
+        if self.auto_increment {
+            sql.push_str(" AUTOINCREMENT");
+        }
+        if let Some(default) = &self.default {
+            sql.push_str(&format!(" DEFAULT {}", default));
+        }
+        sql
+    }
+
+    /// The value of the column in rows inserted without one: its `DEFAULT`, or NULL.
+    /// `now` is the value of `now()`, the same for every row of a statement.
+    pub(crate) fn default_value(&self, now: i64) -> Result<Value> {
+        let Some(default) = &self.default else {
+            return Ok(Value::Null);
+        };
+        if let Expression::Literal(literal) = default {
+            return literal.to_value(self);
+        }
+        if !default.is_constant() {
+            return Err(RqlError::Schema(format!(
+                "The DEFAULT of column '{}' cannot refer to columns or aggregates: {}",
+                self.name, default
+            )));
+        }
+
+        let no_columns = TableDefinition::default();
+        let (bound, _) = Binder::new(&no_columns, now).bind(default)?;
+        let value = bound.evaluate(&Scope::row(&[]))?;
+        value.widen_to(self.data_type).ok_or_else(|| {
+            RqlError::Type(format!(
+                "DEFAULT {} is {}, not a valid {} value for column '{}'",
+                default,
+                value,
+                self.data_type.to_sql_type(),
+                self.name
+            ))
         })
     }
-    pub(crate) fn to_sql(&self) -> String {
-        format!(
-            "{} {}{}{}",
-            self.name,
-            self.data_type.to_sql_type(),
-            if self.nullable { "" } else { " NOT NULL" },
-            if self.unique { " UNIQUE" } else { "" }
-        )
+}
+
+impl AlterDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';').trim_end();
+        let rest = sql
+            .strip_prefix("ALTER TABLE")
+            .ok_or_else(|| RqlError::parse_at(source, sql, "Expected ALTER TABLE"))?
+            .trim_start();
+
+        let name_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let table_name = rest[..name_end].to_string();
+        if table_name.is_empty() {
+            return Err(RqlError::parse_at(source, rest, "Expected a table name"));
+        }
+
+        let rest = rest[name_end..].trim_start();
+        let definition = rest
+            .strip_prefix("ADD")
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Expected ADD COLUMN after the table name"))?
+            .trim_start();
+        let definition = definition.strip_prefix("COLUMN").unwrap_or(definition).trim_start();
+        let (column, checks, foreign_key) = ColumnDefinition::from_sql_in(source, definition)?;
+        if column.primary_key {
+            return Err(RqlError::parse_at(source, definition, "ALTER TABLE cannot add a PRIMARY KEY column"));
+        }
+
+        Ok(AlterDefinition { table_name, column, checks, foreign_key })
+    }
+
+    /// Adds the column to the table. Existing rows get its default, or the next ids
+    /// if it is `AUTOINCREMENT`, and are rewritten together with the new schema and
+    /// rebuilt indexes.
+    pub fn execute(&self, db: &Database) -> Result<()> {
+        let mut table_definition = read_table_definition(db, &self.table_name)?;
+        if table_definition.columns.iter().any(|column| column.name.eq_ignore_ascii_case(&self.column.name)) {
+            return Err(RqlError::Schema(format!(
+                "Table '{}' already has a column '{}'",
+                self.table_name, self.column.name
+            )));
+        }
+        let mut rows = read_rows_filtered(db, &self.table_name, &table_definition, |_| true)?;
+        table_definition.columns.push(self.column.clone());
+        table_definition.checks.extend(self.checks.iter().cloned());
+        table_definition.foreign_keys.extend(self.foreign_key.iter().cloned());
+        if self.column.unique {
+            let index = table_definition.implicit_index(&format!("{}_key", self.column.name), vec![self.column.name.clone()]);
+            table_definition.indexes.push(index);
+        }
+        table_definition.check_constraints()?;
+        check_foreign_keys(db, &table_definition, self.foreign_key.as_slice())?;
+
+        let now = now();
+        let column = &self.column;
+        for row in &mut rows {
+            let value = column.default_value(now)?;
+            let value = if column.auto_increment { next_id(column, value, &mut table_definition.last_id)? } else { value };
+            if value == Value::Null && !column.nullable {
+                return Err(RqlError::Constraint(format!(
+                    "Column '{}' cannot be NULL, so it needs a DEFAULT to be added to a table with rows",
+                    column.name
+                )));
+            }
+            row.push(value);
+        }
+        table_definition.check_rows(&self.checks.iter().collect::<Vec<_>>(), &rows, now)?;
+        check_parents(db, &table_definition, &self.foreign_key.iter().collect::<Vec<_>>(), &rows, || Ok(rows.clone()))?;
+
+        let encoded = rows
+            .iter()
+            .map(|row| row.iter().map(serialize_from_value).collect::<Result<Vec<_>>>())
+            .collect::<Result<Vec<_>>>()?;
+        write_rows_and_table_definition(db, &self.table_name, encoded, &table_definition, false)
     }
 }
 
-impl InsertDefinition {
-    pub fn from_sql(sql: &str) -> Option<Self> {
-        let sql = sql.trim();
-        if !sql.starts_with("INSERT") || !sql.contains("VALUES") {
-            return None;
+impl DeleteDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';');
+        let mut parser = Parser::new(source, sql)?;
+        parser.expect_keyword("DELETE")?;
+        parser.expect_keyword("FROM")?;
+        let table_name = parser.identifier()?;
+        let filter = if parser.eat_keyword("WHERE") { Some(parser.expression()?) } else { None };
+        parser.expect_end()?;
+
+        Ok(DeleteDefinition { table_name, filter })
+    }
+
+    /// Deletes the rows matching the condition, or every row without one, and
+    /// applies the `ON DELETE` action of the foreign keys that reference them.
+    /// Every table this changes is rewritten in the same write. Returns the number
+    /// of rows deleted from the table itself.
+    pub fn execute(&self, db: &Database) -> Result<usize> {
+        let table_definition = read_table_definition(db, &self.table_name)?;
+        let filter = match &self.filter {
+            Some(filter) => Some(Binder::new(&table_definition, now()).condition(filter)?),
+            None => None,
+        };
+
+        let (mut kept, mut deleted) = (Vec::new(), Vec::new());
+        for row in read_rows_filtered(db, &self.table_name, &table_definition, |_| true)? {
+            if filter.as_ref().map_or(Ok(true), |filter| filter.matches(&Scope::row(&row)))? {
+                deleted.push(row);
+            } else {
+                kept.push(row);
+            }
+        }
+        let count = deleted.len();
+        if count == 0 {
+            return Ok(0);
         }
 
-        let name_start = "INSERT INTO ".len();
-        let name_end = sql.find("VALUES")?;
-        let table_name = sql[name_start..name_end].trim().to_string();
+        let mut changes = Changes::new();
+        changes.insert(self.table_name.clone(), (table_definition, kept));
+        delete_cascade(db, &mut changes, &self.table_name, deleted)?;
 
-        let values_start = sql.find('(')? + 1;
-        let values_end = sql.rfind(')')?;
-        let values_str = sql[values_start..values_end].trim();
+        let tables = changes
+            .iter()
+            .map(|(name, (_, rows))| {
+                let encoded = rows
+                    .iter()
+                    .map(|row| row.iter().map(serialize_from_value).collect::<Result<Vec<_>>>())
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((name.as_str(), encoded))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        write_tables(db, tables)?;
+        Ok(count)
+    }
+}
 
-        let values: Vec<String> = values_str.split(',').map(|v| v.trim().to_string()).collect();
+impl DropDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';');
+        let mut parser = Parser::new(source, sql)?;
+        parser.expect_keyword("DROP")?;
+        parser.expect_keyword("TABLE")?;
+        let table_name = parser.identifier()?;
+        parser.expect_end()?;
 
-        Some(InsertDefinition {
-            name: table_name,
-            table_definition: TableDefinition {
-                name: String::new(),
-                columns: Vec::new(),
-            },
-            values,
-        })
+        Ok(DropDefinition { table_name })
     }
 
-    pub fn validate_and_insert(&mut self) -> io::Result<()> {
-        let table_definition = read_table_definition(&self.name)?;
-        self.table_definition = table_definition;
+    /// Removes the table with its rows and indexes. A table that another table references
+    /// cannot be dropped before that one.
+    pub fn execute(&self, db: &Database) -> Result<()> {
+        let table_definition = read_table_definition(db, &self.table_name)?;
+        let children = referencing_tables(db, &self.table_name)?;
+        if let Some(child) = children.iter().find(|child| child.name != self.table_name) {
+            return Err(RqlError::Constraint(format!(
+                "Cannot drop table '{}' because table '{}' references it",
+                self.table_name, child.name
+            )));
+        }
+        remove_table(db, &self.table_name, &table_definition)
+    }
+}
+
+impl CreateIndexDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';');
+        let mut parser = Parser::new(source, sql)?;
+        parser.expect_keyword("CREATE")?;
+        let unique = parser.eat_keyword("UNIQUE");
+        parser.expect_keyword("INDEX")?;
+        let name = parser.identifier()?;
+        parser.expect_keyword("ON")?;
+        let table_name = parser.identifier()?;
+        let mut kind = IndexKind::BTree;
+        if parser.eat_keyword("USING") {
+            if parser.eat_keyword("HASH") {
+                kind = IndexKind::Hash;
+            } else if !parser.eat_keyword("BTREE") {
+                return Err(parser.error("Expected BTREE or HASH after USING"));
+            }
+        }
+        let columns = column_list(&mut parser)?;
+        parser.expect_end()?;
+
+        Ok(CreateIndexDefinition {
+            table_name,
+            index: IndexDefinition { name, columns, unique, implicit: false, kind },
+        })
+    }
 
-        if self.values.len() != self.table_definition.columns.len() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Number of values does not match the table definition",
-            ));
+    /// Builds the index over the rows already in the table and saves it together
+    /// with the table's new definition.
+    pub fn execute(&self, db: &Database) -> Result<()> {
+        let mut table_definition = read_table_definition(db, &self.table_name)?;
+        if table_definition.indexes.iter().any(|index| index.name.eq_ignore_ascii_case(&self.index.name)) {
+            return Err(RqlError::Schema(format!(
+                "Table '{}' already has an index '{}'",
+                self.table_name, self.index.name
+            )));
+        }
+        let mut columns = self.index.positions(&table_definition)?;
+        columns.sort_unstable();
+        columns.dedup();
+        if columns.len() < self.index.columns.len() {
+            return Err(RqlError::Schema(format!("Index '{}' lists a column more than once", self.index.name)));
         }
+        table_definition.indexes.push(self.index.clone());
+        table_definition.check_constraints()?;
+        write_index(db, &self.table_name, &table_definition, &self.index)
+    }
+}
 
-        let mut row_data = Vec::new();
-        for (value, column) in self.values.iter().zip(self.table_definition.columns.iter()) {
-            let serialized_value = serialize_value(value, &column.data_type)?;
-            row_data.extend(serialized_value);
+impl AttachDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';');
+        let rest = sql
+            .strip_prefix("ATTACH")
+            .ok_or_else(|| RqlError::parse_at(source, sql, "Expected ATTACH"))?
+            .trim_start();
+        let rest = rest.strip_prefix("DATABASE").unwrap_or(rest).trim_start();
+
+        let path_start = rest
+            .strip_prefix('\'')
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Expected a quoted database path"))?;
+        let path_end = path_start
+            .find('\'')
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Unterminated database path"))?;
+        let path = path_start[..path_end].to_string();
+
+        let alias = path_start[path_end + 1..].trim_start();
+        let name = alias
+            .strip_prefix("AS")
+            .ok_or_else(|| RqlError::parse_at(source, alias, "Expected AS followed by a database name"))?
+            .trim()
+            .to_string();
+        if path.is_empty() || name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(RqlError::parse_at(source, alias, "Expected a non-empty path and a single-word database name"));
         }
 
-        write_vec_of_bytes_to_file(vec![vec![row_data]], self.name.as_str(), true)?;
+        Ok(AttachDefinition { path, name })
+    }
+}
+
+impl SqliteImportDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';').trim_end();
+        let rest = sql
+            .strip_prefix("IMPORT")
+            .ok_or_else(|| RqlError::parse_at(source, sql, "Expected IMPORT"))?
+            .trim_start();
+        let rest = rest
+            .strip_prefix("SQLITE")
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Expected SQLITE"))?
+            .trim_start();
+
+        let path_start = rest
+            .strip_prefix('\'')
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Expected a quoted database file path"))?;
+        let path_end = path_start
+            .find('\'')
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Unterminated database file path"))?;
+        let path = path_start[..path_end].to_string();
+
+        let rest = path_start[path_end + 1..].trim_start();
+        let rest = rest
+            .strip_prefix("TABLE")
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Expected TABLE followed by a table name"))?;
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        let (source_table, table_name) = match words[..] {
+            [source_table] => (source_table, source_table),
+            [source_table, "AS", table_name] => (source_table, table_name),
+            _ => return Err(RqlError::parse_at(source, rest, "Expected a table name, optionally followed by AS and a new name")),
+        };
 
-        Ok(())
+        Ok(SqliteImportDefinition {
+            path,
+            source_table: source_table.to_string(),
+            table_name: table_name.to_string(),
+        })
     }
 }
 
-impl SelectDefinition {
-    pub fn from_sql(sql: &str) -> Option<Self> {
-        let sql = sql.trim().trim_end_matches(';');
-        if !sql.starts_with("SELECT") || !sql.contains("FROM") {
-            return None;
+impl CopyFormat {
+    pub fn from_name(name: &str) -> Option<CopyFormat> {
+        match name.to_lowercase().as_str() {
+            "csv" | "tsv" => Some(CopyFormat::Csv),
+            "arrow" | "ipc" | "feather" => Some(CopyFormat::Arrow),
+            "parquet" | "pq" => Some(CopyFormat::Parquet),
+            _ => None,
         }
+    }
 
-        let table_start = sql.find("FROM")? + 5;
-        let table_end = sql.find("WHERE").unwrap_or_else(|| sql.len());
-        let table_name = sql[table_start..table_end].trim().to_string();
+    /// Guesses the format from a file extension, defaulting to CSV.
+    pub fn from_path(path: &str) -> CopyFormat {
+        std::path::Path::new(path)
+            .extension()
+            .and_then(|extension| CopyFormat::from_name(&extension.to_string_lossy()))
+            .unwrap_or_default()
+    }
+}
 
-        let criteria = if let Some(where_index) = sql.find("WHERE") {
-            sql[where_index + 6..].trim().to_string()
+impl CopyDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';').trim_end();
+        let rest = sql
+            .strip_prefix("COPY")
+            .ok_or_else(|| RqlError::parse_at(source, sql, "Expected COPY"))?
+            .trim_start();
+
+        let (table_name, query, rest) = if rest.starts_with('(') {
+            let close = find_closing_paren(rest, 0)
+                .ok_or_else(|| RqlError::parse_at(source, rest, "Unterminated query"))?;
+            let query = rest[1..close].trim();
+            let select = SelectDefinition::from_sql(query)?;
+            (select.table_name, Some(query.to_string()), &rest[close + 1..])
         } else {
-            String::new()
+            let (table_name, rest) = rest
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| RqlError::parse_at(source, rest, "Expected a table name followed by FROM or TO"))?;
+            (table_name.to_string(), None, rest)
         };
+        let rest = rest.trim_start();
+        let (direction, rest) = if let Some(rest) = rest.strip_prefix("FROM") {
+            (CopyDirection::From, rest)
+        } else if let Some(rest) = rest.strip_prefix("TO") {
+            (CopyDirection::To, rest)
+        } else {
+            return Err(RqlError::parse_at(source, rest, "Expected FROM or TO"));
+        };
+        if query.is_some() && direction == CopyDirection::From {
+            return Err(RqlError::parse_at(source, rest, "A query can only be copied TO a file"));
+        }
 
-        let parsed_criteria = SelectDefinition::parse_criteria(&criteria)?;
+        let rest = rest.trim_start();
+        let path_start = rest
+            .strip_prefix('\'')
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Expected a quoted file path"))?;
+        let path_end = path_start
+            .find('\'')
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Unterminated file path"))?;
+        let path = path_start[..path_end].to_string();
 
-        Some(SelectDefinition {
+        let mut copy = CopyDefinition {
             table_name,
-            criteria: parsed_criteria,
-        })
+            query,
+            direction,
+            format: CopyFormat::from_path(&path),
+            path,
+            header: false,
+            delimiter: b',',
+        };
+
+        let options = path_start[path_end + 1..].trim();
+        if options.is_empty() {
+            return Ok(copy);
+        }
+        let expected = || RqlError::parse_at(source, options, "Expected WITH (format 'csv', header, delimiter ',')");
+        let list = options.strip_prefix("WITH").ok_or_else(expected)?.trim();
+        let list = list.strip_prefix('(').and_then(|l| l.strip_suffix(')')).ok_or_else(expected)?;
+        for option in split_outside_quotes(list, ',').into_iter().map(str::trim).filter(|o| !o.is_empty()) {
+            let (key, value) = match option.split_once(|c: char| c == '=' || c.is_whitespace()) {
+                Some((key, value)) => (key, value.trim().trim_start_matches('=').trim()),
+                None => (option, ""),
+            };
+            let invalid = || RqlError::parse_at(source, option, format!("Invalid COPY option '{}'", option));
+            match (key.to_lowercase().as_str(), value.trim_matches('\'')) {
+                ("format", format) => copy.format = CopyFormat::from_name(format).ok_or_else(invalid)?,
+                ("header", "" | "true") => copy.header = true,
+                ("header", "false") => copy.header = false,
+                ("delimiter", "\\t" | "tab") => copy.delimiter = b'\t',
+                ("delimiter", delimiter) if delimiter.len() == 1 => copy.delimiter = delimiter.as_bytes()[0],
+                _ => return Err(invalid()),
+            }
+        }
+        if copy.format != CopyFormat::Csv && (copy.header || copy.delimiter != b',') {
+            return Err(RqlError::parse_at(source, options, "header and delimiter only apply to CSV files"));
+        }
+
+        Ok(copy)
     }
+}
 
-    pub fn execute(&self) -> io::Result<Vec<HashMap<String, Value>>> {
-        let all_rows = read_vec_of_bytes_from_file(self.table_name.as_str())?;
+/// Splits `s` on `separator`, except where it appears inside a string literal or
+/// inside parentheses, as in `VEC3(1, 2, 3)`.
+fn split_outside_quotes(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    for (index, c) in mask_literals(s).char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(&s[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
 
-        let filtered_rows: Vec<HashMap<String, Value>> = all_rows
-            .into_iter()
-            .filter(|row| self.matches_criteria(row))
-            .collect();
+impl DetachDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';');
+        let rest = sql
+            .strip_prefix("DETACH")
+            .ok_or_else(|| RqlError::parse_at(source, sql, "Expected DETACH"))?
+            .trim_start();
+        let rest = rest.strip_prefix("DATABASE").unwrap_or(rest).trim();
+        if rest.is_empty() || rest.contains(char::is_whitespace) {
+            return Err(RqlError::parse_at(source, rest, "Expected a single-word database name"));
+        }
 
-        Ok(filtered_rows)
+        Ok(DetachDefinition { name: rest.to_string() })
     }
+}
 
-    fn parse_criteria(criteria: &str) -> Option<Vec<(String, String, String)>> {
-        if criteria.is_empty() {
-            return Some(vec![]);
+impl InsertDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';').trim_end();
+        let rest = sql
+            .strip_prefix("INSERT INTO")
+            .ok_or_else(|| RqlError::parse_at(source, sql, "Expected INSERT INTO"))?
+            .trim_start();
+
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '(').unwrap_or(rest.len());
+        let table_name = rest[..name_end].to_string();
+        if table_name.is_empty() {
+            return Err(RqlError::parse_at(source, rest, "Expected a table name"));
         }
 
-        let conditions: Vec<&str> = criteria
-            .split("AND")
-            .flat_map(|part| part.split("OR"))
-            .map(|s| s.trim())
-            .collect();
+        let mut rest = rest[name_end..].trim_start();
+        let mut columns = None;
+        if rest.starts_with('(') {
+            let close = find_closing_paren(rest, 0)
+                .ok_or_else(|| RqlError::parse_at(source, rest, "Unclosed '(' in the column list"))?;
+            let names: Vec<String> = rest[1..close].split(',').map(|c| c.trim().to_string()).collect();
+            if names.iter().any(|name| name.is_empty() || name.contains(char::is_whitespace)) {
+                return Err(RqlError::parse_at(source, rest, "Expected a comma separated list of column names"));
+            }
+            columns = Some(names);
+            rest = rest[close + 1..].trim_start();
+        }
 
-        let mut parsed_conditions = vec![];
+        let insert_source = if let Some(tuples) = rest.strip_prefix("VALUES") {
+            let rows = split_tuples(source, tuples)?
+                .into_iter()
+                .map(|tuple| {
+                    split_outside_quotes(tuple, ',')
+                        .into_iter()
+                        .map(|value| match value.trim() {
+                            keyword if keyword.eq_ignore_ascii_case("DEFAULT") => Ok(None),
+                            value => Literal::from_sql(source, value).map(Some),
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+                .collect::<Result<Vec<_>>>()?;
+            InsertSource::Values(rows)
+        } else if rest.starts_with("SELECT") {
+            InsertSource::Select(SelectDefinition::from_sql(rest)?)
+        } else {
+            return Err(RqlError::parse_at(source, rest, "Expected VALUES or SELECT after the table name"));
+        };
+
+        Ok(InsertDefinition {
+            name: table_name,
+            table_definition: TableDefinition::default(),
+            columns,
+            source: insert_source,
+        })
+    }
+
+    /// Checks every row against the table and appends them all in one write.
+    /// Columns left out of the column list, and values given as `DEFAULT`, take
+    /// their column's default, and the `AUTOINCREMENT` column the next id when it
+    /// has no value. Returns the number of rows inserted.
+    pub fn validate_and_insert(&mut self, db: &Database) -> Result<usize> {
+        let table_definition = read_table_definition(db, &self.name)?;
+        self.table_definition = table_definition;
 
-        for condition in conditions {
-            let parts: Vec<&str> = condition.split('=').collect();
-            if parts.len() != 2 {
-                return None;
+        let targets = self.target_columns()?;
+        let now = now();
+        let mut last_id = self.table_definition.last_id;
+        let mut rows = Vec::new();
+        match &self.source {
+            InsertSource::Values(values) => {
+                for values in values {
+                    self.check_value_count(values.len())?;
+                    rows.push(self.build_row(&targets, values, literal_value, now, &mut last_id)?);
+                }
             }
+            InsertSource::Select(select) => {
+                let selected = select.execute(db, &mut Timings::default())?;
+                self.check_value_count(selected.columns().len())?;
+                for row in selected.iter() {
+                    rows.push(self.build_row(&targets, row.values(), convert_value, now, &mut last_id)?);
+                }
+            }
+        }
+        append_values(db, &self.name, &mut self.table_definition, &rows, last_id, now)?;
+        Ok(rows.len())
+    }
 
-            let key = parts[0].trim().to_string();
-            let value = parts[1].trim().replace("'", "").to_string();
+    /// The position in the schema of each column values are given for.
+    fn target_columns(&self) -> Result<Vec<usize>> {
+        let columns = &self.table_definition.columns;
+        let Some(names) = &self.columns else {
+            return Ok((0..columns.len()).collect());
+        };
 
-            parsed_conditions.push((key, "=".to_string(), value)); // TODO: Implement more than =
+        let mut targets = Vec::with_capacity(names.len());
+        for name in names {
+            let index = columns.iter().position(|c| c.name == *name).ok_or_else(|| {
+                RqlError::Schema(format!("Table '{}' has no column '{}'", self.name, name))
+            })?;
+            if targets.contains(&index) {
+                return Err(RqlError::Schema(format!("Column '{}' is listed more than once", name)));
+            }
+            targets.push(index);
         }
+        Ok(targets)
+    }
 
-        Some(parsed_conditions)
+    fn check_value_count(&self, count: usize) -> Result<()> {
+        let expected = self.columns.as_ref().map_or(self.table_definition.columns.len(), Vec::len);
+        if count != expected {
+            return Err(RqlError::Schema(format!(
+                "Table '{}' expects {} values per row but {} were supplied",
+                self.name, expected, count
+            )));
+        }
+        Ok(())
     }
 
-    fn matches_criteria(&self, row: &HashMap<String, Value>) -> bool {
-        self.criteria.iter().all(|(key, operator, value)| {
-            match operator.as_str() {
-                "=" => row.get(key).map(|v| v.to_string() == *value).unwrap_or(false),
-                _ => false,
+    /// One row in schema order. Columns without a value, or whose value is `DEFAULT`
+    /// (`None`), take their default; NULL in the `AUTOINCREMENT` column takes the next id.
+    fn build_row<T, F>(&self, targets: &[usize], values: &[T], value_of: F, now: i64, last_id: &mut i64) -> Result<Vec<Value>>
+    where
+        F: Fn(&T, &ColumnDefinition) -> Result<Option<Value>>,
+    {
+        let mut row = Vec::with_capacity(self.table_definition.columns.len());
+        for (index, column) in self.table_definition.columns.iter().enumerate() {
+            let value = match targets.iter().position(|target| *target == index) {
+                Some(position) => value_of(&values[position], column)?,
+                None => None,
+            };
+            let value = match value {
+                Some(value) => value,
+                None => column.default_value(now)?,
+            };
+            let value = if column.auto_increment { next_id(column, value, last_id)? } else { value };
+            if value == Value::Null && !column.nullable {
+                return Err(RqlError::Constraint(format!("Column '{}' cannot be NULL", column.name)));
             }
-        })
+            row.push(value);
+        }
+        Ok(row)
+    }
+}
+
+/// A `VALUES` entry as a value of its column, or `None` for `DEFAULT`.
+fn literal_value(value: &Option<Literal>, column: &ColumnDefinition) -> Result<Option<Value>> {
+    value.as_ref().map(|literal| literal.to_value(column)).transpose()
+}
+
+/// A selected value as a value of its column, widening numbers to wider types.
+fn convert_value(value: &Value, column: &ColumnDefinition) -> Result<Option<Value>> {
+    match value.widen_to(column.data_type) {
+        Some(value) => Ok(Some(value)),
+        None => Err(RqlError::Type(format!(
+            "{} is not a valid {} value for column '{}'",
+            value,
+            column.data_type.to_sql_type(),
+            column.name
+        ))),
+    }
+}
+
+/// Checks rows in schema order against the table's CHECK constraints, evaluated
+/// at `now`, and foreign keys, and appends them. When `last_id` has moved past the
+/// table's `AUTOINCREMENT` counter, the counter is saved with the rows, so a
+/// failed write cannot reuse ids.
+pub(crate) fn append_values(
+    db: &Database,
+    table_name: &str,
+    table_definition: &mut TableDefinition,
+    rows: &[Vec<Value>],
+    last_id: i64,
+    now: i64,
+) -> Result<()> {
+    let checks: Vec<&Expression> = table_definition.checks.iter().collect();
+    table_definition.check_rows(&checks, rows, now)?;
+    let foreign_keys: Vec<&ForeignKey> = table_definition.foreign_keys.iter().collect();
+    check_parents(db, table_definition, &foreign_keys, rows, || {
+        let mut own_rows = read_rows_filtered(db, table_name, table_definition, |_| true)?;
+        own_rows.extend(rows.iter().cloned());
+        Ok(own_rows)
+    })?;
+    let encoded = rows
+        .iter()
+        .map(|row| row.iter().map(serialize_from_value).collect::<Result<Vec<_>>>())
+        .collect::<Result<Vec<_>>>()?;
+    if last_id != table_definition.last_id {
+        table_definition.last_id = last_id;
+        write_rows_and_table_definition(db, table_name, encoded, table_definition, true)?;
+    } else if !rows.is_empty() {
+        write_vec_of_bytes_to_file(db, encoded, table_name, true)?;
+    }
+    Ok(())
+}
+
+/// The value of an `AUTOINCREMENT` column: the id after `last_id` for NULL. An
+/// explicit id moves `last_id` up to it, so later generated ids do not collide.
+pub(crate) fn next_id(column: &ColumnDefinition, value: Value, last_id: &mut i64) -> Result<Value> {
+    let exhausted = || RqlError::Constraint(format!("AUTOINCREMENT column '{}' has run out of ids", column.name));
+    match value {
+        Value::Null => {
+            *last_id = last_id.checked_add(1).ok_or_else(exhausted)?;
+            match column.data_type {
+                DataType::Integer => i32::try_from(*last_id).map(Value::Integer).map_err(|_| exhausted()),
+                _ => Ok(Value::BigInt(*last_id)),
+            }
+        }
+        Value::Integer(id) => {
+            *last_id = (*last_id).max(id as i64);
+            Ok(value)
+        }
+        Value::BigInt(id) => {
+            *last_id = (*last_id).max(id);
+            Ok(value)
+        }
+        value => Ok(value),
+    }
+}
+
+/// Splits `(..), (..), ...` into the text inside each pair of parentheses,
+/// ignoring parentheses and commas inside string literals.
+fn split_tuples<'a>(source: &str, sql: &'a str) -> Result<Vec<&'a str>> {
+    let mut tuples = Vec::new();
+    let mut rest = sql.trim_start();
+    loop {
+        if !rest.starts_with('(') {
+            return Err(RqlError::parse_at(source, rest, "Expected '(' to start a row of values"));
+        }
+
+        let close = find_closing_paren(rest, 0)
+            .ok_or_else(|| RqlError::parse_at(source, rest, "Unclosed '(' in VALUES"))?;
+        tuples.push(&rest[1..close]);
+
+        rest = rest[close + 1..].trim_start();
+        match rest.strip_prefix(',') {
+            Some(next) => rest = next.trim_start(),
+            None if rest.is_empty() => return Ok(tuples),
+            None => return Err(RqlError::parse_at(source, rest, "Expected ',' between rows of values")),
+        }
     }
 }
 
 impl UpdateDefinition {
-    pub fn from_sql(sql: &str) -> Option<Self> {
-        let sql = sql.trim().trim_end_matches(';');
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';');
 
-        if !sql.starts_with("UPDATE") || !sql.contains("SET") {
-            return None;
+        if !sql.starts_with("UPDATE") {
+            return Err(RqlError::parse_at(source, sql, "Expected UPDATE"));
         }
 
-        let table_start = "UPDATE ".len();
-        let table_end = sql.find("SET")?;
+        let table_start = "UPDATE".len();
+        let table_end = sql
+            .find("SET")
+            .ok_or_else(|| RqlError::parse_at(source, &sql[sql.len()..], "Expected SET after the table name"))?;
         let table_name = sql[table_start..table_end].trim().to_string();
+        if table_name.is_empty() {
+            return Err(RqlError::parse_at(source, &sql[table_end..], "Expected a table name"));
+        }
 
         let set_start = table_end + "SET".len();
         let set_query = sql[set_start..].trim().to_string();
 
-        Some(UpdateDefinition {
+        Ok(UpdateDefinition {
             table_name,
             set_query,
         })
     }
 
-    pub fn load_data(&self) -> io::Result<HashMap<String, Vec<Value>>> {
+    /// Runs the `SET` expressions over every row on the GPU, or on the CPU when the
+    /// GPU cannot process the table's 64-bit columns, writes the table back and
+    /// returns the updated rows.
+    pub fn load_data(&self, db: &Database, timings: &mut Timings) -> Result<Vec<HashMap<String, Value>>> {
 
-        let table_def = read_table_definition(self.table_name.as_str()).unwrap();
+        let table_def = read_table_definition(db, self.table_name.as_str())?;
 
-        let all_rows: Vec<HashMap<String, Value>> = read_vec_of_bytes_from_file(self.table_name.as_str()).unwrap();
+        let all_rows: Vec<HashMap<String, Value>> = read_vec_of_bytes_from_file(db, self.table_name.as_str())?;
 
-        let mut column_map: HashMap<String, Vec<Value>> = HashMap::new();
+        let parse_start = Instant::now();
+        let assignments = ExpressionParser::parse(&self.set_query)?.assignments;
+        timings.parse += parse_start.elapsed();
 
-        for row in all_rows {
-            for (key, value) in row {
-                let key_is_number = table_def
-                    .columns
-                    .iter()
-                    .any(|i| (i.data_type == DataType::Float || i.data_type == DataType::Integer) && i.name == key);
-                if key_is_number {
-                    column_map
-                        .entry(key)
-                        .or_insert_with(Vec::new)
-                        .push(value);
+        // Only the columns the assignments read or write are uploaded, so a 64-bit
+        // column the GPU cannot process does not get in the way of the others.
+        let names = Regex::new(r"(\.\s*)?\b([A-Za-z_][A-Za-z0-9_]*)\b(\s*\()?")
+            .map_err(|e| RqlError::parse(e.to_string()))?;
+        let mut referenced: HashSet<&str> = assignments
+            .iter()
+            .flat_map(|assignment| {
+                let mut names = references(&names, &assignment.expression);
+                names.push(assignment.variable.as_str());
+                names
+            })
+            .collect();
+        // Only the CHECK constraints over an assigned column can start failing.
+        let checks: Vec<&Expression> = table_def
+            .checks
+            .iter()
+            .filter(|check| check.columns().iter().any(|name| assignments.iter().any(|a| a.variable == *name)))
+            .collect();
+        // The 32-bit columns they read are uploaded too, so the GPU can check them.
+        for check in &checks {
+            for name in check.columns() {
+                let column = table_def.columns.iter().find(|c| c.name == name);
+                if column.is_some_and(|c| matches!(c.data_type, DataType::Integer | DataType::Float)) {
+                    referenced.insert(name);
+                }
+            }
+        }
+        let computable = |c: &&ColumnDefinition| c.data_type.is_computable();
+        if !table_def.columns.iter().any(|c| computable(&c)) {
+            return Err(RqlError::Type(format!(
+                "Table '{}' has no numeric columns to update",
+                self.table_name
+            )));
+        }
+        let numeric_columns: Vec<&ColumnDefinition> = table_def
+            .columns
+            .iter()
+            .filter(computable)
+            .filter(|c| referenced.contains(c.name.as_str()))
+            .collect();
+        let not_computable = |assignment: &&Assignment| {
+            table_def.columns.iter().any(|c| c.name == assignment.variable && !computable(&c))
+        };
+        if let Some(assignment) = assignments.iter().find(not_computable) {
+            return Err(RqlError::Type(format!(
+                "Column '{}' is not numeric and cannot be assigned in UPDATE",
+                assignment.variable
+            )));
+        }
+        let embedding = |c: &&ColumnDefinition| matches!(c.data_type, DataType::Vector(_)) && referenced.contains(c.name.as_str());
+        if let Some(column) = table_def.columns.iter().find(embedding) {
+            return Err(RqlError::Type(format!(
+                "{} column '{}' cannot be used in UPDATE expressions",
+                column.data_type.to_sql_type(),
+                column.name
+            )));
+        }
+        let Some(first_numeric) = numeric_columns.first() else {
+            return Ok(all_rows);
+        };
+        if all_rows.is_empty() {
+            return Ok(all_rows);
+        }
+
+        let plan_start = Instant::now();
+        let mut column_map: HashMap<String, Vec<Value>> = HashMap::new();
+        // The shader sees NULL as zero; which rows were NULL is kept aside.
+        let mut null_rows: HashMap<String, Vec<bool>> = HashMap::new();
+
+        for row in &all_rows {
+            for column in &numeric_columns {
+                if let Some(value) = row.get(&column.name) {
+                    let (value, is_null) = match (value, column.data_type) {
+                        (Value::Null, DataType::Integer) => (Value::Integer(0), true),
+                        (Value::Null, DataType::BigInt) => (Value::BigInt(0), true),
+                        (Value::Null, DataType::Double) => (Value::Double(0.0), true),
+                        (Value::Null, DataType::Date) => (Value::Date(0), true),
+                        (Value::Null, DataType::Time) => (Value::Time(0), true),
+                        (Value::Null, DataType::Timestamp) => (Value::Timestamp(0), true),
+                        (Value::Null, data_type) if data_type.is_vector() => {
+                            (Value::vector(data_type, &[0.0; 16][..data_type.components()]).unwrap_or(Value::Null), true)
+                        }
+                        (Value::Null, _) => (Value::Float(0.0), true),
+                        (value, _) => (value.clone(), false),
+                    };
+                    column_map.entry(column.name.clone()).or_default().push(value);
+                    null_rows.entry(column.name.clone()).or_default().push(is_null);
                 }
             }
         }
@@ -283,16 +1410,16 @@ impl UpdateDefinition {
 
         let wgsl_declarations: Vec<String> = column_map.keys()
             .map(|key| {
-                let column_type = table_def
-                    .columns
-                    .iter()
-                    .find(|col| col.name == *key)
-                    .map(|col| match col.data_type {
-                        DataType::Integer => "array<i32>",
-                        DataType::Float => "array<f32>",
-                        _ => "array<unknown>",
-                    })
-                    .unwrap();
+                let column_type = match table_def.columns.iter().find(|col| col.name == *key).map(|col| col.data_type) {
+                    Some(DataType::Integer | DataType::Date) => "array<i32>",
+                    Some(DataType::BigInt | DataType::Time | DataType::Timestamp) => "array<i64>",
+                    Some(DataType::Double) => "array<f64>",
+                    Some(DataType::Vec2) => "array<vec2<f32>>",
+                    Some(DataType::Vec3) => "array<vec3<f32>>",
+                    Some(DataType::Vec4) => "array<vec4<f32>>",
+                    Some(DataType::Mat4) => "array<mat4x4<f32>>",
+                    _ => "array<f32>",
+                };
 
                 let declaration = format!(
                     "@group(0)\n@binding({})\nvar<storage, read_write> {}: {};",
@@ -306,78 +1433,205 @@ impl UpdateDefinition {
 
         let wgsl_code_header = wgsl_declarations.join("\n\n");
 
-        let assignments = match ExpressionParser::parse(&*self.set_query) {
-            Ok(intermediary_code) => {
-                intermediary_code.assignments
-            }
-            Err(err) => panic!("Failed to parse intermediary code {}", err),
-        };
-
-        let statements = assignments
-            .iter()
-            .map(|assignment| {
-                let mut adjusted_expression = assignment.expression.clone();
-                for key in column_map.keys() {
-                    let pattern = format!(r"\b{}\b", key);
-                    let replacement = format!("{}[sys_index]", key);
-
-                    let regex = Regex::new(&pattern).unwrap();
-                    adjusted_expression = regex.replace_all(&adjusted_expression, replacement.as_str()).to_string();
-                }
+        // The columns each assigned name is computed from, following temporaries.
+        let mut sources: HashMap<String, HashSet<String>> = column_map
+            .keys()
+            .map(|key| (key.clone(), HashSet::from([key.clone()])))
+            .collect();
 
-                if column_map.contains_key(&assignment.variable) {
-                    format!(
-                        "{}[sys_index] = {};",
-                        assignment.variable,
-                        adjusted_expression
-                    )
-                } else {
-                    format!(
-                        "let {} = {};",
-                        assignment.variable,
-                        adjusted_expression
-                    )
-                }
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
+        let mut statements = Vec::new();
+        for assignment in &assignments {
+            let referenced = references(&names, &assignment.expression)
+                .into_iter()
+                .filter_map(|name| sources.get(name))
+                .flatten()
+                .cloned()
+                .collect();
+            sources.insert(assignment.variable.clone(), referenced);
+
+            let adjusted_expression = to_wgsl(&names, &assignment.expression, &column_map);
+
+            if column_map.contains_key(&assignment.variable) {
+                statements.push(format!(
+                    "{}[sys_index] = {};",
+                    assignment.variable,
+                    adjusted_expression
+                ));
+            } else {
+                statements.push(format!(
+                    "let {} = {};",
+                    assignment.variable,
+                    adjusted_expression
+                ));
+            }
+        }
+        let statements = statements.join("\n");
 
-        let total_wgsl_code = wgsl_code_header + "
+        let mut total_wgsl_code = wgsl_code_header + "
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) gid: vec3<u32>,
         @builtin(local_invocation_id) lid: vec3<u32>,
         @builtin(workgroup_id) wid: vec3<u32>) {
     let sys_index = wid.x * 64u + lid.x;
-    if (sys_index < arrayLength(&" + column_map.keys().next().unwrap() + ")) {"
+    if (sys_index < arrayLength(&" + &first_numeric.name + ")) {"
         + &statements +
 "    }
 }";
 
-        let new_vals = ShaderExecutor.main(total_wgsl_code, column_map, table_def);
+        // The CPU stands in when there is no GPU, or none with the 64-bit support
+        // the columns need.
+        let data_types: Vec<DataType> = numeric_columns.iter().map(|c| c.data_type).collect();
+        let on_gpu = ShaderExecutor.supports(&data_types)?;
+
+        // Counts the violations of the CHECK constraints on the GPU, with the NULLs
+        // known beforehand: a column is NULL wherever one of its sources was.
+        let mut check_pass = None;
+        if on_gpu && !checks.is_empty() {
+            let check_columns: Vec<(&str, DataType)> = numeric_columns
+                .iter()
+                .filter(|c| checks.iter().any(|check| check.columns().contains(&c.name.as_str())))
+                .map(|c| (c.name.as_str(), c.data_type))
+                .collect();
+            if let Some(shader) = check_shader(&checks, &check_columns, column_map.len()) {
+                let nulls = (0..all_rows.len())
+                    .map(|index| {
+                        check_columns.iter().enumerate().fold(0u32, |nulls, (bit, (name, _))| {
+                            let null = sources[*name].iter().any(|column| null_rows[column][index]);
+                            nulls | (null as u32) << bit
+                        })
+                    })
+                    .collect();
+                total_wgsl_code += &shader;
+                check_pass = Some(CheckPass { nulls, checks: checks.len() });
+            }
+        }
+        timings.plan = plan_start.elapsed();
+
+        if !on_gpu {
+            if let Some(vector) = numeric_columns.iter().find(|c| c.data_type.is_vector()) {
+                return Err(RqlError::Gpu(format!(
+                    "{} column '{}' can only be computed on the GPU, and no adapter supports all the columns of this UPDATE",
+                    vector.data_type.to_sql_type(),
+                    vector.name
+                )));
+            }
+        }
 
-        let reconstructed_rows = reconstruct_rows(new_vals.clone());
+        let (mut new_vals, violations) = if on_gpu {
+            timings.device = Some(ComputeDevice::Gpu);
+            ShaderExecutor.main(total_wgsl_code, column_map, table_def.clone(), check_pass.as_ref(), timings)?
+        } else {
+            timings.device = Some(ComputeDevice::Cpu);
+            (CpuExecutor.main(&assignments, column_map, &table_def, timings)?, Vec::new())
+        };
+        if let Some((check, count)) = checks.iter().zip(&violations).find(|(_, count)| **count > 0) {
+            return Err(RqlError::Constraint(format!(
+                "{} {} would violate CHECK ({}) of table '{}'",
+                count,
+                if *count == 1 { "row" } else { "rows" },
+                check,
+                self.table_name
+            )));
+        }
+        // A result is NULL wherever one of the columns it was computed from was NULL.
+        for (name, values) in new_vals.iter_mut() {
+            let Some(columns) = sources.get(name) else {
+                continue;
+            };
+            let nullable = table_def.columns.iter().find(|c| c.name == *name).is_none_or(|c| c.nullable);
+            for (index, value) in values.iter_mut().enumerate() {
+                if columns.iter().any(|column| null_rows[column][index]) {
+                    if !nullable {
+                        return Err(RqlError::Constraint(format!("Column '{}' cannot be NULL", name)));
+                    }
+                    *value = Value::Null;
+                }
+            }
+        }
+        let updated_rows = update_rows(all_rows, reconstruct_rows(new_vals)?);
 
-        let updated_data: Vec<Vec<Vec<u8>>> = reconstructed_rows
+        // Foreign keys over an assigned column, whether this table is their child or their parent.
+        let assigned: Vec<&str> = assignments.iter().map(|assignment| assignment.variable.as_str()).collect();
+        let foreign_keys: Vec<&ForeignKey> = table_def
+            .foreign_keys
             .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|(_, value)| serialize_from_value(value).unwrap())
-                    .collect()
+            .filter(|foreign_key| foreign_key.columns.iter().any(|c| assigned.contains(&c.as_str())))
+            .collect();
+        let children: Vec<TableDefinition> = referencing_tables(db, &self.table_name)?
+            .into_iter()
+            .filter(|child| {
+                child.foreign_keys.iter().any(|foreign_key| {
+                    foreign_key.parent == self.table_name && foreign_key.parent_columns.iter().any(|c| assigned.contains(&c.as_str()))
+                })
             })
             .collect();
+        let check_on_cpu = check_pass.is_none() && !checks.is_empty();
+        if check_on_cpu || !foreign_keys.is_empty() || !children.is_empty() {
+            let rows: Vec<Vec<Value>> = updated_rows
+                .iter()
+                .map(|row| table_def.columns.iter().map(|c| row.get(&c.name).cloned().unwrap_or(Value::Null)).collect())
+                .collect();
+            if check_on_cpu {
+                table_def.check_rows(&checks, &rows, now())?;
+            }
+            check_parents(db, &table_def, &foreign_keys, &rows, || Ok(rows.clone()))?;
+            check_children(db, &table_def, &rows, &children, &assigned)?;
+        }
 
-        write_vec_of_bytes_to_file(updated_data, self.table_name.as_str(), false);
-
-        let reconstructed_rows = reconstruct_rows(new_vals.clone());
-        print_table(reconstructed_rows);
+        let updated_data = updated_rows
+            .iter()
+            .map(|row| {
+                table_def
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        let value = row.get(&column.name).ok_or_else(|| {
+                            RqlError::Schema(format!("Row is missing column '{}'", column.name))
+                        })?;
+                        serialize_from_value(value)
+                    })
+                    .collect::<Result<Vec<Vec<u8>>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        let empty_map: HashMap<String, Vec<Value>> = HashMap::new();
+        write_vec_of_bytes_to_file(db, updated_data, self.table_name.as_str(), false)?;
 
-        Ok(empty_map)
+        Ok(updated_rows)
     }
 
     pub fn execute() {
         // TODO: Separate into execute and load
     }
+}
+
+/// The names an `UPDATE` expression reads, leaving out the functions it calls
+/// and the fields it selects after a `.`, as in `pos.x`.
+fn references<'a>(names: &Regex, expression: &'a str) -> Vec<&'a str> {
+    names
+        .captures_iter(expression)
+        .filter(|captures| captures.get(1).is_none() && captures.get(3).is_none())
+        .filter_map(|captures| captures.get(2).map(|name| name.as_str()))
+        .collect()
+}
+
+/// Translates an `UPDATE` expression to WGSL: columns are indexed by the row the
+/// invocation processes, and the vector constructors `VEC2(..)` to `VEC4(..)` and
+/// `MAT4(..)` become `vec2<f32>(..)` to `vec4<f32>(..)` and `mat4x4<f32>(..)`.
+fn to_wgsl(names: &Regex, expression: &str, columns: &HashMap<String, Vec<Value>>) -> String {
+    names
+        .replace_all(expression, |captures: &regex::Captures| {
+            let whole = &captures[0];
+            let name = &captures[2];
+            match (captures.get(1), captures.get(3)) {
+                (None, Some(_)) => match DataType::from_sql_type(name).filter(|data_type| data_type.is_vector()) {
+                    Some(DataType::Mat4) => "mat4x4<f32>(".to_string(),
+                    Some(data_type) => format!("vec{}<f32>(", data_type.components()),
+                    None => whole.to_string(),
+                },
+                (None, None) if columns.contains_key(name) => format!("{}[sys_index]", name),
+                _ => whole.to_string(),
+            }
+        })
+        .to_string()
 }
\ No newline at end of file
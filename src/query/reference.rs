@@ -0,0 +1,333 @@
+use crate::database::database::Database;
+use crate::io::reader::{read_rows_filtered, read_table_definition};
+use crate::io::writer::serialize_from_value;
+use crate::query::expression::Parser;
+use crate::query::temporal::now;
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{ForeignKey, ReferentialAction, TableDefinition, Value};
+use std::collections::{BTreeMap, HashSet};
+
+/// The serialized values of a key, compared byte by byte like primary keys.
+type Key = Vec<Vec<u8>>;
+
+/// The tables a `DELETE` changes, by name, with their definitions and the rows
+/// they keep, in schema order.
+pub(crate) type Changes = BTreeMap<String, (TableDefinition, Vec<Vec<Value>>)>;
+
+impl ReferentialAction {
+    pub fn to_sql(self) -> &'static str {
+        match self {
+            ReferentialAction::Restrict => "RESTRICT",
+            ReferentialAction::Cascade => "CASCADE",
+            ReferentialAction::SetNull => "SET NULL",
+        }
+    }
+}
+
+impl ForeignKey {
+    /// Parses what follows `REFERENCES`, as in `REFERENCES devices (id) ON DELETE CASCADE`,
+    /// for a key over the referencing `columns`.
+    pub(crate) fn parse(parser: &mut Parser, columns: Vec<String>) -> Result<ForeignKey> {
+        let parent = parser.identifier()?;
+        let parent_columns = column_list(parser)?;
+        if parent_columns.len() != columns.len() {
+            return Err(parser.error(format!(
+                "{} columns cannot reference {} columns of '{}'",
+                columns.len(),
+                parent_columns.len(),
+                parent
+            )));
+        }
+
+        let mut on_delete = ReferentialAction::Restrict;
+        if parser.eat_keyword("ON") {
+            parser.expect_keyword("DELETE")?;
+            on_delete = if parser.eat_keyword("CASCADE") {
+                ReferentialAction::Cascade
+            } else if parser.eat_keyword("RESTRICT") {
+                ReferentialAction::Restrict
+            } else if parser.eat_keyword("NO") {
+                parser.expect_keyword("ACTION")?;
+                ReferentialAction::Restrict
+            } else if parser.eat_keyword("SET") {
+                parser.expect_keyword("NULL")?;
+                ReferentialAction::SetNull
+            } else {
+                return Err(parser.error("Expected CASCADE, RESTRICT, SET NULL or NO ACTION after ON DELETE"));
+            };
+        }
+
+        Ok(ForeignKey { columns, parent, parent_columns, on_delete })
+    }
+
+    /// The table constraint, as in `FOREIGN KEY (device) REFERENCES devices (id)`.
+    pub fn to_sql(&self) -> String {
+        let mut sql = format!(
+            "FOREIGN KEY ({}) REFERENCES {} ({})",
+            self.columns.join(", "),
+            self.parent,
+            self.parent_columns.join(", ")
+        );
+        if self.on_delete != ReferentialAction::Restrict {
+            sql.push_str(&format!(" ON DELETE {}", self.on_delete.to_sql()));
+        }
+        sql
+    }
+}
+
+/// Parses `(a, b, ...)`.
+pub(crate) fn column_list(parser: &mut Parser) -> Result<Vec<String>> {
+    parser.expect_symbol("(")?;
+    let mut columns = vec![parser.identifier()?];
+    while parser.eat_symbol(",") {
+        columns.push(parser.identifier()?);
+    }
+    parser.expect_symbol(")")?;
+    Ok(columns)
+}
+
+/// The positions of the columns `names` in `table`.
+pub(crate) fn positions(table: &TableDefinition, names: &[String]) -> Result<Vec<usize>> {
+    names
+        .iter()
+        .map(|name| {
+            table
+                .columns
+                .iter()
+                .position(|column| column.name == *name)
+                .ok_or_else(|| RqlError::Schema(format!("Table '{}' has no column '{}'", table.name, name)))
+        })
+        .collect()
+}
+
+/// The key of `row` at `positions`, or `None` when one of its values is NULL:
+/// such a row references nothing.
+fn key_of(row: &[Value], positions: &[usize]) -> Result<Option<Key>> {
+    if positions.iter().any(|position| row[*position] == Value::Null) {
+        return Ok(None);
+    }
+    positions.iter().map(|position| serialize_from_value(&row[*position])).collect::<Result<_>>().map(Some)
+}
+
+fn keys_of(rows: &[Vec<Value>], positions: &[usize]) -> Result<HashSet<Key>> {
+    rows.iter().filter_map(|row| key_of(row, positions).transpose()).collect()
+}
+
+/// `(a, b) = (1, x)`, for error messages.
+fn describe(names: &[String], row: &[Value], positions: &[usize]) -> String {
+    let values: Vec<String> = positions.iter().map(|position| row[*position].to_string()).collect();
+    format!("({}) = ({})", names.join(", "), values.join(", "))
+}
+
+/// Checks that the parent of each of `foreign_keys` of `table` exists, and that
+/// the referenced columns are its primary key or a `UNIQUE` column of the same
+/// types as the referencing ones. A table may reference itself.
+pub(crate) fn check_foreign_keys(db: &Database, table: &TableDefinition, foreign_keys: &[ForeignKey]) -> Result<()> {
+    for foreign_key in foreign_keys {
+        let parent = if foreign_key.parent == table.name {
+            table.clone()
+        } else {
+            read_table_definition(db, &foreign_key.parent)?
+        };
+        let columns = positions(table, &foreign_key.columns)?;
+        let parent_columns = positions(&parent, &foreign_key.parent_columns)?;
+
+        let mut key = parent_columns.clone();
+        key.sort_unstable();
+        let unique = key == parent.primary_key() || (key.len() == 1 && parent.columns[key[0]].unique);
+        if !unique {
+            return Err(RqlError::Schema(format!(
+                "REFERENCES {} ({}) needs the primary key or a UNIQUE column of '{}'",
+                parent.name,
+                foreign_key.parent_columns.join(", "),
+                parent.name
+            )));
+        }
+        for (column, parent_column) in columns.iter().zip(&parent_columns) {
+            let (column, parent_column) = (&table.columns[*column], &parent.columns[*parent_column]);
+            if column.data_type != parent_column.data_type {
+                return Err(RqlError::Schema(format!(
+                    "Column '{}' is {} but references {}.{}, which is {}",
+                    column.name,
+                    column.data_type.to_sql_type(),
+                    parent.name,
+                    parent_column.name,
+                    parent_column.data_type.to_sql_type()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails unless every row of `rows` matches a row of the parent of each of
+/// `foreign_keys` whose columns it sets. `own_rows` gives the rows `table` has
+/// after the statement, which a key referencing `table` itself must match.
+pub(crate) fn check_parents<F>(
+    db: &Database,
+    table: &TableDefinition,
+    foreign_keys: &[&ForeignKey],
+    rows: &[Vec<Value>],
+    own_rows: F,
+) -> Result<()>
+where
+    F: Fn() -> Result<Vec<Vec<Value>>>,
+{
+    for foreign_key in foreign_keys {
+        let columns = positions(table, &foreign_key.columns)?;
+        if rows.iter().all(|row| columns.iter().any(|column| row[*column] == Value::Null)) {
+            continue;
+        }
+
+        let (parent, parent_rows) = if foreign_key.parent == table.name {
+            (table.clone(), own_rows()?)
+        } else {
+            let parent = read_table_definition(db, &foreign_key.parent)?;
+            let parent_rows = read_rows_filtered(db, &foreign_key.parent, &parent, |_| true)?;
+            (parent, parent_rows)
+        };
+        let existing = keys_of(&parent_rows, &positions(&parent, &foreign_key.parent_columns)?)?;
+        for row in rows {
+            if key_of(row, &columns)?.is_some_and(|key| !existing.contains(&key)) {
+                return Err(RqlError::Constraint(format!(
+                    "{} of table '{}' references no row of '{}'",
+                    describe(&foreign_key.columns, row, &columns),
+                    table.name,
+                    parent.name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The tables with a foreign key referencing `table_name`, which may include
+/// the table itself.
+pub(crate) fn referencing_tables(db: &Database, table_name: &str) -> Result<Vec<TableDefinition>> {
+    let mut tables = Vec::new();
+    for name in db.tables()? {
+        let table = read_table_definition(db, &name)?;
+        if table.foreign_keys.iter().any(|foreign_key| foreign_key.parent == table_name) {
+            tables.push(table);
+        }
+    }
+    Ok(tables)
+}
+
+/// Fails if changing the rows of `table` to `rows` leaves a row of one of the
+/// `children`, from `referencing_tables`, without its parent. Only keys over one
+/// of the `changed` columns are checked.
+pub(crate) fn check_children(
+    db: &Database,
+    table: &TableDefinition,
+    rows: &[Vec<Value>],
+    children: &[TableDefinition],
+    changed: &[&str],
+) -> Result<()> {
+    for child in children {
+        let foreign_keys = child.foreign_keys.iter().filter(|foreign_key| {
+            foreign_key.parent == table.name && foreign_key.parent_columns.iter().any(|c| changed.contains(&c.as_str()))
+        });
+        for foreign_key in foreign_keys {
+            let existing = keys_of(rows, &positions(table, &foreign_key.parent_columns)?)?;
+            let child_rows = if child.name == table.name {
+                rows.to_vec()
+            } else {
+                read_rows_filtered(db, &child.name, child, |_| true)?
+            };
+            let columns = positions(child, &foreign_key.columns)?;
+            for row in &child_rows {
+                if key_of(row, &columns)?.is_some_and(|key| !existing.contains(&key)) {
+                    return Err(RqlError::Constraint(format!(
+                        "Updating table '{}' would leave {} of table '{}' without a parent row",
+                        table.name,
+                        describe(&foreign_key.columns, row, &columns),
+                        child.name
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies the `ON DELETE` action of every foreign key referencing the rows
+/// `deleted` from `table_name`, whose remaining rows are in `changes`: the rows
+/// referencing them are deleted in turn, cleared, or make the `DELETE` fail.
+/// Every table this changes is added to `changes`.
+pub(crate) fn delete_cascade(db: &Database, changes: &mut Changes, table_name: &str, deleted: Vec<Vec<Value>>) -> Result<()> {
+    let mut pending = vec![(table_name.to_string(), deleted)];
+    while let Some((parent_name, deleted)) = pending.pop() {
+        if deleted.is_empty() {
+            continue;
+        }
+        let parent = changes[&parent_name].0.clone();
+        for child in referencing_tables(db, &parent_name)? {
+            for foreign_key in child.foreign_keys.iter().filter(|foreign_key| foreign_key.parent == parent_name) {
+                let parent_columns = positions(&parent, &foreign_key.parent_columns)?;
+                // A key another parent row still has is not gone.
+                let remaining = keys_of(&changes[&parent_name].1, &parent_columns)?;
+                let mut gone = keys_of(&deleted, &parent_columns)?;
+                gone.retain(|key| !remaining.contains(key));
+                if gone.is_empty() {
+                    continue;
+                }
+
+                if !changes.contains_key(&child.name) {
+                    let rows = read_rows_filtered(db, &child.name, &child, |_| true)?;
+                    changes.insert(child.name.clone(), (child.clone(), rows));
+                }
+                let (child_definition, rows) = changes.get_mut(&child.name).expect("inserted above");
+                let columns = positions(child_definition, &foreign_key.columns)?;
+                let mut referencing = Vec::new();
+                for (index, row) in rows.iter().enumerate() {
+                    if key_of(row, &columns)?.is_some_and(|key| gone.contains(&key)) {
+                        referencing.push(index);
+                    }
+                }
+                let Some(first) = referencing.first() else {
+                    continue;
+                };
+
+                match foreign_key.on_delete {
+                    ReferentialAction::Restrict => {
+                        return Err(RqlError::Constraint(format!(
+                            "Cannot delete from table '{}': {} of table '{}' still references it",
+                            parent_name,
+                            describe(&foreign_key.columns, &rows[*first], &columns),
+                            child.name
+                        )))
+                    }
+                    ReferentialAction::Cascade => {
+                        let removed = referencing.iter().map(|index| rows[*index].clone()).collect();
+                        let referencing: HashSet<usize> = referencing.into_iter().collect();
+                        let mut index = 0;
+                        rows.retain(|_| {
+                            index += 1;
+                            !referencing.contains(&(index - 1))
+                        });
+                        pending.push((child.name.clone(), removed));
+                    }
+                    ReferentialAction::SetNull => {
+                        if let Some(column) = columns.iter().map(|c| &child_definition.columns[*c]).find(|c| !c.nullable) {
+                            return Err(RqlError::Constraint(format!(
+                                "ON DELETE SET NULL cannot clear column '{}' of table '{}', which is NOT NULL",
+                                column.name, child.name
+                            )));
+                        }
+                        let mut cleared = Vec::new();
+                        for index in &referencing {
+                            for column in &columns {
+                                rows[*index][*column] = Value::Null;
+                            }
+                            cleared.push(rows[*index].clone());
+                        }
+                        let checks: Vec<_> = child_definition.checks.iter().collect();
+                        child_definition.check_rows(&checks, &cleared, now())?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
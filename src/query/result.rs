@@ -0,0 +1,455 @@
+use crate::types::types::{Column, ColumnBatch, ColumnData, ColumnDefinition, DataType, ResultSet, Row, TableDefinition, Value};
+use crate::types::error::{Result, RqlError};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+impl Column {
+    pub(crate) fn from_definition(column: &ColumnDefinition) -> Column {
+        Column {
+            name: column.name.clone(),
+            data_type: column.data_type,
+            nullable: column.nullable,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+}
+
+impl ResultSet {
+    /// Builds a result set from decoded rows, ordering columns as in the schema.
+    pub(crate) fn from_rows(table_definition: &TableDefinition, rows: Vec<HashMap<String, Value>>) -> ResultSet {
+        let columns: Arc<[Column]> = table_definition
+            .columns
+            .iter()
+            .filter(|c| rows.first().is_none_or(|row| row.contains_key(&c.name)))
+            .map(Column::from_definition)
+            .collect();
+
+        let rows = rows
+            .into_iter()
+            .map(|mut row| Row {
+                columns: columns.clone(),
+                values: columns
+                    .iter()
+                    .filter_map(|column| row.remove(&column.name))
+                    .collect(),
+            })
+            .collect();
+
+        ResultSet { columns, rows }
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Row> {
+        self.rows.iter()
+    }
+
+    /// The same rows, stored column by column.
+    pub fn to_batch(&self) -> Result<ColumnBatch> {
+        let mut batch = ColumnBatch::new(self.columns.clone());
+        for row in &self.rows {
+            batch.push_row(row.values.iter().cloned())?;
+        }
+        Ok(batch)
+    }
+}
+
+impl ColumnBatch {
+    /// An empty batch with the given columns.
+    pub fn new(columns: Arc<[Column]>) -> ColumnBatch {
+        let data = columns.iter().map(|c| ColumnData::new(c.data_type)).collect();
+        ColumnBatch { columns, data }
+    }
+
+    pub(crate) fn for_table(table_definition: &TableDefinition) -> ColumnBatch {
+        ColumnBatch::new(table_definition.columns.iter().map(Column::from_definition).collect())
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn data(&self) -> &[ColumnData] {
+        &self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.first().map_or(0, ColumnData::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends one row given in column order. NULL is only accepted by nullable columns.
+    pub fn push_row<I: IntoIterator<Item = Value>>(&mut self, values: I) -> Result<()> {
+        let mut count = 0;
+        for (value, (column, data)) in values.into_iter().zip(self.columns.iter().zip(self.data.iter_mut())) {
+            if value == Value::Null && !column.nullable {
+                return Err(RqlError::Constraint(format!("Column '{}' cannot be NULL", column.name)));
+            }
+            data.push(value).map_err(|value| {
+                RqlError::Type(format!(
+                    "{:?} is not a valid {} value for column '{}'",
+                    value,
+                    column.data_type.to_sql_type(),
+                    column.name
+                ))
+            })?;
+            count += 1;
+        }
+
+        if count != self.columns.len() {
+            return Err(RqlError::Schema(format!(
+                "Expected {} values per row, found {}",
+                self.columns.len(),
+                count
+            )));
+        }
+        Ok(())
+    }
+
+    /// The values of one row, in column order.
+    pub fn row(&self, index: usize) -> Vec<Value> {
+        self.data.iter().map(|data| data.value(index)).collect()
+    }
+}
+
+impl ColumnData {
+    pub(crate) fn new(data_type: DataType) -> ColumnData {
+        match data_type {
+            DataType::Integer => ColumnData::Integer(Vec::new()),
+            DataType::Float => ColumnData::Float(Vec::new()),
+            DataType::Text => ColumnData::Text(Vec::new()),
+            DataType::Boolean => ColumnData::Boolean(Vec::new()),
+            DataType::BigInt => ColumnData::BigInt(Vec::new()),
+            DataType::Double => ColumnData::Double(Vec::new()),
+            DataType::Date => ColumnData::Date(Vec::new()),
+            DataType::Time => ColumnData::Time(Vec::new()),
+            DataType::Timestamp => ColumnData::Timestamp(Vec::new()),
+            DataType::Vec2 => ColumnData::Vec2(Vec::new()),
+            DataType::Vec3 => ColumnData::Vec3(Vec::new()),
+            DataType::Vec4 => ColumnData::Vec4(Vec::new()),
+            DataType::Mat4 => ColumnData::Mat4(Vec::new()),
+            DataType::Vector(dimensions) => ColumnData::Vector(dimensions, Vec::new()),
+            DataType::Blob => ColumnData::Blob(Vec::new()),
+        }
+    }
+
+    pub fn data_type(&self) -> DataType {
+        match self {
+            ColumnData::Integer(_) => DataType::Integer,
+            ColumnData::Float(_) => DataType::Float,
+            ColumnData::Text(_) => DataType::Text,
+            ColumnData::Boolean(_) => DataType::Boolean,
+            ColumnData::BigInt(_) => DataType::BigInt,
+            ColumnData::Double(_) => DataType::Double,
+            ColumnData::Date(_) => DataType::Date,
+            ColumnData::Time(_) => DataType::Time,
+            ColumnData::Timestamp(_) => DataType::Timestamp,
+            ColumnData::Vec2(_) => DataType::Vec2,
+            ColumnData::Vec3(_) => DataType::Vec3,
+            ColumnData::Vec4(_) => DataType::Vec4,
+            ColumnData::Mat4(_) => DataType::Mat4,
+            ColumnData::Vector(dimensions, _) => DataType::Vector(*dimensions),
+            ColumnData::Blob(_) => DataType::Blob,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnData::Integer(values) => values.len(),
+            ColumnData::Float(values) => values.len(),
+            ColumnData::Text(values) => values.len(),
+            ColumnData::Boolean(values) => values.len(),
+            ColumnData::BigInt(values) => values.len(),
+            ColumnData::Double(values) => values.len(),
+            ColumnData::Date(values) => values.len(),
+            ColumnData::Time(values) => values.len(),
+            ColumnData::Timestamp(values) => values.len(),
+            ColumnData::Vec2(values) => values.len(),
+            ColumnData::Vec3(values) => values.len(),
+            ColumnData::Vec4(values) => values.len(),
+            ColumnData::Mat4(values) => values.len(),
+            ColumnData::Vector(_, values) => values.len(),
+            ColumnData::Blob(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends a value, widening numbers, and hands it back if it does not fit
+    /// the column's type.
+    pub(crate) fn push(&mut self, value: Value) -> std::result::Result<(), Value> {
+        let Some(widened) = value.widen_to(self.data_type()) else {
+            return Err(value);
+        };
+        match (self, widened) {
+            (ColumnData::Integer(values), Value::Integer(v)) => values.push(Some(v)),
+            (ColumnData::Integer(values), Value::Null) => values.push(None),
+            (ColumnData::Float(values), Value::Float(v)) => values.push(Some(v)),
+            (ColumnData::Float(values), Value::Null) => values.push(None),
+            (ColumnData::Text(values), Value::Text(v)) => values.push(Some(v)),
+            (ColumnData::Text(values), Value::Null) => values.push(None),
+            (ColumnData::Boolean(values), Value::Boolean(v)) => values.push(Some(v)),
+            (ColumnData::Boolean(values), Value::Null) => values.push(None),
+            (ColumnData::BigInt(values), Value::BigInt(v)) => values.push(Some(v)),
+            (ColumnData::BigInt(values), Value::Null) => values.push(None),
+            (ColumnData::Double(values), Value::Double(v)) => values.push(Some(v)),
+            (ColumnData::Double(values), Value::Null) => values.push(None),
+            (ColumnData::Date(values), Value::Date(v)) => values.push(Some(v)),
+            (ColumnData::Date(values), Value::Null) => values.push(None),
+            (ColumnData::Time(values), Value::Time(v)) => values.push(Some(v)),
+            (ColumnData::Time(values), Value::Null) => values.push(None),
+            (ColumnData::Timestamp(values), Value::Timestamp(v)) => values.push(Some(v)),
+            (ColumnData::Timestamp(values), Value::Null) => values.push(None),
+            (ColumnData::Vec2(values), Value::Vec2(v)) => values.push(Some(v)),
+            (ColumnData::Vec2(values), Value::Null) => values.push(None),
+            (ColumnData::Vec3(values), Value::Vec3(v)) => values.push(Some(v)),
+            (ColumnData::Vec3(values), Value::Null) => values.push(None),
+            (ColumnData::Vec4(values), Value::Vec4(v)) => values.push(Some(v)),
+            (ColumnData::Vec4(values), Value::Null) => values.push(None),
+            (ColumnData::Mat4(values), Value::Mat4(v)) => values.push(Some(v)),
+            (ColumnData::Mat4(values), Value::Null) => values.push(None),
+            (ColumnData::Vector(_, values), Value::Vector(v)) => values.push(Some(v)),
+            (ColumnData::Vector(_, values), Value::Null) => values.push(None),
+            (ColumnData::Blob(values), Value::Blob(v)) => values.push(Some(v)),
+            (ColumnData::Blob(values), Value::Null) => values.push(None),
+            _ => return Err(value),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn value(&self, index: usize) -> Value {
+        let value = match self {
+            ColumnData::Integer(values) => values[index].map(Value::Integer),
+            ColumnData::Float(values) => values[index].map(Value::Float),
+            ColumnData::Text(values) => values[index].clone().map(Value::Text),
+            ColumnData::Boolean(values) => values[index].map(Value::Boolean),
+            ColumnData::BigInt(values) => values[index].map(Value::BigInt),
+            ColumnData::Double(values) => values[index].map(Value::Double),
+            ColumnData::Date(values) => values[index].map(Value::Date),
+            ColumnData::Time(values) => values[index].map(Value::Time),
+            ColumnData::Timestamp(values) => values[index].map(Value::Timestamp),
+            ColumnData::Vec2(values) => values[index].map(Value::Vec2),
+            ColumnData::Vec3(values) => values[index].map(Value::Vec3),
+            ColumnData::Vec4(values) => values[index].map(Value::Vec4),
+            ColumnData::Mat4(values) => values[index].map(Value::Mat4),
+            ColumnData::Vector(_, values) => values[index].clone().map(Value::Vector),
+            ColumnData::Blob(values) => values[index].clone().map(Value::Blob),
+        };
+        value.unwrap_or(Value::Null)
+    }
+}
+
+impl IntoIterator for ResultSet {
+    type Item = Row;
+    type IntoIter = std::vec::IntoIter<Row>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ResultSet {
+    type Item = &'a Row;
+    type IntoIter = std::slice::Iter<'a, Row>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.iter()
+    }
+}
+
+impl Row {
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The raw value of a column, by position or by name.
+    pub fn value<I: RowIndex>(&self, index: I) -> Option<&Value> {
+        self.values.get(index.position(&self.columns)?)
+    }
+
+    /// The value of a column, by position or by name, converted to `T`.
+    pub fn get<T: FromValue, I: RowIndex>(&self, index: I) -> Result<T> {
+        let description = index.describe();
+        let value = self
+            .value(index)
+            .ok_or_else(|| RqlError::Schema(format!("No column {} in row", description)))?;
+
+        T::from_value(value).ok_or_else(|| {
+            RqlError::Type(format!(
+                "Column {} holds {:?}, which cannot be read as {}",
+                description,
+                value,
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+}
+
+/// Addresses a column of a `Row` by position (`usize`) or by name (`&str`).
+pub trait RowIndex {
+    fn position(&self, columns: &[Column]) -> Option<usize>;
+    fn describe(&self) -> String;
+}
+
+impl RowIndex for usize {
+    fn position(&self, columns: &[Column]) -> Option<usize> {
+        (*self < columns.len()).then_some(*self)
+    }
+
+    fn describe(&self) -> String {
+        format!("#{}", self)
+    }
+}
+
+impl RowIndex for &str {
+    fn position(&self, columns: &[Column]) -> Option<usize> {
+        columns.iter().position(|c| c.name == *self)
+    }
+
+    fn describe(&self) -> String {
+        format!("'{}'", self)
+    }
+}
+
+/// Conversion from a stored `Value` into a Rust type for `Row::get`.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+/// NULL reads as `None`, anything else as `T`.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::BigInt(v) => Some(*v),
+            Value::Integer(v) => Some(*v as i64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Double(v) => Some(*v),
+            Value::Float(v) => Some(*v as f64),
+            Value::Integer(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Text(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Boolean(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+/// A vector or matrix reads as an array of its components, `[f32; 3]` for a VEC3.
+impl<const N: usize> FromValue for [f32; N] {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.components()?.try_into().ok()
+    }
+}
+
+/// A vector of any size, such as a `VECTOR(n)` embedding, reads as its components.
+impl FromValue for Vec<f32> {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.components().map(<[f32]>::to_vec)
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Blob(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
@@ -1,70 +1,317 @@
-use crate::io::util::print_table;
-use crate::io::writer::write_table_definition;
-use crate::types::types::{InsertDefinition, SelectDefinition, TableDefinition, UpdateDefinition};
+use crate::database::connection::Connection;
+use crate::io::format::format_result;
+use crate::io::reader::read_table_definition;
+use crate::query::script::{Statement, StatementSplitter};
+use crate::types::error::{Result, RqlError};
+use crate::rqle::shader_executor::ShaderExecutor;
+use crate::types::types::{BlobFormat, ColumnDefinition, ComputeDevice, CopyFormat, DataType, OutputFormat, ResultSet, StatementResult, TableDefinition, Timings, Value};
+use std::collections::HashMap;
 use std::io;
-use std::io::Write;
+use std::io::{BufRead, Write};
 
-pub struct QueryRunner;
+pub struct QueryRunner {
+    connection: Connection,
+    quiet: bool,
+    format: OutputFormat,
+    precision: Option<usize>,
+    blobs: BlobFormat,
+    timer: bool,
+}
+
+const META_HELP: &str = ".blob [hex|base64]       Print BLOB values in hexadecimal or base64
+.describe <table>        Show the columns of a table
+.export <table> <file>   Write a table to a CSV file with a header
+.gpu                     Show the GPU adapter, backend and limits
+.help                    Show this message
+.import <file> <table>   Load a CSV file with a header, creating the table if needed;
+                         an empty field is NULL and \"\" an empty string
+.mode [format]           Print results as table, csv, json, ndjson or markdown
+.precision [digits|off]  Print floats with a fixed number of decimal places
+.schema [table]          Show the CREATE TABLE and CREATE INDEX statements of one or every table
+.tables                  List the tables, including those of attached databases
+.timer [on|off]          Report how long each stage of a statement takes";
 
 impl QueryRunner {
-    pub fn run_command(command: &str) -> io::Result<()> {
-        let command = command.trim();
-        if command.starts_with("CREATE TABLE") {
-            if let Some(table_def) = TableDefinition::from_sql(command) {
-                write_table_definition(&table_def)?;
-                println!("Table '{}' created successfully.", table_def.name);
-            } else {
-                println!("Error: Invalid CREATE TABLE syntax.");
-            }
-        } else if command.starts_with("INSERT INTO") {
-            if let Some(mut insert_def) = InsertDefinition::from_sql(command) {
-                insert_def.validate_and_insert()?;
-                println!("Row inserted successfully into table '{}'.", insert_def.name);
-            } else {
-                println!("Error: Invalid INSERT INTO syntax.");
-            }
-        } else if command.starts_with("SELECT") {
-            if let Some(select_def) = SelectDefinition::from_sql(command) {
-                let rows = select_def.execute()?;
-                print_table(rows);
-            }
-        } else if command.starts_with("UPDATE") {
-            if let Some(update_def) = UpdateDefinition::from_sql(command) {
-                match update_def.load_data() {
-                    Ok(column_map) => {
+    pub fn new(connection: Connection) -> QueryRunner {
+        QueryRunner {
+            connection,
+            quiet: false,
+            format: OutputFormat::Table,
+            precision: None,
+            blobs: BlobFormat::Hex,
+            timer: false,
+        }
+    }
 
-                    }
-                    Err(err) => println!("Error loading data: {}", err),
+    /// With `quiet` set, only query results and errors are printed.
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    /// Number of decimal places floats are printed with; `None` prints them in full.
+    pub fn set_precision(&mut self, precision: Option<usize>) {
+        self.precision = precision;
+    }
+
+    pub fn set_blob_format(&mut self, blobs: BlobFormat) {
+        self.blobs = blobs;
+    }
+
+    pub fn run_command(&mut self, command: &str) -> Result<()> {
+        let result = self.connection.run(command);
+        if self.timer {
+            print_timings(&self.connection.last_timings());
+        }
+        match result? {
+            StatementResult::Rows(rows) => print!("{}", format_result(&rows, self.format, self.precision, self.blobs)),
+            StatementResult::Done { message, .. } => {
+                if !self.quiet {
+                    println!("{}", message)
                 }
-            } else {
-                println!("Error: Invalid UPDATE syntax.");
             }
-        } else {
-            println!("Error: Unsupported command.");
         }
         Ok(())
     }
 
-    pub fn repl() -> io::Result<()> {
+    /// Runs every statement read from `input` without prompting, reporting
+    /// failures on stderr. Returns the number of statements that failed.
+    pub fn run_script<R: BufRead>(&mut self, input: R) -> Result<usize> {
+        let mut splitter = StatementSplitter::new();
+        let mut failures = 0;
+
+        for line in input.lines() {
+            for statement in splitter.push_line(&line?) {
+                failures += self.run_statement(&statement) as usize;
+            }
+        }
+        if let Some(statement) = splitter.take() {
+            failures += self.run_statement(&statement) as usize;
+        }
+
+        Ok(failures)
+    }
+
+    pub fn repl(&mut self) -> Result<()> {
         println!("Welcome to the RQL. Type your RQL commands below. Type 'EXIT' to quit.");
 
+        let recovered = self.connection.database().recovered_statements();
+        if recovered > 0 {
+            println!("Recovered {} interrupted statement(s) from the write-ahead log.", recovered);
+        }
+
+        let mut splitter = StatementSplitter::new();
         loop {
-            print!("rql> ");
+            print!("{}", if splitter.is_pending() { "...> " } else { "rql> " });
             io::stdout().flush()?;
             let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
+            if io::stdin().read_line(&mut input)? == 0 {
+                break;
+            }
 
-            let input = input.trim();
-            if input.eq_ignore_ascii_case("EXIT") {
+            let line = input.trim();
+            if !splitter.is_pending() && line.trim_end_matches(';').eq_ignore_ascii_case("EXIT") {
+                self.rollback_open_transaction()?;
                 println!("Exiting REPL.");
                 break;
             }
 
-            if let Err(e) = QueryRunner::run_command(input) {
-                eprintln!("Error: {}", e);
+            // An empty line submits a statement that was left without a `;`.
+            let statements = if line.is_empty() {
+                splitter.take().into_iter().collect()
+            } else {
+                splitter.push_line(line)
+            };
+            for statement in statements {
+                if let Err(e) = self.execute(&statement) {
+                    eprintln!("Error: {}", e);
+                }
             }
         }
 
+        self.rollback_open_transaction()
+    }
+
+    /// Runs a line starting with `.`, which configures the runner instead of the database.
+    pub fn run_meta_command(&mut self, line: &str) -> Result<()> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let command = parts.first().copied().unwrap_or("");
+        if let [".import" | ".export", first, second] = parts[..] {
+            let (table, direction, path) = match command {
+                ".import" => (second, "FROM", first),
+                _ => (first, "TO", second),
+            };
+            let options = match CopyFormat::from_path(path) {
+                CopyFormat::Csv => " WITH (header)",
+                _ => "",
+            };
+            let sql = format!("COPY {} {} '{}'{};", table, direction, path, options);
+            return self.run_command(&sql);
+        }
+        if parts.len() > 2 {
+            return Err(RqlError::parse(format!("Too many arguments for {}", command)));
+        }
+
+        match (command, parts.get(1).copied()) {
+            (".mode", None) => println!("{}", self.format.name()),
+            (".mode", Some(name)) => {
+                self.format = OutputFormat::from_name(name).ok_or_else(|| {
+                    RqlError::parse(format!("Unknown mode '{}', expected csv, json, ndjson, markdown or table", name))
+                })?
+            }
+            (".precision", None) => match self.precision {
+                Some(precision) => println!("{}", precision),
+                None => println!("off"),
+            },
+            (".precision", Some("off")) => self.precision = None,
+            (".precision", Some(digits)) => {
+                let digits = digits
+                    .parse()
+                    .map_err(|_| RqlError::parse(format!("Expected a number of digits or 'off', got '{}'", digits)))?;
+                self.precision = Some(digits)
+            }
+            (".blob", None) => println!("{}", self.blobs.name()),
+            (".blob", Some(name)) => {
+                self.blobs = BlobFormat::from_name(name)
+                    .ok_or_else(|| RqlError::parse(format!("Unknown blob format '{}', expected hex or base64", name)))?
+            }
+            (".timer", None) => println!("{}", if self.timer { "on" } else { "off" }),
+            (".timer", Some("on")) => self.timer = true,
+            (".timer", Some("off")) => self.timer = false,
+            (".tables", None) => {
+                for table in self.connection.database().tables()? {
+                    println!("{}", table);
+                }
+            }
+            (".schema", table) => {
+                let tables = match table {
+                    Some(table) => vec![table.to_string()],
+                    None => self.connection.database().tables()?,
+                };
+                for table in tables {
+                    let table_definition = read_table_definition(self.connection.database(), &table)?;
+                    println!("{}", table_definition.to_sql());
+                    for index in table_definition.indexes.iter().filter(|index| !index.implicit) {
+                        println!("{}", index.to_sql(&table));
+                    }
+                }
+            }
+            (".describe", Some(table)) => {
+                let table_definition = read_table_definition(self.connection.database(), table)?;
+                print!("{}", format_result(&describe(&table_definition), self.format, None, self.blobs));
+            }
+            (".gpu", None) => {
+                let info = ShaderExecutor.adapter_info()?;
+                let width = info.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+                for (key, value) in info {
+                    println!("{:<width$}  {}", key, value, width = width);
+                }
+            }
+            (".help", None) => println!("{}", META_HELP),
+            _ => {
+                return Err(RqlError::parse(format!(
+                    "Unknown command or arguments '{}', enter .help for a list of commands",
+                    line
+                )))
+            }
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Runs a statement or meta-command read by the splitter.
+    fn execute(&mut self, statement: &Statement) -> Result<()> {
+        if statement.is_meta_command() {
+            self.run_meta_command(&statement.sql)
+        } else {
+            self.run_command(&statement.sql)
+        }
+    }
+
+    fn run_statement(&mut self, statement: &Statement) -> bool {
+        match self.execute(statement) {
+            Ok(()) => false,
+            Err(e) => {
+                eprintln!("Error on line {}: {}", statement.line, e);
+                true
+            }
+        }
+    }
+
+    /// Rolls back a transaction that was begun but never committed.
+    pub fn rollback_open_transaction(&mut self) -> Result<()> {
+        if self.connection.in_transaction() {
+            self.connection.run("ROLLBACK")?;
+            if !self.quiet {
+                println!("Uncommitted transaction rolled back.");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn print_timings(timings: &Timings) {
+    let ms = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+    let device = match timings.device {
+        Some(ComputeDevice::Gpu) => " (GPU)",
+        Some(ComputeDevice::Cpu) => " (CPU)",
+        None => "",
+    };
+    println!(
+        "Run Time: parse {:.3} ms, plan {:.3} ms, upload {:.3} ms, compute {:.3} ms{}, readback {:.3} ms, total {:.3} ms",
+        ms(timings.parse),
+        ms(timings.plan),
+        ms(timings.upload),
+        ms(timings.compute),
+        device,
+        ms(timings.readback),
+        ms(timings.total)
+    );
+}
+
+/// One row per column of `table_definition`, for `.describe`.
+fn describe(table_definition: &TableDefinition) -> ResultSet {
+    let column = |name: &str, data_type| ColumnDefinition {
+        name: name.to_string(),
+        data_type,
+        nullable: false,
+        unique: false,
+        primary_key: false,
+        auto_increment: false,
+        default: None,
+    };
+    let definition = TableDefinition {
+        name: table_definition.name.clone(),
+        columns: vec![
+            column("column", DataType::Text),
+            column("type", DataType::Text),
+            column("nullable", DataType::Boolean),
+            column("unique", DataType::Boolean),
+            column("primary_key", DataType::Boolean),
+            ColumnDefinition { nullable: true, ..column("default", DataType::Text) },
+        ],
+        ..TableDefinition::default()
+    };
+
+    let rows = table_definition
+        .columns
+        .iter()
+        .map(|c| {
+            HashMap::from([
+                ("column".to_string(), Value::Text(c.name.clone())),
+                ("type".to_string(), Value::Text(c.data_type.to_sql_type())),
+                ("nullable".to_string(), Value::Boolean(c.nullable)),
+                ("unique".to_string(), Value::Boolean(c.unique)),
+                ("primary_key".to_string(), Value::Boolean(c.primary_key)),
+                ("default".to_string(), match (&c.default, c.auto_increment) {
+                    (Some(default), _) => Value::Text(default.to_string()),
+                    (None, true) => Value::Text("AUTOINCREMENT".to_string()),
+                    (None, false) => Value::Null,
+                }),
+            ])
+        })
+        .collect();
+    ResultSet::from_rows(&definition, rows)
+}
@@ -0,0 +1,119 @@
+/// Splits SQL text into statements as it arrives line by line. A statement ends
+/// at a `;` outside a quoted string and keeps its `;`; `--` starts a comment
+/// that runs to the end of the line. Inside an `E'...'` string a backslash
+/// escapes the next character, so `\'` does not end it. A `.` where a statement
+/// would start begins a meta-command, which runs to the end of the line.
+#[derive(Debug)]
+pub struct StatementSplitter {
+    pending: String,
+    start_line: usize,
+    line: usize,
+    in_quote: bool,
+    escapes: bool,
+}
+
+/// A complete statement together with the 1-based line it starts on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub line: usize,
+    pub sql: String,
+}
+
+impl Statement {
+    /// Whether this is a meta-command like `.tables` rather than SQL.
+    pub fn is_meta_command(&self) -> bool {
+        self.sql.starts_with('.')
+    }
+}
+
+impl Default for StatementSplitter {
+    fn default() -> Self {
+        StatementSplitter::new()
+    }
+}
+
+impl StatementSplitter {
+    pub fn new() -> StatementSplitter {
+        StatementSplitter {
+            pending: String::new(),
+            start_line: 1,
+            line: 0,
+            in_quote: false,
+            escapes: false,
+        }
+    }
+
+    /// Feeds one line of input and returns the statements it completed.
+    pub fn push_line(&mut self, line: &str) -> Vec<Statement> {
+        self.line += 1;
+        let mut statements = Vec::new();
+        let mut chars = line.trim_end_matches(['\n', '\r']).chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if self.pending.trim().is_empty() {
+                self.start_line = self.line;
+                if c == '.' {
+                    self.pending.clear();
+                    let sql = format!(".{}", chars.collect::<String>());
+                    statements.push(Statement { line: self.line, sql: sql.trim_end().to_string() });
+                    break;
+                }
+            }
+            match c {
+                '\'' => {
+                    if !self.in_quote {
+                        self.escapes = self.pending.ends_with(['E', 'e']);
+                    }
+                    self.in_quote = !self.in_quote;
+                    self.pending.push(c);
+                }
+                '\\' if self.in_quote && self.escapes => {
+                    self.pending.push(c);
+                    self.pending.extend(chars.next());
+                }
+                '-' if !self.in_quote && chars.peek() == Some(&'-') => break,
+                ';' if !self.in_quote => {
+                    self.pending.push(';');
+                    if let Some(statement) = self.take() {
+                        statements.push(statement);
+                    }
+                }
+                _ => self.pending.push(c),
+            }
+        }
+
+        if !self.pending.trim().is_empty() {
+            self.pending.push('\n');
+        }
+        statements
+    }
+
+    /// Whether part of a statement has been read but not yet terminated.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.trim().is_empty()
+    }
+
+    /// Returns the unterminated statement read so far, if any, and starts over.
+    pub fn take(&mut self) -> Option<Statement> {
+        let sql = std::mem::take(&mut self.pending);
+        self.in_quote = false;
+        self.escapes = false;
+        let sql = sql.trim();
+        // A lone `;` is an empty statement, not something to run.
+        if sql.trim_end_matches(';').trim().is_empty() {
+            return None;
+        }
+        Some(Statement {
+            line: self.start_line,
+            sql: sql.to_string(),
+        })
+    }
+}
+
+/// Splits a whole script into statements, including a final one without `;`.
+pub fn split_statements(sql: &str) -> Vec<Statement> {
+    let mut splitter = StatementSplitter::new();
+    let mut statements: Vec<Statement> = sql.lines().flat_map(|line| splitter.push_line(line)).collect();
+    statements.extend(splitter.take());
+    statements
+}
@@ -0,0 +1,455 @@
+use crate::database::database::Database;
+use crate::io::index::{encode_value, prefix_end};
+use crate::io::reader::{read_rows_at, read_rows_filtered, read_table_definition};
+use crate::query::expression::{compare, Accumulator, Binder, Bound, Parser, Scope};
+use crate::query::temporal::now;
+use crate::rqle::similarity::{nearest, Metric};
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{
+    BinaryOperator, ChunkHeader, Column, ColumnStats, ComputeDevice, DataType, Expression, IndexDefinition, IndexKind, Literal, OrderBy, ResultSet,
+    Row, SelectDefinition, SelectItem, TableDefinition, Timings, Value,
+};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The keys of an index from `low` up to, but not including, `high`: a range that
+/// holds every row the filter of a `SELECT` can match.
+pub(crate) struct IndexScan<'a> {
+    pub(crate) index: &'a IndexDefinition,
+    low: Vec<u8>,
+    high: Option<Vec<u8>>,
+}
+
+/// What an `ORDER BY` entry sorts on: a column of the result, named or numbered,
+/// or an expression over the row or group.
+enum SortKey {
+    Output(usize),
+    Expression(Bound),
+}
+
+impl SelectDefinition {
+    pub fn from_sql(source: &str) -> Result<Self> {
+        let sql = source.trim().trim_end_matches(';');
+        let mut parser = Parser::new(source, sql)?;
+        parser.expect_keyword("SELECT")?;
+
+        let mut items = Vec::new();
+        loop {
+            if parser.eat_symbol("*") {
+                items.push(SelectItem::Wildcard);
+            } else {
+                let expression = parser.expression()?;
+                let name = if parser.eat_keyword("AS") || parser.is_identifier() {
+                    parser.identifier()?
+                } else if let Expression::Column(name) = &expression {
+                    name.clone()
+                } else {
+                    expression.to_string()
+                };
+                items.push(SelectItem::Expression { expression, name });
+            }
+            if !parser.eat_symbol(",") {
+                break;
+            }
+        }
+
+        parser.expect_keyword("FROM")?;
+        let table_name = parser.identifier()?;
+
+        let filter = if parser.eat_keyword("WHERE") { Some(parser.expression()?) } else { None };
+
+        let mut group_by = Vec::new();
+        if parser.eat_keyword("GROUP") {
+            parser.expect_keyword("BY")?;
+            loop {
+                group_by.push(parser.expression()?);
+                if !parser.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+
+        let mut order_by = Vec::new();
+        if parser.eat_keyword("ORDER") {
+            parser.expect_keyword("BY")?;
+            loop {
+                let expression = parser.expression()?;
+                let descending = parser.eat_keyword("DESC");
+                if !descending {
+                    parser.eat_keyword("ASC");
+                }
+                order_by.push(OrderBy { expression, descending });
+                if !parser.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+
+        let limit = if parser.eat_keyword("LIMIT") { Some(parser.unsigned_integer()?) } else { None };
+        let offset = if parser.eat_keyword("OFFSET") { parser.unsigned_integer()? } else { 0 };
+        parser.expect_end()?;
+
+        Ok(SelectDefinition {
+            table_name,
+            items,
+            filter,
+            group_by,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+
+    /// `SELECT * FROM table_name`
+    pub(crate) fn table(table_name: &str) -> SelectDefinition {
+        SelectDefinition {
+            table_name: table_name.to_string(),
+            items: vec![SelectItem::Wildcard],
+            filter: None,
+            group_by: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            offset: 0,
+        }
+    }
+
+    pub fn execute(&self, db: &Database, timings: &mut Timings) -> Result<ResultSet> {
+        let table_definition = read_table_definition(db, self.table_name.as_str())?;
+        let mut binder = Binder::new(&table_definition, now());
+        let filter = self.filter.as_ref().map(|filter| binder.condition(filter)).transpose()?;
+
+        let grouped = !self.group_by.is_empty()
+            || self.order_by.iter().any(|order| order.expression.contains_aggregate())
+            || self.items.iter().any(|item| match item {
+                SelectItem::Expression { expression, .. } => expression.contains_aggregate(),
+                SelectItem::Wildcard => false,
+            });
+        let groups = if grouped { binder.group_by(&self.group_by)? } else { Vec::new() };
+
+        let mut columns = Vec::new();
+        let mut outputs = Vec::new();
+        for item in &self.items {
+            match item {
+                SelectItem::Wildcard if grouped => {
+                    return Err(RqlError::Schema(
+                        "SELECT * cannot be combined with GROUP BY or aggregate functions".to_string(),
+                    ))
+                }
+                SelectItem::Wildcard => {
+                    for (index, column) in table_definition.columns.iter().enumerate() {
+                        columns.push(Column::from_definition(column));
+                        outputs.push(Bound::Column(index));
+                    }
+                }
+                SelectItem::Expression { expression, name } => {
+                    let (bound, data_type) = binder.bind(expression)?;
+                    let nullable = match expression {
+                        Expression::Column(column) => {
+                            table_definition.columns.iter().find(|c| c.name == *column).is_none_or(|c| c.nullable)
+                        }
+                        Expression::Function(function, _) => function != "count",
+                        _ => true,
+                    };
+                    columns.push(Column {
+                        name: name.clone(),
+                        data_type: data_type.unwrap_or(DataType::Text),
+                        nullable,
+                    });
+                    outputs.push(bound);
+                }
+            }
+        }
+        let keys = self
+            .order_by
+            .iter()
+            .map(|order| SelectDefinition::sort_key(&order.expression, &columns, &mut binder))
+            .collect::<Result<Vec<_>>>()?;
+
+        let rows = match self.index_scan(&table_definition) {
+            Some(scan) => {
+                let locations = scan.index.lookup(db, &self.table_name, &table_definition, &scan.low, scan.high.as_deref())?;
+                read_rows_at(db, &self.table_name, &table_definition, &locations)?
+            }
+            None => {
+                let ranges = self.chunk_ranges(&table_definition);
+                read_rows_filtered(db, self.table_name.as_str(), &table_definition, |header| {
+                    SelectDefinition::chunk_may_match(&ranges, header)
+                })?
+            }
+        };
+
+        // Each result row with the values it is sorted on.
+        let evaluate = |scope: &Scope| -> Result<(Vec<Value>, Vec<Value>)> {
+            let values = outputs.iter().map(|output| output.evaluate(scope)).collect::<Result<Vec<_>>>()?;
+            let sort_values = keys
+                .iter()
+                .map(|key| match key {
+                    SortKey::Output(index) => Ok(values[*index].clone()),
+                    SortKey::Expression(bound) => bound.evaluate(scope),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok((sort_values, values))
+        };
+        let matches = |row: &[Value]| filter.as_ref().map_or(Ok(true), |filter| filter.matches(&Scope::row(row)));
+
+        let mut results = Vec::new();
+        let search = self.limit.filter(|_| !grouped).and_then(|limit| Some((self.nearest_search(&keys, &outputs)?, limit)));
+        if let Some(((metric, column, query), limit)) = search {
+            // Rows without a distance sort last in storage order, so only the
+            // others are searched.
+            let wanted = limit + self.offset;
+            let zero = |v: &[f32]| v.iter().map(|c| c * c).sum::<f32>() == 0.0;
+            let (mut candidates, mut undefined, mut vectors) = (Vec::new(), Vec::new(), Vec::new());
+            for (index, row) in rows.iter().enumerate() {
+                if !matches(row)? {
+                    continue;
+                }
+                match row[column].components() {
+                    Some(v) if !(metric == Metric::Cosine && (zero(v) || zero(&query))) => {
+                        candidates.push(index);
+                        vectors.extend_from_slice(v);
+                    }
+                    _ => undefined.push(index),
+                }
+            }
+
+            match nearest(metric, &query, &vectors, wanted, timings) {
+                Ok(found) => {
+                    timings.device = Some(ComputeDevice::Gpu);
+                    for index in found.into_iter().map(|position| candidates[position]).chain(undefined).take(wanted) {
+                        results.push(evaluate(&Scope::row(&rows[index]))?);
+                    }
+                }
+                // Without a GPU, every row is sorted on the CPU instead.
+                Err(RqlError::Gpu(_)) => {
+                    timings.device = Some(ComputeDevice::Cpu);
+                    candidates.append(&mut undefined);
+                    candidates.sort_unstable();
+                    for index in candidates {
+                        results.push(evaluate(&Scope::row(&rows[index]))?);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        } else if grouped {
+            let aggregates = &binder.aggregates;
+            let mut positions: HashMap<Vec<u8>, usize> = HashMap::new();
+            let mut states: Vec<(Vec<Value>, Vec<Accumulator>)> = Vec::new();
+            for row in &rows {
+                if !matches(row)? {
+                    continue;
+                }
+                let key = groups.iter().map(|group| group.evaluate(&Scope::row(row))).collect::<Result<Vec<_>>>()?;
+                let position = *positions.entry(bincode::serialize(&key)?).or_insert_with(|| {
+                    states.push((key, vec![Accumulator::default(); aggregates.len()]));
+                    states.len() - 1
+                });
+                for (accumulator, aggregate) in states[position].1.iter_mut().zip(aggregates) {
+                    accumulator.update(aggregate, row)?;
+                }
+            }
+            // Without GROUP BY, aggregates cover the whole table, even when it is empty.
+            if self.group_by.is_empty() && states.is_empty() {
+                states.push((Vec::new(), vec![Accumulator::default(); aggregates.len()]));
+            }
+
+            for (key, accumulators) in &states {
+                let finished: Vec<Value> =
+                    accumulators.iter().zip(aggregates).map(|(accumulator, aggregate)| accumulator.finish(aggregate)).collect();
+                results.push(evaluate(&Scope { row: &[], groups: key, aggregates: &finished })?);
+            }
+        } else {
+            let wanted = self.limit.filter(|_| self.order_by.is_empty()).map(|limit| limit + self.offset);
+            for row in &rows {
+                if wanted.is_some_and(|wanted| results.len() >= wanted) {
+                    break;
+                }
+                if matches(row)? {
+                    results.push(evaluate(&Scope::row(row))?);
+                }
+            }
+        }
+
+        // NULLs sort after every other value, and so first in descending order.
+        results.sort_by(|(a, _), (b, _)| {
+            for ((a, b), order) in a.iter().zip(b).zip(&self.order_by) {
+                let ordering = match (a, b) {
+                    (Value::Null, Value::Null) => Ordering::Equal,
+                    (Value::Null, _) => Ordering::Greater,
+                    (_, Value::Null) => Ordering::Less,
+                    (a, b) => compare(a, b).unwrap_or(Ordering::Equal),
+                };
+                let ordering = if order.descending { ordering.reverse() } else { ordering };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        });
+
+        let columns: Arc<[Column]> = columns.into();
+        let rows = results
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|(_, values)| Row { columns: columns.clone(), values })
+            .collect();
+        Ok(ResultSet { columns, rows })
+    }
+
+    /// An `ORDER BY` entry names a result column by its name or its position
+    /// counted from 1, or else is an expression like those of the select list.
+    fn sort_key(expression: &Expression, columns: &[Column], binder: &mut Binder) -> Result<SortKey> {
+        match expression {
+            Expression::Column(name) if columns.iter().any(|c| c.name == *name) => {
+                Ok(SortKey::Output(columns.iter().position(|c| c.name == *name).unwrap_or(0)))
+            }
+            Expression::Literal(Literal::Integer(position)) => match usize::try_from(*position) {
+                Ok(position) if (1..=columns.len()).contains(&position) => Ok(SortKey::Output(position - 1)),
+                _ => Err(RqlError::Schema(format!("ORDER BY position {} is not in the select list", position))),
+            },
+            expression => Ok(SortKey::Expression(binder.bind(expression)?.0)),
+        }
+    }
+
+    /// The nearest neighbour search an `ORDER BY` is, if it is a single ascending
+    /// key that is the distance from a column to a constant vector: its metric,
+    /// the column and the query vector.
+    fn nearest_search(&self, keys: &[SortKey], outputs: &[Bound]) -> Option<(Metric, usize, Vec<f32>)> {
+        let ([key], [order]) = (keys, self.order_by.as_slice()) else {
+            return None;
+        };
+        let bound = match key {
+            SortKey::Output(index) => &outputs[*index],
+            SortKey::Expression(bound) => bound,
+        };
+        let Bound::Distance(metric, left, right) = bound else {
+            return None;
+        };
+        let (column, query) = match (left.as_ref(), right.as_ref()) {
+            (Bound::Column(column), Bound::Constant(query)) | (Bound::Constant(query), Bound::Column(column)) => {
+                (*column, query.components()?)
+            }
+            _ => return None,
+        };
+        (!order.descending).then(|| (*metric, column, query.to_vec()))
+    }
+
+    /// The conditions of the filter that chunk statistics can rule out: the
+    /// `column op literal` comparisons every matching row must satisfy.
+    fn chunk_ranges(&self, table_definition: &TableDefinition) -> Vec<(usize, BinaryOperator, Value)> {
+        let Some(filter) = &self.filter else {
+            return Vec::new();
+        };
+        filter
+            .conjuncts()
+            .into_iter()
+            .filter_map(|condition| {
+                let Expression::Binary(left, operator, right) = condition else {
+                    return None;
+                };
+                let (name, operator, literal) = match (left.as_ref(), right.as_ref()) {
+                    (Expression::Column(name), Expression::Literal(literal)) => (name, *operator, literal),
+                    (Expression::Literal(literal), Expression::Column(name)) => (name, operator.flip(), literal),
+                    _ => return None,
+                };
+                let index = table_definition.columns.iter().position(|c| c.name == *name)?;
+                let value = literal.cast(table_definition.columns[index].data_type)?;
+                let prunes = operator.is_comparison() && operator != BinaryOperator::NotEqual && value != Value::Null;
+                prunes.then_some((index, operator, value))
+            })
+            .collect()
+    }
+
+    /// The index that narrows down the rows to read the most, if any: the one
+    /// whose leading columns the filter compares with the most constants, preferring
+    /// one whose next column it also bounds from below or above. A hash index is
+    /// only used when all of its columns are compared. A comparison with a value
+    /// that cannot be part of a key is left out. The rows read are still checked
+    /// against the whole filter.
+    pub(crate) fn index_scan<'a>(&self, table_definition: &'a TableDefinition) -> Option<IndexScan<'a>> {
+        let ranges = self.chunk_ranges(table_definition);
+        let mut best: Option<(usize, IndexScan)> = None;
+        for index in &table_definition.indexes {
+            let Ok(positions) = index.positions(table_definition) else {
+                continue;
+            };
+            let mut prefix = Vec::new();
+            let mut equal = 0;
+            for position in &positions {
+                let Some((_, _, value)) =
+                    ranges.iter().find(|(column, operator, _)| column == position && *operator == BinaryOperator::Equal)
+                else {
+                    break;
+                };
+                // A value that cannot be part of a key ends the prefix like a missing one.
+                let mut key = Vec::new();
+                if encode_value(value, &mut key).is_err() {
+                    break;
+                }
+                prefix.extend(key);
+                equal += 1;
+            }
+
+            // A hash index only finds whole keys, but beats a B-tree at finding them.
+            let (score, low, high) = if index.kind == IndexKind::Hash {
+                let score = if equal == positions.len() { equal * 2 + 1 } else { 0 };
+                (score, prefix.clone(), prefix_end(&prefix))
+            } else {
+                let (mut low, mut high) = (prefix.clone(), prefix_end(&prefix));
+                let mut bounded = false;
+                for (_, operator, value) in ranges.iter().filter(|(column, _, _)| Some(column) == positions.get(equal)) {
+                    let mut key = prefix.clone();
+                    if encode_value(value, &mut key).is_err() {
+                        continue;
+                    }
+                    // The tightest of the bounds: a key past the end is an empty range.
+                    let (from, to) = match operator {
+                        BinaryOperator::Greater => (prefix_end(&key)?, None),
+                        BinaryOperator::GreaterOrEqual => (key, None),
+                        BinaryOperator::Less => (Vec::new(), Some(key)),
+                        BinaryOperator::LessOrEqual => (Vec::new(), prefix_end(&key)),
+                        _ => continue,
+                    };
+                    low = low.max(from);
+                    high = match (high, to) {
+                        (Some(high), Some(to)) => Some(high.min(to)),
+                        (high, to) => high.or(to),
+                    };
+                    bounded = true;
+                }
+                if bounded {
+                    // NULL sorts first and matches no comparison.
+                    low = low.max([prefix, vec![1]].concat());
+                }
+                let score = equal * 2 + bounded as usize;
+                (score, low, high)
+            };
+            if score > 0 && best.as_ref().is_none_or(|(best, _)| score > *best) {
+                best = Some((score, IndexScan { index, low, high }));
+            }
+        }
+        best.map(|(_, scan)| scan)
+    }
+
+    /// Uses the min/max statistics of a compressed chunk to rule out chunks that
+    /// cannot contain a matching row.
+    fn chunk_may_match(ranges: &[(usize, BinaryOperator, Value)], header: &ChunkHeader) -> bool {
+        ranges.iter().all(|(index, operator, value)| {
+            let Some(ColumnStats { min: Some(min), max: Some(max) }) = header.stats.get(*index) else {
+                return true;
+            };
+
+            let (low, high) = (compare(min, value), compare(max, value));
+            match operator {
+                BinaryOperator::Equal => low != Some(Ordering::Greater) && high != Some(Ordering::Less),
+                BinaryOperator::Less => !matches!(low, Some(Ordering::Greater | Ordering::Equal)),
+                BinaryOperator::LessOrEqual => low != Some(Ordering::Greater),
+                BinaryOperator::Greater => !matches!(high, Some(Ordering::Less | Ordering::Equal)),
+                BinaryOperator::GreaterOrEqual => high != Some(Ordering::Less),
+                _ => true,
+            }
+        })
+    }
+}
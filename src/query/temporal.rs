@@ -0,0 +1,298 @@
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{DataType, Interval, Value};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use std::fmt;
+
+pub(crate) const MICROS_PER_SECOND: i64 = 1_000_000;
+pub(crate) const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// Parses an ISO-8601 date, `YYYY-MM-DD`, into days since 1970-01-01.
+pub(crate) fn parse_date(text: &str) -> Option<i32> {
+    let date = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()?;
+    i32::try_from(date.signed_duration_since(DateTime::UNIX_EPOCH.date_naive()).num_days()).ok()
+}
+
+/// Parses `HH:MM[:SS[.ffffff]]` into microseconds since midnight.
+pub(crate) fn parse_time(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let time = NaiveTime::parse_from_str(text, "%H:%M:%S%.f")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .ok()?;
+    Some(time.num_seconds_from_midnight() as i64 * MICROS_PER_SECOND + (time.nanosecond() / 1000) as i64)
+}
+
+/// Parses an ISO-8601 timestamp into microseconds since the epoch. The date and
+/// time may be separated by `T` or a space, and a date alone means midnight. A
+/// time zone offset, as in `2024-01-31T12:00:00+02:00`, is converted to UTC.
+pub(crate) fn parse_timestamp(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text)
+        .or_else(|_| DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z"))
+    {
+        return Some(timestamp.timestamp_micros());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"] {
+        if let Ok(timestamp) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(timestamp.and_utc().timestamp_micros());
+        }
+    }
+    parse_date(text).map(|days| days as i64 * MICROS_PER_DAY)
+}
+
+pub(crate) fn format_date(days: i32) -> String {
+    match DateTime::UNIX_EPOCH.date_naive().checked_add_signed(Duration::days(days as i64)) {
+        Some(date) => date.format("%Y-%m-%d").to_string(),
+        None => days.to_string(),
+    }
+}
+
+pub(crate) fn format_time(micros: i64) -> String {
+    let seconds = micros.div_euclid(MICROS_PER_SECOND);
+    let fraction = micros.rem_euclid(MICROS_PER_SECOND);
+    let time = format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+    with_fraction(time, fraction)
+}
+
+pub(crate) fn format_timestamp(micros: i64) -> String {
+    match DateTime::from_timestamp_micros(micros) {
+        Some(timestamp) => with_fraction(
+            timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            micros.rem_euclid(MICROS_PER_SECOND),
+        ),
+        None => micros.to_string(),
+    }
+}
+
+/// Appends the microseconds of a time, without trailing zeros.
+fn with_fraction(time: String, micros: i64) -> String {
+    if micros == 0 {
+        return time;
+    }
+    format!("{}.{}", time, format!("{:06}", micros).trim_end_matches('0'))
+}
+
+/// The current time, as a TIMESTAMP.
+pub(crate) fn now() -> i64 {
+    Utc::now().timestamp_micros()
+}
+
+fn to_datetime(micros: i64) -> Result<NaiveDateTime> {
+    DateTime::from_timestamp_micros(micros)
+        .map(|timestamp| timestamp.naive_utc())
+        .ok_or_else(|| RqlError::Type(format!("Timestamp {} is out of range", micros)))
+}
+
+fn from_datetime(timestamp: NaiveDateTime) -> i64 {
+    timestamp.and_utc().timestamp_micros()
+}
+
+impl Interval {
+    /// Parses the text of an `INTERVAL` literal: a list of amounts with units, as
+    /// in `1 year 2 months`, `3 days` or `90 minutes`, optionally followed by a
+    /// time of day such as `01:30:00`. Units below a day may have fractions.
+    pub(crate) fn parse(text: &str) -> Option<Interval> {
+        let mut interval = Interval::default();
+        let mut words = text.split_whitespace();
+        while let Some(word) = words.next() {
+            if word.contains(':') {
+                let (negative, time) = match word.strip_prefix('-') {
+                    Some(time) => (true, time),
+                    None => (false, word.strip_prefix('+').unwrap_or(word)),
+                };
+                let micros = parse_time(time)?;
+                interval.micros += if negative { -micros } else { micros };
+                continue;
+            }
+
+            let amount: f64 = word.parse().ok()?;
+            let unit = words.next()?.to_lowercase();
+            let whole = || (amount.fract() == 0.0).then_some(amount as i32);
+            match unit.as_str() {
+                "year" | "years" | "yr" | "yrs" | "y" => {
+                    interval.months = interval.months.checked_add(whole()?.checked_mul(12)?)?
+                }
+                "month" | "months" | "mon" | "mons" => interval.months = interval.months.checked_add(whole()?)?,
+                "week" | "weeks" | "w" => interval.days = interval.days.checked_add(whole()?.checked_mul(7)?)?,
+                "day" | "days" | "d" => interval.days = interval.days.checked_add(whole()?)?,
+                unit => {
+                    let scale = match unit {
+                        "hour" | "hours" | "hr" | "hrs" | "h" => 3600 * MICROS_PER_SECOND,
+                        "minute" | "minutes" | "min" | "mins" | "m" => 60 * MICROS_PER_SECOND,
+                        "second" | "seconds" | "sec" | "secs" | "s" => MICROS_PER_SECOND,
+                        "millisecond" | "milliseconds" | "ms" => 1000,
+                        "microsecond" | "microseconds" | "us" => 1,
+                        _ => return None,
+                    };
+                    interval.micros = interval.micros.checked_add((amount * scale as f64).round() as i64)?;
+                }
+            }
+        }
+        Some(interval)
+    }
+
+    /// Adds the interval, or subtracts it when `sign` is -1, to a timestamp.
+    /// Months are added first, keeping the day of the month where it exists.
+    pub(crate) fn add_to_timestamp(&self, micros: i64, sign: i32) -> Result<i64> {
+        let out_of_range = || RqlError::Type(format!("Adding {} to a timestamp is out of range", self));
+        let timestamp = to_datetime(micros)?;
+        let months = self.months * sign;
+        let timestamp = if months >= 0 {
+            timestamp.checked_add_months(Months::new(months as u32))
+        } else {
+            timestamp.checked_sub_months(Months::new(months.unsigned_abs()))
+        }
+        .and_then(|timestamp| timestamp.checked_add_signed(Duration::days((self.days * sign) as i64)))
+        .ok_or_else(out_of_range)?;
+        from_datetime(timestamp).checked_add(self.micros * sign as i64).ok_or_else(out_of_range)
+    }
+
+    /// Adds the interval to a date. The result is a DATE as long as the interval
+    /// has no time part, and a TIMESTAMP otherwise.
+    pub(crate) fn add_to_date(&self, days: i32, sign: i32) -> Result<Value> {
+        let micros = self.add_to_timestamp(days as i64 * MICROS_PER_DAY, sign)?;
+        if self.micros == 0 {
+            Ok(Value::Date(micros.div_euclid(MICROS_PER_DAY) as i32))
+        } else {
+            Ok(Value::Timestamp(micros))
+        }
+    }
+
+    /// Adds the time part of the interval to a time of day, wrapping at midnight.
+    pub(crate) fn add_to_time(&self, micros: i64, sign: i32) -> i64 {
+        (micros + self.micros * sign as i64).rem_euclid(MICROS_PER_DAY)
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        let plural = |amount: i32, unit: &str| format!("{} {}{}", amount, unit, if amount.abs() == 1 { "" } else { "s" });
+        if self.months / 12 != 0 {
+            parts.push(plural(self.months / 12, "year"));
+        }
+        if self.months % 12 != 0 {
+            parts.push(plural(self.months % 12, "month"));
+        }
+        if self.days != 0 {
+            parts.push(plural(self.days, "day"));
+        }
+        if self.micros != 0 || parts.is_empty() {
+            let sign = if self.micros < 0 { "-" } else { "" };
+            let micros = self.micros.unsigned_abs() as i64;
+            let hours = micros / (3600 * MICROS_PER_SECOND);
+            let rest = format_time(micros % (3600 * MICROS_PER_SECOND));
+            parts.push(format!("{}{:02}{}", sign, hours, &rest[2..]));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// `date_trunc(unit, value)`: the value rounded down to the start of its unit,
+/// which may be `microsecond` up to `year`. Dates and times keep their type.
+pub(crate) fn date_trunc(unit: &str, value: &Value) -> Result<Value> {
+    let invalid = || RqlError::Type(format!("'{}' is not a unit date_trunc can round {} values to", unit, value_type(value)));
+    let fixed = match unit.to_lowercase().as_str() {
+        "microsecond" | "microseconds" => Some(1),
+        "millisecond" | "milliseconds" => Some(1000),
+        "second" => Some(MICROS_PER_SECOND),
+        "minute" => Some(60 * MICROS_PER_SECOND),
+        "hour" => Some(3600 * MICROS_PER_SECOND),
+        "day" => Some(MICROS_PER_DAY),
+        "week" | "month" | "quarter" | "year" => None,
+        _ => return Err(invalid()),
+    };
+
+    let truncate = |micros: i64| -> Result<i64> {
+        if let Some(step) = fixed {
+            return Ok(micros - micros.rem_euclid(step));
+        }
+        let date = to_datetime(micros)?.date();
+        let start = match unit.to_lowercase().as_str() {
+            "week" => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            "month" => date.with_day(1).ok_or_else(invalid)?,
+            "quarter" => NaiveDate::from_ymd_opt(date.year(), (date.month0() / 3) * 3 + 1, 1).ok_or_else(invalid)?,
+            _ => NaiveDate::from_ymd_opt(date.year(), 1, 1).ok_or_else(invalid)?,
+        };
+        Ok(from_datetime(start.and_time(NaiveTime::MIN)))
+    };
+
+    match value {
+        Value::Timestamp(micros) => Ok(Value::Timestamp(truncate(*micros)?)),
+        Value::Date(days) => {
+            let micros = truncate(*days as i64 * MICROS_PER_DAY)?;
+            Ok(Value::Date(micros.div_euclid(MICROS_PER_DAY) as i32))
+        }
+        Value::Time(micros) if fixed.is_some_and(|step| step < MICROS_PER_DAY) => Ok(Value::Time(truncate(*micros)?)),
+        Value::Null => Ok(Value::Null),
+        _ => Err(invalid()),
+    }
+}
+
+/// The fields `extract` accepts, and whether they are defined for TIME values.
+const EXTRACT_FIELDS: [(&str, bool); 14] = [
+    ("year", false),
+    ("quarter", false),
+    ("month", false),
+    ("week", false),
+    ("day", false),
+    ("dow", false),
+    ("isodow", false),
+    ("doy", false),
+    ("hour", true),
+    ("minute", true),
+    ("second", true),
+    ("millisecond", true),
+    ("microsecond", true),
+    ("epoch", true),
+];
+
+/// The type `extract(field FROM ..)` returns on a value of `data_type`: DOUBLE
+/// seconds for `epoch`, INTEGER for every other field.
+pub(crate) fn extract_type(field: &str, data_type: DataType) -> Result<DataType> {
+    let field = field.to_lowercase();
+    let known = EXTRACT_FIELDS.iter().find(|(name, _)| *name == field.trim_end_matches('s'));
+    match (known, data_type) {
+        (Some((_, true)), DataType::Time) | (Some(_), DataType::Date | DataType::Timestamp) => {
+            Ok(if field == "epoch" { DataType::Double } else { DataType::Integer })
+        }
+        _ => Err(RqlError::Type(format!("Cannot extract '{}' from a {} value", field, data_type.to_sql_type()))),
+    }
+}
+
+/// `extract(field FROM value)`, such as the year of a date or the hour of a timestamp.
+pub(crate) fn extract(field: &str, value: &Value) -> Result<Value> {
+    let (micros, data_type) = match value {
+        Value::Null => return Ok(Value::Null),
+        Value::Date(days) => (*days as i64 * MICROS_PER_DAY, DataType::Date),
+        Value::Time(micros) => (*micros, DataType::Time),
+        Value::Timestamp(micros) => (*micros, DataType::Timestamp),
+        _ => return Err(RqlError::Type(format!("Cannot extract '{}' from {}", field, value))),
+    };
+    extract_type(field, data_type)?;
+
+    let timestamp = to_datetime(micros)?;
+    let date = timestamp.date();
+    let seconds_of_minute = (micros.rem_euclid(60 * MICROS_PER_SECOND) / MICROS_PER_SECOND) as i32;
+    let micros_of_minute = micros.rem_euclid(60 * MICROS_PER_SECOND);
+    let result = match field.to_lowercase().trim_end_matches('s') {
+        "epoch" => return Ok(Value::Double(micros as f64 / MICROS_PER_SECOND as f64)),
+        "year" => date.year(),
+        "quarter" => date.month0() as i32 / 3 + 1,
+        "month" => date.month() as i32,
+        "week" => date.iso_week().week() as i32,
+        "day" => date.day() as i32,
+        "dow" => date.weekday().num_days_from_sunday() as i32,
+        "isodow" => date.weekday().number_from_monday() as i32,
+        "doy" => date.ordinal() as i32,
+        "hour" => timestamp.hour() as i32,
+        "minute" => timestamp.minute() as i32,
+        "second" => seconds_of_minute,
+        "millisecond" => (micros_of_minute / 1000) as i32,
+        _ => micros_of_minute as i32,
+    };
+    Ok(Value::Integer(result))
+}
+
+fn value_type(value: &Value) -> String {
+    value.data_type().map_or("NULL".to_string(), DataType::to_sql_type)
+}
@@ -0,0 +1,218 @@
+use crate::types::types::{BinaryOperator, DataType, Expression, Literal, UnaryOperator};
+
+/// The most constraints a pass can count, and the most columns it can read: one
+/// lane of a workgroup flushes each counter, and one bit of a word marks each
+/// NULL column.
+const MAX_CHECKS: usize = 32;
+
+/// The `CHECK` constraints an `UPDATE` verifies on the GPU. The `check_constraints`
+/// entry point runs after `main` over the updated columns and counts the rows
+/// that violate each constraint, first per workgroup and then in `violations`.
+#[derive(Debug)]
+pub(crate) struct CheckPass {
+    /// One word per row, with a bit set for each checked column that is NULL there.
+    pub(crate) nulls: Vec<u32>,
+    /// The number of constraints, each with its own violation counter.
+    pub(crate) checks: usize,
+}
+
+/// Translates `checks` over the INTEGER and FLOAT columns `columns` into the
+/// `check_constraints` entry point, whose two buffers are bound at `binding` and
+/// after. The column at position `j` of `columns` is NULL where bit `j` of its
+/// row's word in the NULL buffer is set.
+///
+/// Returns `None` when a check uses anything the GPU cannot evaluate exactly as
+/// the CPU does: other types, functions, division, integer arithmetic, which
+/// wraps in WGSL where the CPU reports an overflow, or float literals that are
+/// not exact in `f32`.
+pub(crate) fn check_shader(checks: &[&Expression], columns: &[(&str, DataType)], binding: usize) -> Option<String> {
+    if checks.len() > MAX_CHECKS || columns.len() > MAX_CHECKS {
+        return None;
+    }
+
+    let mut translator = Translator { columns, statements: Vec::new(), next: 0 };
+    for (index, check) in checks.iter().enumerate() {
+        let (value, null, data_type) = translator.emit(check)?;
+        if data_type != DataType::Boolean {
+            return None;
+        }
+        translator.statements.push(format!(
+            "if (!{} && !{}) {{ atomicAdd(&_workgroup_violations[{}], 1u); }}",
+            null, value, index
+        ));
+    }
+
+    Some(format!(
+        "
+@group(0) @binding({binding}) var<storage, read_write> _check_nulls: array<u32>;
+@group(0) @binding({violations}) var<storage, read_write> _violations: array<atomic<u32>>;
+var<workgroup> _workgroup_violations: array<atomic<u32>, {count}>;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn check_constraints(@builtin(local_invocation_id) lid: vec3<u32>, @builtin(workgroup_id) wid: vec3<u32>) {{
+    let sys_index = wid.x * 64u + lid.x;
+    if (sys_index < arrayLength(&_check_nulls)) {{
+        let _nulls = _check_nulls[sys_index];
+        {statements}
+    }}
+    workgroupBarrier();
+    if (lid.x < {count}u) {{
+        let count = atomicLoad(&_workgroup_violations[lid.x]);
+        if (count > 0u) {{
+            atomicAdd(&_violations[lid.x], count);
+        }}
+    }}
+}}",
+        violations = binding + 1,
+        count = checks.len(),
+        statements = translator.statements.join("\n        "),
+    ))
+}
+
+/// Emits WGSL that evaluates expressions in three-valued logic: every
+/// sub-expression gets a `let` for its value and one for whether it is NULL.
+struct Translator<'a> {
+    columns: &'a [(&'a str, DataType)],
+    statements: Vec<String>,
+    next: usize,
+}
+
+impl Translator<'_> {
+    /// Returns the names of the value and NULL flag of `expression`, and its type.
+    fn emit(&mut self, expression: &Expression) -> Option<(String, String, DataType)> {
+        let (value, null, data_type) = match expression {
+            Expression::Column(name) => {
+                let bit = self.columns.iter().position(|(column, _)| column == name)?;
+                let data_type = self.columns[bit].1;
+                if !matches!(data_type, DataType::Integer | DataType::Float) {
+                    return None;
+                }
+                (format!("{}[sys_index]", name), format!("(_nulls & {}u) != 0u", 1u32 << bit), data_type)
+            }
+            Expression::Literal(Literal::Integer(v)) => {
+                // `-2147483648i` would negate a literal out of range.
+                let v = i32::try_from(*v).ok().filter(|v| *v != i32::MIN)?;
+                (format!("{}i", v), "false".to_string(), DataType::Integer)
+            }
+            Expression::Literal(Literal::Float(v)) => {
+                let float = *v as f32;
+                if float as f64 != *v || !float.is_finite() {
+                    return None;
+                }
+                (format!("bitcast<f32>({}u)", float.to_bits()), "false".to_string(), DataType::Float)
+            }
+            Expression::Literal(Literal::Boolean(v)) => (v.to_string(), "false".to_string(), DataType::Boolean),
+            Expression::Unary(UnaryOperator::Negate, operand) => {
+                let (value, null, data_type) = self.emit(operand)?;
+                // Integers wrap in WGSL, where the CPU reports an overflow, but no
+                // integer literal is out of range when negated.
+                let literal = matches!(**operand, Expression::Literal(Literal::Integer(_)));
+                if data_type == DataType::Boolean || (data_type == DataType::Integer && !literal) {
+                    return None;
+                }
+                (format!("-{}", value), null, data_type)
+            }
+            Expression::Unary(UnaryOperator::Not, operand) => {
+                let (value, null, data_type) = self.emit(operand)?;
+                if data_type != DataType::Boolean {
+                    return None;
+                }
+                (format!("!{}", value), null, data_type)
+            }
+            Expression::IsNull(operand, negated) => {
+                let (_, null, _) = self.emit(operand)?;
+                let value = if *negated { format!("!{}", null) } else { null };
+                (value, "false".to_string(), DataType::Boolean)
+            }
+            Expression::Binary(left, operator @ (BinaryOperator::And | BinaryOperator::Or), right) => {
+                let (left_value, left_null, left_type) = self.emit(left)?;
+                let (right_value, right_null, right_type) = self.emit(right)?;
+                if left_type != DataType::Boolean || right_type != DataType::Boolean {
+                    return None;
+                }
+                // FALSE decides an AND, and TRUE an OR, even when the other side is NULL.
+                let (symbol, deciding) = match operator {
+                    BinaryOperator::And => ("&&", "!"),
+                    _ => ("||", ""),
+                };
+                (
+                    format!("{} {} {}", left_value, symbol, right_value),
+                    format!(
+                        "!((!{ln} && {d}{lv}) || (!{rn} && {d}{rv})) && ({ln} || {rn})",
+                        ln = left_null,
+                        lv = left_value,
+                        rn = right_null,
+                        rv = right_value,
+                        d = deciding
+                    ),
+                    DataType::Boolean,
+                )
+            }
+            Expression::Binary(left, operator, right) => {
+                let symbol = match operator {
+                    BinaryOperator::Add => "+",
+                    BinaryOperator::Subtract => "-",
+                    BinaryOperator::Multiply => "*",
+                    BinaryOperator::Equal => "==",
+                    BinaryOperator::NotEqual => "!=",
+                    BinaryOperator::Less => "<",
+                    BinaryOperator::LessOrEqual => "<=",
+                    BinaryOperator::Greater => ">",
+                    BinaryOperator::GreaterOrEqual => ">=",
+                    // Division by zero is an error on the CPU but not in WGSL.
+                    _ => return None,
+                };
+                let (left_value, left_null, left_type) = self.emit(left)?;
+                let (right_value, right_null, right_type) = self.emit(right)?;
+                let (left_value, right_value, data_type) = match (left_type, right_type) {
+                    (DataType::Boolean, DataType::Boolean)
+                        if matches!(operator, BinaryOperator::Equal | BinaryOperator::NotEqual) =>
+                    {
+                        (left_value, right_value, DataType::Boolean)
+                    }
+                    (DataType::Boolean, _) | (_, DataType::Boolean) => return None,
+                    // The CPU compares integers with floats in double precision, so
+                    // only integers that f32 holds exactly can be converted.
+                    (DataType::Integer, DataType::Float) if exact_in_f32(left) => {
+                        (format!("f32({})", left_value), right_value, DataType::Float)
+                    }
+                    (DataType::Float, DataType::Integer) if exact_in_f32(right) => {
+                        (left_value, format!("f32({})", right_value), DataType::Float)
+                    }
+                    (DataType::Integer, DataType::Float) | (DataType::Float, DataType::Integer) => return None,
+                    (data_type, _) => (left_value, right_value, data_type),
+                };
+                if data_type == DataType::Integer && !operator.is_comparison() {
+                    return None;
+                }
+                let data_type = if operator.is_comparison() { DataType::Boolean } else { data_type };
+                (
+                    format!("{} {} {}", left_value, symbol, right_value),
+                    format!("{} || {}", left_null, right_null),
+                    data_type,
+                )
+            }
+            _ => return None,
+        };
+
+        let index = self.next;
+        self.next += 1;
+        self.statements.push(format!("let _value{} = {};", index, value));
+        self.statements.push(format!("let _null{} = {};", index, null));
+        Some((format!("_value{}", index), format!("_null{}", index), data_type))
+    }
+}
+
+/// Whether `expression` is an integer literal that converts to f32 without rounding.
+fn exact_in_f32(expression: &Expression) -> bool {
+    let v = match expression {
+        Expression::Literal(Literal::Integer(v)) => *v,
+        Expression::Unary(UnaryOperator::Negate, operand) => match **operand {
+            Expression::Literal(Literal::Integer(v)) => -v,
+            _ => return false,
+        },
+        _ => return false,
+    };
+    v as f32 as i64 == v
+}
@@ -0,0 +1,395 @@
+use crate::rqle::rqle_parser::Assignment;
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{DataType, TableDefinition, Timings, Value};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Evaluates `UPDATE` assignments row by row on the CPU. It stands in for the
+/// GPU when there is none, or when it has no shader support for the columns.
+/// Integer columns and literals are computed like a shader computes them, with
+/// 64-bit integers and division that truncates at every step, except that
+/// overflow is an error. Other values are doubles when a DOUBLE column takes
+/// part, and are otherwise rounded to `f32` after every step, as a shader
+/// computes FLOAT columns.
+#[derive(Debug)]
+pub struct CpuExecutor;
+
+impl CpuExecutor {
+    pub fn main(
+        &self,
+        assignments: &[Assignment],
+        data: HashMap<String, Vec<Value>>,
+        table_definition: &TableDefinition,
+        timings: &mut Timings,
+    ) -> Result<HashMap<String, Vec<Value>>> {
+        let expressions = assignments
+            .iter()
+            .map(|assignment| Node::parse(&assignment.expression))
+            .collect::<Result<Vec<_>>>()?;
+
+        let data_types = data
+            .keys()
+            .map(|key| {
+                table_definition
+                    .columns
+                    .iter()
+                    .find(|c| c.name == *key)
+                    .map(|c| (key.clone(), c.data_type))
+                    .ok_or_else(|| RqlError::Schema(format!("Column '{}' not found in table '{}'", key, table_definition.name)))
+            })
+            .collect::<Result<HashMap<String, DataType>>>()?;
+
+        let single = !data_types.values().any(|data_type| *data_type == DataType::Double);
+        let compute_start = Instant::now();
+        let mut variables = HashMap::new();
+        let mut updated_data = data.clone();
+        let total_rows = data.values().next().map_or(0, Vec::len);
+        for index in 0..total_rows {
+            for (key, values) in &data {
+                variables.insert(key.as_str(), Number::from_value(&values[index])?);
+            }
+            for (assignment, expression) in assignments.iter().zip(&expressions) {
+                let result = expression.evaluate(&variables, single).map_err(|message| {
+                    RqlError::Type(format!("Cannot evaluate '{}': {}", assignment.expression, message))
+                })?;
+                variables.insert(assignment.variable.as_str(), result);
+                if let Some(values) = updated_data.get_mut(&assignment.variable) {
+                    values[index] = result.to_value(data_types[&assignment.variable]).map_err(|message| {
+                        RqlError::Type(format!("{} for column '{}'", message, assignment.variable))
+                    })?;
+                }
+            }
+        }
+        timings.compute = compute_start.elapsed();
+
+        Ok(updated_data)
+    }
+}
+
+/// A value in an `UPDATE` expression: integers stay integers unless a double
+/// takes part in the operation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(value: &Value) -> Result<Number> {
+        match value {
+            Value::Integer(v) | Value::Date(v) => Ok(Number::Int(*v as i64)),
+            Value::BigInt(v) | Value::Time(v) | Value::Timestamp(v) => Ok(Number::Int(*v)),
+            Value::Float(v) => Ok(Number::Float(*v as f64)),
+            Value::Double(v) => Ok(Number::Float(*v)),
+            _ => Err(RqlError::Type(format!("Unexpected value {} in a numeric expression", value))),
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(v) => v as f64,
+            Number::Float(v) => v,
+        }
+    }
+
+    /// Converts a result to the column type. Doubles are truncated towards zero
+    /// for integer columns, as a shader would; integers must fit.
+    fn to_value(self, data_type: DataType) -> std::result::Result<Value, String> {
+        let narrow = |v: i64| i32::try_from(v).map_err(|_| format!("{} is out of range", v));
+        Ok(match (self, data_type) {
+            (Number::Int(v), DataType::Integer) => Value::Integer(narrow(v)?),
+            (Number::Int(v), DataType::Date) => Value::Date(narrow(v)?),
+            (Number::Int(v), DataType::BigInt) => Value::BigInt(v),
+            (Number::Int(v), DataType::Time) => Value::Time(v),
+            (Number::Int(v), DataType::Timestamp) => Value::Timestamp(v),
+            (Number::Float(v), DataType::Integer) => Value::Integer(v as i32),
+            (Number::Float(v), DataType::Date) => Value::Date(v as i32),
+            (Number::Float(v), DataType::BigInt) => Value::BigInt(v as i64),
+            (Number::Float(v), DataType::Time) => Value::Time(v as i64),
+            (Number::Float(v), DataType::Timestamp) => Value::Timestamp(v as i64),
+            (number, DataType::Float) => Value::Float(number.as_f64() as f32),
+            (number, _) => Value::Double(number.as_f64()),
+        })
+    }
+}
+
+/// A parsed `UPDATE` expression, with the operator precedence of WGSL.
+#[derive(Debug)]
+enum Node {
+    Number(Number),
+    Variable(String),
+    Negate(Box<Node>),
+    Binary(Box<Node>, char, Box<Node>),
+    Call(String, Vec<Node>),
+}
+
+impl Node {
+    fn parse(expression: &str) -> Result<Node> {
+        let error = |message: String| RqlError::parse(format!("Cannot evaluate '{}': {}", expression, message));
+        let mut parser = NodeParser { chars: expression.chars().collect(), position: 0 };
+        let node = parser.additive().map_err(error)?;
+        parser.skip_whitespace();
+        match parser.chars.get(parser.position) {
+            None => Ok(node),
+            Some(c) => Err(error(format!("unexpected '{}'", c))),
+        }
+    }
+
+    /// The value of the node, with non-integers rounded to `f32` when `single`.
+    fn evaluate(&self, variables: &HashMap<&str, Number>, single: bool) -> std::result::Result<Number, String> {
+        let number = match self {
+            Node::Number(number) => *number,
+            Node::Variable(name) => match (variables.get(name.as_str()), name.as_str()) {
+                (Some(number), _) => *number,
+                (None, "pi") => Number::Float(std::f64::consts::PI),
+                (None, "e") => Number::Float(std::f64::consts::E),
+                (None, _) => return Err(format!("unknown variable '{}'", name)),
+            },
+            Node::Negate(operand) => match operand.evaluate(variables, single)? {
+                Number::Int(v) => Number::Int(v.checked_neg().ok_or_else(|| format!("-{} is out of range", v))?),
+                Number::Float(v) => Number::Float(-v),
+            },
+            Node::Binary(left, operator, right) => {
+                arithmetic(left.evaluate(variables, single)?, *operator, right.evaluate(variables, single)?)?
+            }
+            Node::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(variables, single))
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                call(name, &args)?
+            }
+        };
+        Ok(match number {
+            Number::Float(v) if single => Number::Float(v as f32 as f64),
+            number => number,
+        })
+    }
+}
+
+fn arithmetic(left: Number, operator: char, right: Number) -> std::result::Result<Number, String> {
+    match (left, right) {
+        (Number::Int(a), Number::Int(b)) => {
+            // As in WGSL, dividing by zero gives the dividend and its remainder 0,
+            // which also keeps rows whose divisor is NULL from failing.
+            if b == 0 && matches!(operator, '/' | '%') {
+                return Ok(Number::Int(if operator == '/' { a } else { 0 }));
+            }
+            let result = match operator {
+                '+' => a.checked_add(b),
+                '-' => a.checked_sub(b),
+                '*' => a.checked_mul(b),
+                '/' => a.checked_div(b),
+                _ => a.checked_rem(b),
+            };
+            result.map(Number::Int).ok_or_else(|| format!("{} {} {} is out of range", a, operator, b))
+        }
+        (a, b) => {
+            let (a, b) = (a.as_f64(), b.as_f64());
+            Ok(Number::Float(match operator {
+                '+' => a + b,
+                '-' => a - b,
+                '*' => a * b,
+                '/' => a / b,
+                _ => a % b,
+            }))
+        }
+    }
+}
+
+/// The functions of the CPU fallback: the WGSL built-ins that apply to scalars,
+/// and a few more, like `ln` and `atan2`. `abs`, `sign`, `min`, `max` and `clamp`
+/// of integers are integers; the others compute in double precision.
+fn call(name: &str, args: &[Number]) -> std::result::Result<Number, String> {
+    let all_ints = args.iter().all(|arg| matches!(arg, Number::Int(_)));
+    let ints: Vec<i64> = args.iter().filter_map(|arg| if let Number::Int(v) = arg { Some(*v) } else { None }).collect();
+    let floats: Vec<f64> = args.iter().map(|arg| arg.as_f64()).collect();
+    let arity = |count: usize| {
+        if args.len() == count {
+            Ok(())
+        } else {
+            Err(format!("{}() takes {} argument{}", name, count, if count == 1 { "" } else { "s" }))
+        }
+    };
+
+    let unary: Option<fn(f64) -> f64> = match name {
+        "sqrt" => Some(f64::sqrt),
+        "inverseSqrt" => Some(|x| 1.0 / x.sqrt()),
+        "exp" => Some(f64::exp),
+        "exp2" => Some(f64::exp2),
+        "ln" | "log" => Some(f64::ln),
+        "log2" => Some(f64::log2),
+        "log10" => Some(f64::log10),
+        "sin" => Some(f64::sin),
+        "cos" => Some(f64::cos),
+        "tan" => Some(f64::tan),
+        "asin" => Some(f64::asin),
+        "acos" => Some(f64::acos),
+        "atan" => Some(f64::atan),
+        "sinh" => Some(f64::sinh),
+        "cosh" => Some(f64::cosh),
+        "tanh" => Some(f64::tanh),
+        "asinh" => Some(f64::asinh),
+        "acosh" => Some(f64::acosh),
+        "atanh" => Some(f64::atanh),
+        "floor" => Some(f64::floor),
+        "ceil" => Some(f64::ceil),
+        "round" => Some(f64::round),
+        "trunc" => Some(f64::trunc),
+        "fract" => Some(f64::fract),
+        _ => None,
+    };
+    if let Some(function) = unary {
+        arity(1)?;
+        return Ok(Number::Float(function(floats[0])));
+    }
+
+    match name {
+        "abs" | "sign" | "signum" => {
+            arity(1)?;
+            Ok(match args[0] {
+                Number::Int(v) if name == "abs" => Number::Int(v.checked_abs().ok_or_else(|| format!("abs({}) is out of range", v))?),
+                Number::Int(v) => Number::Int(v.signum()),
+                Number::Float(v) if name == "abs" => Number::Float(v.abs()),
+                // WGSL's sign is 0 at 0, unlike f64::signum.
+                Number::Float(v) => Number::Float(if v == 0.0 { 0.0 } else { v.signum() }),
+            })
+        }
+        "pow" | "atan2" => {
+            arity(2)?;
+            Ok(Number::Float(if name == "pow" { floats[0].powf(floats[1]) } else { floats[0].atan2(floats[1]) }))
+        }
+        "min" | "max" => {
+            if args.is_empty() {
+                return Err(format!("{}() takes at least 1 argument", name));
+            }
+            let pick_min = name == "min";
+            if all_ints {
+                let result = if pick_min { ints.iter().min() } else { ints.iter().max() };
+                return Ok(Number::Int(*result.unwrap()));
+            }
+            let fold = |a: f64, b: f64| if pick_min { a.min(b) } else { a.max(b) };
+            Ok(Number::Float(floats[1..].iter().fold(floats[0], |a, b| fold(a, *b))))
+        }
+        "clamp" => {
+            arity(3)?;
+            if all_ints {
+                return Ok(Number::Int(ints[0].max(ints[1]).min(ints[2])));
+            }
+            Ok(Number::Float(floats[0].max(floats[1]).min(floats[2])))
+        }
+        _ => Err(format!("unknown function '{}'", name)),
+    }
+}
+
+struct NodeParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl NodeParser {
+    /// Skips the whitespace `rqle.pest` allows between tokens.
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.position).is_some_and(|c| matches!(c, ' ' | '\t')) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn additive(&mut self) -> std::result::Result<Node, String> {
+        let mut node = self.multiplicative()?;
+        while let Some(operator @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            node = Node::Binary(Box::new(node), operator, Box::new(self.multiplicative()?));
+        }
+        Ok(node)
+    }
+
+    fn multiplicative(&mut self) -> std::result::Result<Node, String> {
+        let mut node = self.unary()?;
+        while let Some(operator @ ('*' | '/' | '%')) = self.peek() {
+            self.position += 1;
+            node = Node::Binary(Box::new(node), operator, Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> std::result::Result<Node, String> {
+        if self.eat('-') {
+            // `-5` is a literal, so that the most negative integer can be written.
+            if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return self.number("-");
+            }
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    /// Digits alone are an integer, and digits with a point a double.
+    fn number(&mut self, sign: &str) -> std::result::Result<Node, String> {
+        let start = self.position;
+        while self.chars.get(self.position).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+            self.position += 1;
+        }
+        let text: String = sign.chars().chain(self.chars[start..self.position].iter().copied()).collect();
+        let number = match text.parse::<i64>() {
+            Ok(v) => Number::Int(v),
+            Err(_) => Number::Float(text.parse().map_err(|_| format!("invalid number '{}'", text))?),
+        };
+        Ok(Node::Number(number))
+    }
+
+    fn primary(&mut self) -> std::result::Result<Node, String> {
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let node = self.additive()?;
+                if !self.eat(')') {
+                    return Err("expected ')'".to_string());
+                }
+                Ok(node)
+            }
+            Some(c) if c.is_ascii_digit() => self.number(""),
+            Some(c) if c.is_alphanumeric() || c == '_' => {
+                let start = self.position;
+                while self.chars.get(self.position).is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+                    self.position += 1;
+                }
+                let name: String = self.chars[start..self.position].iter().collect();
+                if self.peek() == Some('.') {
+                    return Err(format!("'{}' has no components on the CPU", name));
+                }
+                if !self.eat('(') {
+                    return Ok(Node::Variable(name));
+                }
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.additive()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return Err("expected ',' or ')'".to_string());
+                        }
+                    }
+                }
+                Ok(Node::Call(name, args))
+            }
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
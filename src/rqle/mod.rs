@@ -1,2 +1,5 @@
+pub mod check;
+pub mod cpu_executor;
 pub mod rqle_parser;
-pub mod shader_executor;
\ No newline at end of file
+pub mod shader_executor;
+pub mod similarity;
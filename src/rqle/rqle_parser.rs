@@ -1,5 +1,6 @@
 #![allow(unused)]
-#![allow(unused)]
+use crate::types::error::{Position, Result, RqlError};
+use pest::error::LineColLocation;
 use pest::Parser;
 use pest_derive::Parser;
 
@@ -19,31 +20,34 @@ pub struct ExpressionParser {
 }
 
 impl ExpressionParser {
-    pub fn parse(input: &str) -> Result<Self, String> {
-        let pairs = UpdateParser::parse(Rule::update_stmt, input.trim())
-            .map_err(|e| format!("Parse error: {}", e))?;
+    pub fn parse(input: &str) -> Result<Self> {
+        let pairs = UpdateParser::parse(Rule::update_stmt, input.trim()).map_err(|e| {
+            let (line, column) = match e.line_col {
+                LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+            };
+            RqlError::Parse {
+                message: e.variant.message().into_owned(),
+                position: Some(Position { line, column }),
+            }
+        })?;
         let mut assignments = Vec::new();
 
         for pair in pairs {
-            match pair.as_rule() {
-                Rule::update_stmt => {
-                    for inner_pair in pair.into_inner() {
-                        match inner_pair.as_rule() {
-                            Rule::assignments => {
-                                for assignment_pair in inner_pair.into_inner() {
-                                    if let Rule::assignment = assignment_pair.as_rule() {
-                                        let mut inner = assignment_pair.into_inner();
-                                        let variable = inner.next().unwrap().as_str().to_string();
-                                        let expression = inner.next().unwrap().as_str().to_string();
-                                        assignments.push(Assignment { variable, expression });
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
+            if pair.as_rule() != Rule::update_stmt {
+                continue;
+            }
+            for inner_pair in pair.into_inner() {
+                if inner_pair.as_rule() != Rule::assignments {
+                    continue;
+                }
+                for assignment_pair in inner_pair.into_inner() {
+                    if let Rule::assignment = assignment_pair.as_rule() {
+                        let mut inner = assignment_pair.into_inner();
+                        let variable = inner.next().unwrap().as_str().to_string();
+                        let expression = inner.next().unwrap().as_str().to_string();
+                        assignments.push(Assignment { variable, expression });
                     }
                 }
-                _ => {}
             }
         }
 
@@ -1,77 +1,145 @@
-use crate::types::types::{DataType, TableDefinition, Value};
+use crate::rqle::check::CheckPass;
+use crate::types::error::{Result, RqlError};
+use crate::types::types::{DataType, TableDefinition, Timings, Value};
 use std::collections::HashMap;
 use std::sync::Once;
+use std::time::Instant;
 use wgpu::util::DeviceExt;
-use crate::io::util::print_table;
+
+/// The new values of each column, and the rows violating each `CHECK` constraint.
+type ShaderOutput = (HashMap<String, Vec<Value>>, Vec<u32>);
 
 #[derive(Debug)]
 pub struct ShaderExecutor;
 
 impl ShaderExecutor {
-    async fn run(&self, wgsl: String, data: HashMap<String, Vec<Value>>, table_definition: TableDefinition) -> HashMap<String, Vec<Value>> {
+    async fn run(
+        &self,
+        wgsl: String,
+        data: HashMap<String, Vec<Value>>,
+        table_definition: TableDefinition,
+        checks: Option<&CheckPass>,
+        timings: &mut Timings,
+    ) -> Result<ShaderOutput> {
         let keys: Vec<String> = data.keys().cloned().collect();
-        let first_column = data.iter().next().unwrap().clone();
-
-        let instance = wgpu::Instance::default();
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions::default())
-            .await
-            .unwrap();
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::downlevel_defaults(),
-                    memory_hints: wgpu::MemoryHints::MemoryUsage,
-                },
-                None,
-            )
-            .await
-            .unwrap();
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(wgsl.into()),
-        });
+        let Some(total_rows) = data.values().next().map(Vec::len) else {
+            return Ok((HashMap::new(), Vec::new()));
+        };
+        if total_rows == 0 {
+            return Ok((keys.into_iter().map(|key| (key, Vec::new())).collect(), Vec::new()));
+        }
 
-        let buffers: Vec<Vec<u8>> = data
+        let data_types = keys
             .iter()
-            .map(|(key, values)| {
-                let column = table_definition
+            .map(|key| {
+                table_definition
                     .columns
                     .iter()
                     .find(|c| c.name == *key)
-                    .expect("Column not found in table definition");
+                    .map(|c| c.data_type)
+                    .ok_or_else(|| RqlError::Schema(format!("Column '{}' not found in table '{}'", key, table_definition.name)))
+            })
+            .collect::<Result<Vec<DataType>>>()?;
 
-                match column.data_type {
+        let buffers: Vec<Vec<u8>> = keys
+            .iter()
+            .zip(&data_types)
+            .map(|(key, data_type)| {
+                let values = &data[key];
+                let mismatch = |v: &Value| RqlError::Type(format!("Unexpected value {} in column '{}'", v, key));
+                match data_type {
+                    DataType::Double => {
+                        let double_values = values
+                            .iter()
+                            .map(|v| match v {
+                                Value::Double(f) => Ok(*f),
+                                _ => Err(mismatch(v)),
+                            })
+                            .collect::<Result<Vec<f64>>>()?;
+                        Ok(bytemuck::cast_slice(&double_values).to_vec())
+                    }
+                    DataType::BigInt | DataType::Time | DataType::Timestamp => {
+                        let bigint_values = values
+                            .iter()
+                            .map(|v| match v {
+                                Value::BigInt(i) | Value::Time(i) | Value::Timestamp(i) => Ok(*i),
+                                _ => Err(mismatch(v)),
+                            })
+                            .collect::<Result<Vec<i64>>>()?;
+                        Ok(bytemuck::cast_slice(&bigint_values).to_vec())
+                    }
                     DataType::Float => {
-                        let float_values: Vec<f32> = values
+                        let float_values = values
                             .iter()
                             .map(|v| match v {
-                                Value::Float(f) => *f,
-                                _ => panic!("Expected Value::Float for column {}", key),
+                                Value::Float(f) => Ok(*f),
+                                _ => Err(mismatch(v)),
                             })
-                            .collect();
-                        bytemuck::cast_slice(&float_values).to_vec()
+                            .collect::<Result<Vec<f32>>>()?;
+                        Ok(bytemuck::cast_slice(&float_values).to_vec())
                     }
-                    DataType::Integer => {
-                        let int_values: Vec<i32> = values
+                    DataType::Integer | DataType::Date => {
+                        let int_values = values
                             .iter()
                             .map(|v| match v {
-                                Value::Integer(i) => *i,
-                                _ => panic!("Expected Value::Integer for column {}", key),
+                                Value::Integer(i) | Value::Date(i) => Ok(*i),
+                                _ => Err(mismatch(v)),
                             })
-                            .collect();
-
-                        bytemuck::cast_slice(&int_values).to_vec()
+                            .collect::<Result<Vec<i32>>>()?;
+                        Ok(bytemuck::cast_slice(&int_values).to_vec())
                     }
-                    DataType::Text | DataType::Boolean => {
-                        panic!("Unsupported data type for GPU buffers: {}", key)
+                    DataType::Vec2 | DataType::Vec3 | DataType::Vec4 | DataType::Mat4 => {
+                        let stride = stride(*data_type);
+                        let mut float_values = Vec::with_capacity(values.len() * stride);
+                        for value in values {
+                            let components = value.components().ok_or_else(|| mismatch(value))?;
+                            float_values.extend_from_slice(components);
+                            float_values.resize(float_values.len() + stride - components.len(), 0.0);
+                        }
+                        Ok(bytemuck::cast_slice(&float_values).to_vec())
                     }
+                    DataType::Text | DataType::Boolean | DataType::Vector(_) | DataType::Blob => Err(RqlError::Type(format!(
+                        "Column '{}' of type {} cannot be used in a GPU expression",
+                        key,
+                        data_type.to_sql_type()
+                    ))),
                 }
             })
-            .collect();
+            .collect::<Result<_>>()?;
+
+        let upload_start = Instant::now();
+        let adapter = request_adapter().await?;
+        let required_features = required_features(&data_types);
+        if !adapter.features().contains(required_features) {
+            return Err(RqlError::Gpu(format!(
+                "The GPU adapter does not support {:?}, which 64-bit columns need",
+                required_features.difference(adapter.features())
+            )));
+        }
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features,
+                    // Every column is a storage buffer, so take as many as the adapter allows.
+                    required_limits: wgpu::Limits {
+                        max_storage_buffers_per_shader_stage: adapter.limits().max_storage_buffers_per_shader_stage,
+                        ..wgpu::Limits::downlevel_defaults()
+                    },
+                    memory_hints: wgpu::MemoryHints::MemoryUsage,
+                },
+                None,
+            )
+            .await
+            .map_err(|e| RqlError::Gpu(format!("Failed to open GPU device: {}", e)))?;
+
+        // Invalid WGSL is reported through the device's error scope rather than
+        // returned from create_shader_module, so capture it before dispatching.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(wgsl.into()),
+        });
 
         let storage_staging_buffs: Vec<(wgpu::Buffer, wgpu::Buffer)> = buffers
             .iter()
@@ -100,7 +168,29 @@ impl ShaderExecutor {
             })
             .collect();
 
-        let storage_binding_entries: Vec<wgpu::BindGroupEntry> = storage_staging_buffs
+        // The NULL flags and violation counters of the CHECK pass, bound after the columns.
+        let check_buffers = checks.map(|checks| {
+            let nulls = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(&checks.nulls),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+            let size = (checks.checks * size_of::<u32>()) as u64;
+            let violations = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &vec![0; size as usize],
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            });
+            let staging = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            (nulls, [(violations, staging)])
+        });
+
+        let mut storage_binding_entries: Vec<wgpu::BindGroupEntry> = storage_staging_buffs
             .iter()
             .enumerate()
             .map(|(index, (storage_buffer, _staging_buffer))| wgpu::BindGroupEntry {
@@ -108,6 +198,11 @@ impl ShaderExecutor {
                 resource: storage_buffer.as_entire_binding(),
             })
             .collect();
+        if let Some((nulls, [(violations, _)])) = &check_buffers {
+            let binding = storage_binding_entries.len() as u32;
+            storage_binding_entries.push(wgpu::BindGroupEntry { binding, resource: nulls.as_entire_binding() });
+            storage_binding_entries.push(wgpu::BindGroupEntry { binding: binding + 1, resource: violations.as_entire_binding() });
+        }
 
         let bind_group_layout_entries: Vec<wgpu::BindGroupLayoutEntry> = (0..storage_binding_entries.len())
             .map(|index| wgpu::BindGroupLayoutEntry {
@@ -145,6 +240,19 @@ impl ShaderExecutor {
             compilation_options: Default::default(),
             cache: None,
         });
+        let check_pipeline = checks.map(|_| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some("check_constraints"),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        });
+        if let Some(error) = device.pop_error_scope().await {
+            return Err(RqlError::Gpu(format!("Generated shader was rejected: {}", error)));
+        }
 
         let mut command_encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
@@ -155,34 +263,67 @@ impl ShaderExecutor {
             });
             compute_pass.set_pipeline(&pipeline);
             compute_pass.set_bind_group(0, &bind_group, &[]);
-            let total_rows = first_column.1.len();
             let workgroup_size = 64;
-            let num_workgroups = (total_rows + workgroup_size - 1) / workgroup_size;
+            let num_workgroups = total_rows.div_ceil(workgroup_size);
             compute_pass.dispatch_workgroups(num_workgroups as u32, 1, 1);
+            // Runs once `main` has written every row, over the updated columns.
+            if let Some(check_pipeline) = &check_pipeline {
+                compute_pass.set_pipeline(check_pipeline);
+                compute_pass.dispatch_workgroups(num_workgroups as u32, 1, 1);
+            }
         }
+        timings.upload = upload_start.elapsed();
+
+        let compute_start = Instant::now();
         queue.submit(Some(command_encoder.finish()));
+        device.poll(wgpu::Maintain::Wait);
+        timings.compute = compute_start.elapsed();
 
-        let mut outputs: Vec<Vec<f32>> = storage_staging_buffs
+        let mut outputs: Vec<Vec<u8>> = storage_staging_buffs
             .iter()
-            .map(|(storage_buffer, _)| {
-                vec![0.0; (storage_buffer.size() / size_of::<f32>() as u64) as usize]
-            })
+            .map(|(storage_buffer, _)| vec![0; storage_buffer.size() as usize])
             .collect();
 
+        let readback_start = Instant::now();
         self.get_data(
             &mut outputs,
             &storage_staging_buffs,
             &device,
             &queue,
-        ).await;
+        ).await?;
+        let mut violations = [Vec::new()];
+        if let (Some(checks), Some((_, counters))) = (checks, &check_buffers) {
+            violations[0] = vec![0u32; checks.checks];
+            self.get_data(&mut violations, counters, &device, &queue).await?;
+        }
+        timings.readback = readback_start.elapsed();
 
         let mut updated_data = HashMap::new();
-        for (key, output) in keys.into_iter().zip(outputs.into_iter()) {
-            let values = output.into_iter().map(Value::Float).collect();
+        for ((key, data_type), output) in keys.into_iter().zip(data_types).zip(outputs) {
+            let words = output.chunks_exact(4).map(|w| [w[0], w[1], w[2], w[3]]);
+            let double_words = output.chunks_exact(8).map(|w| [w[0], w[1], w[2], w[3], w[4], w[5], w[6], w[7]]);
+            let values = match data_type {
+                DataType::Integer => words.map(|w| Value::Integer(i32::from_le_bytes(w))).collect(),
+                DataType::BigInt => double_words.map(|w| Value::BigInt(i64::from_le_bytes(w))).collect(),
+                DataType::Double => double_words.map(|w| Value::Double(f64::from_le_bytes(w))).collect(),
+                DataType::Date => words.map(|w| Value::Date(i32::from_le_bytes(w))).collect(),
+                DataType::Time => double_words.map(|w| Value::Time(i64::from_le_bytes(w))).collect(),
+                DataType::Timestamp => double_words.map(|w| Value::Timestamp(i64::from_le_bytes(w))).collect(),
+                DataType::Vec2 | DataType::Vec3 | DataType::Vec4 | DataType::Mat4 => {
+                    let floats: Vec<f32> = words.map(f32::from_le_bytes).collect();
+                    floats
+                        .chunks_exact(stride(data_type))
+                        .map(|element| Value::vector(data_type, &element[..data_type.components()]))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| RqlError::Gpu(format!("Malformed GPU output for column '{}'", key)))?
+                }
+                _ => words.map(|w| Value::Float(f32::from_le_bytes(w))).collect(),
+            };
             updated_data.insert(key, values);
         }
 
-        updated_data
+        let [violations] = violations;
+        Ok((updated_data, violations))
     }
 
     async fn get_data<T: bytemuck::Pod>(
@@ -191,7 +332,7 @@ impl ShaderExecutor {
         storage_staging_buffs: &[(wgpu::Buffer, wgpu::Buffer)],
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-    ) {
+    ) -> Result<()> {
         for (output, (storage_buffer, staging_buffer)) in outputs.iter_mut().zip(storage_staging_buffs) {
             let mut command_encoder =
                 device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
@@ -205,20 +346,33 @@ impl ShaderExecutor {
             queue.submit(Some(command_encoder.finish()));
             let buffer_slice = staging_buffer.slice(..);
             let (sender, receiver) = flume::bounded(1);
-            buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+            buffer_slice.map_async(wgpu::MapMode::Read, move |r| {
+                let _ = sender.send(r);
+            });
             device.poll(wgpu::Maintain::Wait);
-            receiver.recv_async().await.unwrap().unwrap();
+            receiver
+                .recv_async()
+                .await
+                .map_err(|e| RqlError::Gpu(format!("GPU readback was cancelled: {}", e)))?
+                .map_err(|e| RqlError::Gpu(format!("Failed to map GPU buffer: {}", e)))?;
             output.copy_from_slice(bytemuck::cast_slice(&buffer_slice.get_mapped_range()[..]));
             staging_buffer.unmap();
         }
+
+        Ok(())
     }
 
-    pub fn main(
+    /// Runs the `main` entry point of `wgsl` once per row over the columns in `data`
+    /// and returns their new values. With `checks`, it then runs `check_constraints`
+    /// and also returns the number of rows violating each constraint.
+    pub(crate) fn main(
         &self,
         wgsl: String,
         data: HashMap<String, Vec<Value>>,
         table_definition: TableDefinition,
-    ) -> HashMap<String, Vec<Value>> {
+        checks: Option<&CheckPass>,
+        timings: &mut Timings,
+    ) -> Result<ShaderOutput> {
         #[cfg(not(target_arch = "wasm32"))]
         {
             static INIT: Once = Once::new();
@@ -229,7 +383,7 @@ impl ShaderExecutor {
                     .init();
             });
 
-            pollster::block_on(self.run(wgsl, data, table_definition))
+            pollster::block_on(self.run(wgsl, data, table_definition, checks, timings))
         }
 
         #[cfg(target_arch = "wasm32")]
@@ -245,13 +399,86 @@ impl ShaderExecutor {
 
             // In WASM, you cannot block on a future, so the function needs to be adjusted
             // to be async if you need the result.
+            let _ = (timings, checks);
             wasm_bindgen_futures::spawn_local(async {
-                self.run(wgsl, data, table_definition).await;
+                let _ = self.run(wgsl, data, table_definition, None, &mut Timings::default()).await;
             });
 
             // Return an empty HashMap or handle WASM differently since it doesn't
             // allow blocking and returning the result directly.
-            HashMap::new() // Placeholder for WASM
+            Ok((HashMap::new(), Vec::new())) // Placeholder for WASM
+        }
+    }
+
+    /// Whether the GPU can run shaders over columns of these types: there must be
+    /// an adapter, with `SHADER_INT64` for BIGINT and `SHADER_F64` for DOUBLE.
+    /// Without one, `UPDATE` runs on the CPU.
+    pub fn supports(&self, data_types: &[DataType]) -> Result<bool> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let features = required_features(data_types);
+            Ok(pollster::block_on(find_adapter()).is_some_and(|adapter| adapter.features().contains(features)))
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = data_types;
+            Ok(false)
         }
     }
-}
\ No newline at end of file
+
+    /// Describes the adapter statements run on: its name, backend and the limits
+    /// that bound how large a table a single shader can process.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn adapter_info(&self) -> Result<Vec<(String, String)>> {
+        let Some(adapter) = pollster::block_on(find_adapter()) else {
+            return Ok(vec![("name".to_string(), "none, statements run on the CPU".to_string())]);
+        };
+        let info = adapter.get_info();
+        let limits = adapter.limits();
+
+        Ok(vec![
+            ("name".to_string(), info.name),
+            ("backend".to_string(), format!("{:?}", info.backend)),
+            ("device type".to_string(), format!("{:?}", info.device_type)),
+            ("driver".to_string(), format!("{} {}", info.driver, info.driver_info).trim().to_string()),
+            ("shader f64".to_string(), adapter.features().contains(wgpu::Features::SHADER_F64).to_string()),
+            ("shader int64".to_string(), adapter.features().contains(wgpu::Features::SHADER_INT64).to_string()),
+            ("max buffer size".to_string(), limits.max_buffer_size.to_string()),
+            ("max storage buffer binding size".to_string(), limits.max_storage_buffer_binding_size.to_string()),
+            ("max storage buffers per stage".to_string(), limits.max_storage_buffers_per_shader_stage.to_string()),
+            ("max workgroups per dimension".to_string(), limits.max_compute_workgroups_per_dimension.to_string()),
+            ("max invocations per workgroup".to_string(), limits.max_compute_invocations_per_workgroup.to_string()),
+        ])
+    }
+}
+
+/// The number of floats an element of an array of `data_type` takes in a storage
+/// buffer. `vec3<f32>` is aligned to 16 bytes, so each one is followed by a float
+/// of padding.
+fn stride(data_type: DataType) -> usize {
+    match data_type {
+        DataType::Vec3 => 4,
+        data_type => data_type.components(),
+    }
+}
+
+fn required_features(data_types: &[DataType]) -> wgpu::Features {
+    let mut features = wgpu::Features::empty();
+    for data_type in data_types {
+        match data_type {
+            DataType::BigInt | DataType::Time | DataType::Timestamp => features |= wgpu::Features::SHADER_INT64,
+            DataType::Double => features |= wgpu::Features::SHADER_F64,
+            _ => {}
+        }
+    }
+    features
+}
+
+async fn find_adapter() -> Option<wgpu::Adapter> {
+    wgpu::Instance::default().request_adapter(&wgpu::RequestAdapterOptions::default()).await
+}
+
+pub(crate) async fn request_adapter() -> Result<wgpu::Adapter> {
+    find_adapter().await.ok_or_else(|| RqlError::Gpu("No compatible GPU adapter found".to_string()))
+}
@@ -0,0 +1,442 @@
+use crate::rqle::shader_executor::request_adapter;
+use crate::types::error::{Result, RqlError};
+use crate::types::types::Timings;
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+
+/// How `l2_distance` and `cosine_distance` measure the distance between two vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Metric {
+    L2,
+    Cosine,
+}
+
+impl Metric {
+    pub(crate) fn from_name(name: &str) -> Option<Metric> {
+        match name {
+            "l2_distance" => Some(Metric::L2),
+            "cosine_distance" => Some(Metric::Cosine),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Metric::L2 => "l2_distance",
+            Metric::Cosine => "cosine_distance",
+        }
+    }
+
+    /// The distance between two vectors of the same size, summed in `f32` in the
+    /// order the shader sums it. The cosine distance to a zero vector is `None`.
+    pub(crate) fn distance(self, a: &[f32], b: &[f32]) -> Option<f32> {
+        match self {
+            Metric::L2 => Some(a.iter().zip(b).fold(0.0f32, |sum, (a, b)| sum + (a - b) * (a - b)).sqrt()),
+            Metric::Cosine => {
+                let (dot, a_norm, b_norm) = a
+                    .iter()
+                    .zip(b)
+                    .fold((0.0f32, 0.0f32, 0.0f32), |(dot, a_norm, b_norm), (a, b)| {
+                        (dot + a * b, a_norm + a * a, b_norm + b * b)
+                    });
+                (a_norm != 0.0 && b_norm != 0.0).then(|| 1.0 - dot / (a_norm.sqrt() * b_norm.sqrt()))
+            }
+        }
+    }
+}
+
+/// Computes the distance of every vector to the query, one invocation per row.
+/// Rows past `count` pad the entries to a power of two and sort after all others.
+const DISTANCE_SHADER: &str = "
+struct Params {
+    count: u32,
+    dimensions: u32,
+    metric: u32,
+    padding: u32,
+}
+
+struct Entry {
+    key: u32,
+    row: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> vectors: array<f32>;
+@group(0) @binding(2) var<storage, read> query: array<f32>;
+@group(0) @binding(3) var<storage, read_write> entries: array<Entry>;
+
+// Maps a float to an integer of the same order, leaving the largest one for NULL.
+fn sort_key(distance: f32) -> u32 {
+    let bits = bitcast<u32>(distance);
+    return select(bits | 0x80000000u, ~bits, (bits & 0x80000000u) != 0u);
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = gid.y * groups.x * 64u + gid.x;
+    if (index >= arrayLength(&entries)) {
+        return;
+    }
+    if (index >= params.count) {
+        entries[index] = Entry(0xffffffffu, 0xffffffffu);
+        return;
+    }
+
+    let start = index * params.dimensions;
+    var squares = 0.0;
+    var dot_product = 0.0;
+    var norm = 0.0;
+    var query_norm = 0.0;
+    for (var i = 0u; i < params.dimensions; i++) {
+        let a = vectors[start + i];
+        let b = query[i];
+        squares += (a - b) * (a - b);
+        dot_product += a * b;
+        norm += a * a;
+        query_norm += b * b;
+    }
+
+    // The square root does not change the order of L2 distances, so it is left out.
+    var key = sort_key(squares);
+    if (params.metric == 1u) {
+        let cosine = sort_key(1.0 - dot_product / (sqrt(norm) * sqrt(query_norm)));
+        key = select(cosine, 0xffffffffu, norm == 0.0 || query_norm == 0.0);
+    }
+    entries[index] = Entry(key, index);
+}
+";
+
+/// Selects the nearest entries with a bitonic sort that is cut short: blocks of
+/// `block` entries are sorted, then pairs of blocks are halved and merged again
+/// until a single sorted block is left. Ties are broken by row, as a stable
+/// sort in storage order would.
+const SELECT_SHADER: &str = "
+struct Params {
+    j: u32,
+    k: u32,
+    count: u32,
+    block: u32,
+}
+
+struct Entry {
+    key: u32,
+    row: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> source: array<Entry>;
+@group(0) @binding(2) var<storage, read_write> entries: array<Entry>;
+
+fn before(a: Entry, b: Entry) -> bool {
+    return a.key < b.key || (a.key == b.key && a.row < b.row);
+}
+
+// Orders entries `i` and `i ^ j`, ascending where bit `k` of `i` is clear and
+// descending where it is set.
+@compute @workgroup_size(64)
+fn exchange(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = gid.y * groups.x * 64u + gid.x;
+    let other = i ^ params.j;
+    if (i >= params.count || other <= i) {
+        return;
+    }
+    let a = entries[i];
+    let b = entries[other];
+    let ascending = (i & params.k) == 0u;
+    if (select(before(a, b), before(b, a), ascending)) {
+        entries[i] = b;
+        entries[other] = a;
+    }
+}
+
+// Of each ascending block and the descending block after it, keeps the smaller
+// entry of every position: a bitonic sequence holding the smallest of the two.
+@compute @workgroup_size(64)
+fn halve(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = gid.y * groups.x * 64u + gid.x;
+    if (i >= params.count) {
+        return;
+    }
+    let first = (i / params.block) * 2u * params.block + i % params.block;
+    let a = source[first];
+    let b = source[first + params.block];
+    if (before(a, b)) {
+        entries[i] = a;
+    } else {
+        entries[i] = b;
+    }
+}
+";
+
+/// One dispatch of the search, with the entry buffer it writes.
+enum Pass {
+    Distances,
+    Exchange { j: u32, k: u32, count: u32, target: usize },
+    Halve { block: u32, count: u32, target: usize },
+}
+
+/// Finds the `k` vectors nearest to `query` on the GPU. `vectors` holds the rows
+/// one after the other, `query.len()` floats each. Returns the positions of the
+/// nearest rows, nearest first; rows at the same distance come in order.
+pub(crate) fn nearest(metric: Metric, query: &[f32], vectors: &[f32], k: usize, timings: &mut Timings) -> Result<Vec<usize>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        pollster::block_on(search(metric, query, vectors, k, timings))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (metric, query, vectors, k, timings);
+        Err(RqlError::Gpu("Nearest neighbour search cannot block on the GPU in WASM".to_string()))
+    }
+}
+
+async fn search(metric: Metric, query: &[f32], vectors: &[f32], k: usize, timings: &mut Timings) -> Result<Vec<usize>> {
+    let rows = vectors.len() / query.len().max(1);
+    let k = k.min(rows);
+    if k == 0 {
+        return Ok(Vec::new());
+    }
+
+    let upload_start = Instant::now();
+    let adapter = request_adapter().await?;
+    let limits = adapter.limits();
+    let size = rows.next_power_of_two();
+    let block = k.next_power_of_two();
+    let largest = size_of_val(vectors).max(size * 2 * size_of::<u32>()) as u64;
+    if largest > limits.max_storage_buffer_binding_size as u64 || largest > limits.max_buffer_size {
+        return Err(RqlError::Gpu(format!("{} vectors do not fit in a GPU storage buffer", rows)));
+    }
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits {
+                    max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+                    max_buffer_size: limits.max_buffer_size,
+                    ..wgpu::Limits::downlevel_defaults()
+                },
+                memory_hints: wgpu::MemoryHints::MemoryUsage,
+            },
+            None,
+        )
+        .await
+        .map_err(|e| RqlError::Gpu(format!("Failed to open GPU device: {}", e)))?;
+    let max_groups = device.limits().max_compute_workgroups_per_dimension as usize;
+    let alignment = device.limits().min_uniform_buffer_offset_alignment as usize;
+
+    // Sort blocks of `block` entries, then halve until one block is left.
+    let mut passes = vec![Pass::Distances];
+    let mut target = 0;
+    let mut k_bit = 2;
+    while k_bit <= block {
+        let mut j = k_bit / 2;
+        while j > 0 {
+            passes.push(Pass::Exchange { j: j as u32, k: k_bit as u32, count: size as u32, target });
+            j /= 2;
+        }
+        k_bit *= 2;
+    }
+    let mut count = size;
+    while count > block {
+        count /= 2;
+        target = 1 - target;
+        passes.push(Pass::Halve { block: block as u32, count: count as u32, target });
+        let mut j = block / 2;
+        while j > 0 {
+            passes.push(Pass::Exchange { j: j as u32, k: block as u32, count: count as u32, target });
+            j /= 2;
+        }
+    }
+
+    let mut params = vec![0u8; passes.len() * alignment];
+    for (pass, chunk) in passes.iter().zip(params.chunks_exact_mut(alignment)) {
+        let words = match *pass {
+            Pass::Distances => [rows as u32, query.len() as u32, (metric == Metric::Cosine) as u32, 0],
+            Pass::Exchange { j, k, count, .. } => [j, k, count, 0],
+            Pass::Halve { block, count, .. } => [0, 0, count, block],
+        };
+        chunk[..16].copy_from_slice(bytemuck::cast_slice(&words));
+    }
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: &params,
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let vectors_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(vectors),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let query_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(query),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let entry_buffers: Vec<wgpu::Buffer> = (0..2)
+        .map(|_| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: (size * 2 * size_of::<u32>()) as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            })
+        })
+        .collect();
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (k * 2 * size_of::<u32>()) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let params_entry = wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: true,
+            min_binding_size: wgpu::BufferSize::new(16),
+        },
+        count: None,
+    };
+    let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    let params_binding = wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+        buffer: &params_buffer,
+        offset: 0,
+        size: wgpu::BufferSize::new(16),
+    });
+
+    let distance_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[params_entry, storage_entry(1, true), storage_entry(2, true), storage_entry(3, false)],
+    });
+    let distance_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &distance_layout,
+        entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: params_binding.clone() },
+            wgpu::BindGroupEntry { binding: 1, resource: vectors_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 2, resource: query_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 3, resource: entry_buffers[0].as_entire_binding() },
+        ],
+    });
+    let select_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[params_entry, storage_entry(1, true), storage_entry(2, false)],
+    });
+    // The group that writes entry buffer `target`, reading the other one.
+    let select_groups: Vec<wgpu::BindGroup> = (0..2)
+        .map(|target| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &select_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: params_binding.clone() },
+                    wgpu::BindGroupEntry { binding: 1, resource: entry_buffers[1 - target].as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: entry_buffers[target].as_entire_binding() },
+                ],
+            })
+        })
+        .collect();
+
+    let pipeline = |layout: &wgpu::BindGroupLayout, source: &str, entry_point: &str| {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            cache: None,
+        })
+    };
+    let distance_pipeline = pipeline(&distance_layout, DISTANCE_SHADER, "main");
+    let exchange_pipeline = pipeline(&select_layout, SELECT_SHADER, "exchange");
+    let halve_pipeline = pipeline(&select_layout, SELECT_SHADER, "halve");
+    if let Some(error) = device.pop_error_scope().await {
+        return Err(RqlError::Gpu(format!("Nearest neighbour shader was rejected: {}", error)));
+    }
+
+    // Invocations are spread over a second dimension when there are more
+    // workgroups than one dimension allows.
+    let workgroups = |threads: usize| {
+        let groups = threads.div_ceil(64).max(1);
+        let x = groups.min(max_groups);
+        (x as u32, groups.div_ceil(x) as u32)
+    };
+    let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: None,
+            timestamp_writes: None,
+        });
+        for (index, pass) in passes.iter().enumerate() {
+            let offset = [(index * alignment) as u32];
+            let threads = match *pass {
+                Pass::Distances => {
+                    compute_pass.set_pipeline(&distance_pipeline);
+                    compute_pass.set_bind_group(0, &distance_group, &offset);
+                    size
+                }
+                Pass::Exchange { count, target, .. } => {
+                    compute_pass.set_pipeline(&exchange_pipeline);
+                    compute_pass.set_bind_group(0, &select_groups[target], &offset);
+                    count as usize
+                }
+                Pass::Halve { count, target, .. } => {
+                    compute_pass.set_pipeline(&halve_pipeline);
+                    compute_pass.set_bind_group(0, &select_groups[target], &offset);
+                    count as usize
+                }
+            };
+            let (x, y) = workgroups(threads);
+            compute_pass.dispatch_workgroups(x, y, 1);
+        }
+    }
+    command_encoder.copy_buffer_to_buffer(&entry_buffers[target], 0, &staging_buffer, 0, staging_buffer.size());
+    timings.upload = upload_start.elapsed();
+
+    let compute_start = Instant::now();
+    queue.submit(Some(command_encoder.finish()));
+    device.poll(wgpu::Maintain::Wait);
+    timings.compute = compute_start.elapsed();
+
+    let readback_start = Instant::now();
+    let buffer_slice = staging_buffer.slice(..);
+    let (sender, receiver) = flume::bounded(1);
+    buffer_slice.map_async(wgpu::MapMode::Read, move |r| {
+        let _ = sender.send(r);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv_async()
+        .await
+        .map_err(|e| RqlError::Gpu(format!("GPU readback was cancelled: {}", e)))?
+        .map_err(|e| RqlError::Gpu(format!("Failed to map GPU buffer: {}", e)))?;
+    let entries: Vec<u32> = bytemuck::cast_slice(&buffer_slice.get_mapped_range()[..]).to_vec();
+    staging_buffer.unmap();
+    timings.readback = readback_start.elapsed();
+
+    Ok(entries.chunks_exact(2).map(|entry| entry[1] as usize).collect())
+}
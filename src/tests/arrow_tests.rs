@@ -0,0 +1,101 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::types::types::{CopyDefinition, CopyFormat};
+    use crate::{ColumnData, DataType, Database, RqlError, Value};
+    use std::fs;
+
+    fn sample(conn: &mut crate::Connection) {
+        conn.execute("CREATE TABLE readings (id INTEGER NOT NULL, value FLOAT, label TEXT, ok BOOLEAN);").unwrap();
+        conn.execute("INSERT INTO readings VALUES (1, 0.5, 'a', true)").unwrap();
+        conn.execute("INSERT INTO readings VALUES (2, NULL, NULL, false)").unwrap();
+        conn.execute("INSERT INTO readings VALUES (3, 2.5, 'c', NULL)").unwrap();
+    }
+
+    #[test]
+    fn test_nulls_are_stored_and_never_equal() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        sample(&mut conn);
+
+        let rows = conn.query("SELECT * FROM readings;").unwrap();
+        assert_eq!(rows.rows()[1].value("value"), Some(&Value::Null));
+        assert_eq!(rows.rows()[1].get::<Option<String>, _>("label").unwrap(), None);
+        assert_eq!(rows.rows()[0].get::<Option<f32>, _>("value").unwrap(), Some(0.5));
+        assert_eq!(conn.query("SELECT * FROM readings WHERE label=NULL;").unwrap().len(), 0);
+
+        let err = conn.execute("INSERT INTO readings VALUES (NULL, 1.0, 'd', true)").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+
+        let batch = conn.query("SELECT * FROM readings;").unwrap().to_batch().unwrap();
+        assert_eq!(batch.data()[1], ColumnData::Float(vec![Some(0.5), None, Some(2.5)]));
+    }
+
+    #[test]
+    fn test_parse_copy_formats() {
+        let copy = CopyDefinition::from_sql("COPY readings TO 'out.parquet';").unwrap();
+        assert_eq!(copy.format, CopyFormat::Parquet);
+
+        let copy = CopyDefinition::from_sql("COPY (SELECT * FROM readings WHERE ok=true) TO 'out' WITH (format 'arrow');").unwrap();
+        assert_eq!(copy.format, CopyFormat::Arrow);
+        assert_eq!(copy.table_name, "readings");
+        assert_eq!(copy.query.as_deref(), Some("SELECT * FROM readings WHERE ok=true"));
+
+        assert!(CopyDefinition::from_sql("COPY (SELECT * FROM readings) FROM 'in.arrow'").is_err());
+        assert!(CopyDefinition::from_sql("COPY readings TO 'out.arrow' WITH (header)").is_err());
+        assert!(CopyDefinition::from_sql("COPY readings TO 'out' WITH (format 'xml')").is_err());
+    }
+
+    #[test]
+    fn test_arrow_and_parquet_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rql_arrow_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        sample(&mut conn);
+        let expected = conn.query("SELECT * FROM readings;").unwrap();
+
+        for file in ["readings.arrow", "readings.parquet"] {
+            let path = dir.join(file);
+            assert_eq!(conn.execute(&format!("COPY readings TO '{}';", path.display())).unwrap(), 3);
+
+            let table = file.replace('.', "_");
+            assert_eq!(conn.execute(&format!("COPY {} FROM '{}';", table, path.display())).unwrap(), 3);
+            let copied = conn.query(&format!("SELECT * FROM {};", table)).unwrap();
+            assert_eq!(copied.rows().iter().map(|r| r.values()).collect::<Vec<_>>(),
+                       expected.rows().iter().map(|r| r.values()).collect::<Vec<_>>());
+            assert!(!copied.columns()[0].is_nullable());
+            assert!(copied.columns()[1].is_nullable());
+
+            // Appending into an existing table converts to its column types.
+            assert_eq!(conn.execute(&format!("COPY {} FROM '{}';", table, path.display())).unwrap(), 3);
+            assert_eq!(conn.query(&format!("SELECT * FROM {};", table)).unwrap().len(), 6);
+        }
+
+        let path = dir.join("active.parquet");
+        let copied = conn
+            .execute(&format!("COPY (SELECT * FROM readings WHERE ok=true) TO '{}';", path.display()))
+            .unwrap();
+        assert_eq!(copied, 1);
+
+        // A text column cannot be read into an INTEGER one, and nothing is written.
+        conn.execute("CREATE TABLE numbers (label INTEGER);").unwrap();
+        let err = conn.execute(&format!("COPY numbers FROM '{}';", path.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Schema(_)), "{}", err);
+        conn.execute("CREATE TABLE labels (id INTEGER, value FLOAT, label INTEGER, ok BOOLEAN);").unwrap();
+        let err = conn.execute(&format!("COPY labels FROM '{}';", path.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+        assert!(conn.query("SELECT * FROM labels;").unwrap().is_empty());
+
+        let types: Vec<DataType> = conn
+            .query("SELECT * FROM readings_parquet;")
+            .unwrap()
+            .columns()
+            .iter()
+            .map(|c| c.data_type())
+            .collect();
+        assert_eq!(types, vec![DataType::Integer, DataType::Float, DataType::Text, DataType::Boolean]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
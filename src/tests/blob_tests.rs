@@ -0,0 +1,134 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::format::format_result;
+    use crate::query::blob::format_base64;
+    use crate::types::types::Literal;
+    use crate::{BlobFormat, DataType, Database, OutputFormat, RqlError, Value};
+    use std::fs;
+
+    #[test]
+    fn test_parse_blob_literals() {
+        assert_eq!(Literal::from_sql("X'00ff1A'", "X'00ff1A'").unwrap(), Literal::Blob(vec![0x00, 0xff, 0x1a]));
+        assert_eq!(Literal::from_sql("x''", "x''").unwrap(), Literal::Blob(Vec::new()));
+        assert_eq!(Literal::Blob(vec![0xde, 0xad]).to_string(), "X'dead'");
+        for sql in ["X'abc'", "X'zz'", "X'+1'", "X'00"] {
+            let err = Literal::from_sql(sql, sql).unwrap_err();
+            assert!(err.position().is_some(), "{}: {}", sql, err);
+        }
+
+        assert_eq!(Literal::Text("\\x0102".to_string()).cast(DataType::Blob), Some(Value::Blob(vec![1, 2])));
+        assert_eq!(Literal::Text("0102".to_string()).cast(DataType::Blob), None);
+        assert_eq!(Value::Blob(vec![0, 0x7f, 0xff]).to_string(), "\\x007fff");
+
+        let encoded: Vec<String> = ["", "f", "fo", "foo", "foobar"].iter().map(|s| format_base64(s.as_bytes())).collect();
+        assert_eq!(encoded, ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYmFy"]);
+    }
+
+    #[test]
+    fn test_blob_columns() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE files (id INTEGER, name TEXT, data BLOB, thumb BYTEA);").unwrap();
+        conn.execute(
+            "INSERT INTO files VALUES (1, 'a.bin', X'00010203', NULL), (2, 'héllo.txt', '\\x68656c6c6f', X''), \
+             (3, 'c', X'ff', X'00ff00ff');",
+        )
+        .unwrap();
+        let err = conn.execute("INSERT INTO files VALUES (4, 'd', 'not hex', NULL);").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM files;").unwrap().columns()[3].data_type(), DataType::Blob);
+
+        let rows = conn.query("SELECT id, data FROM files WHERE data = X'00010203';").unwrap();
+        assert_eq!(rows.rows()[0].get::<Vec<u8>, _>("data").unwrap(), [0, 1, 2, 3]);
+        let rows = conn.query("SELECT id FROM files ORDER BY data DESC;").unwrap();
+        let ids: Vec<i32> = rows.iter().map(|row| row.get::<i32, _>("id").unwrap()).collect();
+        assert_eq!(ids, [3, 2, 1]);
+
+        let rows = conn
+            .query("SELECT length(data) AS n, length(name) AS chars, substring(data, 2, 2) AS part, substring(name, 2) AS rest, length(thumb) AS t FROM files;")
+            .unwrap();
+        assert_eq!(rows.columns()[0].data_type(), DataType::Integer);
+        assert_eq!(rows.columns()[2].data_type(), DataType::Blob);
+        assert_eq!(rows.rows()[0].values(), &[
+            Value::Integer(4),
+            Value::Integer(5),
+            Value::Blob(vec![1, 2]),
+            Value::Text(".bin".to_string()),
+            Value::Null,
+        ]);
+        assert_eq!(rows.rows()[1].value("chars"), Some(&Value::Integer(9)));
+        assert_eq!(rows.rows()[1].value("rest"), Some(&Value::Text("éllo.txt".to_string())));
+        assert_eq!(rows.rows()[1].value("t"), Some(&Value::Integer(0)));
+        assert_eq!(rows.rows()[2].value("part"), Some(&Value::Blob(Vec::new())));
+
+        // A start before the first byte shortens the result, as in PostgreSQL.
+        let rows = conn.query("SELECT substring(data, 0, 2) AS part FROM files WHERE id = 1;").unwrap();
+        assert_eq!(rows.rows()[0].value("part"), Some(&Value::Blob(vec![0])));
+
+        for sql in [
+            "SELECT length(id) FROM files;",
+            "SELECT substring(data, 'x') FROM files;",
+            "SELECT substring(data, 1, -1) FROM files;",
+            "SELECT * FROM files WHERE data = 'abc';",
+        ] {
+            let err = conn.query(sql).unwrap_err();
+            assert!(matches!(err, RqlError::Type(_)), "{}: {}", sql, err);
+        }
+        let err = conn.query("SELECT substring(data) FROM files;").unwrap_err();
+        assert!(err.to_string().contains("substring() takes 2 or 3 arguments"), "{}", err);
+    }
+
+    #[test]
+    fn test_print_blobs_in_hex_or_base64() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE payloads (body BLOB);").unwrap();
+        conn.execute("INSERT INTO payloads VALUES (X'666f6f'), (NULL);").unwrap();
+        let rows = conn.query("SELECT * FROM payloads;").unwrap();
+
+        assert_eq!(format_result(&rows, OutputFormat::Csv, None, BlobFormat::Hex), "body\n\\x666f6f\n\n");
+        assert_eq!(format_result(&rows, OutputFormat::Csv, None, BlobFormat::Base64), "body\nZm9v\n\n");
+        assert_eq!(
+            format_result(&rows, OutputFormat::Ndjson, None, BlobFormat::Hex),
+            "{\"body\":\"\\\\x666f6f\"}\n{\"body\":null}\n"
+        );
+        assert!(format_result(&rows, OutputFormat::Table, None, BlobFormat::Base64).contains(" Zm9v "));
+    }
+
+    #[test]
+    fn test_blobs_are_stored_and_copied() {
+        let dir = std::env::temp_dir().join(format!("rql_blobs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let payload: Vec<u8> = (0..=255).collect();
+        let hex: String = payload.iter().map(|byte| format!("{:02x}", byte)).collect();
+        {
+            let db = Database::open(&dir).unwrap();
+            let mut conn = db.connect();
+            conn.execute("CREATE TABLE packed (id INTEGER, body BLOB) WITH (compression = 'lz4');").unwrap();
+            conn.execute(&format!("INSERT INTO packed VALUES (1, X'{}'), (2, NULL), (3, X'');", hex)).unwrap();
+        }
+
+        let db = Database::open(&dir).unwrap();
+        let mut conn = db.connect();
+        let rows = conn.query("SELECT body FROM packed;").unwrap();
+        let bodies: Vec<&Value> = rows.iter().map(|row| &row.values()[0]).collect();
+        assert_eq!(bodies, [&Value::Blob(payload.clone()), &Value::Null, &Value::Blob(Vec::new())]);
+
+        let parquet = dir.join("packed.parquet");
+        conn.execute(&format!("COPY packed TO '{}';", parquet.display())).unwrap();
+        conn.execute(&format!("COPY from_parquet FROM '{}';", parquet.display())).unwrap();
+        // CSV holds blobs the way they are printed, as `\x` and hexadecimal digits.
+        let csv = dir.join("packed.csv");
+        conn.execute(&format!("COPY packed TO '{}' WITH (header);", csv.display())).unwrap();
+        conn.execute("CREATE TABLE from_csv (id INTEGER, body BLOB);").unwrap();
+        conn.execute(&format!("COPY from_csv FROM '{}' WITH (header);", csv.display())).unwrap();
+
+        for table in ["from_parquet", "from_csv"] {
+            let rows = conn.query(&format!("SELECT body FROM {} ORDER BY id;", table)).unwrap();
+            assert_eq!(rows.columns()[0].data_type(), DataType::Blob, "{}", table);
+            let copied: Vec<&Value> = rows.iter().map(|row| &row.values()[0]).collect();
+            assert_eq!(copied, bodies, "{}", table);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::rqle::check::check_shader;
+    use crate::types::types::{ColumnDefinition, Expression, TableDefinition};
+    use crate::{DataType, Database, RqlError, Value};
+    use std::fs;
+
+    #[test]
+    fn test_parse_checks() {
+        let table = TableDefinition::from_sql(
+            "CREATE TABLE samples (prob FLOAT CHECK (prob >= 0 AND prob <= 1), lo INTEGER, hi INTEGER NOT NULL, \
+             CHECK (lo <= hi));",
+        )
+        .unwrap();
+        assert_eq!(table.checks.len(), 2);
+        assert_eq!(table.checks[1].to_string(), "lo <= hi");
+        let sql = table.to_sql();
+        assert!(sql.contains("CHECK (lo <= hi)"), "{}", sql);
+        assert_eq!(TableDefinition::from_sql(&sql).unwrap().checks, table.checks);
+
+        for sql in [
+            "CREATE TABLE t (a INTEGER CHECK (b > 0));",
+            "CREATE TABLE t (a INTEGER, CHECK (count(*) > 0));",
+            "CREATE TABLE t (a INTEGER, CHECK (a > 0);",
+            "CREATE TABLE t (a INTEGER, CHECK a > 0);",
+        ] {
+            assert!(TableDefinition::from_sql(sql).is_err(), "{}", sql);
+        }
+        assert!(ColumnDefinition::from_sql("a INTEGER CHECK (a > 0)").is_err());
+    }
+
+    #[test]
+    fn test_check_compares_with_parentheses_in_text() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE u (id INTEGER, s TEXT, CHECK (s <> ')'));").unwrap();
+        conn.execute("INSERT INTO u VALUES (1, '(');").unwrap();
+
+        let err = conn.execute("INSERT INTO u VALUES (2, ')');").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("violates CHECK (s <> ')')"), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM u;").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_check_shader_translation() {
+        let check = |sql: &str| {
+            let table = TableDefinition::from_sql(&format!("CREATE TABLE t (a INTEGER, b FLOAT, c DOUBLE, CHECK ({}));", sql)).unwrap();
+            table.checks[0].clone()
+        };
+        let columns = [("a", DataType::Integer), ("b", DataType::Float)];
+        let translate = |expression: &Expression| check_shader(&[expression], &columns, 2);
+
+        let shader = translate(&check("a >= 0 AND b < 1.5 OR b IS NULL")).unwrap();
+        assert!(shader.contains("@binding(3)"), "{}", shader);
+        assert!(shader.contains("fn check_constraints"), "{}", shader);
+        assert!(translate(&check("-b * 2 <> 3")).is_some());
+        assert!(translate(&check("a > -1")).is_some());
+        assert!(translate(&check("b <= -16777216")).is_some());
+        // Division, modulo, integer arithmetic, inexact literals, integers compared
+        // with floats and 64-bit columns are left to the CPU. In f32, a = 16777217
+        // would pass a <= b for b = 16777216.0.
+        let cpu_only = ["a / 2 > 0", "a % 2 = 0", "a * a > 0", "a + 1 > a", "-a <= 0", "b < 0.1", "a <= b", "b >= 16777217", "c > 0"];
+        for sql in cpu_only {
+            assert!(translate(&check(sql)).is_none(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_insert_rejects_violations() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE ranges (lo INTEGER CHECK (lo >= 0), hi INTEGER, CHECK (lo <= hi));").unwrap();
+        conn.execute("INSERT INTO ranges VALUES (0, 1), (2, 2), (NULL, 5), (3, NULL);").unwrap();
+
+        let err = conn.execute("INSERT INTO ranges VALUES (1, 2), (5, 4);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("Row (5, 4) violates CHECK (lo <= hi)"), "{}", err);
+        let err = conn.execute("INSERT INTO ranges VALUES (-1, 0);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM ranges;").unwrap().len(), 4);
+
+        conn.execute("ALTER TABLE ranges ADD COLUMN width INTEGER DEFAULT 1 CHECK (width > 0);").unwrap();
+        let err = conn.execute("ALTER TABLE ranges ADD COLUMN depth INTEGER DEFAULT 0 CHECK (depth > 0);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        let err = conn.execute("INSERT INTO ranges VALUES (1, 1, 0);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM ranges;").unwrap().columns().len(), 3);
+    }
+
+    #[test]
+    fn test_imports_reject_violations() {
+        let dir = std::env::temp_dir().join(format!("rql_check_import_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("ranges.csv");
+        fs::write(&csv, "lo,hi\n1,2\n5,4\n").unwrap();
+        let sqlite_path = dir.join("ranges.db");
+        let sqlite = rusqlite::Connection::open(&sqlite_path).unwrap();
+        sqlite.execute_batch("CREATE TABLE ranges (lo INTEGER, hi INTEGER); INSERT INTO ranges VALUES (1, 2), (-1, 0);").unwrap();
+        drop(sqlite);
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE ranges (lo INTEGER CHECK (lo >= 0), hi INTEGER, CHECK (lo <= hi));").unwrap();
+        let err = conn.execute(&format!("COPY ranges FROM '{}' WITH (header);", csv.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("Row (5, 4) violates CHECK (lo <= hi)"), "{}", err);
+        let err = conn.execute(&format!("IMPORT SQLITE '{}' TABLE ranges;", sqlite_path.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("Row (-1, 0) violates CHECK (lo >= 0)"), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM ranges;").unwrap().len(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_update_rejects_violations_without_writing() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE model (id INTEGER, prob FLOAT CHECK (prob >= 0 AND prob <= 1), w FLOAT);").unwrap();
+        conn.execute("INSERT INTO model VALUES (1, 0.25, 1.0), (2, 0.75, 2.0), (3, NULL, 3.0);").unwrap();
+
+        let err = conn.execute("UPDATE model SET prob = prob * 2.0;").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("1 row would violate CHECK (prob >= 0 AND prob <= 1)"), "{}", err);
+        let rows = conn.query("SELECT prob FROM model ORDER BY id;").unwrap();
+        let probs: Vec<&Value> = rows.iter().map(|row| &row.values()[0]).collect();
+        assert_eq!(probs, [&Value::Float(0.25), &Value::Float(0.75), &Value::Null]);
+
+        // NULL passes a check, and constraints over other columns are not evaluated.
+        conn.execute("UPDATE model SET prob = prob / 2.0, w = w * 10.0;").unwrap();
+        conn.execute("UPDATE model SET w = 0.0 - w;").unwrap();
+        let rows = conn.query("SELECT prob, w FROM model ORDER BY id;").unwrap();
+        assert_eq!(rows.rows()[0].values(), &[Value::Float(0.125), Value::Float(-10.0)]);
+        assert_eq!(rows.rows()[2].values(), &[Value::Null, Value::Float(-30.0)]);
+    }
+
+    #[test]
+    fn test_update_checks_on_the_cpu() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE counts (n INTEGER, total DOUBLE, CHECK (n / 2 < 10), CHECK (total < 100));").unwrap();
+        conn.execute("INSERT INTO counts VALUES (4, 10), (8, 20);").unwrap();
+
+        let err = conn.execute("UPDATE counts SET n = n * 2 + 4;").unwrap_err();
+        assert!(err.to_string().contains("Row (20, 20) violates CHECK (n / 2 < 10)"), "{}", err);
+        let err = conn.execute("UPDATE counts SET total = total * 5.0;").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        let rows = conn.query("SELECT n, total FROM counts ORDER BY n;").unwrap();
+        assert_eq!(rows.rows()[1].values(), &[Value::Integer(8), Value::Double(20.0)]);
+
+        conn.execute("UPDATE counts SET n = n + 1;").unwrap();
+        assert_eq!(conn.query("SELECT * FROM counts WHERE n = 9;").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,27 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::types::types::SelectDefinition;
+    use crate::{Connection, Value};
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Name of the index the planner would use for `sql`, if any.
+    pub fn index_used(conn: &Connection, sql: &str) -> Option<String> {
+        let select = SelectDefinition::from_sql(sql).unwrap();
+        let table = crate::io::reader::read_table_definition(conn.database(), &select.table_name).unwrap();
+        select.index_scan(&table).map(|scan| scan.index.name.clone())
+    }
+
+    /// First column of every row `sql` returns.
+    pub fn column(conn: &mut Connection, sql: &str) -> Vec<Value> {
+        conn.query(sql).unwrap().iter().map(|row| row.values()[0].clone()).collect()
+    }
+
+    /// An empty directory under the system temp dir, unique to this test run.
+    pub fn temp_dir(prefix: &str, name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rql_{}_{}_{}", prefix, std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
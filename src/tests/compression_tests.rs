@@ -0,0 +1,163 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::chunk::{encode_chunks, next_chunk_header, read_chunk_rows, CHUNK_ROWS};
+    use crate::io::chunk::skip_chunk_rows;
+    use crate::io::reader::decode_row;
+    use crate::io::storage::{MemoryStorage, RangeReader, Storage, HEADER_BUFFER};
+    use crate::io::writer::{encode_row, serialize_value};
+    use crate::types::types::{Compression, TableDefinition, Value, WalOp};
+    use crate::Database;
+    use std::collections::BTreeMap;
+    use std::io::{Cursor, Seek};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the bytes read from a storage.
+    #[derive(Debug, Default)]
+    struct CountingStorage {
+        inner: MemoryStorage,
+        bytes_read: AtomicUsize,
+    }
+
+    impl Storage for CountingStorage {
+        fn read(&self, path: &str) -> std::io::Result<Vec<u8>> {
+            let bytes = self.inner.read(path)?;
+            self.bytes_read.fetch_add(bytes.len(), Ordering::Relaxed);
+            Ok(bytes)
+        }
+
+        fn read_range(&self, path: &str, offset: u64, len: usize) -> std::io::Result<Vec<u8>> {
+            let bytes = self.inner.read_range(path, offset, len)?;
+            self.bytes_read.fetch_add(bytes.len(), Ordering::Relaxed);
+            Ok(bytes)
+        }
+
+        fn file_len(&self, path: &str) -> std::io::Result<u64> {
+            self.inner.file_len(path)
+        }
+
+        fn commit(&self, ops: &[WalOp]) -> std::io::Result<()> {
+            self.inner.commit(ops)
+        }
+
+        fn version(&self, path: &str) -> u64 {
+            self.inner.version(path)
+        }
+
+        fn commit_unchanged(&self, ops: &[WalOp], expected: &BTreeMap<String, u64>) -> std::io::Result<Option<String>> {
+            self.inner.commit_unchanged(ops, expected)
+        }
+
+        fn list(&self, dir: &str) -> std::io::Result<Vec<String>> {
+            self.inner.list(dir)
+        }
+    }
+
+    fn floats_table() -> TableDefinition {
+        TableDefinition::from_sql("CREATE TABLE floats (col1 FLOAT, col2 INTEGER) WITH (compression = 'lz4');").unwrap()
+    }
+
+    #[test]
+    fn test_parse_compression_option() {
+        let table = floats_table();
+        assert_eq!(table.compression, Compression::Lz4);
+        assert!(table.to_sql().ends_with("WITH (compression = 'lz4');"));
+
+        let reparsed = TableDefinition::from_sql(&table.to_sql()).unwrap();
+        assert_eq!(reparsed.compression, Compression::Lz4);
+
+        let plain = TableDefinition::from_sql("CREATE TABLE plain (a INTEGER);").unwrap();
+        assert_eq!(plain.compression, Compression::None);
+
+        assert!(TableDefinition::from_sql("CREATE TABLE bad (a INTEGER) WITH (compression = 'zip');").is_err());
+    }
+
+    #[test]
+    fn test_chunks_round_trip_with_stats() {
+        let table = floats_table();
+        let rows: Vec<Vec<u8>> = (0..CHUNK_ROWS + 10)
+            .map(|i| {
+                encode_row(&[
+                    serialize_value(&format!("{}.5", i), &table.columns[0].data_type).unwrap(),
+                    serialize_value(&i.to_string(), &table.columns[1].data_type).unwrap(),
+                ])
+            })
+            .collect();
+
+        let encoded = encode_chunks(&rows, &table).unwrap();
+        let mut cursor = Cursor::new(encoded);
+
+        let first = next_chunk_header(&mut cursor).unwrap().unwrap();
+        assert_eq!(first.row_count, CHUNK_ROWS as u64);
+        assert_eq!(first.stats[1].min, Some(Value::Integer(0)));
+        assert_eq!(first.stats[1].max, Some(Value::Integer(CHUNK_ROWS as i32 - 1)));
+        let first_rows = read_chunk_rows(&mut cursor, &first).unwrap();
+        assert_eq!(first_rows, rows[..CHUNK_ROWS]);
+
+        let second = next_chunk_header(&mut cursor).unwrap().unwrap();
+        assert_eq!(second.row_count, 10);
+        assert_eq!(second.stats[0].min, Some(Value::Float(CHUNK_ROWS as f32 + 0.5)));
+        let second_rows = read_chunk_rows(&mut cursor, &second).unwrap();
+        let last = decode_row(second_rows.last().unwrap(), &table).unwrap();
+        assert_eq!(last.get("col2"), Some(&Value::Integer(CHUNK_ROWS as i32 + 9)));
+
+        assert!(next_chunk_header(&mut cursor).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_chunk_headers_are_read_without_payloads() {
+        let table = floats_table();
+        let rows: Vec<Vec<u8>> = (0..CHUNK_ROWS * 8 + 10)
+            .map(|i| {
+                encode_row(&[
+                    serialize_value(&format!("{}.25", i * 7919 % 100_003), &table.columns[0].data_type).unwrap(),
+                    serialize_value(&(i * 104_729 % 1_000_003).to_string(), &table.columns[1].data_type).unwrap(),
+                ])
+            })
+            .collect();
+        let storage = CountingStorage::default();
+        let path = "data/floats_data.bin";
+        let bytes = encode_chunks(&rows, &table).unwrap();
+        storage.commit(&[WalOp::Write { path: path.to_string(), offset: 0, bytes }]).unwrap();
+
+        let mut file = RangeReader::new(&storage, path, HEADER_BUFFER).unwrap();
+        let mut last = None;
+        loop {
+            let offset = file.stream_position().unwrap();
+            let Some(header) = next_chunk_header(&mut file).unwrap() else {
+                break;
+            };
+            skip_chunk_rows(&mut file, &header).unwrap();
+            last = Some((offset, header));
+        }
+        let (offset, header) = last.unwrap();
+        file.seek(std::io::SeekFrom::Start(offset)).unwrap();
+        next_chunk_header(&mut file).unwrap();
+        assert_eq!(read_chunk_rows(&mut file, &header).unwrap(), rows[CHUNK_ROWS * 8..]);
+
+        let read = storage.bytes_read.load(Ordering::Relaxed);
+        let len = storage.file_len(path).unwrap() as usize;
+        assert!(read < len / 2, "read {} of {} bytes", read, len);
+    }
+
+    #[test]
+    fn test_appends_merge_into_the_last_chunk() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE t (a INTEGER) WITH (compression = 'lz4');").unwrap();
+        for batch in 0..3 {
+            let values: Vec<String> = (0..CHUNK_ROWS / 2 + 1).map(|i| format!("({})", batch * CHUNK_ROWS + i)).collect();
+            conn.execute(&format!("INSERT INTO t VALUES {};", values.join(", "))).unwrap();
+        }
+        assert_eq!(conn.query("SELECT * FROM t;").unwrap().len(), (CHUNK_ROWS / 2 + 1) * 3);
+
+        // Two inserts fill the first chunk and start a second one.
+        let contents = db.storage().read("data/t_data.bin").unwrap();
+        let mut file = Cursor::new(contents);
+        let mut counts = Vec::new();
+        while let Some(header) = next_chunk_header(&mut file).unwrap() {
+            skip_chunk_rows(&mut file, &header).unwrap();
+            counts.push(header.row_count as usize);
+        }
+        assert_eq!(counts, [CHUNK_ROWS, (CHUNK_ROWS / 2 + 1) * 3 - CHUNK_ROWS]);
+    }
+}
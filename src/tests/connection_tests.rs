@@ -0,0 +1,119 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::{DataType, Database, RqlError, Value};
+
+    #[test]
+    fn test_query_returns_typed_rows_in_schema_order() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+
+        assert_eq!(conn.execute("CREATE TABLE users (id INTEGER NOT NULL, name TEXT, is_active BOOLEAN, score FLOAT);").unwrap(), 0);
+        assert_eq!(conn.execute("INSERT INTO users VALUES (1, 'Leo', true, 0.5)").unwrap(), 1);
+        conn.execute("INSERT INTO users VALUES (2, 'Ryan', false, 1.5)").unwrap();
+
+        let rows = conn.query("SELECT * FROM users WHERE is_active=false;").unwrap();
+        let names: Vec<&str> = rows.columns().iter().map(|c| c.name()).collect();
+        assert_eq!(names, vec!["id", "name", "is_active", "score"]);
+        assert_eq!(rows.columns()[3].data_type(), DataType::Float);
+        assert_eq!(rows.len(), 1);
+
+        let row = &rows.rows()[0];
+        assert_eq!(row.get::<i32, _>("id").unwrap(), 2);
+        assert_eq!(row.get::<String, _>(1).unwrap(), "Ryan");
+        assert!(!row.get::<bool, _>("is_active").unwrap());
+        assert_eq!(row.get::<f64, _>("score").unwrap(), 1.5);
+        assert_eq!(row.value("name"), Some(&Value::Text("Ryan".to_string())));
+
+        assert!(row.get::<i32, _>("name").is_err());
+        assert!(row.get::<i32, _>("missing").is_err());
+        assert!(row.get::<i32, _>(4).is_err());
+    }
+
+    #[test]
+    fn test_statement_errors_are_returned() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+
+        assert!(conn.execute("CREATE TABLE broken").is_err());
+        assert!(conn.query("SELECT * FROM missing;").is_err());
+        assert!(conn.execute("DROP EVERYTHING").is_err());
+
+        conn.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        assert!(conn.query("INSERT INTO t VALUES (1)").is_err());
+        assert!(conn.execute("INSERT INTO t VALUES (1, 2)").is_err());
+
+        // Creating a table again keeps the existing one and its rows.
+        conn.execute("CREATE TABLE u (a INTEGER);").unwrap();
+        conn.execute("INSERT INTO u VALUES (1);").unwrap();
+        let err = conn.execute("CREATE TABLE u (b TEXT);").unwrap_err();
+        assert!(matches!(err, RqlError::Schema(_)), "{}", err);
+        assert_eq!(err.to_string(), "Schema error: Table 'u' already exists");
+        let rows = conn.query("SELECT * FROM u;").unwrap();
+        assert_eq!((rows.len(), rows.columns()[0].name()), (1, "a"));
+    }
+
+    #[test]
+    fn test_connections_have_separate_transactions() {
+        let db = Database::open_in_memory();
+        let mut writer = db.connect();
+        let mut reader = db.connect();
+
+        writer.execute("CREATE TABLE t (a INTEGER);").unwrap();
+        writer.execute("BEGIN;").unwrap();
+        writer.execute("INSERT INTO t VALUES (1)").unwrap();
+        assert!(writer.in_transaction());
+        assert!(!reader.in_transaction());
+
+        assert_eq!(writer.query("SELECT * FROM t;").unwrap().len(), 1);
+        assert_eq!(reader.query("SELECT * FROM t;").unwrap().len(), 0);
+
+        writer.execute("COMMIT;").unwrap();
+        assert_eq!(reader.query("SELECT * FROM t;").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_update_keeps_non_numeric_columns() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE users (id INTEGER, name TEXT, score FLOAT);").unwrap();
+        conn.execute("INSERT INTO users VALUES (1, 'Leo', 0.5)").unwrap();
+        conn.execute("INSERT INTO users VALUES (2, 'Ryan', 1.5)").unwrap();
+
+        match conn.execute("UPDATE users SET id = id * 10, score = score * 2.0;") {
+            Err(crate::RqlError::Gpu(e)) => {
+                eprintln!("Skipping, no usable GPU: {}", e);
+                return;
+            }
+            result => assert_eq!(result.unwrap(), 2),
+        }
+
+        let rows = conn.query("SELECT * FROM users;").unwrap();
+        let row = &rows.rows()[1];
+        assert_eq!(row.get::<i32, _>("id").unwrap(), 20);
+        assert_eq!(row.get::<String, _>("name").unwrap(), "Ryan");
+        assert_eq!(row.get::<f32, _>("score").unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_update_propagates_nulls() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE f (a FLOAT, b FLOAT, c INTEGER NOT NULL);").unwrap();
+        conn.execute("INSERT INTO f VALUES (1.0, 2.0, 1)").unwrap();
+        conn.execute("INSERT INTO f VALUES (NULL, 3.0, 2)").unwrap();
+
+        match conn.execute("UPDATE f SET t = a * 2.0, b = t + b, c = c * 3;") {
+            Err(crate::RqlError::Gpu(e)) => {
+                eprintln!("Skipping, no usable GPU: {}", e);
+                return;
+            }
+            result => assert_eq!(result.unwrap(), 2),
+        }
+
+        let rows = conn.query("SELECT * FROM f;").unwrap();
+        assert_eq!(rows.rows()[0].get::<Option<f32>, _>("b").unwrap(), Some(4.0));
+        assert_eq!(rows.rows()[1].get::<Option<f32>, _>("a").unwrap(), None);
+        assert_eq!(rows.rows()[1].get::<Option<f32>, _>("b").unwrap(), None);
+        assert_eq!(rows.rows()[1].get::<i32, _>("c").unwrap(), 6);
+    }
+}
@@ -0,0 +1,194 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::reader::read_table_definition;
+    use crate::types::types::{CopyDefinition, Expression, Literal, TableDefinition};
+    use crate::{DataType, Database, RqlError, Value};
+    use std::fs;
+
+    #[test]
+    fn test_parse_keys_identity_and_defaults() {
+        let table = TableDefinition::from_sql(
+            "CREATE TABLE orders (id BIGINT GENERATED BY DEFAULT AS IDENTITY, customer INTEGER NOT NULL, \
+             note TEXT DEFAULT 'n/a, none', placed TIMESTAMP DEFAULT now(), qty INTEGER DEFAULT -1 NOT NULL, \
+             PRIMARY KEY (customer, id));",
+        )
+        .unwrap();
+        assert!(table.columns[0].auto_increment);
+        assert_eq!(table.primary_key(), [0, 1]);
+        assert!(!table.columns[0].nullable);
+        assert_eq!(table.columns[2].default, Some(Expression::Literal(Literal::Text("n/a, none".to_string()))));
+        assert!(!table.columns[4].nullable);
+
+        let sql = table.to_sql();
+        assert!(sql.contains("id BIGINT NOT NULL AUTOINCREMENT"), "{}", sql);
+        assert!(sql.contains("placed TIMESTAMP DEFAULT now()"), "{}", sql);
+        assert!(sql.contains("PRIMARY KEY (id, customer)"), "{}", sql);
+        let reparsed = TableDefinition::from_sql(&sql).unwrap();
+        assert_eq!(reparsed.columns, table.columns);
+
+        let single = TableDefinition::from_sql("CREATE TABLE t (id INTEGER primary key autoincrement, primary TEXT);").unwrap();
+        assert_eq!(single.primary_key(), [0]);
+        assert_eq!(single.columns[1].data_type, DataType::Text);
+        assert_eq!(single.to_sql(), "CREATE TABLE t (\n  id INTEGER PRIMARY KEY AUTOINCREMENT,\n  primary TEXT\n);");
+
+        for sql in [
+            "CREATE TABLE t (a INTEGER PRIMARY KEY, b INTEGER PRIMARY KEY);",
+            "CREATE TABLE t (a INTEGER PRIMARY KEY, PRIMARY KEY (a));",
+            "CREATE TABLE t (a INTEGER, PRIMARY KEY (b));",
+            "CREATE TABLE t (a TEXT AUTOINCREMENT);",
+            "CREATE TABLE t (a INTEGER AUTOINCREMENT, b BIGINT AUTO_INCREMENT);",
+            "CREATE TABLE t (a INTEGER AUTOINCREMENT DEFAULT 1);",
+            "CREATE TABLE t (a INTEGER DEFAULT 'x');",
+            "CREATE TABLE t (a INTEGER DEFAULT b + 1);",
+            "CREATE TABLE t (a INTEGER DEFAULT count(*));",
+            "CREATE TABLE t (a INTEGER DEFAULT);",
+        ] {
+            assert!(TableDefinition::from_sql(sql).is_err(), "{}", sql);
+        }
+
+        // Text after the type used to be ignored.
+        let err = TableDefinition::from_sql("CREATE TABLE t (a INTEGER NOT NUL);").unwrap_err();
+        assert!(err.position().is_some(), "{}", err);
+        let err = TableDefinition::from_sql("CREATE TABLE t (a INTEGER CHECKED);").unwrap_err();
+        assert!(err.to_string().contains("Unexpected 'CHECKED'"), "{}", err);
+    }
+
+    #[test]
+    fn test_parentheses_inside_quoted_defaults() {
+        let table = TableDefinition::from_sql("CREATE TABLE t (id INTEGER, s TEXT DEFAULT ')', n INTEGER DEFAULT 2);").unwrap();
+        assert_eq!(table.columns.len(), 3);
+        assert_eq!(table.columns[1].default, Some(Expression::Literal(Literal::Text(")".to_string()))));
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE t (id INTEGER, s TEXT DEFAULT ')', n INTEGER DEFAULT 2);").unwrap();
+        conn.execute("INSERT INTO t (id) VALUES (1);").unwrap();
+        conn.execute("INSERT INTO t (id, s) VALUES (2, '(');").unwrap();
+        let rows = conn.query("SELECT s, n FROM t;").unwrap();
+        assert_eq!(rows.rows()[0].values(), [Value::Text(")".to_string()), Value::Integer(2)]);
+        assert_eq!(rows.rows()[1].values(), [Value::Text("(".to_string()), Value::Integer(2)]);
+
+        let copy = CopyDefinition::from_sql("COPY (SELECT * FROM t WHERE s = ')') TO 'out.csv';").unwrap();
+        assert_eq!(copy.query.as_deref(), Some("SELECT * FROM t WHERE s = ')'"));
+    }
+
+    #[test]
+    fn test_insert_fills_defaults_and_ids() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute(
+            "CREATE TABLE events (id INTEGER PRIMARY KEY AUTOINCREMENT, kind TEXT DEFAULT 'click', \
+             weight DOUBLE DEFAULT 1.5 * 2, at DATE DEFAULT '2024-01-31', seen TIMESTAMP DEFAULT now());",
+        )
+        .unwrap();
+        conn.execute("INSERT INTO events (kind) VALUES ('view'), (DEFAULT);").unwrap();
+        conn.execute("INSERT INTO events VALUES (10, 'scroll', NULL, NULL, NULL);").unwrap();
+        conn.execute("INSERT INTO events (id, weight) VALUES (NULL, 0), (DEFAULT, 1);").unwrap();
+
+        let rows = conn.query("SELECT id, kind, weight, at FROM events ORDER BY id;").unwrap();
+        let ids: Vec<i32> = rows.iter().map(|row| row.get::<i32, _>("id").unwrap()).collect();
+        assert_eq!(ids, [1, 2, 10, 11, 12]);
+        assert_eq!(rows.rows()[0].values()[1..], [
+            Value::Text("view".to_string()),
+            Value::Double(3.0),
+            Value::Date(19753),
+        ]);
+        assert_eq!(rows.rows()[1].value("kind"), Some(&Value::Text("click".to_string())));
+        assert_eq!(rows.rows()[2].value("weight"), Some(&Value::Null));
+        assert_eq!(rows.rows()[3].value("weight"), Some(&Value::Double(0.0)));
+        let seen = conn.query("SELECT seen FROM events WHERE seen IS NOT NULL;").unwrap();
+        assert_eq!(seen.len(), 4);
+
+        conn.execute("INSERT INTO events (kind) SELECT kind FROM events WHERE id < 3;").unwrap();
+        let rows = conn.query("SELECT id FROM events WHERE id > 12;").unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn test_primary_key_rejects_duplicates() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE stock (store INTEGER, item TEXT, qty INTEGER, PRIMARY KEY (store, item));").unwrap();
+        conn.execute("INSERT INTO stock VALUES (1, 'apple', 3), (1, 'pear', 4), (2, 'apple', 5);").unwrap();
+
+        let err = conn.execute("INSERT INTO stock VALUES (3, 'fig', 1), (1, 'pear', 9);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("(store, item) = (1, pear)"), "{}", err);
+        let err = conn.execute("INSERT INTO stock VALUES (4, 'kiwi', 1), (4, 'kiwi', 2);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        let err = conn.execute("INSERT INTO stock (store, qty) VALUES (5, 1);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM stock;").unwrap().len(), 3);
+
+        // An explicit id moves the counter, so generated ids never repeat it.
+        conn.execute("CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);").unwrap();
+        conn.execute("INSERT INTO users VALUES (2, 'a');").unwrap();
+        conn.execute("INSERT INTO users (name) VALUES ('b');").unwrap();
+        let err = conn.execute("INSERT INTO users VALUES (3, 'c');").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_counter_is_persisted_with_the_rows() {
+        let dir = std::env::temp_dir().join(format!("rql_identity_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        {
+            let db = Database::open(&dir).unwrap();
+            let mut conn = db.connect();
+            conn.execute("CREATE TABLE logs (id BIGINT AUTO_INCREMENT, line TEXT) WITH (compression = 'lz4');").unwrap();
+            conn.execute("INSERT INTO logs (line) VALUES ('a'), ('b');").unwrap();
+            conn.execute("BEGIN;").unwrap();
+            conn.execute("INSERT INTO logs (line) VALUES ('c');").unwrap();
+            conn.execute("ROLLBACK;").unwrap();
+        }
+
+        let db = Database::open(&dir).unwrap();
+        assert_eq!(read_table_definition(&db, "logs").unwrap().last_id, 2);
+        let mut conn = db.connect();
+        conn.execute("INSERT INTO logs (line) VALUES ('d');").unwrap();
+        let rows = conn.query("SELECT id, line FROM logs ORDER BY id;").unwrap();
+        let ids: Vec<i64> = rows.iter().map(|row| row.get::<i64, _>("id").unwrap()).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_alter_table_add_column() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE items (name TEXT);").unwrap();
+        conn.execute("INSERT INTO items VALUES ('a'), ('b');").unwrap();
+
+        conn.execute("ALTER TABLE items ADD COLUMN qty INTEGER NOT NULL DEFAULT 0;").unwrap();
+        conn.execute("ALTER TABLE items ADD id BIGINT AUTOINCREMENT;").unwrap();
+        conn.execute("ALTER TABLE items ADD COLUMN note TEXT;").unwrap();
+        conn.execute("INSERT INTO items (name) VALUES ('c');").unwrap();
+
+        let rows = conn.query("SELECT * FROM items;").unwrap();
+        assert_eq!(rows.rows()[0].values(), &[
+            Value::Text("a".to_string()),
+            Value::Integer(0),
+            Value::BigInt(1),
+            Value::Null,
+        ]);
+        assert_eq!(rows.rows()[2].values()[1..3], [Value::Integer(0), Value::BigInt(3)]);
+
+        for sql in [
+            "ALTER TABLE items ADD COLUMN name TEXT;",
+            "ALTER TABLE items ADD COLUMN code TEXT NOT NULL;",
+            "ALTER TABLE items ADD COLUMN key INTEGER PRIMARY KEY;",
+            "ALTER TABLE items ADD COLUMN seq INTEGER AUTOINCREMENT;",
+            "ALTER TABLE items DROP COLUMN note;",
+            "ALTER TABLE missing ADD COLUMN x INTEGER;",
+        ] {
+            assert!(conn.execute(sql).is_err(), "{}", sql);
+        }
+        assert_eq!(conn.query("SELECT * FROM items;").unwrap().columns().len(), 4);
+
+        // A NOT NULL column without a default can be added while the table is empty.
+        conn.execute("CREATE TABLE empty (a INTEGER);").unwrap();
+        conn.execute("ALTER TABLE empty ADD COLUMN b TEXT NOT NULL;").unwrap();
+        let err = conn.execute("INSERT INTO empty (a) VALUES (1);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+    }
+}
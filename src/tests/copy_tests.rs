@@ -0,0 +1,180 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::copy::IMPORT_BATCH_ROWS;
+    use crate::tests::common::tests::{column, temp_dir};
+    use crate::types::types::{CopyDefinition, CopyDirection};
+    use crate::{DataType, Database, RqlError, Value};
+    use std::fs;
+
+    #[test]
+    fn test_parse_copy() {
+        let copy = CopyDefinition::from_sql("COPY floats FROM 'in.csv' WITH (header, delimiter ';');").unwrap();
+        assert_eq!(copy.table_name, "floats");
+        assert_eq!(copy.direction, CopyDirection::From);
+        assert_eq!(copy.path, "in.csv");
+        assert!(copy.header);
+        assert_eq!(copy.delimiter, b';');
+
+        let copy = CopyDefinition::from_sql("COPY floats TO 'out.csv'").unwrap();
+        assert_eq!(copy.direction, CopyDirection::To);
+        assert!(!copy.header);
+        assert_eq!(copy.delimiter, b',');
+
+        assert!(CopyDefinition::from_sql("COPY floats FROM 'in.csv' WITH (delimiter ',,')").is_err());
+        assert!(CopyDefinition::from_sql("COPY floats INTO 'in.csv'").is_err());
+    }
+
+    #[test]
+    fn test_import_infers_schema_and_round_trips() {
+        let dir = std::env::temp_dir().join(format!("rql_copy_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.csv");
+        let output = dir.join("out.csv");
+        fs::write(&input, "id,score,active,\"full name\"\n1,0.5,true,\"Doe, Jane\"\n2,3,false,Leo\n").unwrap();
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        let imported = conn.execute(&format!("COPY people FROM '{}' WITH (header);", input.display())).unwrap();
+        assert_eq!(imported, 2);
+
+        let rows = conn.query("SELECT * FROM people;").unwrap();
+        let types: Vec<DataType> = rows.columns().iter().map(|c| c.data_type()).collect();
+        assert_eq!(types, vec![DataType::Integer, DataType::Float, DataType::Boolean, DataType::Text]);
+        assert_eq!(rows.columns()[3].name(), "full_name");
+        assert_eq!(rows.rows()[0].get::<String, _>("full_name").unwrap(), "Doe, Jane");
+
+        conn.execute(&format!("COPY people TO '{}' WITH (header);", output.display())).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "id,score,active,full_name\n1,0.5,true,\"Doe, Jane\"\n2,3,false,Leo\n"
+        );
+
+        // A bad row anywhere in the file leaves the table untouched.
+        fs::write(&input, "id,score,active,full name\n3,1.0,true,Ann\nfour,1.0,true,Bob\n").unwrap();
+        let err = conn.execute(&format!("COPY people FROM '{}' WITH (header);", input.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM people;").unwrap().len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_advances_autoincrement() {
+        let dir = std::env::temp_dir().join(format!("rql_copy_auto_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.csv");
+        fs::write(&input, ",a\n10,b\n,c\n").unwrap();
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE t (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);").unwrap();
+        conn.execute("INSERT INTO t (name) VALUES ('x');").unwrap();
+        conn.execute(&format!("COPY t FROM '{}';", input.display())).unwrap();
+        conn.execute("INSERT INTO t (name) VALUES ('y');").unwrap();
+
+        let ids: Vec<Value> = conn.query("SELECT id FROM t;").unwrap().iter().map(|row| row.values()[0].clone()).collect();
+        assert_eq!(ids, [1, 2, 10, 11, 12].map(Value::Integer));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_empty_fields_are_null_and_quoted_ones_empty_strings() {
+        let dir = temp_dir("copy", "empty");
+        let input = dir.join("in.csv");
+        let output = dir.join("out.csv");
+        fs::write(&input, "id,note,tag\n1,\"two\nlines, \"\"quoted\"\"\",\"\"\n,\"\",\n3,,x\n").unwrap();
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute(&format!("COPY t FROM '{}' WITH (header);", input.display())).unwrap();
+        let rows = conn.query("SELECT * FROM t;").unwrap();
+        let types: Vec<DataType> = rows.columns().iter().map(|c| c.data_type()).collect();
+        assert_eq!(types, vec![DataType::Integer, DataType::Text, DataType::Text]);
+        let text = |v: &str| Value::Text(v.to_string());
+        let values: Vec<Vec<Value>> = rows.iter().map(|row| row.values().to_vec()).collect();
+        assert_eq!(
+            values,
+            vec![
+                vec![Value::Integer(1), text("two\nlines, \"quoted\""), text("")],
+                vec![Value::Null, text(""), Value::Null],
+                vec![Value::Integer(3), Value::Null, text("x")],
+            ]
+        );
+
+        // COPY TO writes them the same way, so the file reads back unchanged.
+        conn.execute(&format!("COPY t TO '{}' WITH (header);", output.display())).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), fs::read_to_string(&input).unwrap());
+
+        conn.execute("CREATE TABLE strict (id INTEGER, note TEXT NOT NULL);").unwrap();
+        fs::write(&input, "1,\"\"\n2,\n").unwrap();
+        let err = conn.execute(&format!("COPY strict FROM '{}';", input.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("line 2: column 'note' cannot be NULL"), "{}", err);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_import_leaves_no_batch_behind() {
+        let dir = std::env::temp_dir().join(format!("rql_copy_batches_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.csv");
+        let rows = IMPORT_BATCH_ROWS + 10;
+        let mut csv: String = (0..rows).map(|n| format!("{}\n", n)).collect();
+        fs::write(&input, &csv).unwrap();
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute(&format!("CREATE TABLE t (n INTEGER CHECK (n < {}));", rows - 1)).unwrap();
+
+        // The last row fails in the second batch, after the first was written.
+        let err = conn.execute(&format!("COPY t FROM '{}';", input.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM t;").unwrap().len(), 0);
+
+        // Inside a transaction the import is staged with the other statements.
+        csv.truncate(csv.len() - format!("{}\n", rows - 1).len());
+        fs::write(&input, &csv).unwrap();
+        conn.execute("BEGIN;").unwrap();
+        assert_eq!(conn.execute(&format!("COPY t FROM '{}';", input.display())).unwrap(), rows - 1);
+        assert_eq!(conn.query("SELECT * FROM t;").unwrap().len(), rows - 1);
+        conn.execute("ROLLBACK;").unwrap();
+        assert_eq!(conn.query("SELECT * FROM t;").unwrap().len(), 0);
+        assert_eq!(conn.execute(&format!("COPY t FROM '{}';", input.display())).unwrap(), rows - 1);
+        assert_eq!(conn.query("SELECT * FROM t;").unwrap().len(), rows - 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_import_restores_chunks_and_indexes() {
+        let dir = temp_dir("copy", "undo");
+        let input = dir.join("in.csv");
+        let rows = IMPORT_BATCH_ROWS + 10;
+        let csv: String = (10..rows + 10).map(|n| format!("{},{}\n", n, n % 7)).collect();
+        fs::write(&input, &csv).unwrap();
+
+        let db = Database::open(dir.join("db")).unwrap();
+        let mut conn = db.connect();
+        conn.execute(&format!(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, k INTEGER CHECK (k < 7 AND id < {})) WITH (compression = 'lz4');",
+            rows + 9
+        ))
+        .unwrap();
+        conn.execute("CREATE INDEX by_k ON t (k);").unwrap();
+        for id in 0..10 {
+            conn.execute(&format!("INSERT INTO t VALUES ({}, {});", id, id % 7)).unwrap();
+        }
+
+        // The first batch is merged into the last chunk before the last row fails.
+        let err = conn.execute(&format!("COPY t FROM '{}';", input.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert_eq!(column(&mut conn, "SELECT id FROM t;"), (0..10).map(Value::Integer).collect::<Vec<_>>());
+        assert_eq!(column(&mut conn, "SELECT id FROM t WHERE k = 3;"), vec![Value::Integer(3)]);
+        conn.execute("INSERT INTO t VALUES (10, 3);").unwrap();
+        assert_eq!(column(&mut conn, "SELECT id FROM t WHERE id = 10;"), vec![Value::Integer(10)]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::database::database::Database;
+    use crate::io::writer::write_table_definition;
+    use crate::tests::common::tests::temp_dir;
+    use crate::types::types::{AttachDefinition, DetachDefinition, InsertDefinition, SelectDefinition, TableDefinition, Timings, Value};
+    use crate::RqlError;
+    use std::fs;
+
+    #[test]
+    fn test_parse_attach_and_detach() {
+        let attach = AttachDefinition::from_sql("ATTACH DATABASE '/tmp/other db' AS other;").unwrap();
+        assert_eq!(attach.path, "/tmp/other db");
+        assert_eq!(attach.name, "other");
+        assert!(AttachDefinition::from_sql("ATTACH 'x'").is_err());
+
+        assert_eq!(DetachDefinition::from_sql("DETACH DATABASE other;").unwrap().name, "other");
+    }
+
+    #[test]
+    fn test_cross_database_queries() {
+        let main_dir = temp_dir("db", "main");
+        let other_dir = temp_dir("db", "other");
+
+        let mut db = Database::open(&main_dir).unwrap();
+        db.attach(&other_dir, "other").unwrap();
+        assert!(db.attach(&other_dir, "other").is_err());
+
+        let table = TableDefinition::from_sql("CREATE TABLE other.readings (id INTEGER, value FLOAT);").unwrap();
+        write_table_definition(&db, &table).unwrap();
+        InsertDefinition::from_sql("INSERT INTO other.readings VALUES (1, 0.5)")
+            .unwrap()
+            .validate_and_insert(&db)
+            .unwrap();
+
+        assert!(other_dir.join("schema").join("readings_def.bin").exists());
+        assert!(other_dir.join("data").join("readings_data.bin").exists());
+        assert!(!main_dir.join("schema").exists());
+
+        let rows = SelectDefinition::from_sql("SELECT * FROM other.readings WHERE id = 1;")
+            .unwrap()
+            .execute(&db, &mut Timings::default())
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows.rows()[0].value("value"), Some(&Value::Float(0.5)));
+        assert_eq!(db.tables().unwrap(), vec!["other.readings".to_string()]);
+
+        db.detach("other").unwrap();
+        assert!(SelectDefinition::from_sql("SELECT * FROM other.readings;").unwrap().execute(&db, &mut Timings::default()).is_err());
+
+        fs::remove_dir_all(main_dir).unwrap();
+        fs::remove_dir_all(other_dir).unwrap();
+    }
+
+    #[test]
+    fn test_table_names_cannot_leave_the_database() {
+        let dir = temp_dir("db", "names");
+        let db = Database::open(dir.join("db")).unwrap();
+        let mut conn = db.connect();
+        for sql in [
+            "CREATE TABLE ../escaped (id INTEGER);",
+            "CREATE TABLE x/y (id INTEGER);",
+            "CREATE TABLE main.a.b (id INTEGER);",
+            "CREATE TABLE main. (id INTEGER);",
+            "SELECT * FROM ../escaped;",
+        ] {
+            let err = conn.execute(sql).unwrap_err();
+            assert!(matches!(err, RqlError::Parse { .. }), "{}: {}", sql, err);
+        }
+        conn.execute("CREATE TABLE t (id INTEGER);").unwrap();
+        let err = conn.execute("CREATE INDEX ../i ON t (id);").unwrap_err();
+        assert!(matches!(err, RqlError::Parse { .. }), "{}", err);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::types::error::Position;
+    use crate::types::types::TableDefinition;
+    use crate::{Database, RqlError};
+
+    #[test]
+    fn test_parse_errors_carry_positions() {
+        let err = TableDefinition::from_sql("CREATE TABLE users (\n    id INTEGER,\n    name STRING\n);").unwrap_err();
+        assert!(matches!(err, RqlError::Parse { .. }));
+        assert_eq!(err.position(), Some(Position { line: 3, column: 10 }));
+        assert_eq!(err.to_string(), "Parse error at line 3, column 10: Unknown column type 'STRING'");
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE floats (col1 FLOAT, col2 FLOAT);").unwrap();
+        conn.execute("INSERT INTO floats VALUES (1.0, 2.0)").unwrap();
+
+        let err = conn.execute("UPDATE floats SET col1 = col2 * ;").unwrap_err();
+        assert!(matches!(err, RqlError::Parse { position: Some(_), .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_statement_errors_are_classified() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE users (id INTEGER, name TEXT);").unwrap();
+
+        assert!(matches!(conn.query("SELECT * FROM missing;"), Err(RqlError::Schema(_))));
+        assert!(matches!(conn.execute("INSERT INTO users VALUES (1)"), Err(RqlError::Schema(_))));
+        assert!(matches!(conn.execute("INSERT INTO users VALUES ('one', 'Leo')"), Err(RqlError::Type(_))));
+        assert!(matches!(conn.execute("COMMIT"), Err(RqlError::Transaction(_))));
+        assert!(matches!(conn.execute("SELECT * FROM other.users;"), Err(RqlError::Schema(_))));
+
+        // Neither an empty table nor a table without numeric columns reaches the GPU.
+        conn.execute("UPDATE users SET id = id + 1;").unwrap();
+        conn.execute("CREATE TABLE names (name TEXT);").unwrap();
+        assert!(matches!(conn.execute("UPDATE names SET name = 1;"), Err(RqlError::Type(_))));
+    }
+}
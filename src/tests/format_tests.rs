@@ -0,0 +1,42 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::format::format_result;
+    use crate::{BlobFormat, Database, OutputFormat, ResultSet};
+
+    fn sample() -> ResultSet {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE notes (id INTEGER, body TEXT, score FLOAT, done BOOLEAN);").unwrap();
+        conn.execute("INSERT INTO notes VALUES (1, 'say \"hi\"', 0.375, true)").unwrap();
+        conn.execute("INSERT INTO notes VALUES (2, 'a|b', 2, false)").unwrap();
+        conn.query("SELECT * FROM notes;").unwrap()
+    }
+
+    #[test]
+    fn test_formats_quote_and_keep_schema_order() {
+        let rows = sample();
+
+        assert_eq!(
+            format_result(&rows, OutputFormat::Csv, None, BlobFormat::Hex),
+            "id,body,score,done\n1,\"say \"\"hi\"\"\",0.375,true\n2,a|b,2,false\n"
+        );
+        assert_eq!(
+            format_result(&rows, OutputFormat::Ndjson, None, BlobFormat::Hex),
+            "{\"id\":1,\"body\":\"say \\\"hi\\\"\",\"score\":0.375,\"done\":true}\n\
+             {\"id\":2,\"body\":\"a|b\",\"score\":2,\"done\":false}\n"
+        );
+        assert_eq!(
+            format_result(&rows, OutputFormat::Json, Some(2), BlobFormat::Hex),
+            "[{\"id\":1,\"body\":\"say \\\"hi\\\"\",\"score\":0.38,\"done\":true},\n \
+             {\"id\":2,\"body\":\"a|b\",\"score\":2.00,\"done\":false}]\n"
+        );
+        assert_eq!(
+            format_result(&rows, OutputFormat::Markdown, Some(1), BlobFormat::Hex),
+            "| id | body | score | done |\n|---|---|---|---|\n| 1 | say \"hi\" | 0.4 | true |\n| 2 | a\\|b | 2.0 | false |\n"
+        );
+        assert!(format_result(&rows, OutputFormat::Table, None, BlobFormat::Hex).starts_with(" id | body"));
+
+        assert_eq!(OutputFormat::from_name("NDJSON"), Some(OutputFormat::Ndjson));
+        assert_eq!(OutputFormat::from_name("xml"), None);
+    }
+}
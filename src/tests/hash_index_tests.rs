@@ -0,0 +1,143 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::hash::HashIndex;
+    use crate::io::storage::{MemoryStorage, Storage};
+    use crate::tests::common::tests::{column, index_used};
+    use crate::types::types::{CreateIndexDefinition, IndexKind, RowLocation, WalOp};
+    use crate::{Database, Value};
+
+    fn sessions(options: &str) -> Database {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute(&format!("CREATE TABLE sessions (id INTEGER, token TEXT, user_id INTEGER){};", options)).unwrap();
+        for batch in 0..3 {
+            let values: Vec<String> =
+                (batch * 400..batch * 400 + 400).map(|id| format!("({}, 't{}', {})", id, id, id % 50)).collect();
+            conn.execute(&format!("INSERT INTO sessions VALUES {};", values.join(", "))).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn test_parse_hash_index() {
+        let index = CreateIndexDefinition::from_sql("CREATE UNIQUE INDEX by_token ON sessions USING HASH (token);").unwrap();
+        assert_eq!(index.index.kind, IndexKind::Hash);
+        assert_eq!(index.index.to_sql("sessions"), "CREATE UNIQUE INDEX by_token ON sessions USING HASH (token);");
+        let index = CreateIndexDefinition::from_sql("CREATE INDEX by_user ON sessions USING BTREE (user_id);").unwrap();
+        assert_eq!(index.index.kind, IndexKind::BTree);
+        assert_eq!(index.index.to_sql("sessions"), "CREATE INDEX by_user ON sessions (user_id);");
+        assert!(CreateIndexDefinition::from_sql("CREATE INDEX i ON t USING GIST (a);").is_err());
+        assert!(CreateIndexDefinition::from_sql("CREATE INDEX i ON t (a) USING HASH;").is_err());
+    }
+
+    #[test]
+    fn test_hash_index_splits_and_finds() {
+        let storage = MemoryStorage::default();
+        let key = |n: u32| format!("{:08}{}", n % 4000, "x".repeat(n as usize % 4000 % 300)).into_bytes();
+        let location = |n: u32| RowLocation { offset: n as u64 * 10, row: n % 3 };
+
+        // Keys added in several statements, so buckets split and chains overflow
+        // across reopened files. The keys from 4000 up repeat the first 2000.
+        for batch in (0..6000).collect::<Vec<u32>>().chunks(1000) {
+            let mut index = HashIndex::open(&storage, "index/t.i.bin").unwrap();
+            for n in batch {
+                index.insert(key(*n), location(*n)).unwrap();
+            }
+            storage.commit(&index.ops()).unwrap();
+        }
+
+        let mut index = HashIndex::open(&storage, "index/t.i.bin").unwrap();
+        for n in [0, 17, 1999, 2000, 3999, 4000, 5999] {
+            let mut expected = vec![location(n % 4000)];
+            if n % 4000 < 2000 {
+                expected.push(location(n % 4000 + 4000));
+            }
+            let mut found = index.get(&key(n)).unwrap();
+            found.sort_unstable();
+            assert_eq!(found, expected, "{}", n);
+        }
+        assert!(index.get(b"missing").unwrap().is_empty());
+
+        // An index built at once finds the same rows.
+        let built = MemoryStorage::default();
+        let entries = (0..6000).map(|n| (key(n), location(n))).collect();
+        built.commit(&HashIndex::build(&built, "index/t.i.bin", entries).ops()).unwrap();
+        let mut rebuilt = HashIndex::open(&built, "index/t.i.bin").unwrap();
+        for n in [0, 17, 1999, 3999] {
+            let (mut found, mut expected) = (rebuilt.get(&key(n)).unwrap(), index.get(&key(n)).unwrap());
+            found.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(found, expected, "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_select_uses_hash_indexes() {
+        for options in ["", " WITH (compression = 'lz4')"] {
+            let db = sessions(options);
+            let mut conn = db.connect();
+            let queries = [
+                "SELECT id FROM sessions WHERE token = 't777';",
+                "SELECT id FROM sessions WHERE user_id = 7 AND id < 200;",
+                "SELECT id FROM sessions WHERE user_id > 47 AND id < 100;",
+                "SELECT id FROM sessions WHERE token = 'missing';",
+            ];
+            let expected: Vec<Vec<Value>> = queries.iter().map(|sql| column(&mut conn, sql)).collect();
+            assert_eq!(expected[0], [Value::Integer(777)]);
+            assert_eq!(expected[1].len(), 4);
+
+            conn.execute("CREATE UNIQUE INDEX by_token ON sessions USING HASH (token);").unwrap();
+            conn.execute("CREATE INDEX by_user ON sessions USING HASH (user_id);").unwrap();
+            conn.execute("CREATE INDEX by_user_tree ON sessions (user_id);").unwrap();
+            assert_eq!(index_used(&conn, queries[0]).as_deref(), Some("by_token"));
+            assert_eq!(index_used(&conn, queries[1]).as_deref(), Some("by_user"));
+            assert_eq!(index_used(&conn, queries[2]).as_deref(), Some("by_user_tree"));
+            for (sql, expected) in queries.iter().zip(&expected) {
+                assert_eq!(&column(&mut conn, sql), expected, "{}", sql);
+            }
+
+            let err = conn.execute("INSERT INTO sessions VALUES (5000, 't5', 1);").unwrap_err();
+            assert!(err.to_string().contains("(token) = (t5) in UNIQUE index 'by_token'"), "{}", err);
+            conn.execute("INSERT INTO sessions VALUES (5000, 't5000', 7), (5001, NULL, 7), (5002, NULL, 7);").unwrap();
+            assert_eq!(column(&mut conn, "SELECT id FROM sessions WHERE user_id = 7;").len(), 27);
+            conn.execute("DELETE FROM sessions WHERE user_id = 7;").unwrap();
+            assert_eq!(column(&mut conn, "SELECT id FROM sessions WHERE user_id = 7;"), []);
+            assert_eq!(column(&mut conn, "SELECT id FROM sessions WHERE token = 't8';"), [Value::Integer(8)]);
+        }
+    }
+
+    #[test]
+    fn test_damaged_indexes_are_rebuilt() {
+        let db = sessions("");
+        let mut conn = db.connect();
+        conn.execute("CREATE UNIQUE INDEX by_token ON sessions USING HASH (token);").unwrap();
+        conn.execute("CREATE INDEX by_user ON sessions (user_id);").unwrap();
+        let storage = db.storage();
+        let damage = |path: &str, offset: u64| {
+            storage.commit(&[WalOp::Write { path: path.to_string(), offset, bytes: vec![0xAB; 64] }]).unwrap();
+        };
+
+        // A damaged page is caught by its checksum when a lookup reads it.
+        let hash_path = "index/sessions.by_token.bin";
+        let len = storage.file_len(hash_path).unwrap();
+        for offset in (0..len).step_by(4096) {
+            damage(hash_path, offset + 100);
+        }
+        assert_eq!(column(&mut conn, "SELECT id FROM sessions WHERE token = 't321';"), [Value::Integer(321)]);
+        assert_ne!(storage.read_range(hash_path, 100, 64).unwrap(), vec![0xAB; 64]);
+
+        // A damaged file is rebuilt by an insert too, which still checks uniqueness.
+        damage(hash_path, 0);
+        damage("index/sessions.by_user.bin", 0);
+        let err = conn.execute("INSERT INTO sessions VALUES (2000, 't3', 1);").unwrap_err();
+        assert!(err.to_string().contains("(token) = (t3)"), "{}", err);
+        conn.execute("INSERT INTO sessions VALUES (2000, 't2000', 1);").unwrap();
+        assert_eq!(column(&mut conn, "SELECT id FROM sessions WHERE token = 't2000';"), [Value::Integer(2000)]);
+        assert_eq!(column(&mut conn, "SELECT id FROM sessions WHERE user_id = 1;").len(), 25);
+
+        // So is a missing file.
+        storage.commit(&[WalOp::Remove { path: hash_path.to_string() }]).unwrap();
+        assert_eq!(column(&mut conn, "SELECT id FROM sessions WHERE token = 't5';"), [Value::Integer(5)]);
+        assert!(storage.file_len(hash_path).unwrap() > 0);
+    }
+}
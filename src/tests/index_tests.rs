@@ -0,0 +1,179 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::btree::BTree;
+    use crate::io::storage::{MemoryStorage, Storage};
+    use crate::tests::common::tests::{column, index_used};
+    use crate::types::types::{CreateIndexDefinition, IndexDefinition, IndexKind, SelectDefinition, TableDefinition};
+    use crate::{Database, RqlError, Value};
+
+    #[test]
+    fn test_parse_create_index() {
+        let index = CreateIndexDefinition::from_sql("CREATE UNIQUE INDEX by_site ON readings (site, at);").unwrap();
+        assert_eq!(index.table_name, "readings");
+        assert_eq!(index.index.columns, ["site", "at"]);
+        assert!(index.index.unique);
+        assert_eq!(index.index.to_sql("readings"), "CREATE UNIQUE INDEX by_site ON readings (site, at);");
+        for sql in ["CREATE INDEX ON t (a);", "CREATE INDEX i t (a);", "CREATE INDEX i ON t;", "CREATE INDEX i ON t (a) x;"] {
+            assert!(CreateIndexDefinition::from_sql(sql).is_err(), "{}", sql);
+        }
+
+        let table = TableDefinition::from_sql("CREATE TABLE t (id INTEGER PRIMARY KEY, serial TEXT UNIQUE, v VEC2);").unwrap();
+        let names: Vec<&str> = table.indexes.iter().map(|index| index.name.as_str()).collect();
+        assert_eq!(names, ["t_pkey", "t_serial_key"]);
+        assert!(table.indexes.iter().all(|index| index.unique && index.implicit));
+        assert!(TableDefinition::from_sql("CREATE TABLE t (v VEC2 UNIQUE);").is_err());
+    }
+
+    #[test]
+    fn test_btree_splits_and_scans() {
+        let storage = MemoryStorage::default();
+        let key = |n: u32| format!("{:08}{}", n, "x".repeat(200)).into_bytes();
+
+        // Inserted out of order, in several statements, so pages split at every level.
+        let mut numbers: Vec<u32> = (0..3000).map(|n| n * 7919 % 3000).collect();
+        for batch in numbers.chunks(500) {
+            let mut tree = BTree::open(&storage, "index/t.i.bin").unwrap();
+            for n in batch {
+                tree.insert(key(*n)).unwrap();
+            }
+            storage.commit(&tree.ops()).unwrap();
+        }
+
+        let scan = |storage: &dyn Storage, low: u32, high: u32| {
+            let mut tree = BTree::open(storage, "index/t.i.bin").unwrap();
+            let mut found = Vec::new();
+            tree.scan(&key(low), Some(&key(high)), |key| {
+                found.push(String::from_utf8_lossy(&key[..8]).parse::<u32>().unwrap());
+                true
+            })
+            .unwrap();
+            found
+        };
+        assert_eq!(scan(&storage, 1234, 1300), (1234..1300).collect::<Vec<_>>());
+        assert_eq!(scan(&storage, 0, 3000).len(), 3000);
+
+        // A tree built from sorted keys holds the same keys.
+        numbers.sort_unstable();
+        let built = MemoryStorage::default();
+        built.commit(&BTree::build(&built, "index/t.i.bin", numbers.iter().map(|n| key(*n)).collect()).ops()).unwrap();
+        assert_eq!(scan(&built, 1234, 1300), (1234..1300).collect::<Vec<_>>());
+        assert_eq!(scan(&built, 2990, 5000), (2990..3000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_unique_indexes() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE devices (id INTEGER PRIMARY KEY, serial TEXT UNIQUE, site TEXT);").unwrap();
+        conn.execute("INSERT INTO devices VALUES (1, 'a', 'x'), (2, NULL, 'x'), (3, NULL, 'y');").unwrap();
+
+        let err = conn.execute("INSERT INTO devices VALUES (4, 'b', 'z'), (1, 'c', 'z');").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("Duplicate primary key (id) = (1) in table 'devices'"), "{}", err);
+        let err = conn.execute("INSERT INTO devices VALUES (4, 'a', 'z');").unwrap_err();
+        assert!(err.to_string().contains("(serial) = (a) in UNIQUE index 'devices_serial_key'"), "{}", err);
+        let err = conn.execute("UPDATE devices SET id = 1;").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM devices;").unwrap().len(), 3);
+
+        let err = conn.execute("CREATE UNIQUE INDEX by_site ON devices (site);").unwrap_err();
+        assert!(err.to_string().contains("(site) = (x)"), "{}", err);
+        conn.execute("CREATE UNIQUE INDEX by_site ON devices (site, serial);").unwrap();
+        assert!(conn.execute("CREATE INDEX by_site ON devices (site);").is_err());
+        assert!(conn.execute("CREATE INDEX by_nothing ON devices (nothing);").is_err());
+        conn.execute("INSERT INTO devices VALUES (4, NULL, 'x');").unwrap();
+
+        // Deleted keys can be used again.
+        conn.execute("DELETE FROM devices WHERE id = 1;").unwrap();
+        conn.execute("INSERT INTO devices VALUES (1, 'a', 'x');").unwrap();
+        assert_eq!(column(&mut conn, "SELECT id FROM devices WHERE serial = 'a';"), [Value::Integer(1)]);
+    }
+
+    #[test]
+    fn test_select_uses_indexes() {
+        for options in ["", " WITH (compression = 'lz4')"] {
+            let db = Database::open_in_memory();
+            let mut conn = db.connect();
+            conn.execute(&format!("CREATE TABLE events (id INTEGER, kind TEXT, at DATE, score FLOAT){};", options)).unwrap();
+            // Several inserts, so the indexes are also added to.
+            for batch in 0..4 {
+                let values: Vec<String> = (batch * 250..batch * 250 + 250)
+                    .map(|id| format!("({}, '{}', '2024-01-{:02}', {})", id, ["a", "b", "c"][id % 3], id % 30 + 1, id as f32 / 4.0))
+                    .collect();
+                conn.execute(&format!("INSERT INTO events VALUES {};", values.join(", "))).unwrap();
+            }
+            conn.execute("INSERT INTO events VALUES (NULL, NULL, NULL, NULL);").unwrap();
+
+            let queries = [
+                "SELECT id FROM events WHERE id = 500;",
+                "SELECT id FROM events WHERE id > 990;",
+                "SELECT id FROM events WHERE id BETWEEN 10 AND 20 AND kind = 'b';",
+                "SELECT id FROM events WHERE kind = 'c' AND at >= '2024-01-29';",
+                "SELECT id FROM events WHERE kind = 'a' AND at = '2024-01-02' AND id < 100;",
+                "SELECT id FROM events WHERE score <= 1.5;",
+                "SELECT id FROM events WHERE id < 0 OR id = 3;",
+            ];
+            let expected: Vec<Vec<Value>> = queries.iter().map(|sql| column(&mut conn, sql)).collect();
+            assert_eq!(expected[0], [Value::Integer(500)]);
+            assert_eq!(expected[1].len(), 9);
+
+            conn.execute("CREATE INDEX by_id ON events (id);").unwrap();
+            conn.execute("CREATE INDEX by_kind_at ON events (kind, at);").unwrap();
+            conn.execute("CREATE INDEX by_score ON events (score);").unwrap();
+            assert_eq!(index_used(&conn, queries[0]).as_deref(), Some("by_id"));
+            assert_eq!(index_used(&conn, queries[2]).as_deref(), Some("by_kind_at"));
+            assert_eq!(index_used(&conn, queries[3]).as_deref(), Some("by_kind_at"));
+            assert_eq!(index_used(&conn, queries[5]).as_deref(), Some("by_score"));
+            assert_eq!(index_used(&conn, queries[6]), None);
+            assert_eq!(index_used(&conn, "SELECT id FROM events WHERE id = 2.5;"), None);
+            for (sql, expected) in queries.iter().zip(&expected) {
+                assert_eq!(&column(&mut conn, sql), expected, "{}", sql);
+            }
+
+            // Indexes follow inserts, updates and deletes.
+            conn.execute("INSERT INTO events VALUES (5000, 'a', '2024-02-01', 0.5);").unwrap();
+            assert_eq!(column(&mut conn, "SELECT id FROM events WHERE id >= 1000;"), [Value::Integer(5000)]);
+            conn.execute("UPDATE events SET id = id + 1;").unwrap();
+            assert_eq!(column(&mut conn, "SELECT id FROM events WHERE id = 501;"), [Value::Integer(501)]);
+            conn.execute("DELETE FROM events WHERE kind = 'b';").unwrap();
+            assert_eq!(column(&mut conn, "SELECT id FROM events WHERE kind = 'b';"), []);
+            assert_eq!(column(&mut conn, "SELECT id FROM events WHERE id BETWEEN 1 AND 6;").len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_unencodable_values_only_skip_their_index() {
+        // Vector columns cannot be indexed, so the definition is put together by hand.
+        let mut table = TableDefinition::from_sql("CREATE TABLE events (pos VEC2, id INTEGER);").unwrap();
+        for (name, column) in [("by_pos", "pos"), ("by_id", "id")] {
+            let columns = vec![column.to_string()];
+            table.indexes.push(IndexDefinition { name: name.to_string(), columns, unique: false, implicit: false, kind: IndexKind::BTree });
+        }
+
+        let select = SelectDefinition::from_sql("SELECT id FROM events WHERE pos = '[1, 2]' AND id = 3;").unwrap();
+        assert_eq!(select.index_scan(&table).map(|scan| scan.index.name.as_str()), Some("by_id"));
+        let select = SelectDefinition::from_sql("SELECT id FROM events WHERE pos >= '[1, 2]';").unwrap();
+        assert!(select.index_scan(&table).is_none());
+    }
+
+    #[test]
+    fn test_indexes_in_transactions() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT);").unwrap();
+        conn.execute("INSERT INTO events VALUES (1, 'a'), (2, 'b');").unwrap();
+
+        conn.execute("BEGIN;").unwrap();
+        conn.execute("CREATE INDEX by_kind ON events (kind);").unwrap();
+        conn.execute("INSERT INTO events VALUES (3, 'a');").unwrap();
+        assert_eq!(column(&mut conn, "SELECT id FROM events WHERE kind = 'a';"), [Value::Integer(1), Value::Integer(3)]);
+        conn.execute("ROLLBACK;").unwrap();
+
+        assert_eq!(index_used(&conn, "SELECT id FROM events WHERE kind = 'a';"), None);
+        conn.execute("INSERT INTO events VALUES (3, 'c');").unwrap();
+        assert_eq!(column(&mut conn, "SELECT kind FROM events WHERE id = 3;"), [Value::Text("c".to_string())]);
+
+        conn.execute("DROP TABLE events;").unwrap();
+        assert!(conn.database().storage().list("index").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,74 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::types::types::{InsertDefinition, InsertSource, Literal};
+    use crate::{Database, RqlError, Value};
+
+    #[test]
+    fn test_parse_insert_forms() {
+        let insert = InsertDefinition::from_sql("INSERT INTO t (b, a) VALUES (1, 'x, (y)'), (2, DEFAULT);").unwrap();
+        assert_eq!(insert.name, "t");
+        assert_eq!(insert.columns, Some(vec!["b".to_string(), "a".to_string()]));
+        let InsertSource::Values(rows) = insert.source else {
+            panic!("expected VALUES");
+        };
+        assert_eq!(
+            rows,
+            vec![
+                vec![Some(Literal::Integer(1)), Some(Literal::Text("x, (y)".to_string()))],
+                vec![Some(Literal::Integer(2)), None],
+            ]
+        );
+
+        let insert = InsertDefinition::from_sql("INSERT INTO t(a) SELECT * FROM s WHERE a=1").unwrap();
+        assert!(matches!(insert.source, InsertSource::Select(_)));
+
+        assert!(InsertDefinition::from_sql("INSERT INTO t VALUES (1), 2").is_err());
+        assert!(InsertDefinition::from_sql("INSERT INTO t VALUES (1").is_err());
+        assert!(InsertDefinition::from_sql("INSERT INTO t (a b) VALUES (1)").is_err());
+    }
+
+    #[test]
+    fn test_multi_row_insert_with_columns_and_defaults() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE users (id INTEGER NOT NULL, name TEXT, score FLOAT);").unwrap();
+
+        let inserted = conn
+            .execute("INSERT INTO users (name, id) VALUES ('Leo', 1), ('Ryan', 2), (DEFAULT, 3);")
+            .unwrap();
+        assert_eq!(inserted, 3);
+
+        let rows = conn.query("SELECT * FROM users;").unwrap();
+        assert_eq!(rows.rows()[1].values(), &[Value::Integer(2), Value::Text("Ryan".to_string()), Value::Null]);
+        assert_eq!(rows.rows()[2].value("name"), Some(&Value::Null));
+
+        // One bad row rejects the whole statement.
+        let err = conn.execute("INSERT INTO users VALUES (4, 'Ann', 1.0), ('five', 'Bob', 2.0);").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+        let err = conn.execute("INSERT INTO users (name) VALUES ('Cole');").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        let err = conn.execute("INSERT INTO users (id, missing) VALUES (6, 1);").unwrap_err();
+        assert!(matches!(err, RqlError::Schema(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM users;").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_insert_select() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE source (a INTEGER, b TEXT);").unwrap();
+        conn.execute("CREATE TABLE target (b TEXT, total FLOAT, a INTEGER);").unwrap();
+        conn.execute("INSERT INTO source VALUES (1, 'x'), (2, 'y'), (2, 'z');").unwrap();
+
+        let inserted = conn.execute("INSERT INTO target (a, b) SELECT * FROM source WHERE a=2;").unwrap();
+        assert_eq!(inserted, 2);
+        let rows = conn.query("SELECT * FROM target;").unwrap();
+        assert_eq!(rows.rows()[1].values(), &[Value::Text("z".to_string()), Value::Null, Value::Integer(2)]);
+
+        // Integers widen into FLOAT columns, other types do not convert.
+        assert_eq!(conn.execute("INSERT INTO target (total, b) SELECT * FROM source;").unwrap(), 3);
+        let err = conn.execute("INSERT INTO target (a, total) SELECT * FROM source;").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM target;").unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::types::types::Literal;
+    use crate::{Database, RqlError, Value};
+
+    fn parse(sql: &str) -> Result<Literal, RqlError> {
+        Literal::from_sql(sql, sql)
+    }
+
+    #[test]
+    fn test_parse_literals() {
+        assert_eq!(parse("'it''s, (here)'").unwrap(), Literal::Text("it's, (here)".to_string()));
+        assert_eq!(parse("E'a\\tb\\n\\'c\\\\ \\x41\\u00e9'").unwrap(), Literal::Text("a\tb\n'c\\ Aé".to_string()));
+        assert_eq!(parse("''").unwrap(), Literal::Text(String::new()));
+        assert_eq!(parse("-42").unwrap(), Literal::Integer(-42));
+        assert_eq!(parse("1.5e3").unwrap(), Literal::Float(1500.0));
+        assert_eq!(parse(".5").unwrap(), Literal::Float(0.5));
+        assert_eq!(parse("TRUE").unwrap(), Literal::Boolean(true));
+        assert_eq!(parse("false").unwrap(), Literal::Boolean(false));
+        assert_eq!(parse("null").unwrap(), Literal::Null);
+
+        assert!(parse("Leo").is_err());
+        assert!(parse("'open").is_err());
+        assert!(parse("'a' b").is_err());
+        assert!(parse("1e").is_err());
+        assert!(parse("E'\\xZZ'").is_err());
+        assert_eq!(Literal::Text("it's".to_string()).to_string(), "'it''s'");
+    }
+
+    #[test]
+    fn test_literals_are_checked_against_columns() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE notes (id INTEGER, body TEXT, score FLOAT, done BOOLEAN);").unwrap();
+        conn.execute("INSERT INTO notes VALUES (1, '  a, b (c) AND d = e', 2, TRUE), (2, 'x', -1.5E-1, FALSE);")
+            .unwrap();
+
+        let rows = conn.query("SELECT * FROM notes WHERE body = '  a, b (c) AND d = e' AND done = TRUE;").unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows.rows()[0].value("score"), Some(&Value::Float(2.0)));
+        assert_eq!(conn.query("SELECT * FROM notes WHERE score = -0.15;").unwrap().len(), 1);
+
+        for sql in [
+            "INSERT INTO notes VALUES ('1', 'x', 1.0, TRUE);",
+            "INSERT INTO notes VALUES (1, 2, 1.0, TRUE);",
+            "INSERT INTO notes VALUES (1.5, 'x', 1.0, TRUE);",
+            "INSERT INTO notes VALUES (3000000000, 'x', 1.0, TRUE);",
+            "SELECT * FROM notes WHERE id = 'one';",
+        ] {
+            let err = conn.execute(sql).unwrap_err();
+            assert!(matches!(err, RqlError::Type(_)), "{}: {}", sql, err);
+        }
+
+        let err = conn.execute("INSERT INTO notes VALUES (3, Leo, 1.0, TRUE);").unwrap_err();
+        let position = err.position().unwrap();
+        assert_eq!((position.line, position.column), (1, 30));
+    }
+
+    #[test]
+    fn test_float_literals_out_of_range() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE readings (f FLOAT, d DOUBLE);").unwrap();
+        conn.execute("INSERT INTO readings VALUES (3.4e38, 1e308);").unwrap();
+
+        for (sql, message) in [
+            ("INSERT INTO readings VALUES (1e39, 0);", "1e39 is out of range for FLOAT column 'f'"),
+            ("INSERT INTO readings VALUES (0, 1e400);", "1e400 is out of range for DOUBLE"),
+            ("INSERT INTO readings VALUES (-1e400, 0);", "-1e400 is out of range for DOUBLE"),
+        ] {
+            let err = conn.execute(sql).unwrap_err();
+            assert!(matches!(err, RqlError::Type(_)), "{}: {}", sql, err);
+            assert!(err.to_string().contains(message), "{}: {}", sql, err);
+        }
+        assert_eq!(conn.query("SELECT * FROM readings;").unwrap().len(), 1);
+    }
+}
@@ -1,2 +1,26 @@
+mod common;
 pub mod schema_tests;
-mod rqle_tests;
\ No newline at end of file
+mod rqle_tests;
+mod compression_tests;
+mod wal_tests;
+mod transaction_tests;
+mod database_tests;
+mod connection_tests;
+mod error_tests;
+mod script_tests;
+mod format_tests;
+mod copy_tests;
+mod arrow_tests;
+mod sqlite_tests;
+mod insert_tests;
+mod literal_tests;
+mod wide_types_tests;
+mod temporal_tests;
+mod vector_tests;
+mod similarity_tests;
+mod blob_tests;
+mod constraint_tests;
+mod check_tests;
+mod reference_tests;
+mod index_tests;
+mod hash_index_tests;
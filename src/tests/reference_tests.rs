@@ -0,0 +1,199 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::types::types::{ReferentialAction, TableDefinition};
+    use crate::{Connection, Database, RqlError, Value};
+    use std::fs;
+
+    fn devices_and_readings(on_delete: &str) -> Connection {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE devices (id INTEGER PRIMARY KEY, name TEXT);").unwrap();
+        conn.execute(&format!(
+            "CREATE TABLE readings (device INTEGER REFERENCES devices (id) {}, value FLOAT);",
+            on_delete
+        ))
+        .unwrap();
+        conn.execute("INSERT INTO devices VALUES (1, 'a'), (2, 'b'), (3, 'c');").unwrap();
+        conn.execute("INSERT INTO readings VALUES (1, 0.5), (1, 0.7), (2, 0.1), (NULL, 0.9);").unwrap();
+        conn
+    }
+
+    fn devices_of_readings(conn: &mut Connection) -> Vec<Value> {
+        let rows = conn.query("SELECT device FROM readings;").unwrap();
+        rows.iter().map(|row| row.values()[0].clone()).collect()
+    }
+
+    #[test]
+    fn test_parse_references() {
+        let table = TableDefinition::from_sql(
+            "CREATE TABLE readings (device INTEGER REFERENCES devices(id) ON DELETE CASCADE, site TEXT, at DATE, \
+             FOREIGN KEY (site, at) REFERENCES visits (site, day) ON DELETE SET NULL);",
+        )
+        .unwrap();
+        assert_eq!(table.foreign_keys.len(), 2);
+        assert_eq!(table.foreign_keys[0].on_delete, ReferentialAction::Cascade);
+        assert_eq!(table.foreign_keys[1].parent_columns, ["site", "day"]);
+        let sql = table.to_sql();
+        assert!(sql.contains("FOREIGN KEY (device) REFERENCES devices (id) ON DELETE CASCADE"), "{}", sql);
+        assert_eq!(TableDefinition::from_sql(&sql).unwrap().foreign_keys, table.foreign_keys);
+
+        let restrict = TableDefinition::from_sql("CREATE TABLE t (a INTEGER REFERENCES p (x) ON DELETE NO ACTION);").unwrap();
+        assert_eq!(restrict.foreign_keys[0].on_delete, ReferentialAction::Restrict);
+        assert!(!restrict.to_sql().contains("ON DELETE"));
+
+        for sql in [
+            "CREATE TABLE t (a INTEGER REFERENCES p);",
+            "CREATE TABLE t (a INTEGER REFERENCES p (x, y));",
+            "CREATE TABLE t (a INTEGER REFERENCES p (x) ON DELETE NOTHING);",
+            "CREATE TABLE t (a INTEGER, FOREIGN KEY (b) REFERENCES p (x));",
+            "CREATE TABLE t (a INTEGER, FOREIGN KEY (a, a) REFERENCES p (x, y));",
+        ] {
+            assert!(TableDefinition::from_sql(sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_create_checks_the_parent() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE devices (id INTEGER PRIMARY KEY, serial TEXT UNIQUE, site TEXT);").unwrap();
+        for sql in [
+            "CREATE TABLE r (d INTEGER REFERENCES missing (id));",
+            "CREATE TABLE r (d INTEGER REFERENCES devices (nope));",
+            "CREATE TABLE r (d TEXT REFERENCES devices (site));",
+            "CREATE TABLE r (d BIGINT REFERENCES devices (id));",
+        ] {
+            let err = conn.execute(sql).unwrap_err();
+            assert!(matches!(err, RqlError::Schema(_)), "{}: {}", sql, err);
+        }
+        conn.execute("CREATE TABLE r (serial TEXT REFERENCES devices (serial));").unwrap();
+        conn.execute("CREATE TABLE tree (id INTEGER PRIMARY KEY, parent INTEGER REFERENCES tree (id));").unwrap();
+    }
+
+    #[test]
+    fn test_insert_and_update_need_a_parent() {
+        let mut conn = devices_and_readings("");
+        let err = conn.execute("INSERT INTO readings VALUES (1, 0.2), (9, 0.3);").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("(device) = (9) of table 'readings' references no row of 'devices'"), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM readings;").unwrap().len(), 4);
+
+        let err = conn.execute("UPDATE readings SET device = device + 5;").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        conn.execute("UPDATE readings SET device = device + 1;").unwrap();
+        assert_eq!(devices_of_readings(&mut conn), [Value::Integer(2), Value::Integer(2), Value::Integer(3), Value::Null]);
+
+        // Changing a referenced key would orphan the readings.
+        let err = conn.execute("UPDATE devices SET id = id * 10;").unwrap_err();
+        assert!(err.to_string().contains("without a parent row"), "{}", err);
+        conn.execute("UPDATE devices SET id = 4 - id;").unwrap();
+        assert_eq!(conn.query("SELECT * FROM devices WHERE id = 2;").unwrap().len(), 1);
+
+        // A row may reference another row inserted by the same statement.
+        conn.execute("CREATE TABLE tree (id INTEGER PRIMARY KEY, parent INTEGER REFERENCES tree (id));").unwrap();
+        conn.execute("INSERT INTO tree VALUES (1, NULL), (2, 1), (3, 2);").unwrap();
+        assert!(conn.execute("INSERT INTO tree VALUES (4, 5);").is_err());
+    }
+
+    #[test]
+    fn test_imports_need_a_parent() {
+        let dir = std::env::temp_dir().join(format!("rql_reference_import_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let csv = dir.join("readings.csv");
+        fs::write(&csv, "3,0.4\n7,0.5\n").unwrap();
+        let sqlite_path = dir.join("readings.db");
+        let sqlite = rusqlite::Connection::open(&sqlite_path).unwrap();
+        sqlite
+            .execute_batch("CREATE TABLE readings (device INTEGER, value REAL); INSERT INTO readings VALUES (2, 1), (8, 2);")
+            .unwrap();
+        drop(sqlite);
+
+        let mut conn = devices_and_readings("");
+        let err = conn.execute(&format!("COPY readings FROM '{}';", csv.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("(device) = (7) of table 'readings' references no row of 'devices'"), "{}", err);
+        let err = conn.execute(&format!("IMPORT SQLITE '{}' TABLE readings;", sqlite_path.display())).unwrap_err();
+        assert!(err.to_string().contains("(device) = (8) of table 'readings'"), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM readings;").unwrap().len(), 4);
+
+        // Imported rows may reference each other.
+        conn.execute("CREATE TABLE tree (id INTEGER PRIMARY KEY, parent INTEGER REFERENCES tree (id));").unwrap();
+        fs::write(&csv, "1,\n2,1\n").unwrap();
+        conn.execute(&format!("COPY tree FROM '{}';", csv.display())).unwrap();
+        assert_eq!(conn.query("SELECT * FROM tree;").unwrap().len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_delete_actions() {
+        let mut conn = devices_and_readings("ON DELETE RESTRICT");
+        let err = conn.execute("DELETE FROM devices WHERE id = 1;").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("(device) = (1) of table 'readings' still references it"), "{}", err);
+        assert_eq!(conn.execute("DELETE FROM devices WHERE id = 3;").unwrap(), 1);
+        assert_eq!(conn.query("SELECT * FROM devices;").unwrap().len(), 2);
+
+        let mut conn = devices_and_readings("ON DELETE CASCADE");
+        assert_eq!(conn.execute("DELETE FROM devices WHERE name <> 'b';").unwrap(), 2);
+        assert_eq!(devices_of_readings(&mut conn), [Value::Integer(2), Value::Null]);
+        assert_eq!(conn.execute("DELETE FROM devices;").unwrap(), 1);
+        assert_eq!(devices_of_readings(&mut conn), [Value::Null]);
+
+        let mut conn = devices_and_readings("ON DELETE SET NULL");
+        conn.execute("DELETE FROM devices WHERE id = 1;").unwrap();
+        assert_eq!(devices_of_readings(&mut conn), [Value::Null, Value::Null, Value::Integer(2), Value::Null]);
+        assert_eq!(conn.query("SELECT * FROM readings;").unwrap().len(), 4);
+
+        // Cascades follow a chain of references, here through the table itself.
+        conn.execute("CREATE TABLE tree (id INTEGER PRIMARY KEY, parent INTEGER REFERENCES tree (id) ON DELETE CASCADE);").unwrap();
+        conn.execute("INSERT INTO tree VALUES (1, NULL), (2, 1), (3, 2), (4, NULL);").unwrap();
+        assert_eq!(conn.execute("DELETE FROM tree WHERE id = 1;").unwrap(), 1);
+        let rows = conn.query("SELECT id FROM tree;").unwrap();
+        assert_eq!(rows.rows()[0].values(), &[Value::Integer(4)]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(conn.execute("DELETE FROM tree WHERE id > 10;").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_failed_cascade_writes_nothing() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE devices (id INTEGER PRIMARY KEY);").unwrap();
+        conn.execute("CREATE TABLE readings (id INTEGER PRIMARY KEY, device INTEGER REFERENCES devices (id) ON DELETE CASCADE);").unwrap();
+        conn.execute("CREATE TABLE notes (reading INTEGER NOT NULL REFERENCES readings (id) ON DELETE SET NULL);").unwrap();
+        conn.execute("INSERT INTO devices VALUES (1), (2);").unwrap();
+        conn.execute("INSERT INTO readings VALUES (10, 1), (20, 2);").unwrap();
+        conn.execute("INSERT INTO notes VALUES (10);").unwrap();
+
+        let err = conn.execute("DELETE FROM devices WHERE id = 1;").unwrap_err();
+        assert!(err.to_string().contains("cannot clear column 'reading'"), "{}", err);
+        assert_eq!(conn.query("SELECT * FROM devices;").unwrap().len(), 2);
+        assert_eq!(conn.query("SELECT * FROM readings;").unwrap().len(), 2);
+
+        conn.execute("DELETE FROM devices WHERE id = 2;").unwrap();
+        assert_eq!(conn.query("SELECT * FROM readings;").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_drop_table() {
+        let mut conn = devices_and_readings("ON DELETE CASCADE");
+        let err = conn.execute("DROP TABLE devices;").unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert!(err.to_string().contains("table 'readings' references it"), "{}", err);
+
+        conn.execute("DROP TABLE readings;").unwrap();
+        assert!(conn.query("SELECT * FROM readings;").is_err());
+        conn.execute("DROP TABLE devices;").unwrap();
+        assert!(conn.database().tables().unwrap().is_empty());
+        assert!(conn.execute("DROP TABLE devices;").is_err());
+
+        // Inside a transaction, a dropped table comes back on ROLLBACK.
+        conn.execute("CREATE TABLE kept (a INTEGER);").unwrap();
+        conn.execute("INSERT INTO kept VALUES (1);").unwrap();
+        conn.execute("BEGIN;").unwrap();
+        conn.execute("DROP TABLE kept;").unwrap();
+        conn.execute("ROLLBACK;").unwrap();
+        assert_eq!(conn.query("SELECT * FROM kept;").unwrap().len(), 1);
+    }
+}
@@ -1,7 +1,6 @@
 #[cfg(test)]
 pub mod tests {
     use crate::rqle::rqle_parser::ExpressionParser;
-    use pest::Parser;
 
     #[test]
     fn test_assign_name() {
@@ -10,7 +9,7 @@ pub mod tests {
         match ExpressionParser::parse(input) {
             Ok(parsed) => {
                 assert_eq!(parsed.assignments.len(), 1);
-                assert_eq!(parsed.assignments.iter().next().unwrap().variable, "columnname3");
+                assert_eq!(parsed.assignments.first().unwrap().variable, "columnname3");
             }
             Err(err) => eprintln!("Error: {}", err),
         }
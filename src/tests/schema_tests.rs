@@ -1,7 +1,10 @@
 #[cfg(test)]
 pub mod tests {
-    use crate::io::writer::write_table_definition;
-    use crate::types::types::TableDefinition;
+    use crate::database::database::Database;
+    use crate::io::reader::read_table_definition;
+    use crate::io::writer::{write_table_definition, SCHEMA_MAGIC, SCHEMA_VERSION};
+    use crate::types::types::{DataType, TableDefinition, Value, WalOp};
+    use crate::RqlError;
     use regex::Regex;
 
     fn reduce_spaces(input: &str) -> String {
@@ -19,16 +22,79 @@ pub mod tests {
             );
         "#;
 
-        if let Some(table) = TableDefinition::from_sql(sql) {
+        if let Ok(table) = TableDefinition::from_sql(sql) {
             println!("Parsed Table Definition: {:?}", table);
 
-            write_table_definition(&table).expect("Failed to write");
-            let generatedSql = table.to_sql().replace("\n", "").trim().to_lowercase();
-            let inputtedSql = table.to_sql().replace("\n", "").trim().to_lowercase();
+            let dir = std::env::temp_dir().join(format!("rql_schema_{}", std::process::id()));
+            let db = Database::open(&dir).unwrap();
+            write_table_definition(&db, &table).expect("Failed to write");
+            let stored = read_table_definition(&db, "users").expect("Failed to read");
+            assert_eq!(stored.columns, table.columns);
+            std::fs::remove_dir_all(dir).unwrap();
 
-            assert_eq!(reduce_spaces(&generatedSql), reduce_spaces(&inputtedSql));
+            let generated_sql = table.to_sql().replace("\n", "").trim().to_lowercase();
+            let inputted_sql = table.to_sql().replace("\n", "").trim().to_lowercase();
+
+            assert_eq!(reduce_spaces(&generated_sql), reduce_spaces(&inputted_sql));
         } else {
             println!("Failed to parse SQL");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_duplicate_columns_are_rejected() {
+        let err = TableDefinition::from_sql("CREATE TABLE t (id INTEGER, name TEXT, ID BIGINT);").unwrap_err();
+        assert!(matches!(err, RqlError::Parse { .. }), "{}", err);
+        assert!(err.to_string().contains("Column 'ID' is declared more than once"), "{}", err);
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE t (id INTEGER, name TEXT);").unwrap();
+        let err = conn.execute("ALTER TABLE t ADD COLUMN Name TEXT;").unwrap_err();
+        assert!(matches!(err, RqlError::Schema(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_baseline_schema_files_are_migrated() {
+        // The layout before schema files were versioned: the name, then each column's
+        // name, type, nullability and uniqueness.
+        let baseline = bincode::serialize(&(
+            "users",
+            vec![("id", DataType::Integer, false, true), ("name", DataType::Text, true, false)],
+        ))
+        .unwrap();
+        let db = Database::open_in_memory();
+        let write = |bytes: Vec<u8>| {
+            let path = "schema/users_def.bin".to_string();
+            let ops = [WalOp::Truncate { path: path.clone(), len: 0 }, WalOp::Write { path, offset: 0, bytes }];
+            db.storage().commit(&ops).unwrap();
+        };
+        write(baseline.clone());
+
+        let table = read_table_definition(&db, "users").unwrap();
+        assert_eq!(table.to_sql(), "CREATE TABLE users (\n  id INTEGER NOT NULL UNIQUE,\n  name TEXT\n);");
+        let mut conn = db.connect();
+        conn.execute("INSERT INTO users VALUES (1, 'a');").unwrap();
+        assert!(conn.execute("INSERT INTO users VALUES (1, 'b');").is_err());
+
+        // Changing the table saves it in the current format.
+        conn.execute("ALTER TABLE users ADD COLUMN age INTEGER;").unwrap();
+        let saved = db.storage().read("schema/users_def.bin").unwrap();
+        assert_eq!(saved[..4], SCHEMA_MAGIC);
+        assert_eq!(saved[4..8], SCHEMA_VERSION.to_le_bytes());
+        let rows = conn.query("SELECT * FROM users;").unwrap();
+        assert_eq!(rows.rows()[0].values(), [Value::Integer(1), Value::Text("a".to_string()), Value::Null]);
+
+        // Files in neither layout, or from a newer version, are rejected.
+        let mut newer = SCHEMA_MAGIC.to_vec();
+        newer.extend((SCHEMA_VERSION + 1).to_le_bytes());
+        newer.extend(&saved[8..]);
+        write(newer);
+        let err = read_table_definition(&db, "users").unwrap_err();
+        assert!(matches!(err, RqlError::Schema(_)), "{}", err);
+        assert!(err.to_string().contains(&format!("schema format {}", SCHEMA_VERSION + 1)), "{}", err);
+        write([baseline, vec![0; 3]].concat());
+        let err = read_table_definition(&db, "users").unwrap_err();
+        assert!(err.to_string().contains("is in an unknown format"), "{}", err);
+    }
+}
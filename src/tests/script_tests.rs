@@ -0,0 +1,100 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::query::runner::QueryRunner;
+    use crate::query::script::{split_statements, Statement, StatementSplitter};
+    use crate::Database;
+
+    #[test]
+    fn test_statements_split_on_semicolons_across_lines() {
+        let script = "CREATE TABLE users (\n    id INTEGER,\n    name TEXT\n);\n\
+                      -- seed data\n\
+                      INSERT INTO users VALUES (1, 'a;b'); INSERT INTO users VALUES (2, c);;\n\
+                      SELECT * FROM users";
+
+        let statements = split_statements(script);
+        let sql: Vec<&str> = statements.iter().map(|s| s.sql.as_str()).collect();
+        assert_eq!(
+            sql,
+            vec![
+                "CREATE TABLE users (\n    id INTEGER,\n    name TEXT\n);",
+                "INSERT INTO users VALUES (1, 'a;b');",
+                "INSERT INTO users VALUES (2, c);",
+                "SELECT * FROM users",
+            ]
+        );
+        let lines: Vec<usize> = statements.iter().map(|s| s.line).collect();
+        assert_eq!(lines, vec![1, 6, 6, 7]);
+
+        let mut splitter = StatementSplitter::new();
+        assert!(splitter.push_line("SELECT *").is_empty());
+        assert!(splitter.is_pending());
+        assert_eq!(
+            splitter.push_line("FROM users;"),
+            vec![Statement { line: 1, sql: "SELECT *\nFROM users;".to_string() }]
+        );
+        assert!(!splitter.is_pending());
+    }
+
+    #[test]
+    fn test_escaped_quotes_do_not_end_strings() {
+        let sql = "INSERT INTO t VALUES (E'it\\'s; ok'), ('a\\'); SELECT 'x''; y', E'\\\\' FROM t;";
+        let statements = split_statements(sql);
+        let sql: Vec<&str> = statements.iter().map(|s| s.sql.as_str()).collect();
+        assert_eq!(
+            sql,
+            vec!["INSERT INTO t VALUES (E'it\\'s; ok'), ('a\\');", "SELECT 'x''; y', E'\\\\' FROM t;"]
+        );
+
+        // As passed with `rql -c`.
+        let db = Database::open_in_memory();
+        let mut runner = QueryRunner::new(db.connect());
+        runner.set_quiet(true);
+        let script = "CREATE TABLE t (a TEXT); INSERT INTO t VALUES (E'it\\'s; ok'); INSERT INTO t VALUES ('b');";
+        assert_eq!(runner.run_script(script.as_bytes()).unwrap(), 0);
+        let rows = db.connect().query("SELECT a FROM t;").unwrap();
+        assert_eq!(rows.rows()[0].get::<String, _>("a").unwrap(), "it's; ok");
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn test_run_script_counts_failures_and_continues() {
+        let db = Database::open_in_memory();
+        let mut runner = QueryRunner::new(db.connect());
+        runner.set_quiet(true);
+
+        let script = "CREATE TABLE t (a INTEGER);\nINSERT INTO t VALUES (1);\nINSERT INTO t VALUES ('x');\nINSERT INTO t VALUES (2);\n";
+        assert_eq!(runner.run_script(script.as_bytes()).unwrap(), 1);
+        assert_eq!(db.connect().query("SELECT * FROM t;").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_meta_commands() {
+        let db = Database::open_in_memory();
+        let mut runner = QueryRunner::new(db.connect());
+        runner.set_quiet(true);
+
+        let script = "CREATE TABLE t (a INTEGER);\n.tables\n.schema\n.describe t\n.timer on\nSELECT * FROM t;\n.timer off\n";
+        assert_eq!(runner.run_script(script.as_bytes()).unwrap(), 0);
+
+        // As passed with `rql -c`, meta-commands may follow a statement on the same line.
+        let script = "BEGIN; INSERT INTO t VALUES (1); ROLLBACK; .tables\n.mode csv; .describe t";
+        assert_eq!(split_statements(script).iter().map(|s| s.sql.as_str()).collect::<Vec<_>>(), vec![
+            "BEGIN;",
+            "INSERT INTO t VALUES (1);",
+            "ROLLBACK;",
+            ".tables",
+            ".mode csv; .describe t",
+        ]);
+        assert_eq!(runner.run_script("ROLLBACK; .tables\n.timer off".as_bytes()).unwrap(), 1);
+        assert_eq!(runner.run_script("SELECT * FROM t; .tables".as_bytes()).unwrap(), 0);
+
+        assert!(runner.run_meta_command(".describe missing").is_err());
+        assert!(runner.run_meta_command(".timer maybe").is_err());
+        assert!(runner.run_meta_command(".unknown").is_err());
+
+        let mut conn = db.connect();
+        conn.execute("INSERT INTO t VALUES (1)").unwrap();
+        let timings = conn.last_timings();
+        assert!(timings.total > std::time::Duration::ZERO && timings.total >= timings.parse);
+    }
+}
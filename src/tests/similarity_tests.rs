@@ -0,0 +1,107 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::types::types::ComputeDevice;
+    use crate::{Connection, DataType, Database, RqlError, Value};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn vector_text(components: &[f32]) -> String {
+        let components: Vec<String> = components.iter().map(f32::to_string).collect();
+        format!("'[{}]'", components.join(", "))
+    }
+
+    fn ids(conn: &mut Connection, sql: &str) -> Vec<Value> {
+        conn.query(sql).unwrap().iter().map(|row| row.values()[0].clone()).collect()
+    }
+
+    #[test]
+    fn test_vector_columns_of_any_size() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE docs (id INTEGER, embedding VECTOR(5), score FLOAT);").unwrap();
+        conn.execute("INSERT INTO docs VALUES (1, '[1, 0, 0, 0, 0]', 0), (2, '[0.5, 0.5, 0, 0, 1]', 0), (3, NULL, 0);")
+            .unwrap();
+        let err = conn.execute("INSERT INTO docs VALUES (4, '[1, 0, 0]', 0);").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+        assert!(conn.execute("CREATE TABLE bad (embedding VECTOR(0));").is_err());
+
+        let rows = conn.query("SELECT embedding, l2_distance(embedding, '[1, 0, 0, 0, 0]') AS d FROM docs;").unwrap();
+        assert_eq!(rows.columns()[0].data_type(), DataType::Vector(5));
+        assert_eq!(rows.columns()[1].data_type(), DataType::Float);
+        assert_eq!(rows.rows()[1].get::<Vec<f32>, _>("embedding").unwrap(), [0.5, 0.5, 0.0, 0.0, 1.0]);
+        assert_eq!(rows.rows()[0].value("d"), Some(&Value::Float(0.0)));
+        assert_eq!(rows.rows()[1].value("d"), Some(&Value::Float(1.5f32.sqrt())));
+        assert_eq!(rows.rows()[2].value("d"), Some(&Value::Null));
+
+        let rows = conn.query("SELECT cosine_distance(embedding, '[0, 0, 0, 0, 2]') AS d FROM docs WHERE id < 3;").unwrap();
+        assert_eq!(rows.rows()[0].value("d"), Some(&Value::Float(1.0)));
+        let rows = conn.query("SELECT cosine_distance(embedding, '[0, 0, 0, 0, 0]') AS d FROM docs WHERE id = 1;").unwrap();
+        assert_eq!(rows.rows()[0].value("d"), Some(&Value::Null));
+
+        for sql in [
+            "SELECT l2_distance(embedding, '[1, 0]') FROM docs;",
+            "SELECT l2_distance(embedding, score) FROM docs;",
+            "UPDATE docs SET score = score + embedding;",
+        ] {
+            let err = conn.run(sql).unwrap_err();
+            assert!(matches!(err, RqlError::Type(_)), "{}: {}", sql, err);
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbours_match_the_cpu_reference() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE items (id INTEGER, category INTEGER, embedding VECTOR(16));").unwrap();
+
+        let mut rng = StdRng::seed_from_u64(44);
+        let mut embeddings: Vec<Vec<f32>> = (0..1500).map(|_| (0..16).map(|_| rng.gen_range(-1.0..1.0)).collect()).collect();
+        // Copies of the same vector are at the same distance, and keep their order.
+        embeddings[900] = embeddings[7].clone();
+        embeddings[1200] = embeddings[7].clone();
+        let values: Vec<String> = embeddings
+            .iter()
+            .enumerate()
+            .map(|(id, embedding)| match id % 97 {
+                0 => format!("({}, {}, NULL)", id, id % 3),
+                _ => format!("({}, {}, {})", id, id % 3, vector_text(embedding)),
+            })
+            .collect();
+        for chunk in values.chunks(100) {
+            conn.execute(&format!("INSERT INTO items VALUES {};", chunk.join(", "))).unwrap();
+        }
+
+        let query = vector_text(&embeddings[7]);
+        for metric in ["l2_distance", "cosine_distance"] {
+            for (filter, limit) in [("", "LIMIT 1"), ("", "LIMIT 10"), ("WHERE category = 1", "LIMIT 37 OFFSET 3"), ("", "LIMIT 2000")] {
+                let sql = format!("SELECT id FROM items {} ORDER BY {}(embedding, {}) {};", filter, metric, query, limit);
+                let found = ids(&mut conn, &sql);
+                let device = conn.last_timings().device;
+                // Sorting by a second key leaves the search to the CPU.
+                let sql = format!("SELECT id FROM items {} ORDER BY {}(embedding, {}), id {};", filter, metric, query, limit);
+                let expected = ids(&mut conn, &sql);
+                assert_eq!(conn.last_timings().device, None);
+                assert_eq!(found, expected, "{} {} {}", metric, filter, limit);
+                if device != Some(ComputeDevice::Gpu) {
+                    eprintln!("Searched on the CPU, no GPU");
+                }
+            }
+        }
+
+        let found = ids(&mut conn, &format!("SELECT id FROM items ORDER BY l2_distance(embedding, {}) LIMIT 3;", query));
+        assert_eq!(found, [Value::Integer(7), Value::Integer(900), Value::Integer(1200)]);
+        let rows = conn
+            .query(&format!("SELECT id, l2_distance({}, embedding) AS d FROM items ORDER BY d LIMIT 4;", query))
+            .unwrap();
+        assert_eq!(rows.rows()[3].value("d"), Some(&Value::Float(
+            embeddings[7].iter().zip(&embeddings[rows.rows()[3].get::<i32, _>("id").unwrap() as usize])
+                .fold(0.0f32, |sum, (a, b)| sum + (a - b) * (a - b))
+                .sqrt()
+        )));
+        assert!(ids(&mut conn, &format!("SELECT id FROM items ORDER BY l2_distance(embedding, {}) LIMIT 0;", query)).is_empty());
+
+        // Rows without a vector come last, in storage order.
+        let found = ids(&mut conn, &format!("SELECT id FROM items ORDER BY cosine_distance(embedding, {}) LIMIT 1500;", query));
+        assert_eq!(found[1484..], (0..16).map(|n| Value::Integer(n * 97)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,124 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::copy::IMPORT_BATCH_ROWS;
+    use crate::tests::common::tests::{column, temp_dir};
+    use crate::types::types::SqliteImportDefinition;
+    use crate::{DataType, Database, RqlError, Value};
+    use std::fs;
+
+    #[test]
+    fn test_parse_import_sqlite() {
+        let import = SqliteImportDefinition::from_sql("IMPORT SQLITE 'test.db' TABLE floats;").unwrap();
+        assert_eq!(import.path, "test.db");
+        assert_eq!(import.source_table, "floats");
+        assert_eq!(import.table_name, "floats");
+
+        let import = SqliteImportDefinition::from_sql("IMPORT SQLITE 'test.db' TABLE floats AS sqlite_floats").unwrap();
+        assert_eq!(import.table_name, "sqlite_floats");
+
+        assert!(SqliteImportDefinition::from_sql("IMPORT SQLITE test.db TABLE floats").is_err());
+        assert!(SqliteImportDefinition::from_sql("IMPORT SQLITE 'test.db' floats").is_err());
+    }
+
+    #[test]
+    fn test_import_sqlite_table() {
+        let dir = std::env::temp_dir().join(format!("rql_sqlite_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.db");
+
+        let sqlite = rusqlite::Connection::open(&path).unwrap();
+        sqlite
+            .execute_batch(
+                "CREATE TABLE floats (id INTEGER NOT NULL, col1 REAL, \"label text\" VARCHAR(10), ok BOOLEAN);
+                 INSERT INTO floats VALUES (1, 0.5, 'a', 1), (2, NULL, NULL, 0), (3, 2, 'c', NULL);
+                 CREATE TABLE big (id INTEGER, amount NUMERIC, note);
+                 INSERT INTO big VALUES (1, 2.5, 'a'), (5000000000, 3, 4);
+                 CREATE TABLE wide (id INTEGER);
+                 INSERT INTO wide VALUES (1), (5000000000);
+                 CREATE TABLE ids (id INTEGER, col1 REAL);
+                 INSERT INTO ids VALUES (NULL, 1), (7, 2), (NULL, 3);",
+            )
+            .unwrap();
+        drop(sqlite);
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        let imported = conn
+            .execute(&format!("IMPORT SQLITE '{}' TABLE floats AS sqlite_floats;", path.display()))
+            .unwrap();
+        assert_eq!(imported, 3);
+
+        let rows = conn.query("SELECT * FROM sqlite_floats;").unwrap();
+        let types: Vec<DataType> = rows.columns().iter().map(|c| c.data_type()).collect();
+        assert_eq!(types, vec![DataType::BigInt, DataType::Float, DataType::Text, DataType::Boolean]);
+        assert_eq!(rows.columns()[2].name(), "label_text");
+        assert!(!rows.columns()[0].is_nullable());
+        assert_eq!(rows.rows()[1].value("col1"), Some(&Value::Null));
+        assert_eq!(rows.rows()[2].get::<f32, _>("col1").unwrap(), 2.0);
+        assert!(rows.rows()[0].get::<bool, _>("ok").unwrap());
+
+        // Importing again appends to the existing table.
+        conn.execute(&format!("IMPORT SQLITE '{}' TABLE floats AS sqlite_floats;", path.display())).unwrap();
+        assert_eq!(conn.query("SELECT * FROM sqlite_floats;").unwrap().len(), 6);
+
+        // SQLite integers are 64-bit, NUMERIC columns are read as doubles and
+        // columns without a type as text.
+        conn.execute(&format!("IMPORT SQLITE '{}' TABLE big;", path.display())).unwrap();
+        let rows = conn.query("SELECT * FROM big;").unwrap();
+        let types: Vec<DataType> = rows.columns().iter().map(|c| c.data_type()).collect();
+        assert_eq!(types, vec![DataType::BigInt, DataType::Double, DataType::Text]);
+        assert_eq!(rows.rows()[1].values(), &[Value::BigInt(5000000000), Value::Double(3.0), Value::Text("4".to_string())]);
+
+        // A value that does not fit an existing INTEGER column leaves nothing behind.
+        conn.execute("CREATE TABLE narrow (id INTEGER);").unwrap();
+        let err = conn.execute(&format!("IMPORT SQLITE '{}' TABLE wide AS narrow;", path.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+        assert!(err.to_string().contains("5000000000 is not a valid INTEGER value"), "{}", err);
+        assert!(conn.query("SELECT * FROM narrow;").unwrap().is_empty());
+
+        // NULL ids in an AUTOINCREMENT column are generated, as INSERT does.
+        conn.execute("CREATE TABLE ids (id BIGINT NOT NULL AUTOINCREMENT, col1 FLOAT);").unwrap();
+        conn.execute("INSERT INTO ids (col1) VALUES (0.0);").unwrap();
+        conn.execute(&format!("IMPORT SQLITE '{}' TABLE ids;", path.display())).unwrap();
+        conn.execute("INSERT INTO ids (col1) VALUES (4.0);").unwrap();
+        let ids: Vec<Value> = conn.query("SELECT id FROM ids;").unwrap().iter().map(|row| row.values()[0].clone()).collect();
+        assert_eq!(ids, [1, 2, 7, 8, 9].map(Value::BigInt));
+
+        let err = conn.execute(&format!("IMPORT SQLITE '{}' TABLE missing;", path.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Schema(_)), "{}", err);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_import_sqlite_is_undone() {
+        let dir = temp_dir("sqlite", "undo");
+        let path = dir.join("test.db");
+        let rows = IMPORT_BATCH_ROWS + 10;
+
+        let sqlite = rusqlite::Connection::open(&path).unwrap();
+        sqlite
+            .execute_batch(&format!(
+                "CREATE TABLE t (id INTEGER);
+                 WITH RECURSIVE n(id) AS (SELECT 1 UNION ALL SELECT id + 1 FROM n WHERE id < {})
+                 INSERT INTO t SELECT id FROM n;",
+                rows
+            ))
+            .unwrap();
+        drop(sqlite);
+
+        let db = Database::open(dir.join("db")).unwrap();
+        let mut conn = db.connect();
+        conn.execute(&format!("CREATE TABLE t (id INTEGER UNIQUE CHECK (id < {}));", rows)).unwrap();
+        conn.execute("INSERT INTO t VALUES (0);").unwrap();
+
+        // The last row fails in the second batch, after the first was appended.
+        let err = conn.execute(&format!("IMPORT SQLITE '{}' TABLE t;", path.display())).unwrap_err();
+        assert!(matches!(err, RqlError::Constraint(_)), "{}", err);
+        assert_eq!(column(&mut conn, "SELECT id FROM t;"), vec![Value::Integer(0)]);
+        conn.execute("INSERT INTO t VALUES (1);").unwrap();
+        assert_eq!(column(&mut conn, "SELECT id FROM t WHERE id = 1;"), vec![Value::Integer(1)]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
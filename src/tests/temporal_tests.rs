@@ -0,0 +1,164 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::query::temporal::{date_trunc, extract, parse_date, parse_time, parse_timestamp};
+    use crate::types::types::{Interval, Literal, SelectDefinition};
+    use crate::{DataType, Database, RqlError, Value};
+
+    fn timestamp(text: &str) -> Value {
+        Value::Timestamp(parse_timestamp(text).unwrap())
+    }
+
+    #[test]
+    fn test_parse_and_format_temporal_values() {
+        assert_eq!(parse_date("1970-01-02"), Some(1));
+        assert_eq!(parse_date("1969-12-31"), Some(-1));
+        assert_eq!(parse_time("01:02:03.5"), Some(3_723_500_000));
+        assert_eq!(parse_timestamp("2024-01-31T12:00:00+02:00"), parse_timestamp("2024-01-31 10:00"));
+        assert_eq!(parse_timestamp("2024-01-31"), parse_timestamp("2024-01-31 00:00:00"));
+        assert_eq!(parse_date("2024-02-30"), None);
+
+        assert_eq!(Value::Date(19753).to_string(), "2024-01-31");
+        assert_eq!(Value::Time(3_723_500_000).to_string(), "01:02:03.5");
+        assert_eq!(timestamp("2024-01-31T10:00:00.000250").to_string(), "2024-01-31 10:00:00.00025");
+
+        let sql = "INTERVAL '1 year 2 mons 3 days 04:05:06'";
+        let interval = Literal::from_sql(sql, sql).unwrap();
+        assert_eq!(interval, Literal::Interval(Interval { months: 14, days: 3, micros: 14_706_000_000 }));
+        assert_eq!(interval.to_string(), "INTERVAL '1 year 2 months 3 days 04:05:06'");
+        let sql = "DATE '2024-13-01'";
+        assert!(matches!(Literal::from_sql(sql, sql), Err(RqlError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_date_trunc_and_extract() {
+        let value = timestamp("2024-05-15 13:45:30.25");
+        assert_eq!(date_trunc("hour", &value).unwrap(), timestamp("2024-05-15 13:00"));
+        assert_eq!(date_trunc("week", &value).unwrap(), timestamp("2024-05-13"));
+        assert_eq!(date_trunc("quarter", &value).unwrap(), timestamp("2024-04-01"));
+        assert_eq!(date_trunc("month", &Value::Date(parse_date("2024-05-15").unwrap())).unwrap(), Value::Date(19844));
+        assert!(date_trunc("day", &Value::Time(0)).is_err());
+        assert!(date_trunc("fortnight", &value).is_err());
+
+        assert_eq!(extract("year", &value).unwrap(), Value::Integer(2024));
+        assert_eq!(extract("dow", &value).unwrap(), Value::Integer(3));
+        assert_eq!(extract("second", &value).unwrap(), Value::Integer(30));
+        assert_eq!(extract("epoch", &Value::Date(1)).unwrap(), Value::Double(86_400.0));
+        assert!(extract("year", &Value::Time(0)).is_err());
+    }
+
+    #[test]
+    fn test_temporal_columns_in_queries() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE readings (id INTEGER, day DATE, at TIMESTAMP, shift TIME, value FLOAT);")
+            .unwrap();
+        conn.execute(
+            "INSERT INTO readings VALUES \
+             (1, '2024-01-31', '2024-01-31 08:15:00', '08:00', 1.5), \
+             (2, DATE '2024-02-01', TIMESTAMP '2024-02-01T09:30:00', TIME '09:00', 2.5), \
+             (3, '2024-02-01', '2024-02-01 17:45:30', '17:00', 4.0), \
+             (4, NULL, NULL, NULL, 8.0);",
+        )
+        .unwrap();
+        let err = conn.execute("INSERT INTO readings VALUES (5, '31/01/2024', NULL, NULL, 1.0);").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+
+        let rows = conn.query("SELECT id, day, at FROM readings WHERE day = '2024-02-01' ORDER BY at DESC;").unwrap();
+        assert_eq!(rows.columns()[1].data_type(), DataType::Date);
+        assert_eq!(rows.rows()[0].value("id"), Some(&Value::Integer(3)));
+        assert_eq!(rows.rows()[1].value("at"), Some(&timestamp("2024-02-01 09:30")));
+
+        let rows = conn
+            .query("SELECT id FROM readings WHERE at >= DATE '2024-02-01' AND at < TIMESTAMP '2024-02-01' + INTERVAL '12 hours';")
+            .unwrap();
+        assert_eq!(rows.rows().iter().map(|r| r.values()[0].clone()).collect::<Vec<_>>(), vec![Value::Integer(2)]);
+
+        let rows = conn
+            .query(
+                "SELECT id, day + 1 AS next, at + INTERVAL '1 month' AS later, day - DATE '2024-01-01' AS days, \
+                 extract(hour FROM at) AS hour, shift - TIME '07:30' AS since FROM readings WHERE id = 1;",
+            )
+            .unwrap();
+        let row = &rows.rows()[0];
+        assert_eq!(row.value("next"), Some(&Value::Date(parse_date("2024-02-01").unwrap())));
+        assert_eq!(row.value("later"), Some(&timestamp("2024-02-29 08:15")));
+        assert_eq!(row.value("days"), Some(&Value::Integer(30)));
+        assert_eq!(row.value("hour"), Some(&Value::Integer(8)));
+        assert_eq!(row.value("since"), Some(&Value::BigInt(30 * 60 * 1_000_000)));
+
+        let rows = conn.query("SELECT id FROM readings WHERE at < now() AND day IS NOT NULL;").unwrap();
+        assert_eq!(rows.len(), 3);
+        for sql in ["SELECT * FROM readings WHERE day = 1;", "SELECT * FROM readings WHERE at + 1 > now();"] {
+            let err = conn.query(sql).unwrap_err();
+            assert!(matches!(err, RqlError::Type(_)), "{}: {}", sql, err);
+        }
+    }
+
+    #[test]
+    fn test_group_by_order_by_and_limit() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE readings (sensor TEXT, at TIMESTAMP, value DOUBLE) WITH (compression = 'lz4');")
+            .unwrap();
+        conn.execute(
+            "INSERT INTO readings VALUES ('a', '2024-01-01 10:05', 1.0), ('a', '2024-01-01 10:55', 3.0), \
+             ('b', '2024-01-01 10:30', 5.0), ('a', '2024-01-01 11:10', 7.0), ('b', '2024-01-01 11:20', NULL);",
+        )
+        .unwrap();
+
+        let rows = conn
+            .query(
+                "SELECT date_trunc('hour', at) AS hour, count(*), avg(value) AS mean, max(value) FROM readings \
+                 GROUP BY date_trunc('hour', at) ORDER BY hour;",
+            )
+            .unwrap();
+        let names: Vec<&str> = rows.columns().iter().map(|c| c.name()).collect();
+        assert_eq!(names, ["hour", "count(*)", "mean", "max(value)"]);
+        assert_eq!(rows.rows()[0].values(), &[timestamp("2024-01-01 10:00"), Value::BigInt(3), Value::Double(3.0), Value::Double(5.0)]);
+        assert_eq!(rows.rows()[1].values(), &[timestamp("2024-01-01 11:00"), Value::BigInt(2), Value::Double(7.0), Value::Double(7.0)]);
+
+        let rows = conn.query("SELECT sensor, sum(value) FROM readings GROUP BY sensor ORDER BY 2 DESC LIMIT 1;").unwrap();
+        assert_eq!(rows.rows()[0].values(), &[Value::Text("a".to_string()), Value::Double(11.0)]);
+
+        let rows = conn.query("SELECT count(value), min(at) FROM readings WHERE value > 100;").unwrap();
+        assert_eq!(rows.rows()[0].values(), &[Value::BigInt(0), Value::Null]);
+
+        let rows = conn.query("SELECT value FROM readings ORDER BY value DESC, at LIMIT 2 OFFSET 1;").unwrap();
+        let values: Vec<Value> = rows.iter().map(|r| r.values()[0].clone()).collect();
+        assert_eq!(values, [Value::Double(7.0), Value::Double(5.0)]);
+
+        let err = conn.query("SELECT sensor, value FROM readings GROUP BY sensor;").unwrap_err();
+        assert!(matches!(err, RqlError::Schema(_)), "{}", err);
+        let err = conn.query("SELECT * FROM readings WHERE count(*) > 1;").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_select_clauses() {
+        let select = SelectDefinition::from_sql(
+            "SELECT a + b * 2 AS total, (a + b) * 2, NOT a > 1 OR b IS NULL FROM t \
+             WHERE a BETWEEN 1 AND 3 ORDER BY total DESC LIMIT 10 OFFSET 5;",
+        )
+        .unwrap();
+        assert_eq!((select.limit, select.offset), (Some(10), 5));
+        assert!(select.order_by[0].descending);
+        let filter = select.filter.unwrap();
+        assert_eq!(filter.to_string(), "a >= 1 AND a <= 3");
+
+        let select = SelectDefinition::from_sql("SELECT (a + b) * 2, NOT a > 1 OR b IS NULL, -x FROM t;").unwrap();
+        let names: Vec<String> = select
+            .items
+            .iter()
+            .map(|item| match item {
+                crate::types::types::SelectItem::Expression { name, .. } => name.clone(),
+                crate::types::types::SelectItem::Wildcard => "*".to_string(),
+            })
+            .collect();
+        assert_eq!(names, ["(a + b) * 2", "NOT a > 1 OR b IS NULL", "-x"]);
+
+        for sql in ["SELECT FROM t;", "SELECT * FROM t WHERE;", "SELECT * FROM t LIMIT x;", "SELECT nope(1) FROM t;"] {
+            let err = SelectDefinition::from_sql(sql).unwrap_err();
+            assert!(err.position().is_some(), "{}: {}", sql, err);
+        }
+    }
+}
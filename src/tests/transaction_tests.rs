@@ -0,0 +1,127 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::database::database::Database;
+    use crate::io::writer::write_table_definition;
+    use crate::types::error::RqlError;
+    use crate::types::types::{InsertDefinition, SelectDefinition, TableDefinition, Timings};
+
+    fn insert(db: &Database, sql: &str) {
+        InsertDefinition::from_sql(sql).unwrap().validate_and_insert(db).unwrap();
+    }
+
+    fn count(db: &Database, table: &str) -> Option<usize> {
+        let select = SelectDefinition::from_sql(&format!("SELECT * FROM {};", table)).unwrap();
+        select.execute(db, &mut Timings::default()).ok().map(|rows| rows.len())
+    }
+
+    #[test]
+    fn test_rollback_restores_prior_state() {
+        let mut db = Database::open_in_memory();
+        write_table_definition(&db, &TableDefinition::from_sql("CREATE TABLE t (a INTEGER);").unwrap()).unwrap();
+        insert(&db, "INSERT INTO t VALUES (1)");
+
+        db.begin().unwrap();
+        insert(&db, "INSERT INTO t VALUES (2)");
+        write_table_definition(&db, &TableDefinition::from_sql("CREATE TABLE u (b INTEGER);").unwrap()).unwrap();
+        assert_eq!(count(&db, "t"), Some(2));
+        assert!(db.storage().list("schema").unwrap().contains(&"schema/u_def.bin".to_string()));
+
+        db.rollback().unwrap();
+        assert!(!db.in_transaction());
+        assert_eq!(count(&db, "t"), Some(1));
+        assert_eq!(db.storage().list("schema").unwrap(), vec!["schema/t_def.bin".to_string()]);
+    }
+
+    #[test]
+    fn test_commit_publishes_all_statements() {
+        let mut db = Database::open_in_memory();
+        let other = db.clone();
+
+        db.begin().unwrap();
+        assert!(db.begin().is_err());
+        write_table_definition(&db, &TableDefinition::from_sql("CREATE TABLE t (a INTEGER);").unwrap()).unwrap();
+        insert(&db, "INSERT INTO t VALUES (1)");
+        insert(&db, "INSERT INTO t VALUES (2)");
+
+        // Another handle on the same storage does not see uncommitted changes.
+        assert_eq!(count(&other, "t"), None);
+
+        db.commit().unwrap();
+        assert_eq!(count(&other, "t"), Some(2));
+        assert!(db.commit().is_err());
+
+        // Autocommit is back in effect.
+        insert(&db, "INSERT INTO t VALUES (3)");
+        assert_eq!(count(&other, "t"), Some(3));
+    }
+
+    #[test]
+    fn test_overlapping_transactions_conflict() {
+        let mut first = Database::open_in_memory();
+        let mut second = first.clone();
+        for table in ["t", "u"] {
+            let sql = format!("CREATE TABLE {} (a INTEGER);", table);
+            write_table_definition(&first, &TableDefinition::from_sql(&sql).unwrap()).unwrap();
+        }
+
+        // Both append to t, each from the file as it was before the other commits.
+        first.begin().unwrap();
+        second.begin().unwrap();
+        insert(&first, "INSERT INTO t VALUES (1)");
+        insert(&second, "INSERT INTO t VALUES (2)");
+        first.commit().unwrap();
+        let err = second.commit().unwrap_err();
+        assert!(matches!(err, RqlError::Transaction(_)), "{}", err);
+        assert!(err.to_string().contains("data/t_data.bin was changed by another connection"), "{}", err);
+        assert!(!second.in_transaction());
+        assert_eq!(count(&second, "t"), Some(1));
+
+        // An autocommitted statement conflicts the same way.
+        second.begin().unwrap();
+        insert(&second, "INSERT INTO t VALUES (2)");
+        insert(&first, "INSERT INTO t VALUES (3)");
+        assert!(second.commit().is_err());
+        assert_eq!(count(&first, "t"), Some(2));
+
+        // Transactions on different tables both commit.
+        first.begin().unwrap();
+        second.begin().unwrap();
+        insert(&first, "INSERT INTO t VALUES (4)");
+        insert(&second, "INSERT INTO u VALUES (5)");
+        first.commit().unwrap();
+        second.commit().unwrap();
+        assert_eq!(count(&first, "t"), Some(3));
+        assert_eq!(count(&first, "u"), Some(1));
+    }
+
+    #[test]
+    fn test_failed_commit_ends_the_transaction_everywhere() {
+        let mut first = Database::open_in_memory();
+        first.attach(":memory:", "a").unwrap();
+        first.attach(":memory:", "b").unwrap();
+        let second = first.clone();
+        for table in ["t", "a.t", "b.t"] {
+            let sql = format!("CREATE TABLE {} (x INTEGER);", table);
+            write_table_definition(&first, &TableDefinition::from_sql(&sql).unwrap()).unwrap();
+        }
+
+        // main is published before a conflicts, and b comes after a.
+        first.begin().unwrap();
+        for table in ["t", "a.t", "b.t"] {
+            insert(&first, &format!("INSERT INTO {} VALUES (1)", table));
+        }
+        insert(&second, "INSERT INTO a.t VALUES (2)");
+        let err = first.commit().unwrap_err();
+        assert!(err.to_string().contains("only committed to main:"), "{}", err);
+        assert!(err.to_string().contains("data/t_data.bin was changed by another connection"), "{}", err);
+        assert!(!first.in_transaction());
+        assert!(first.attached().all(|(_, attached)| !attached.in_transaction()));
+        assert_eq!(count(&second, "t"), Some(1));
+        assert_eq!(count(&second, "a.t"), Some(1));
+        assert_eq!(count(&second, "b.t"), Some(0));
+
+        // Later statements are autocommitted again, in every database.
+        insert(&first, "INSERT INTO b.t VALUES (3)");
+        assert_eq!(count(&second, "b.t"), Some(1));
+    }
+}
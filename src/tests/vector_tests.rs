@@ -0,0 +1,119 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::format::format_result;
+    use crate::types::types::Literal;
+    use crate::{BlobFormat, DataType, Database, OutputFormat, RqlError, Value};
+    use std::fs;
+
+    fn parse(sql: &str) -> Result<Literal, RqlError> {
+        Literal::from_sql(sql, sql)
+    }
+
+    #[test]
+    fn test_parse_vector_literals() {
+        assert_eq!(parse("VEC3(1, -2.5, 3e1)").unwrap(), Literal::Vector(vec![1.0, -2.5, 30.0]));
+        assert_eq!(parse("vec2( .5 ,0 )").unwrap(), Literal::Vector(vec![0.5, 0.0]));
+        let identity = "MAT4(1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1)";
+        assert_eq!(parse(identity).unwrap().to_string(), identity);
+        assert_eq!(parse("VEC4(1, 2, 3, 4)").unwrap().cast(DataType::Vec4), Some(Value::Vec4([1.0, 2.0, 3.0, 4.0])));
+        assert_eq!(parse("VEC4(1, 2, 3, 4)").unwrap().cast(DataType::Vec3), None);
+        assert_eq!(Literal::Text("[1, 2]".to_string()).cast(DataType::Vec2), Some(Value::Vec2([1.0, 2.0])));
+
+        for sql in ["VEC3(1, 2)", "VEC2(1, x)", "VEC2(1, 2", "MAT4(1)"] {
+            let err = parse(sql).unwrap_err();
+            assert!(err.position().is_some(), "{}: {}", sql, err);
+        }
+
+        assert_eq!(Value::parse("[0.5, 1, -2]", &DataType::Vec3), Some(Value::Vec3([0.5, 1.0, -2.0])));
+        assert_eq!(Value::parse("[1, 2, 3]", &DataType::Vec4), None);
+        assert_eq!(Value::Vec3([0.5, 1.0, -2.0]).to_string(), "[0.5, 1, -2]");
+    }
+
+    #[test]
+    fn test_vector_columns() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE points (id INTEGER, pos VEC3, uv VEC2, transform MAT4);").unwrap();
+        conn.execute(
+            "INSERT INTO points VALUES (1, VEC3(1, 2, 3), VEC2(0, 1), NULL), \
+             (2, '[4, 5, 6]', VEC2(1, 1), MAT4(2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 1));",
+        )
+        .unwrap();
+        let err = conn.execute("INSERT INTO points VALUES (3, VEC2(1, 2), NULL, NULL);").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+
+        let rows = conn.query("SELECT id, pos, transform FROM points WHERE pos = VEC3(4, 5, 6);").unwrap();
+        assert_eq!(rows.columns()[1].data_type(), DataType::Vec3);
+        assert_eq!(rows.rows()[0].get::<[f32; 3], _>("pos").unwrap(), [4.0, 5.0, 6.0]);
+        assert_eq!(rows.rows()[0].get::<[f32; 16], _>("transform").unwrap()[5], 2.0);
+        assert_eq!(conn.query("SELECT * FROM points WHERE uv <> VEC2(1, 1);").unwrap().len(), 1);
+
+        let err = conn.query("SELECT * FROM points WHERE pos < VEC3(4, 5, 6);").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+
+        let rows = conn.query("SELECT pos, uv FROM points WHERE id = 1;").unwrap();
+        assert_eq!(format_result(&rows, OutputFormat::Ndjson, None, BlobFormat::Hex), "{\"pos\":[1,2,3],\"uv\":[0,1]}\n");
+    }
+
+    #[test]
+    fn test_vector_arrow_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rql_vectors_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("points.parquet");
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE points (pos VEC3, color VEC4);").unwrap();
+        conn.execute("INSERT INTO points VALUES (VEC3(1, 2, 3), NULL), (NULL, VEC4(0, 0.5, 1, 1));").unwrap();
+        conn.execute(&format!("COPY points TO '{}';", path.display())).unwrap();
+        conn.execute(&format!("COPY copied FROM '{}';", path.display())).unwrap();
+
+        let rows = conn.query("SELECT * FROM copied;").unwrap();
+        assert_eq!(rows.columns()[0].data_type(), DataType::Vec3);
+        assert_eq!(rows.rows()[0].values(), &[Value::Vec3([1.0, 2.0, 3.0]), Value::Null]);
+        assert_eq!(rows.rows()[1].values(), &[Value::Null, Value::Vec4([0.0, 0.5, 1.0, 1.0])]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_update_vectors_on_the_gpu() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE particles (id BIGINT, pos VEC3, vel VEC3, speed FLOAT, height FLOAT, m MAT4);")
+            .unwrap();
+        conn.execute(
+            "INSERT INTO particles VALUES \
+             (1, VEC3(1, 2, 3), VEC3(3, 0, 4), 0, 0, MAT4(2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 1)), \
+             (2, VEC3(0, 1, 0), VEC3(0, 2, 0), 0, 0, NULL), \
+             (3, NULL, VEC3(1, 0, 0), 0, 0, NULL);",
+        )
+        .unwrap();
+
+        // The BIGINT column is not referenced, so it does not need 64-bit shader support.
+        match conn.execute(
+            "UPDATE particles SET speed = length(vel), pos = pos + vel * 0.5, height = pos.y + dot(vel, VEC3(0, 1, 0)), \
+             vel = normalize(cross(vel, VEC3(0, 0, 1)));",
+        ) {
+            Err(RqlError::Gpu(message)) => {
+                eprintln!("Skipping, no GPU: {}", message);
+                return;
+            }
+            result => assert_eq!(result.unwrap(), 3),
+        }
+
+        let rows = conn.query("SELECT pos, vel, speed, height FROM particles;").unwrap();
+        let expected = [
+            [Value::Vec3([2.5, 2.0, 5.0]), Value::Vec3([0.0, -1.0, 0.0]), Value::Float(5.0), Value::Float(2.0)],
+            [Value::Vec3([0.0, 2.0, 0.0]), Value::Vec3([1.0, 0.0, 0.0]), Value::Float(2.0), Value::Float(4.0)],
+            [Value::Null, Value::Vec3([0.0, -1.0, 0.0]), Value::Float(1.0), Value::Null],
+        ];
+        for (row, expected) in rows.iter().zip(&expected) {
+            assert_eq!(row.values(), expected);
+        }
+
+        conn.execute("UPDATE particles SET m = m * MAT4(1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 1, 2, 3, 1);").unwrap();
+        let rows = conn.query("SELECT m FROM particles WHERE id = 1;").unwrap();
+        let m = rows.rows()[0].get::<[f32; 16], _>("m").unwrap();
+        assert_eq!(m[12..], [2.0, 4.0, 6.0, 1.0]);
+    }
+}
@@ -0,0 +1,132 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::io::wal::Wal;
+    use crate::tests::common::tests::temp_dir;
+    use crate::types::types::WalOp;
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::path::PathBuf;
+
+    fn append_ops(path: &str, offset: u64, bytes: &[u8]) -> Vec<WalOp> {
+        vec![
+            WalOp::Truncate { path: path.to_string(), len: offset },
+            WalOp::Write { path: path.to_string(), offset, bytes: bytes.to_vec() },
+        ]
+    }
+
+    #[test]
+    fn test_recover_after_failure_mid_write() {
+        let dir = temp_dir("wal", "mid_write");
+        let data_path = dir.join("floats_data.bin").to_string_lossy().to_string();
+        fs::write(&data_path, b"committed").unwrap();
+
+        let wal = Wal::open(&dir);
+        let ops = append_ops(&data_path, 9, b" and appended");
+        wal.append_entry(&ops).unwrap();
+
+        // The process dies after the log was synced but halfway through the data file.
+        fs::write(&data_path, b"committed and ap").unwrap();
+
+        assert_eq!(wal.recover().unwrap(), 1);
+        assert_eq!(fs::read(&data_path).unwrap(), b"committed and appended");
+        assert_eq!(fs::metadata(dir.join("data").join("wal.log")).unwrap().len(), 0);
+
+        // Replaying again is harmless.
+        wal.apply(&ops).unwrap();
+        assert_eq!(fs::read(&data_path).unwrap(), b"committed and appended");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_recover_discards_incomplete_entry() {
+        let dir = temp_dir("wal", "torn_entry");
+        let data_path = dir.join("floats_data.bin").to_string_lossy().to_string();
+        fs::write(&data_path, b"committed").unwrap();
+
+        let wal = Wal::open(&dir);
+        wal.append_entry(&append_ops(&data_path, 0, b"never committed")).unwrap();
+
+        // The process dies while the log entry itself is being written.
+        let wal_file = OpenOptions::new().write(true).open(dir.join("data").join("wal.log")).unwrap();
+        let len = wal_file.metadata().unwrap().len();
+        wal_file.set_len(len - 4).unwrap();
+
+        assert_eq!(wal.recover().unwrap(), 0);
+        assert_eq!(fs::read(&data_path).unwrap(), b"committed");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_commit_applies_and_clears_log() {
+        let dir = temp_dir("wal", "commit");
+        let data_path = dir.join("nested").join("t_data.bin").to_string_lossy().to_string();
+
+        let wal = Wal::open(&dir);
+        wal.commit(&append_ops(&data_path, 0, b"first")).unwrap();
+        wal.commit(&append_ops(&data_path, 5, b"second")).unwrap();
+
+        assert_eq!(fs::read(&data_path).unwrap(), b"firstsecond");
+        assert_eq!(wal.recover().unwrap(), 0);
+
+        wal.commit(&[WalOp::Remove { path: data_path.clone() }]).unwrap();
+        assert!(!PathBuf::from(&data_path).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_commit_replays_entry_that_failed_to_apply() {
+        let dir = temp_dir("wal", "failed_apply");
+        let data_path = dir.join("t_data.bin").to_string_lossy().to_string();
+        let blocked = dir.join("blocked");
+        fs::write(&blocked, b"").unwrap();
+        let index_path = blocked.join("t.index.bin").to_string_lossy().to_string();
+
+        // The data file is written, then the index file cannot be created.
+        let wal = Wal::open(&dir);
+        let mut ops = append_ops(&data_path, 0, b"rows");
+        ops.extend(append_ops(&index_path, 0, b"keys"));
+        assert!(wal.commit(&ops).is_err());
+        assert_eq!(fs::read(&data_path).unwrap(), b"rows");
+
+        // The next commit finishes the failed one before logging its own.
+        fs::remove_file(&blocked).unwrap();
+        wal.commit(&append_ops(&data_path, 4, b" more")).unwrap();
+        assert_eq!(fs::read(&data_path).unwrap(), b"rows more");
+        assert_eq!(fs::read(&index_path).unwrap(), b"keys");
+        assert_eq!(fs::metadata(dir.join("data").join("wal.log")).unwrap().len(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_commits_are_all_applied() {
+        let dir = temp_dir("wal", "concurrent");
+        let wal = Wal::open(&dir);
+        let paths: Vec<String> =
+            (0..4).map(|n| dir.join(format!("t{}_data.bin", n)).to_string_lossy().to_string()).collect();
+
+        // Connections sharing a database commit through the same log at once.
+        std::thread::scope(|scope| {
+            for path in &paths {
+                let wal = &wal;
+                scope.spawn(move || {
+                    for n in 0..50u64 {
+                        wal.commit(&append_ops(path, n, &[n as u8])).unwrap();
+                        assert_eq!(fs::metadata(path).unwrap().len(), n + 1);
+                    }
+                });
+            }
+        });
+
+        for path in &paths {
+            assert_eq!(fs::read(path).unwrap(), (0..50).collect::<Vec<u8>>());
+        }
+        assert_eq!(wal.recover().unwrap(), 0);
+        assert_eq!(fs::metadata(dir.join("data").join("wal.log")).unwrap().len(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
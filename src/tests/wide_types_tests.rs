@@ -0,0 +1,179 @@
+#[cfg(test)]
+pub mod tests {
+    use crate::rqle::cpu_executor::CpuExecutor;
+    use crate::rqle::rqle_parser::{Assignment, ExpressionParser};
+    use crate::types::types::{ComputeDevice, TableDefinition, Timings};
+    use crate::{DataType, Database, RqlError, Value};
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn test_bigint_and_double_values() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE wide (id BIGINT NOT NULL, reading DOUBLE, small INTEGER);").unwrap();
+        conn.execute("INSERT INTO wide VALUES (9007199254740993, 0.1, 1), (-2, 2, NULL), (3, NULL, 3);").unwrap();
+
+        let rows = conn.query("SELECT * FROM wide WHERE id = 9007199254740993;").unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows.rows()[0].get::<i64, _>("id").unwrap(), 9_007_199_254_740_993);
+        assert_eq!(rows.rows()[0].get::<f64, _>("reading").unwrap(), 0.1);
+        assert_eq!(conn.query("SELECT * FROM wide WHERE reading = 2.0;").unwrap().rows()[0].value("id"), Some(&Value::BigInt(-2)));
+
+        // INTEGER values widen into BIGINT and DOUBLE columns; narrowing is refused.
+        conn.execute("CREATE TABLE ints (a INTEGER, b INTEGER);").unwrap();
+        conn.execute("INSERT INTO ints VALUES (1, 2);").unwrap();
+        conn.execute("CREATE TABLE widened (id BIGINT, reading DOUBLE);").unwrap();
+        assert_eq!(conn.execute("INSERT INTO widened SELECT * FROM ints;").unwrap(), 1);
+        let rows = conn.query("SELECT * FROM widened;").unwrap();
+        assert_eq!(rows.rows()[0].values(), &[Value::BigInt(1), Value::Double(2.0)]);
+        let err = conn.execute("INSERT INTO ints SELECT * FROM widened;").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+        let err = conn.execute("INSERT INTO wide VALUES (99999999999999999999, 1.0, 1);").unwrap_err();
+        assert!(matches!(err, RqlError::Type(_)), "{}", err);
+
+        let batch = conn.query("SELECT * FROM wide;").unwrap().to_batch().unwrap();
+        assert_eq!(batch.data()[0].data_type(), DataType::BigInt);
+        assert_eq!(batch.data()[1].data_type(), DataType::Double);
+    }
+
+    #[test]
+    fn test_wide_types_round_trip_through_files() {
+        let dir = std::env::temp_dir().join(format!("rql_wide_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE wide (id BIGINT, reading DOUBLE);").unwrap();
+        conn.execute("INSERT INTO wide VALUES (9007199254740993, 0.1), (NULL, 1e300);").unwrap();
+        let expected = conn.query("SELECT * FROM wide;").unwrap();
+
+        for file in ["wide.parquet", "wide.csv"] {
+            let path = dir.join(file);
+            let header = if file.ends_with(".csv") { " WITH (header)" } else { "" };
+            conn.execute(&format!("COPY wide TO '{}'{};", path.display(), header)).unwrap();
+            let table = file.replace('.', "_");
+            conn.execute(&format!("COPY {} FROM '{}'{};", table, path.display(), header)).unwrap();
+
+            let copied = conn.query(&format!("SELECT * FROM {};", table)).unwrap();
+            let types: Vec<DataType> = copied.columns().iter().map(|c| c.data_type()).collect();
+            assert_eq!(types, vec![DataType::BigInt, DataType::Double], "{}", file);
+            assert_eq!(copied.rows()[0].values(), expected.rows()[0].values(), "{}", file);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cpu_executor_evaluates_assignments() {
+        let table = TableDefinition::from_sql("CREATE TABLE t (id BIGINT, x DOUBLE, n INTEGER);").unwrap();
+        let data = HashMap::from([
+            ("id".to_string(), vec![Value::BigInt(4_000_000_000), Value::BigInt(-3)]),
+            ("x".to_string(), vec![Value::Double(0.5), Value::Double(2.0)]),
+            ("n".to_string(), vec![Value::Integer(7), Value::Integer(1)]),
+        ]);
+        let assignments = ExpressionParser::parse("tmp = x * 2.0, id = id + n, n = tmp + pow(n, 2) / 3, x = tmp").unwrap();
+
+        let updated = CpuExecutor.main(&assignments.assignments, data, &table, &mut Timings::default()).unwrap();
+        assert_eq!(updated["id"], vec![Value::BigInt(4_000_000_007), Value::BigInt(-2)]);
+        assert_eq!(updated["n"], vec![Value::Integer(17), Value::Integer(4)]);
+        assert_eq!(updated["x"], vec![Value::Double(1.0), Value::Double(4.0)]);
+        assert!(!updated.contains_key("tmp"));
+
+        // Only what the grammar accepts between tokens is skipped.
+        let table = TableDefinition::from_sql("CREATE TABLE t (n INTEGER);").unwrap();
+        let data = HashMap::from([("n".to_string(), vec![Value::Integer(1)])]);
+        let assignments = [Assignment { variable: "n".to_string(), expression: "n \\ + 1".to_string() }];
+        let err = CpuExecutor.main(&assignments, data, &table, &mut Timings::default()).unwrap_err();
+        assert!(matches!(err, RqlError::Parse { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_cpu_executor_keeps_integers_exact() {
+        let table = TableDefinition::from_sql("CREATE TABLE t (id BIGINT, n INTEGER);").unwrap();
+        let data = HashMap::from([
+            ("id".to_string(), vec![Value::BigInt(9_007_199_254_740_993), Value::BigInt(i64::MAX - 1)]),
+            ("n".to_string(), vec![Value::Integer(7), Value::Integer(-7)]),
+        ]);
+        let assignments = ExpressionParser::parse("id = id + n % 2, n = n / 2 * 2 + n / 0").unwrap();
+        let updated = CpuExecutor.main(&assignments.assignments, data.clone(), &table, &mut Timings::default()).unwrap();
+        assert_eq!(updated["id"], vec![Value::BigInt(9_007_199_254_740_994), Value::BigInt(i64::MAX - 2)]);
+        assert_eq!(updated["n"], vec![Value::Integer(13), Value::Integer(-13)]);
+
+        let assignments = ExpressionParser::parse("id = id * 2").unwrap();
+        let err = CpuExecutor.main(&assignments.assignments, data, &table, &mut Timings::default()).unwrap_err();
+        assert!(err.to_string().contains("is out of range"), "{}", err);
+    }
+
+    #[test]
+    fn test_cpu_executor_computes_floats_in_single_precision() {
+        let values = vec![Value::Float(1.1), Value::Float(2.7)];
+        let assignments = ExpressionParser::parse("f = (f * 0.1 + 0.2) / 3.0").unwrap();
+        let table = TableDefinition::from_sql("CREATE TABLE t (f FLOAT, n INTEGER);").unwrap();
+        let data = HashMap::from([("f".to_string(), values.clone())]);
+        let updated = CpuExecutor.main(&assignments.assignments, data, &table, &mut Timings::default()).unwrap();
+        // Every step is rounded to f32, as a shader computes it.
+        assert_eq!(updated["f"], [Value::Float((1.1f32 * 0.1 + 0.2) / 3.0), Value::Float((2.7f32 * 0.1 + 0.2) / 3.0)]);
+        assert_ne!(updated["f"][1], Value::Float(((2.7f32 as f64 * 0.1 + 0.2) / 3.0) as f32));
+
+        // A DOUBLE column takes part, so the FLOAT column is computed in doubles too.
+        let assignments = ExpressionParser::parse("f = (f * 0.1 + 0.2) / 3.0 + d").unwrap();
+        let table = TableDefinition::from_sql("CREATE TABLE t (f FLOAT, d DOUBLE);").unwrap();
+        let data = HashMap::from([("f".to_string(), values), ("d".to_string(), vec![Value::Double(0.0); 2])]);
+        let updated = CpuExecutor.main(&assignments.assignments, data, &table, &mut Timings::default()).unwrap();
+        assert_eq!(updated["f"][1], Value::Float(((2.7f32 as f64 * 0.1 + 0.2) / 3.0) as f32));
+    }
+
+    #[test]
+    fn test_cpu_and_gpu_updates_agree() {
+        let sql = "UPDATE t SET i = i / 2 * 2 + abs(i) % 3 - max(i, 2), f = f * 2.0 - abs(f) / 4.0;";
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE t (i INTEGER, f FLOAT);").unwrap();
+        conn.execute("INSERT INTO t VALUES (7, 0.5), (-7, -1.5), (13, 2.0), (0, 0.0), (-100, 8.0);").unwrap();
+        let before = conn.query("SELECT * FROM t;").unwrap();
+
+        let table = crate::io::reader::read_table_definition(&db, "t").unwrap();
+        let data = HashMap::from([
+            ("i".to_string(), before.rows().iter().map(|row| row.values()[0].clone()).collect::<Vec<_>>()),
+            ("f".to_string(), before.rows().iter().map(|row| row.values()[1].clone()).collect::<Vec<_>>()),
+        ]);
+        let assignments = ExpressionParser::parse(sql.trim_start_matches("UPDATE t SET ").trim_end_matches(';')).unwrap();
+        let on_cpu = CpuExecutor.main(&assignments.assignments, data, &table, &mut Timings::default()).unwrap();
+        assert_eq!(on_cpu["i"][..2], [Value::Integer(0), Value::Integer(-7)]);
+
+        match conn.execute(sql) {
+            Err(RqlError::Gpu(message)) => {
+                eprintln!("Skipping, no GPU: {}", message);
+                return;
+            }
+            result => assert_eq!(result.unwrap(), 5),
+        }
+        assert_eq!(conn.last_timings().device, Some(ComputeDevice::Gpu));
+        let after = conn.query("SELECT * FROM t;").unwrap();
+        for (index, row) in after.rows().iter().enumerate() {
+            assert_eq!(row.values(), &[on_cpu["i"][index].clone(), on_cpu["f"][index].clone()], "row {}", index);
+        }
+    }
+
+    #[test]
+    fn test_update_bigint_columns() {
+        let db = Database::open_in_memory();
+        let mut conn = db.connect();
+        conn.execute("CREATE TABLE wide (id BIGINT, reading DOUBLE);").unwrap();
+        conn.execute("INSERT INTO wide VALUES (5000000000, 0.25), (NULL, 1.5);").unwrap();
+
+        match conn.execute("UPDATE wide SET id = id * 2, reading = reading * 4.0;") {
+            Err(RqlError::Gpu(message)) => {
+                eprintln!("Skipping, no GPU: {}", message);
+                return;
+            }
+            result => assert_eq!(result.unwrap(), 2),
+        }
+        assert!(conn.last_timings().device.is_some());
+
+        let rows = conn.query("SELECT * FROM wide;").unwrap();
+        assert_eq!(rows.rows()[0].values(), &[Value::BigInt(10_000_000_000), Value::Double(1.0)]);
+        assert_eq!(rows.rows()[1].values(), &[Value::Null, Value::Double(6.0)]);
+    }
+}
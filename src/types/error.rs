@@ -0,0 +1,103 @@
+use std::{error, fmt, io};
+
+/// A 1-based line and column within the text a parser was given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of byte `offset` within `source`.
+    pub fn at(source: &str, offset: usize) -> Position {
+        let before = &source[..offset.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        Position { line, column }
+    }
+}
+
+#[derive(Debug)]
+pub enum RqlError {
+    /// The statement or rqle expression could not be parsed.
+    Parse { message: String, position: Option<Position> },
+    /// A table, column or database does not exist or is defined inconsistently.
+    Schema(String),
+    /// A statement would violate a constraint of the table.
+    Constraint(String),
+    /// A transaction was used in the wrong state.
+    Transaction(String),
+    /// Reading or writing the underlying storage failed.
+    Io(io::Error),
+    /// No usable GPU, or the generated compute shader was rejected.
+    Gpu(String),
+    /// A value does not fit the type it is used as.
+    Type(String),
+}
+
+pub type Result<T> = std::result::Result<T, RqlError>;
+
+impl RqlError {
+    pub fn parse<S: Into<String>>(message: S) -> RqlError {
+        RqlError::Parse {
+            message: message.into(),
+            position: None,
+        }
+    }
+
+    /// A parse error located where `rest` starts. `rest` must be a subslice of `source`.
+    pub fn parse_at<S: Into<String>>(source: &str, rest: &str, message: S) -> RqlError {
+        let offset = (rest.as_ptr() as usize).saturating_sub(source.as_ptr() as usize);
+        RqlError::Parse {
+            message: message.into(),
+            position: Some(Position::at(source, offset)),
+        }
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            RqlError::Parse { position, .. } => *position,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RqlError::Parse { message, position: Some(position) } => write!(
+                f,
+                "Parse error at line {}, column {}: {}",
+                position.line, position.column, message
+            ),
+            RqlError::Parse { message, position: None } => write!(f, "Parse error: {}", message),
+            RqlError::Schema(message) => write!(f, "Schema error: {}", message),
+            RqlError::Constraint(message) => write!(f, "Constraint violation: {}", message),
+            RqlError::Transaction(message) => write!(f, "Transaction error: {}", message),
+            RqlError::Io(e) => write!(f, "I/O error: {}", e),
+            RqlError::Gpu(message) => write!(f, "GPU error: {}", message),
+            RqlError::Type(message) => write!(f, "Type error: {}", message),
+        }
+    }
+}
+
+impl error::Error for RqlError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RqlError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RqlError {
+    fn from(e: io::Error) -> Self {
+        RqlError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for RqlError {
+    fn from(e: bincode::Error) -> Self {
+        RqlError::Io(io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
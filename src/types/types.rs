@@ -1,26 +1,119 @@
-use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum DataType {
     Integer,
     Float,
     Text,
     Boolean,
+    BigInt,
+    Double,
+    /// Days since 1970-01-01.
+    Date,
+    /// Microseconds since midnight.
+    Time,
+    /// Microseconds since 1970-01-01 00:00:00, without a time zone.
+    Timestamp,
+    /// `vec2<f32>` in WGSL.
+    Vec2,
+    /// `vec3<f32>` in WGSL, padded to 16 bytes in GPU buffers.
+    Vec3,
+    /// `vec4<f32>` in WGSL.
+    Vec4,
+    /// `mat4x4<f32>` in WGSL: four columns of four floats, column by column.
+    Mat4,
+    /// `VECTOR(n)`, an embedding of `n` floats, searched with `l2_distance` and `cosine_distance`.
+    Vector(u32),
+    /// `BLOB` or `BYTEA`, raw bytes of any length.
+    Blob,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct ColumnDefinition {
     pub(crate) name: String,
     pub(crate) data_type: DataType,
     pub(crate) nullable: bool,
     pub(crate) unique: bool,
+    /// Part of the table's primary key, which may span several columns.
+    pub(crate) primary_key: bool,
+    /// `AUTOINCREMENT`, `AUTO_INCREMENT` or `GENERATED BY DEFAULT AS IDENTITY`.
+    pub(crate) auto_increment: bool,
+    /// The value of rows inserted without one: a constant such as `0` or `now()`.
+    pub(crate) default: Option<Expression>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Clone, Copy)]
+pub enum Compression {
+    #[default]
+    None,
+    Lz4,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct TableDefinition {
     pub(crate) name: String,
     pub(crate) columns: Vec<ColumnDefinition>,
+    pub(crate) compression: Compression,
+    /// The last value given to the `AUTOINCREMENT` column, or the highest inserted into it.
+    pub(crate) last_id: i64,
+    /// `CHECK (condition)` constraints, whether declared on a column or on the table.
+    pub(crate) checks: Vec<Expression>,
+    /// `REFERENCES parent (column)` constraints, whether declared on a column or on the table.
+    pub(crate) foreign_keys: Vec<ForeignKey>,
+    /// B-tree indexes, including those backing the primary key and `UNIQUE` columns.
+    pub(crate) indexes: Vec<IndexDefinition>,
+}
+
+/// An index over one or more columns, kept in its own file next to the table's data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexDefinition {
+    pub(crate) name: String,
+    pub(crate) columns: Vec<String>,
+    pub(crate) unique: bool,
+    /// Created for the primary key or a `UNIQUE` column rather than by `CREATE INDEX`.
+    pub(crate) implicit: bool,
+    pub(crate) kind: IndexKind,
+}
+
+/// How an index file is organized: a B-tree answers range conditions as well as
+/// equality, a hash index only equality on all of its columns.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Clone, Copy)]
+pub enum IndexKind {
+    #[default]
+    BTree,
+    Hash,
+}
+
+/// Where a row is stored in its table's data file: the offset of its frame, or of
+/// the compressed chunk it is in together with its position in the chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RowLocation {
+    pub(crate) offset: u64,
+    pub(crate) row: u32,
+}
+
+/// A foreign key: every row whose `columns` are all set must match a row of the
+/// `parent` table on `parent_columns`, its primary key or a `UNIQUE` column.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ForeignKey {
+    pub(crate) columns: Vec<String>,
+    pub(crate) parent: String,
+    pub(crate) parent_columns: Vec<String>,
+    pub(crate) on_delete: ReferentialAction,
+}
+
+/// What deleting a parent row does to the rows that reference it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReferentialAction {
+    /// `RESTRICT` or `NO ACTION`, the default: the `DELETE` fails.
+    #[default]
+    Restrict,
+    /// The referencing rows are deleted too.
+    Cascade,
+    /// The referencing columns are set to NULL.
+    SetNull,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,14 +121,127 @@ pub struct InsertDefinition {
     pub(crate) name: String,
     #[serde(skip)]
     pub(crate) table_definition: TableDefinition,
-    pub(crate) values: Vec<String>
+    /// The columns the values are given for, or `None` for all of them in schema order.
+    pub(crate) columns: Option<Vec<String>>,
+    pub(crate) source: InsertSource,
+}
+
+/// A constant written in a statement: `NULL`, `TRUE`/`FALSE`, a number, a
+/// `'quoted'` or `E'escaped'` string, or a typed literal such as `DATE '2024-01-31'`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Date(i32),
+    Time(i64),
+    Timestamp(i64),
+    Interval(Interval),
+    /// `VEC2(x, y)`, `VEC3(..)`, `VEC4(..)` or `MAT4(..)`, by its components.
+    Vector(Vec<f32>),
+    /// `X'0a1b'`, by its bytes.
+    Blob(Vec<u8>),
+}
+
+/// A length of time, as in `INTERVAL '1 month 2 days 03:00:00'`. Months and days
+/// are kept apart from the rest because their length varies.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+/// An expression in a statement, such as a `WHERE` condition or an `ORDER BY` key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(Literal),
+    Column(String),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(Box<Expression>, BinaryOperator, Box<Expression>),
+    /// `expr IS NULL`, or `expr IS NOT NULL` when the flag is set.
+    IsNull(Box<Expression>, bool),
+    /// A function call with its lowercased name. `count(*)` has no arguments and
+    /// `extract(field FROM expr)` passes the field as a text literal.
+    Function(String, Vec<Expression>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    And,
+    Or,
+}
+
+/// Where the rows of an `INSERT` come from.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum InsertSource {
+    /// `VALUES (..), (..)`, one list of values per row. `None` stands for `DEFAULT`.
+    Values(Vec<Vec<Option<Literal>>>),
+    /// `SELECT ...`
+    Select(SelectDefinition),
 }
 
+/// `ALTER TABLE name ADD [COLUMN] definition`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AlterDefinition {
+    pub(crate) table_name: String,
+    pub(crate) column: ColumnDefinition,
+    /// The `CHECK` constraints declared on the new column.
+    pub(crate) checks: Vec<Expression>,
+    /// The parent the new column `REFERENCES`, if any.
+    pub(crate) foreign_key: Option<ForeignKey>,
+}
+
+/// `CREATE [UNIQUE] INDEX name ON table (column, ...)`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateIndexDefinition {
+    pub(crate) table_name: String,
+    pub(crate) index: IndexDefinition,
+}
+
+/// `DROP TABLE name`
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DropDefinition {
     pub(crate) table_name: String,
 }
 
+/// `DELETE FROM name [WHERE condition]`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteDefinition {
+    pub(crate) table_name: String,
+    pub(crate) filter: Option<Expression>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AttachDefinition {
+    pub(crate) path: String,
+    pub(crate) name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DetachDefinition {
+    pub(crate) name: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CriteriaDefinition {
     pub(crate) criteria_type: String,
@@ -43,10 +249,29 @@ pub struct CriteriaDefinition {
     pub(crate) value: String,
 }
 
+/// `SELECT items FROM table [WHERE ..] [GROUP BY ..] [ORDER BY ..] [LIMIT n [OFFSET m]]`
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SelectDefinition {
     pub(crate) table_name: String,
-    pub(crate) criteria: Vec<(String, String, String)>,
+    pub(crate) items: Vec<SelectItem>,
+    pub(crate) filter: Option<Expression>,
+    pub(crate) group_by: Vec<Expression>,
+    pub(crate) order_by: Vec<OrderBy>,
+    pub(crate) limit: Option<usize>,
+    pub(crate) offset: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    /// `*`, every column of the table.
+    Wildcard,
+    Expression { expression: Expression, name: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderBy {
+    pub(crate) expression: Expression,
+    pub(crate) descending: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -55,10 +280,177 @@ pub struct UpdateDefinition {
     pub(crate) set_query: String,
 }
 
-#[derive(Debug, Clone)]
+/// `IMPORT SQLITE 'file.db' TABLE name [AS new_name]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqliteImportDefinition {
+    pub path: String,
+    pub source_table: String,
+    pub table_name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyDirection {
+    From,
+    To,
+}
+
+/// The file format of a `COPY`, taken from the `format` option or the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyFormat {
+    #[default]
+    Csv,
+    Arrow,
+    Parquet,
+}
+
+/// `COPY table FROM|TO 'file' [WITH (format 'csv', header, delimiter ',')]`, or
+/// `COPY (SELECT ...) TO 'file'` to export the result of a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopyDefinition {
+    pub table_name: String,
+    pub query: Option<String>,
+    pub direction: CopyDirection,
+    pub path: String,
+    pub format: CopyFormat,
+    pub header: bool,
+    pub delimiter: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Value {
     Integer(i32),
     Float(f32),
     Text(String),
     Boolean(bool),
-}
\ No newline at end of file
+    Null,
+    BigInt(i64),
+    Double(f64),
+    Date(i32),
+    Time(i64),
+    Timestamp(i64),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mat4([f32; 16]),
+    Vector(Vec<f32>),
+    Blob(Vec<u8>),
+}
+
+/// The values of one column in a `ColumnBatch`, `None` standing for NULL.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    Integer(Vec<Option<i32>>),
+    Float(Vec<Option<f32>>),
+    Text(Vec<Option<String>>),
+    Boolean(Vec<Option<bool>>),
+    BigInt(Vec<Option<i64>>),
+    Double(Vec<Option<f64>>),
+    Date(Vec<Option<i32>>),
+    Time(Vec<Option<i64>>),
+    Timestamp(Vec<Option<i64>>),
+    Vec2(Vec<Option<[f32; 2]>>),
+    Vec3(Vec<Option<[f32; 3]>>),
+    Vec4(Vec<Option<[f32; 4]>>),
+    Mat4(Vec<Option<[f32; 16]>>),
+    /// `VECTOR(n)` values, each of the given number of floats.
+    Vector(u32, Vec<Option<Vec<f32>>>),
+    Blob(Vec<Option<Vec<u8>>>),
+}
+
+/// Rows stored column by column, the shape Arrow and Parquet read and write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnBatch {
+    pub(crate) columns: Arc<[Column]>,
+    pub(crate) data: Vec<ColumnData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ColumnStats {
+    pub(crate) min: Option<Value>,
+    pub(crate) max: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkHeader {
+    pub(crate) row_count: u64,
+    pub(crate) compressed_size: u64,
+    pub(crate) stats: Vec<ColumnStats>,
+}
+
+/// A single durable file operation recorded in the write-ahead log. Replaying an
+/// operation more than once leaves the file in the same state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum WalOp {
+    Truncate { path: String, len: u64 },
+    Write { path: String, offset: u64, bytes: Vec<u8> },
+    Remove { path: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub(crate) name: String,
+    pub(crate) data_type: DataType,
+    pub(crate) nullable: bool,
+}
+
+/// Rows returned by a statement, with the schema they follow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    pub(crate) columns: Arc<[Column]>,
+    pub(crate) rows: Vec<Row>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub(crate) columns: Arc<[Column]>,
+    pub(crate) values: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementResult {
+    Rows(ResultSet),
+    Done { rows_affected: usize, message: String },
+}
+
+/// How the CLI prints the rows of a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Csv,
+    Json,
+    Ndjson,
+    Markdown,
+}
+
+/// How the CLI prints `BLOB` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlobFormat {
+    /// `\x` followed by two lowercase hexadecimal digits per byte.
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// Where the time of the last statement went. The GPU stages stay zero for
+/// statements that do not run a shader.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Timings {
+    pub parse: Duration,
+    pub plan: Duration,
+    pub upload: Duration,
+    pub compute: Duration,
+    pub readback: Duration,
+    pub total: Duration,
+    /// Where the expressions were evaluated, for statements that compute any.
+    pub device: Option<ComputeDevice>,
+}
+
+/// Where an `UPDATE` evaluates its expressions, or a `SELECT` its nearest
+/// neighbour search. The CPU is used for 64-bit columns the GPU adapter has no
+/// shader support for, and for searches when there is no adapter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeDevice {
+    Gpu,
+    Cpu,
+}